@@ -39,6 +39,9 @@ cfg_task! {
         }
     }
 
+    /// A set of CPUs that a task is allowed to run on.
+    pub type AxCpuMask = axtask::CpuMask;
+
     /// A handle to a wait queue.
     ///
     /// A wait queue is used to store sleeping tasks waiting for a certain event
@@ -67,6 +70,29 @@ cfg_task! {
         }
     }
 
+    pub fn ax_spawn_with_affinity<F>(
+        f: F,
+        name: alloc::string::String,
+        stack_size: usize,
+        cpumask: AxCpuMask,
+    ) -> crate::AxResult<AxTaskHandle>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let task = axtask::TaskInner::new(f, name, stack_size);
+        if !task.set_cpumask(cpumask) {
+            return axerrno::ax_err!(
+                InvalidInput,
+                "ax_spawn_with_affinity: no CPU in the affinity mask"
+            );
+        }
+        let inner = axtask::spawn_task(task);
+        Ok(AxTaskHandle {
+            id: inner.id().as_u64(),
+            inner,
+        })
+    }
+
     pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32> {
         task.inner.join()
     }
@@ -82,6 +108,17 @@ cfg_task! {
         }
     }
 
+    pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult {
+        if axtask::set_affinity(cpumask) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_current_affinity: no CPU in the affinity mask"
+            )
+        }
+    }
+
     pub fn ax_wait_queue_wait(
         wq: &AxWaitQueueHandle,
         until_condition: impl Fn() -> bool,
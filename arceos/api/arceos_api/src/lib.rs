@@ -121,6 +121,7 @@ pub mod task {
         @cfg "multitask";
         pub type AxTaskHandle;
         pub type AxWaitQueueHandle;
+        pub type AxCpuMask;
     }
 
     define_api! {
@@ -150,11 +151,22 @@ pub mod task {
             name: alloc::string::String,
             stack_size: usize
         ) -> AxTaskHandle;
+        /// Spawns a new task like [`ax_spawn`], which is only allowed to run
+        /// on the CPUs in `cpumask`.
+        pub fn ax_spawn_with_affinity(
+            f: impl FnOnce() + Send + 'static,
+            name: alloc::string::String,
+            stack_size: usize,
+            cpumask: AxCpuMask
+        ) -> crate::AxResult<AxTaskHandle>;
         /// Waits for the given task to exit, and returns its exit code (the
         /// argument of [`ax_exit`]).
         pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32>;
         /// Sets the priority of the current task.
         pub fn ax_set_current_priority(prio: isize) -> crate::AxResult;
+        /// Sets the CPU affinity of the current task, and moves it to one of
+        /// the allowed CPUs if needed.
+        pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult;
 
         /// Blocks the current task and put it into the wait queue, until the
         /// given condition becomes true, or the the given duration has elapsed
@@ -186,6 +198,7 @@ pub mod fs {
         pub type AxFilePerm;
         pub type AxDirEntry;
         pub type AxSeekFrom;
+        pub type AxFsStats;
         #[cfg(feature = "myfs")]
         pub type AxDisk;
         #[cfg(feature = "myfs")]
@@ -250,10 +263,31 @@ pub mod fs {
         /// It will delete the original file if `old` already exists.
         pub fn ax_rename(old: &str, new: &str) -> AxResult;
 
+        /// Creates a symbolic link at `link` that points to `original`.
+        pub fn ax_symlink(original: &str, link: &str) -> AxResult;
+        /// Creates a hard link at `link` to the file at `original`.
+        pub fn ax_hard_link(original: &str, link: &str) -> AxResult;
+        /// Returns the path that the symbolic link at `path` points to.
+        pub fn ax_read_link(path: &str) -> AxResult<alloc::string::String>;
+        /// Gets the file attributes at `path` without following symbolic links.
+        pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr>;
+
         /// Returns the current working directory.
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
         /// Changes the current working directory to the specified path.
         pub fn ax_set_current_dir(path: &str) -> AxResult;
+
+        /// Mounts a filesystem of type `fstype` at `target`.
+        ///
+        /// `source` is ignored by in-memory filesystems such as `ramfs`.
+        pub fn ax_mount(source: &str, target: &str, fstype: &str) -> AxResult;
+        /// Unmounts the filesystem mounted at `target`.
+        ///
+        /// Returns an error if there are still opened files under it.
+        pub fn ax_umount(target: &str) -> AxResult;
+        /// Returns the capacity and usage of the filesystem that the file at
+        /// `path` resides in.
+        pub fn ax_statfs(path: &str) -> AxResult<AxFsStats>;
     }
 }
 
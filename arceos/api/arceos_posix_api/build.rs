@@ -37,10 +37,13 @@ typedef struct {{
 
         let allow_types = [
             "stat",
+            "statfs",
             "size_t",
             "ssize_t",
             "off_t",
             "mode_t",
+            "uid_t",
+            "gid_t",
             "sock.*",
             "fd_set",
             "timeval",
@@ -49,23 +52,36 @@ typedef struct {{
             "pthread_mutex_t",
             "pthread_mutexattr_t",
             "epoll_event",
+            "flock",
+            "inotify_event",
             "iovec",
             "clockid_t",
+            "cpu_set_t",
+            "sched_param",
             "rlimit",
+            "rusage",
+            "tms",
             "aibuf",
         ];
         let allow_vars = [
             "CLOCK_.*",
             "O_.*",
+            "SEEK_.*",
+            "FALLOC_.*",
+            "AT_.*",
             "AF_.*",
             "SOCK_.*",
             "IPPROTO_.*",
             "FD_.*",
             "F_.*",
+            "LOCK_.*",
+            "IN_.*",
             "_SC_.*",
             "EPOLL_CTL_.*",
             "EPOLL.*",
             "RLIMIT_.*",
+            "RUSAGE_.*",
+            "SCHED_.*",
             "EAI_.*",
             "MAXADDRS",
         ];
@@ -0,0 +1,92 @@
+//! User and group IDs of the current task, which files are accessed as.
+//!
+//! Real, effective and saved IDs are not distinguished.
+
+use alloc::vec::Vec;
+use core::ffi::c_int;
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::cred::{self, Credentials};
+
+use crate::ctypes;
+
+/// The maximum number of supplementary groups, the same as Linux.
+const NGROUPS_MAX: usize = 65536;
+
+/// Get the user ID of the current task.
+pub fn sys_getuid() -> ctypes::uid_t {
+    cred::current().uid
+}
+
+/// Get the group ID of the current task.
+pub fn sys_getgid() -> ctypes::gid_t {
+    cred::current().gid
+}
+
+/// Set the user ID of the current task. Only root can change it.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_setuid(uid: ctypes::uid_t) -> c_int {
+    debug!("sys_setuid <= {}", uid);
+    syscall_body!(sys_setuid, update_cred(|cred| cred.uid = uid))
+}
+
+/// Set the group ID of the current task. Only root can change it.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_setgid(gid: ctypes::gid_t) -> c_int {
+    debug!("sys_setgid <= {}", gid);
+    syscall_body!(sys_setgid, update_cred(|cred| cred.gid = gid))
+}
+
+/// Get the supplementary group IDs of the current task into `list`, which can
+/// hold `size` of them.
+///
+/// Return the number of the groups, which are only counted if `size` is 0.
+pub unsafe fn sys_getgroups(size: c_int, list: *mut ctypes::gid_t) -> c_int {
+    debug!("sys_getgroups <= {} {:#x}", size, list as usize);
+    syscall_body!(sys_getgroups, {
+        let groups = cred::current().groups;
+        if size == 0 {
+            return Ok(groups.len() as c_int);
+        } else if size < 0 || (size as usize) < groups.len() {
+            return Err(LinuxError::EINVAL);
+        } else if list.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dst = unsafe { core::slice::from_raw_parts_mut(list, groups.len()) };
+        dst.copy_from_slice(&groups);
+        Ok(groups.len() as c_int)
+    })
+}
+
+/// Set the supplementary group IDs of the current task to the `size` ones in
+/// `list`. Only root can change them.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub unsafe fn sys_setgroups(size: usize, list: *const ctypes::gid_t) -> c_int {
+    debug!("sys_setgroups <= {} {:#x}", size, list as usize);
+    syscall_body!(sys_setgroups, {
+        if size > NGROUPS_MAX {
+            return Err(LinuxError::EINVAL);
+        }
+        let groups: Vec<_> = match size {
+            0 => Vec::new(),
+            _ if list.is_null() => return Err(LinuxError::EFAULT),
+            _ => unsafe { core::slice::from_raw_parts(list, size) }.into(),
+        };
+        update_cred(|cred| cred.groups = groups)
+    })
+}
+
+/// Changes the credentials of the current task by `f`, which is only
+/// permitted to root.
+fn update_cred(f: impl FnOnce(&mut Credentials)) -> LinuxResult<c_int> {
+    let mut cred = cred::current();
+    if !cred.is_root() {
+        return Err(LinuxError::EPERM);
+    }
+    f(&mut cred);
+    cred::set_current(cred);
+    Ok(0)
+}
@@ -1,8 +1,9 @@
-use alloc::sync::Arc;
+use alloc::{format, string::String, sync::Arc};
 use core::ffi::{c_char, c_int};
 
-use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axerrno::{AxError, LinuxError, LinuxResult};
+use axfs::fops::{FileAttr, FsStats, OpenOptions};
+use axfs::lock::{LockType, RecordLock};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
@@ -42,21 +43,7 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        Ok(attr_to_stat(&self.inner.lock().get_attr()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -73,10 +60,130 @@ impl FileLike for File {
     fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
         Ok(())
     }
+
+    fn on_close(&self) {
+        // record locks of the closing process on the file are released, even
+        // if they are placed through other file descriptors
+        let locks = self.inner.lock().locks();
+        locks.unlock_records(lock_owner(), 0, u64::MAX);
+    }
+}
+
+/// A directory opened as a file descriptor, which can be used as the base of
+/// relative paths in the `*at` functions.
+pub struct Directory {
+    inner: Mutex<axfs::fops::Directory>,
+}
+
+impl Directory {
+    fn new(inner: axfs::fops::Directory) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    fn add_to_fd_table(self) -> LinuxResult<c_int> {
+        super::fd_ops::add_file_like(Arc::new(self))
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        let f = super::fd_ops::get_file_like(fd)?;
+        f.into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::ENOTDIR)
+    }
+}
+
+impl FileLike for Directory {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EISDIR)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        Ok(attr_to_stat(&self.inner.lock().get_attr()?))
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: false,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+/// Returns the owner of the record locks placed by the current task.
+fn lock_owner() -> u64 {
+    super::task::sys_getpid() as u64
+}
+
+/// Convert errors of lock operations to [`LinuxError`]. Locks that would block
+/// fail with `EAGAIN`, and locks not permitted by the open mode with `EBADF`.
+fn lock_error(err: AxError) -> LinuxError {
+    match err {
+        AxError::PermissionDenied => LinuxError::EBADF,
+        err => err.into(),
+    }
+}
+
+/// Convert file attributes to [`ctypes::stat`].
+fn attr_to_stat(attr: &FileAttr) -> ctypes::stat {
+    let ty = attr.file_type() as u8;
+    let perm = attr.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: attr.ino().max(1) as _,
+        st_nlink: 1,
+        st_mode,
+        st_uid: attr.uid(),
+        st_gid: attr.gid(),
+        st_size: attr.size() as _,
+        st_blocks: attr.blocks() as _,
+        st_blksize: 512,
+        st_atim: attr.atime().into(),
+        st_mtim: attr.mtime().into(),
+        st_ctim: attr.ctime().into(),
+        ..Default::default()
+    }
+}
+
+/// Convert errors of operations on paths to [`LinuxError`]. Failures that
+/// `axfs` records as loops of symbolic links are converted to `ELOOP`.
+fn path_error(err: AxError) -> LinuxError {
+    match err {
+        AxError::BadState if axfs::api::take_symlink_loop() => LinuxError::ELOOP,
+        err => err.into(),
+    }
+}
+
+/// Returns the path of `path` relative to the directory `dirfd`, which is
+/// either an opened directory or [`AT_FDCWD`](ctypes::AT_FDCWD) for the
+/// current directory.
+fn path_at(dirfd: c_int, path: &str) -> LinuxResult<String> {
+    if path.starts_with('/') || dirfd == ctypes::AT_FDCWD {
+        Ok(path.into())
+    } else if path.is_empty() {
+        Err(LinuxError::ENOENT)
+    } else {
+        let dir = Directory::from_fd(dirfd)?;
+        let path = format!("{}/{}", dir.inner.lock().path(), path);
+        Ok(path)
+    }
 }
 
 /// Convert open flags to [`OpenOptions`].
-fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
+fn flags_to_options(flags: c_int, mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
     let mut options = OpenOptions::new();
     match flags & 0b11 {
@@ -99,6 +206,10 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     if flags & ctypes::O_EXEC != 0 {
         options.create_new(true);
     }
+    if flags & ctypes::O_NOFOLLOW != 0 {
+        options.no_follow(true);
+    }
+    options.mode(mode as u32 & 0o777);
     options
 }
 
@@ -107,12 +218,40 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
 /// Return its index in the file table (`fd`). Return `EMFILE` if it already
 /// has the maximum number of files open.
 pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) -> c_int {
+    sys_openat(ctypes::AT_FDCWD, filename, flags, mode)
+}
+
+/// Open a file by `filename` relative to the directory `dirfd`, and insert it
+/// into the file descriptor table.
+///
+/// Directories are opened if `flags` contains `O_DIRECTORY` or `filename`
+/// is a directory, so that they can be used as `dirfd` later.
+///
+/// Return its index in the file table (`fd`). Return `EMFILE` if it already
+/// has the maximum number of files open.
+pub fn sys_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
     let filename = char_ptr_to_str(filename);
-    debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
-    syscall_body!(sys_open, {
+    debug!(
+        "sys_openat <= {} {:?} {:#o} {:#o}",
+        dirfd, filename, flags, mode
+    );
+    syscall_body!(sys_openat, {
+        let path = path_at(dirfd, filename?)?;
         let options = flags_to_options(flags, mode);
-        let file = axfs::fops::File::open(filename?, &options)?;
-        File::new(file).add_to_fd_table()
+        let is_dir = flags as u32 & ctypes::O_DIRECTORY != 0
+            || axfs::fops::attr(&path).is_ok_and(|attr| attr.is_dir());
+        if is_dir {
+            let dir = axfs::fops::Directory::open_dir(&path, &options).map_err(path_error)?;
+            Directory::new(dir).add_to_fd_table()
+        } else {
+            let file = axfs::fops::File::open(&path, &options).map_err(path_error)?;
+            File::new(file).add_to_fd_table()
+        }
     })
 }
 
@@ -122,10 +261,27 @@ pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) ->
 pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off_t {
     debug!("sys_lseek <= {} {} {}", fd, offset, whence);
     syscall_body!(sys_lseek, {
-        let pos = match whence {
-            0 => SeekFrom::Start(offset as _),
-            1 => SeekFrom::Current(offset as _),
-            2 => SeekFrom::End(offset as _),
+        let pos = match whence as u32 {
+            ctypes::SEEK_SET => SeekFrom::Start(offset as _),
+            ctypes::SEEK_CUR => SeekFrom::Current(offset as _),
+            ctypes::SEEK_END => SeekFrom::End(offset as _),
+            ctypes::SEEK_DATA | ctypes::SEEK_HOLE => {
+                // there is neither data nor holes beyond the end of file
+                if offset < 0 {
+                    return Err(LinuxError::ENXIO);
+                }
+                let file = File::from_fd(fd)?;
+                let mut file = file.inner.lock();
+                let off = if whence as u32 == ctypes::SEEK_DATA {
+                    file.seek_data(offset as _)
+                } else {
+                    file.seek_hole(offset as _)
+                };
+                return off.map_err(|e| match e {
+                    AxError::NotFound => LinuxError::ENXIO,
+                    e => e.into(),
+                });
+            }
             _ => return Err(LinuxError::EINVAL),
         };
         let off = File::from_fd(fd)?.inner.lock().seek(pos)?;
@@ -133,6 +289,64 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
     })
 }
 
+/// Truncate or extend the file indicated by `fd` to `length` bytes.
+///
+/// Return 0 if success.
+pub fn sys_ftruncate(fd: c_int, length: ctypes::off_t) -> c_int {
+    debug!("sys_ftruncate <= {} {}", fd, length);
+    syscall_body!(sys_ftruncate, {
+        if length < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        File::from_fd(fd)?.inner.lock().truncate(length as _)?;
+        Ok(0)
+    })
+}
+
+/// Manipulate the storage allocated for the file indicated by `fd`.
+///
+/// `mode` is 0 to allocate `len` bytes at `offset` and extend the file if
+/// needed, `FALLOC_FL_KEEP_SIZE` to allocate them without extending the
+/// file, or `FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE` to deallocate them.
+/// Return `EOPNOTSUPP` if the filesystem does not support the operation.
+///
+/// Return 0 if success.
+pub fn sys_fallocate(fd: c_int, mode: c_int, offset: ctypes::off_t, len: ctypes::off_t) -> c_int {
+    debug!("sys_fallocate <= {} {:#x} {} {}", fd, mode, offset, len);
+    syscall_body!(sys_fallocate, {
+        if offset < 0 || len <= 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let (offset, len) = (offset as u64, len as u64);
+        let file = File::from_fd(fd)?;
+        let file = file.inner.lock();
+        const KEEP_SIZE: u32 = ctypes::FALLOC_FL_KEEP_SIZE;
+        const PUNCH_HOLE: u32 = ctypes::FALLOC_FL_PUNCH_HOLE | ctypes::FALLOC_FL_KEEP_SIZE;
+        let res = match mode as u32 {
+            0 => file.allocate(offset, len, false),
+            KEEP_SIZE => file.allocate(offset, len, true),
+            PUNCH_HOLE => file.punch_hole(offset, len),
+            _ => return Err(LinuxError::EOPNOTSUPP),
+        };
+        res.map_err(|e| match e {
+            AxError::Unsupported => LinuxError::EOPNOTSUPP,
+            e => e.into(),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Write the cached data of the file indicated by `fd` to the storage device.
+///
+/// Return 0 if success.
+pub fn sys_fsync(fd: c_int) -> c_int {
+    debug!("sys_fsync <= {}", fd);
+    syscall_body!(sys_fsync, {
+        File::from_fd(fd)?.inner.lock().flush()?;
+        Ok(0)
+    })
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -145,7 +359,8 @@ pub unsafe fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
         }
         let mut options = OpenOptions::new();
         options.read(true);
-        let file = axfs::fops::File::open(path?, &options)?;
+        let path = path?;
+        let file = axfs::fops::File::open(path, &options).map_err(path_error)?;
         let st = File::new(file).stat()?;
         unsafe { *buf = st };
         Ok(0)
@@ -167,6 +382,63 @@ pub unsafe fn sys_fstat(fd: c_int, buf: *mut ctypes::stat) -> c_int {
     })
 }
 
+/// Convert the statistics of a filesystem to `struct statfs`.
+fn stats_to_statfs(stats: &FsStats) -> ctypes::statfs {
+    ctypes::statfs {
+        f_type: stats.magic as _,
+        f_bsize: stats.block_size as _,
+        f_blocks: stats.blocks,
+        f_bfree: stats.free_blocks,
+        f_bavail: stats.avail_blocks,
+        f_files: stats.files,
+        f_ffree: stats.free_files,
+        f_namelen: stats.name_max as _,
+        f_frsize: stats.block_size as _,
+        ..Default::default()
+    }
+}
+
+/// Get the statistics of the filesystem that contains the file at `path`
+/// and write into `buf`.
+///
+/// Return 0 if success.
+pub unsafe fn sys_statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_statfs <= {:?} {:#x}", path, buf as usize);
+    syscall_body!(sys_statfs, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let path = path?;
+        let stats = axfs::fops::statfs(path).map_err(path_error)?;
+        unsafe { *buf = stats_to_statfs(&stats) };
+        Ok(0)
+    })
+}
+
+/// Get the statistics of the filesystem that contains the file or directory
+/// indicated by `fd` and write into `buf`.
+///
+/// Return 0 if success.
+pub unsafe fn sys_fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    debug!("sys_fstatfs <= {} {:#x}", fd, buf as usize);
+    syscall_body!(sys_fstatfs, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let f = get_file_like(fd)?.into_any();
+        let stats = if let Some(file) = f.downcast_ref::<File>() {
+            file.inner.lock().statfs()?
+        } else if let Some(dir) = f.downcast_ref::<Directory>() {
+            dir.inner.lock().statfs()?
+        } else {
+            return Err(LinuxError::EINVAL);
+        };
+        unsafe { *buf = stats_to_statfs(&stats) };
+        Ok(0)
+    })
+}
+
 /// Get the metadata of the symbolic link and write into `buf`.
 ///
 /// Return 0 if success.
@@ -177,7 +449,52 @@ pub unsafe fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> ctypes::
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        unsafe { *buf = Default::default() }; // TODO
+        let path = path?;
+        let attr = axfs::fops::symlink_attr(path).map_err(path_error)?;
+        unsafe { *buf = attr_to_stat(&attr) };
+        Ok(0)
+    })
+}
+
+/// Get the metadata of the file at `path` relative to the directory `dirfd`
+/// and write into `buf`.
+///
+/// The symbolic link at the end of `path` is not followed if `flags` contains
+/// `AT_SYMLINK_NOFOLLOW`. If `path` is empty and `flags` contains
+/// `AT_EMPTY_PATH`, the metadata of `dirfd` itself is returned.
+///
+/// Return 0 if success.
+pub unsafe fn sys_fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_fstatat <= {} {:?} {:#x} {:#x}",
+        dirfd, path, buf as usize, flags
+    );
+    syscall_body!(sys_fstatat, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let flags = flags as u32;
+        if flags & !(ctypes::AT_SYMLINK_NOFOLLOW | ctypes::AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let path = path?;
+        if path.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 {
+            unsafe { *buf = get_file_like(dirfd)?.stat()? };
+            return Ok(0);
+        }
+        let path = path_at(dirfd, path)?;
+        let attr = if flags & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            axfs::fops::symlink_attr(&path)
+        } else {
+            axfs::fops::attr(&path)
+        };
+        unsafe { *buf = attr_to_stat(&attr.map_err(path_error)?) };
         Ok(0)
     })
 }
@@ -202,6 +519,69 @@ pub fn sys_getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
     })
 }
 
+/// Change the current directory to `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_chdir(path: *const c_char) -> c_int {
+    syscall_body!(sys_chdir, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_chdir <= {:?}", path);
+        axfs::api::set_current_dir(path).map_err(path_error)?;
+        Ok(0)
+    })
+}
+
+/// Change the current directory to the directory opened as `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchdir(fd: c_int) -> c_int {
+    debug!("sys_fchdir <= {}", fd);
+    syscall_body!(sys_fchdir, {
+        let dir = Directory::from_fd(fd)?;
+        let path = dir.inner.lock().path();
+        axfs::api::set_current_dir(&path)?;
+        Ok(0)
+    })
+}
+
+/// Create a directory at `path` relative to the directory `dirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    syscall_body!(sys_mkdirat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_mkdirat <= {} {:?} {:#o}", dirfd, path, mode);
+        let path = path_at(dirfd, path)?;
+        axfs::api::DirBuilder::new()
+            .mode(mode as u32 & 0o777)
+            .create(&path)
+            .map_err(path_error)?;
+        Ok(0)
+    })
+}
+
+/// Remove the file at `path` relative to the directory `dirfd`, or the empty
+/// directory if `flags` contains `AT_REMOVEDIR`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_unlinkat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_unlinkat <= {} {:?} {:#x}", dirfd, path, flags);
+        let flags = flags as u32;
+        if flags & !ctypes::AT_REMOVEDIR != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let path = path_at(dirfd, path)?;
+        if flags & ctypes::AT_REMOVEDIR != 0 {
+            axfs::api::remove_dir(&path).map_err(path_error)?;
+        } else {
+            axfs::api::remove_file(&path).map_err(path_error)?;
+        }
+        Ok(0)
+    })
+}
+
 /// Rename `old` to `new`
 /// If new exists, it is first removed.
 ///
@@ -211,7 +591,331 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         let old_path = char_ptr_to_str(old)?;
         let new_path = char_ptr_to_str(new)?;
         debug!("sys_rename <= old: {:?}, new: {:?}", old_path, new_path);
-        axfs::api::rename(old_path, new_path)?;
+        axfs::api::rename(old_path, new_path).map_err(path_error)?;
         Ok(0)
     })
 }
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to the
+/// directory `newdirfd`. If `new` exists, it is first removed.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_renameat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+) -> c_int {
+    syscall_body!(sys_renameat, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!(
+            "sys_renameat <= olddirfd: {}, old: {:?}, newdirfd: {}, new: {:?}",
+            olddirfd, old_path, newdirfd, new_path
+        );
+        let old_path = path_at(olddirfd, old_path)?;
+        let new_path = path_at(newdirfd, new_path)?;
+        axfs::api::rename(&old_path, &new_path).map_err(path_error)?;
+        Ok(0)
+    })
+}
+
+/// Create a symbolic link at `linkpath` relative to the directory `newdirfd`,
+/// which contains the string `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_symlinkat(target: *const c_char, newdirfd: c_int, linkpath: *const c_char) -> c_int {
+    syscall_body!(sys_symlinkat, {
+        let target = char_ptr_to_str(target)?;
+        let linkpath = char_ptr_to_str(linkpath)?;
+        debug!(
+            "sys_symlinkat <= target: {:?}, newdirfd: {}, linkpath: {:?}",
+            target, newdirfd, linkpath
+        );
+        let linkpath = path_at(newdirfd, linkpath)?;
+        axfs::api::symlink(target, &linkpath).map_err(path_error)?;
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link at `path` relative to the directory
+/// `dirfd` into `buf`, which is not null-terminated.
+///
+/// Return the number of bytes placed in `buf`.
+pub fn sys_readlinkat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsize: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_readlinkat, {
+        let path = char_ptr_to_str(path)?;
+        debug!(
+            "sys_readlinkat <= dirfd: {}, path: {:?}, buf: {:#x}, bufsize: {}",
+            dirfd, path, buf as usize, bufsize
+        );
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let path = path_at(dirfd, path)?;
+        let target = axfs::api::read_link(&path).map_err(path_error)?;
+        let len = target.len().min(bufsize);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        dst.copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len)
+    })
+}
+
+/// Create a hard link at `newpath` relative to the directory `newdirfd`, to
+/// the file at `oldpath` relative to the directory `olddirfd`.
+///
+/// The symbolic link at the end of `oldpath` is followed only if `flags`
+/// contains `AT_SYMLINK_FOLLOW`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_linkat(
+    olddirfd: c_int,
+    oldpath: *const c_char,
+    newdirfd: c_int,
+    newpath: *const c_char,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_linkat, {
+        let oldpath = char_ptr_to_str(oldpath)?;
+        let newpath = char_ptr_to_str(newpath)?;
+        debug!(
+            "sys_linkat <= olddirfd: {}, oldpath: {:?}, newdirfd: {}, newpath: {:?}, flags: {:#x}",
+            olddirfd, oldpath, newdirfd, newpath, flags
+        );
+        if flags as u32 & !ctypes::AT_SYMLINK_FOLLOW != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let oldpath = path_at(olddirfd, oldpath)?;
+        let newpath = path_at(newdirfd, newpath)?;
+        if flags as u32 & ctypes::AT_SYMLINK_FOLLOW != 0 {
+            let oldpath = axfs::api::canonicalize(&oldpath).map_err(path_error)?;
+            axfs::api::hard_link(&oldpath, &newpath).map_err(path_error)?;
+        } else {
+            axfs::api::hard_link(&oldpath, &newpath).map_err(path_error)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Change the permissions of the file at `path` relative to the directory
+/// `dirfd`, following symbolic links.
+///
+/// `flags` can only be 0, since the permissions of symbolic links cannot be
+/// changed. Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchmodat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t, flags: c_int) -> c_int {
+    syscall_body!(sys_fchmodat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_fchmodat <= {} {:?} {:#o} {:#x}", dirfd, path, mode, flags);
+        match flags as u32 {
+            0 => {}
+            ctypes::AT_SYMLINK_NOFOLLOW => return Err(LinuxError::EOPNOTSUPP),
+            _ => return Err(LinuxError::EINVAL),
+        }
+        let perm = axfs::api::Permissions::from_bits_truncate(mode as u16);
+        let path = path_at(dirfd, path)?;
+        axfs::api::set_permissions(&path, perm).map_err(owner_error)?;
+        Ok(0)
+    })
+}
+
+/// Change the permissions of the file or the directory indicated by `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("sys_fchmod <= {} {:#o}", fd, mode);
+    syscall_body!(sys_fchmod, {
+        let perm = axfs::api::Permissions::from_bits_truncate(mode as u16);
+        match File::from_fd(fd) {
+            Ok(file) => file.inner.lock().set_perm(perm).map_err(owner_error)?,
+            Err(_) => {
+                let path = Directory::from_fd(fd)?.inner.lock().path();
+                axfs::api::set_permissions(&path, perm).map_err(owner_error)?;
+            }
+        }
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the file at `path` relative to the
+/// directory `dirfd`. They are not changed if they are -1.
+///
+/// The symbolic link at the end of `path` is not followed if `flags` contains
+/// `AT_SYMLINK_NOFOLLOW`. Return 0 if the operation succeeds, otherwise
+/// return -1.
+pub fn sys_fchownat(
+    dirfd: c_int,
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_fchownat, {
+        let path = char_ptr_to_str(path)?;
+        debug!(
+            "sys_fchownat <= {} {:?} {} {} {:#x}",
+            dirfd, path, owner as i32, group as i32, flags
+        );
+        let flags = flags as u32;
+        if flags & !ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let path = path_at(dirfd, path)?;
+        let (uid, gid) = (id_arg(owner), id_arg(group));
+        if flags & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            axfs::api::lchown(&path, uid, gid).map_err(owner_error)?;
+        } else {
+            axfs::api::chown(&path, uid, gid).map_err(owner_error)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the file or the directory indicated by
+/// `fd`. They are not changed if they are -1.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    debug!("sys_fchown <= {} {} {}", fd, owner as i32, group as i32);
+    syscall_body!(sys_fchown, {
+        let (uid, gid) = (id_arg(owner), id_arg(group));
+        match File::from_fd(fd) {
+            Ok(file) => file.inner.lock().set_owner(uid, gid).map_err(owner_error)?,
+            Err(_) => {
+                let path = Directory::from_fd(fd)?.inner.lock().path();
+                axfs::api::chown(&path, uid, gid).map_err(owner_error)?;
+            }
+        }
+        Ok(0)
+    })
+}
+
+/// Set the umask of the current task to `mask`, i.e. the permissions cleared
+/// from new files and directories.
+///
+/// Return the previous umask.
+pub fn sys_umask(mask: ctypes::mode_t) -> ctypes::mode_t {
+    debug!("sys_umask <= {:#o}", mask);
+    axfs::api::umask(mask as u32 & 0o777) as _
+}
+
+/// Converts a user or group ID argument, where -1 means unchanged.
+fn id_arg(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
+}
+
+/// Convert errors of permission and owner changes of files to [`LinuxError`]. Changes that the caller is not allowed to make, or that the
+/// filesystem cannot keep, fail with `EPERM`.
+fn owner_error(err: AxError) -> LinuxError {
+    match err {
+        AxError::PermissionDenied | AxError::Unsupported => LinuxError::EPERM,
+        err => path_error(err),
+    }
+}
+
+/// Apply or remove an advisory lock on the whole file indicated by `fd`.
+///
+/// `operation` is one of `LOCK_SH`, `LOCK_EX` and `LOCK_UN`, optionally
+/// combined with `LOCK_NB` to fail with `EWOULDBLOCK` instead of waiting.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("sys_flock <= {} {:#x}", fd, operation);
+    syscall_body!(sys_flock, {
+        let operation = operation as u32;
+        let wait = operation & ctypes::LOCK_NB == 0;
+        let locks = File::from_fd(fd)?.inner.lock().locks();
+        match operation & !ctypes::LOCK_NB {
+            ctypes::LOCK_SH => locks.lock(LockType::Read, wait)?,
+            ctypes::LOCK_EX => locks.lock(LockType::Write, wait)?,
+            ctypes::LOCK_UN => locks.unlock(),
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    })
+}
+
+/// Handle the record lock commands of `fcntl` on the file indicated by `fd`:
+/// `F_GETLK`, `F_SETLK` and `F_SETLKW`, with the lock description at `arg`.
+pub(crate) fn fcntl_lock(fd: c_int, cmd: u32, arg: *mut ctypes::flock) -> LinuxResult<c_int> {
+    if arg.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let flock = unsafe { &mut *arg };
+    let file = File::from_fd(fd).map_err(|_| LinuxError::EBADF)?;
+    let (locks, base) = {
+        let mut inner = file.inner.lock();
+        let base = match flock.l_whence {
+            0 => 0,
+            1 => inner.seek(SeekFrom::Current(0))?,
+            2 => inner.get_attr()?.size(),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        (inner.locks(), base)
+    };
+
+    // a length of 0 extends the range to the end of the file
+    let start = base
+        .checked_add_signed(flock.l_start as i64)
+        .ok_or(LinuxError::EINVAL)?;
+    let len = flock.l_len as i64;
+    let (start, end) = match len {
+        0 => (start, u64::MAX),
+        1.. => (start, start.saturating_add(len as u64)),
+        _ => (
+            start.checked_add_signed(len).ok_or(LinuxError::EINVAL)?,
+            start,
+        ),
+    };
+    let owner = lock_owner();
+    let ty = match flock.l_type as u32 {
+        ctypes::F_RDLCK => Some(LockType::Read),
+        ctypes::F_WRLCK => Some(LockType::Write),
+        ctypes::F_UNLCK => None,
+        _ => return Err(LinuxError::EINVAL),
+    };
+
+    match (cmd, ty) {
+        (ctypes::F_GETLK, ty) => {
+            let ty = ty.ok_or(LinuxError::EINVAL)?;
+            let lock = RecordLock {
+                ty,
+                start,
+                end,
+                owner,
+            };
+            match locks.conflicting_record_lock(&lock) {
+                Some(other) => {
+                    flock.l_type = match other.ty {
+                        LockType::Read => ctypes::F_RDLCK,
+                        LockType::Write => ctypes::F_WRLCK,
+                    } as _;
+                    flock.l_whence = 0;
+                    flock.l_start = other.start as _;
+                    flock.l_len = match other.end {
+                        u64::MAX => 0,
+                        end => (end - other.start) as _,
+                    };
+                    flock.l_pid = other.owner as _;
+                }
+                None => flock.l_type = ctypes::F_UNLCK as _,
+            }
+        }
+        (_, None) => locks.unlock_records(owner, start, end),
+        (cmd, Some(ty)) => {
+            let lock = RecordLock {
+                ty,
+                start,
+                end,
+                owner,
+            };
+            let wait = cmd == ctypes::F_SETLKW;
+            locks.set_record_lock(lock, wait).map_err(lock_error)?;
+        }
+    }
+    Ok(0)
+}
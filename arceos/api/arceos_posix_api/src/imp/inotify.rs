@@ -0,0 +1,154 @@
+use alloc::sync::Arc;
+use core::ffi::{c_char, c_int};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::notify::{Event, EventMask, Watcher};
+use axio::PollState;
+
+use super::fd_ops::{add_file_like, get_file_like, FileLike};
+use crate::{ctypes, utils::char_ptr_to_str};
+
+/// An inotify instance, which reads the events of its watches as a sequence
+/// of `struct inotify_event`.
+pub struct Inotify {
+    watcher: Watcher,
+    nonblocking: AtomicBool,
+}
+
+impl Inotify {
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+}
+
+/// The length of an event as a `struct inotify_event`, whose name is padded
+/// with zeros to a multiple of the header size, as Linux does.
+fn event_len(event: &Event) -> usize {
+    size_of::<ctypes::inotify_event>() + name_len(event)
+}
+
+fn name_len(event: &Event) -> usize {
+    if event.name.is_empty() {
+        0
+    } else {
+        (event.name.len() + 1).next_multiple_of(size_of::<ctypes::inotify_event>())
+    }
+}
+
+impl FileLike for Inotify {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let mut read_len = 0;
+        loop {
+            let remaining = buf.len() - read_len;
+            match self.watcher.read_event_if(|e| event_len(e) <= remaining) {
+                Some(event) => {
+                    let name_len = name_len(&event);
+                    let header = [
+                        event.wd.to_ne_bytes(),
+                        event.mask.bits().to_ne_bytes(),
+                        event.cookie.to_ne_bytes(),
+                        (name_len as u32).to_ne_bytes(),
+                    ];
+                    let record = &mut buf[read_len..read_len + event_len(&event)];
+                    let (head, name) = record.split_at_mut(size_of::<ctypes::inotify_event>());
+                    for (field, bytes) in head.chunks_exact_mut(4).zip(header) {
+                        field.copy_from_slice(&bytes);
+                    }
+                    name.fill(0);
+                    name[..event.name.len()].copy_from_slice(event.name.as_bytes());
+                    read_len += record.len();
+                }
+                None if read_len > 0 => return Ok(read_len),
+                // the next event does not fit in the buffer
+                None if self.watcher.has_events() => return Err(LinuxError::EINVAL),
+                None if self.nonblocking.load(Ordering::Relaxed) => return Err(LinuxError::EAGAIN),
+                None => crate::sys_sched_yield(), // TODO: wait for events to be queued
+            }
+        }
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let st_mode = 0o600u32; // rw-------
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: self.watcher.has_events(),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Create an inotify instance, whose file descriptor is readable when events
+/// of its watches are queued. `flags` may contain `IN_NONBLOCK` and
+/// `IN_CLOEXEC`.
+///
+/// Return the file descriptor of the instance.
+pub fn sys_inotify_init1(flags: c_int) -> c_int {
+    debug!("sys_inotify_init1 <= {:#x}", flags);
+    syscall_body!(sys_inotify_init1, {
+        let flags = flags as u32;
+        if flags & !(ctypes::IN_NONBLOCK | ctypes::IN_CLOEXEC) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let inotify = Inotify {
+            watcher: Watcher::new(),
+            nonblocking: AtomicBool::new(flags & ctypes::IN_NONBLOCK != 0),
+        };
+        add_file_like(Arc::new(inotify))
+    })
+}
+
+/// Watch the file or directory at `path` for the events in `mask`, with the
+/// inotify instance `fd`. Only `IN_CREATE`, `IN_MODIFY`, `IN_DELETE`,
+/// `IN_MOVED_FROM` and `IN_MOVED_TO` are reported, other events and flags are
+/// ignored.
+///
+/// Return the watch descriptor, which is the same for the same path.
+pub fn sys_inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_inotify_add_watch <= {} {:?} {:#x}", fd, path, mask);
+    syscall_body!(sys_inotify_add_watch, {
+        let inotify = Inotify::from_fd(fd)?;
+        let mask = EventMask::from_bits_truncate(mask) & EventMask::ALL_EVENTS;
+        if mask.is_empty() {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(inotify.watcher.add_watch(path?, mask)?)
+    })
+}
+
+/// Remove the watch `wd` from the inotify instance `fd`.
+///
+/// Return 0 if succeed.
+pub fn sys_inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    debug!("sys_inotify_rm_watch <= {} {}", fd, wd);
+    syscall_body!(sys_inotify_rm_watch, {
+        Inotify::from_fd(fd)?.watcher.remove_watch(wd)?;
+        Ok(0)
+    })
+}
@@ -6,10 +6,14 @@ pub mod sys;
 pub mod task;
 pub mod time;
 
+#[cfg(feature = "fs")]
+pub mod cred;
 #[cfg(feature = "fd")]
 pub mod fd_ops;
 #[cfg(feature = "fs")]
 pub mod fs;
+#[cfg(feature = "fs")]
+pub mod inotify;
 #[cfg(any(feature = "select", feature = "epoll"))]
 pub mod io_mpx;
 #[cfg(feature = "net")]
@@ -10,6 +10,11 @@ use crate::ctypes;
 
 pub mod mutex;
 
+// Indices of the fields of `pthread_attr_t` in `__u.__s`, see `pthread.h`.
+const ATTR_STACKSIZE: usize = 0;
+const ATTR_CPUSET: usize = 3;
+const ATTR_CPUSETSIZE: usize = 4;
+
 lazy_static::lazy_static! {
     static ref TID_TO_PTHREAD: RwLock<BTreeMap<u64, ForceSendSync<ctypes::pthread_t>>> = {
         let mut map = BTreeMap::new();
@@ -41,10 +46,29 @@ pub struct Pthread {
 
 impl Pthread {
     fn create(
-        _attr: *const ctypes::pthread_attr_t,
+        attr: *const ctypes::pthread_attr_t,
         start_routine: extern "C" fn(arg: *mut c_void) -> *mut c_void,
         arg: *mut c_void,
     ) -> LinuxResult<ctypes::pthread_t> {
+        let mut stack_size = axconfig::TASK_STACK_SIZE;
+        let mut cpumask = None;
+        if let Some(attr) = unsafe { attr.as_ref() } {
+            let fields = unsafe { &attr.__u.__s };
+            if fields[ATTR_STACKSIZE] != 0 {
+                stack_size = fields[ATTR_STACKSIZE] as usize;
+            }
+            let cpuset = fields[ATTR_CPUSET] as *const ctypes::cpu_set_t;
+            if !cpuset.is_null() {
+                let mask = unsafe {
+                    crate::imp::task::read_cpu_set(fields[ATTR_CPUSETSIZE] as usize, cpuset)
+                };
+                if mask.is_empty() {
+                    return Err(LinuxError::EINVAL);
+                }
+                cpumask = Some(mask);
+            }
+        }
+
         let arg_wrapper = ForceSendSync(arg);
 
         let my_packet: Arc<Packet<*mut c_void>> = Arc::new(Packet {
@@ -59,7 +83,11 @@ impl Pthread {
             drop(their_packet);
         };
 
-        let task_inner = axtask::spawn(main);
+        let task = axtask::TaskInner::new(main, "".into(), stack_size);
+        if let Some(cpumask) = cpumask {
+            task.set_cpumask(cpumask);
+        }
+        let task_inner = axtask::spawn_task(task);
         let tid = task_inner.id().as_u64();
         let thread = Pthread {
             inner: task_inner,
@@ -1,6 +1,55 @@
 use crate::ctypes;
 use axerrno::LinuxError;
-use core::ffi::c_int;
+use core::ffi::{c_int, c_long};
+use core::time::Duration;
+
+/// The CPU time and context switches of a thread, or all the threads.
+#[derive(Default)]
+pub(crate) struct Usage {
+    pub user_time: Duration,
+    pub kernel_time: Duration,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+}
+
+impl Usage {
+    /// Gets the usage of the current thread if `thread`, otherwise of all
+    /// the threads except the idle ones, which are taken as the process.
+    #[cfg(feature = "multitask")]
+    pub(crate) fn get(thread: bool) -> Self {
+        let mut usage = Self::default();
+        let mut add = |stats: axtask::TaskStats| {
+            usage.user_time += stats.user_time;
+            usage.kernel_time += stats.kernel_time;
+            usage.voluntary_switches += stats.voluntary_switches;
+            usage.involuntary_switches += stats.involuntary_switches;
+        };
+        if thread {
+            add(axtask::current().stats());
+        } else {
+            axtask::for_each_task(|task| {
+                if !task.is_idle() {
+                    add(task.stats());
+                }
+            });
+        }
+        usage
+    }
+
+    /// Gets the usage of the only thread, which has been running in the
+    /// kernel since booting.
+    #[cfg(not(feature = "multitask"))]
+    pub(crate) fn get(_thread: bool) -> Self {
+        Self {
+            kernel_time: axhal::time::monotonic_time(),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn cpu_time(&self) -> Duration {
+        self.user_time + self.kernel_time
+    }
+}
 
 /// Get resource limitations
 ///
@@ -49,3 +98,33 @@ pub unsafe fn sys_setrlimit(resource: c_int, rlimits: *mut crate::ctypes::rlimit
         Ok(0)
     })
 }
+
+/// Get resource usage of the process (`RUSAGE_SELF`), or the calling thread
+/// (`RUSAGE_THREAD`)
+///
+/// There are no child processes, so the usage of `RUSAGE_CHILDREN` is zero.
+pub unsafe fn sys_getrusage(who: c_int, usage: *mut ctypes::rusage) -> c_int {
+    debug!("sys_getrusage <= {} {:#x}", who, usage as usize);
+    syscall_body!(sys_getrusage, {
+        const RUSAGE_SELF: c_int = ctypes::RUSAGE_SELF as c_int;
+        const RUSAGE_THREAD: c_int = ctypes::RUSAGE_THREAD as c_int;
+        const RUSAGE_CHILDREN: c_int = ctypes::RUSAGE_CHILDREN as c_int;
+        let res = match who {
+            RUSAGE_SELF => Usage::get(false),
+            RUSAGE_THREAD => Usage::get(true),
+            RUSAGE_CHILDREN => Usage::default(),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        if usage.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        unsafe {
+            *usage = core::mem::zeroed();
+            (*usage).ru_utime = res.user_time.into();
+            (*usage).ru_stime = res.kernel_time.into();
+            (*usage).ru_nvcsw = res.voluntary_switches as c_long;
+            (*usage).ru_nivcsw = res.involuntary_switches as c_long;
+        }
+        Ok(0)
+    })
+}
@@ -17,6 +17,8 @@ pub fn sys_sysconf(name: c_int) -> c_long {
             ctypes::_SC_PHYS_PAGES => Ok(axconfig::PHYS_MEMORY_SIZE / PAGE_SIZE_4K),
             // Number of processors in use
             ctypes::_SC_NPROCESSORS_ONLN => Ok(axconfig::SMP),
+            // Clock ticks per second, in which `times` reports
+            ctypes::_SC_CLK_TCK => Ok(super::time::CLK_TCK as usize),
             // Avaliable physical pages
             #[cfg(feature = "alloc")]
             ctypes::_SC_AVPHYS_PAGES => Ok(axalloc::global_allocator().available_pages()),
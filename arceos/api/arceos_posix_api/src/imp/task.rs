@@ -1,5 +1,19 @@
 use core::ffi::c_int;
 
+#[cfg(feature = "multitask")]
+use {
+    crate::{
+        ctypes,
+        utils::{check_null_mut_ptr, check_null_ptr},
+    },
+    axerrno::{LinuxError, LinuxResult},
+    axtask::{AxTaskRef, CpuMask, SchedPolicy},
+    core::ffi::c_ulong,
+};
+
+#[cfg(feature = "multitask")]
+const BITS_PER_WORD: usize = c_ulong::BITS as usize;
+
 /// Relinquish the CPU, and switches to another task.
 ///
 /// For single-threaded configuration (`multitask` feature is disabled), we just
@@ -38,3 +52,212 @@ pub fn sys_exit(exit_code: c_int) -> ! {
     #[cfg(not(feature = "multitask"))]
     axhal::misc::terminate();
 }
+
+/// Finds the task with the ID `pid`, or the current task if `pid` is 0.
+#[cfg(feature = "multitask")]
+fn find_task(pid: c_int) -> LinuxResult<AxTaskRef> {
+    if pid == 0 {
+        return Ok(axtask::current().as_task_ref().clone());
+    }
+    let mut found = None;
+    axtask::for_each_task(|task| {
+        if task.id().as_u64() == pid as u64 {
+            found = Some(task.clone());
+        }
+    });
+    found.ok_or(LinuxError::ESRCH)
+}
+
+/// Converts the `SCHED_*` constant `policy` to a [`SchedPolicy`].
+#[cfg(feature = "multitask")]
+fn sched_policy(policy: c_int) -> LinuxResult<SchedPolicy> {
+    match policy as u32 {
+        ctypes::SCHED_OTHER => Ok(SchedPolicy::Normal),
+        ctypes::SCHED_FIFO => Ok(SchedPolicy::Fifo),
+        ctypes::SCHED_RR => Ok(SchedPolicy::RoundRobin),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Sets the scheduling policy and the static priority of `task`.
+#[cfg(feature = "multitask")]
+fn set_sched_params(task: &AxTaskRef, policy: SchedPolicy, priority: c_int) -> LinuxResult {
+    let priority = u8::try_from(priority).map_err(|_| LinuxError::EINVAL)?;
+    if !axtask::set_sched_params(task, policy, priority) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(())
+}
+
+/// Reads the CPU set of `cpusetsize` bytes at `cpuset`. Trailing bytes that
+/// do not fill a whole `unsigned long` are ignored.
+#[cfg(feature = "multitask")]
+pub(crate) unsafe fn read_cpu_set(cpusetsize: usize, cpuset: *const ctypes::cpu_set_t) -> CpuMask {
+    let words = unsafe {
+        core::slice::from_raw_parts(
+            cpuset as *const c_ulong,
+            cpusetsize / core::mem::size_of::<c_ulong>(),
+        )
+    };
+    (0..words.len() * BITS_PER_WORD)
+        .filter(|&cpu| words[cpu / BITS_PER_WORD] & (1 << (cpu % BITS_PER_WORD)) != 0)
+        .collect()
+}
+
+/// Writes `cpumask` to the CPU set of `cpusetsize` bytes at `cpuset`, which
+/// must be a multiple of the size of `unsigned long` and large enough for all
+/// CPUs.
+#[cfg(feature = "multitask")]
+unsafe fn write_cpu_set(
+    cpumask: &CpuMask,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> LinuxResult {
+    if cpusetsize % core::mem::size_of::<c_ulong>() != 0 || cpusetsize * 8 < axconfig::SMP {
+        return Err(LinuxError::EINVAL);
+    }
+    let words = unsafe {
+        core::slice::from_raw_parts_mut(
+            cpuset as *mut c_ulong,
+            cpusetsize / core::mem::size_of::<c_ulong>(),
+        )
+    };
+    words.fill(0);
+    for cpu in cpumask.iter() {
+        words[cpu / BITS_PER_WORD] |= 1 << (cpu % BITS_PER_WORD);
+    }
+    Ok(())
+}
+
+/// Set the CPU affinity of the task `pid`, or the current task if `pid` is 0,
+/// to the CPU set of `cpusetsize` bytes at `cpuset`.
+///
+/// The current task is moved to an allowed CPU before returning.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_setaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    cpuset: *const ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_setaffinity <= {} {} {:#x}",
+        pid, cpusetsize, cpuset as usize
+    );
+    syscall_body!(sys_sched_setaffinity, {
+        check_null_ptr(cpuset)?;
+        let cpumask = unsafe { read_cpu_set(cpusetsize, cpuset) };
+        let task = find_task(pid)?;
+        let ok = if task.id() == axtask::current().id() {
+            axtask::set_affinity(cpumask)
+        } else {
+            axtask::set_task_affinity(&task, cpumask)
+        };
+        if !ok {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Get the CPU affinity of the task `pid`, or the current task if `pid` is 0,
+/// into the CPU set of `cpusetsize` bytes at `cpuset`.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_getaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_getaffinity <= {} {} {:#x}",
+        pid, cpusetsize, cpuset as usize
+    );
+    syscall_body!(sys_sched_getaffinity, {
+        check_null_mut_ptr(cpuset)?;
+        let task = find_task(pid)?;
+        unsafe { write_cpu_set(&task.cpumask(), cpusetsize, cpuset)? };
+        Ok(0)
+    })
+}
+
+/// Set the scheduling policy and the static priority of the task `pid`, or the
+/// current task if `pid` is 0.
+///
+/// Real-time policies (`SCHED_FIFO` and `SCHED_RR`) are only supported by the
+/// `sched_rt` scheduler.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_setscheduler(
+    pid: c_int,
+    policy: c_int,
+    param: *const ctypes::sched_param,
+) -> c_int {
+    debug!(
+        "sys_sched_setscheduler <= {} {} {:#x}",
+        pid, policy, param as usize
+    );
+    syscall_body!(sys_sched_setscheduler, {
+        check_null_ptr(param)?;
+        let task = find_task(pid)?;
+        let priority = unsafe { (*param).sched_priority };
+        set_sched_params(&task, sched_policy(policy)?, priority)?;
+        Ok(0)
+    })
+}
+
+/// Get the scheduling policy of the task `pid`, or the current task if `pid`
+/// is 0.
+#[cfg(feature = "multitask")]
+pub fn sys_sched_getscheduler(pid: c_int) -> c_int {
+    debug!("sys_sched_getscheduler <= {}", pid);
+    syscall_body!(sys_sched_getscheduler, {
+        Ok(find_task(pid)?.sched_policy() as c_int)
+    })
+}
+
+/// Set the static priority of the task `pid`, or the current task if `pid` is
+/// 0, without changing its scheduling policy.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_setparam(pid: c_int, param: *const ctypes::sched_param) -> c_int {
+    debug!("sys_sched_setparam <= {} {:#x}", pid, param as usize);
+    syscall_body!(sys_sched_setparam, {
+        check_null_ptr(param)?;
+        let task = find_task(pid)?;
+        let priority = unsafe { (*param).sched_priority };
+        set_sched_params(&task, task.sched_policy(), priority)?;
+        Ok(0)
+    })
+}
+
+/// Get the static priority of the task `pid`, or the current task if `pid` is
+/// 0.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_getparam(pid: c_int, param: *mut ctypes::sched_param) -> c_int {
+    debug!("sys_sched_getparam <= {} {:#x}", pid, param as usize);
+    syscall_body!(sys_sched_getparam, {
+        check_null_mut_ptr(param)?;
+        let task = find_task(pid)?;
+        unsafe { (*param).sched_priority = task.rt_priority() as c_int };
+        Ok(0)
+    })
+}
+
+/// Get the highest static priority of the scheduling policy `policy`.
+#[cfg(feature = "multitask")]
+pub fn sys_sched_get_priority_max(policy: c_int) -> c_int {
+    syscall_body!(sys_sched_get_priority_max, {
+        match sched_policy(policy)? {
+            SchedPolicy::Normal => Ok(0),
+            _ => Ok(axtask::RT_PRIORITY_MAX as c_int),
+        }
+    })
+}
+
+/// Get the lowest static priority of the scheduling policy `policy`.
+#[cfg(feature = "multitask")]
+pub fn sys_sched_get_priority_min(policy: c_int) -> c_int {
+    syscall_body!(sys_sched_get_priority_min, {
+        match sched_policy(policy)? {
+            SchedPolicy::Normal => Ok(0),
+            _ => Ok(axtask::RT_PRIORITY_MIN as c_int),
+        }
+    })
+}
@@ -3,7 +3,14 @@ use core::ffi::{c_int, c_long};
 use core::time::Duration;
 
 use crate::ctypes;
-use crate::ctypes::{CLOCK_MONOTONIC, CLOCK_REALTIME};
+use crate::ctypes::{
+    CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_THREAD_CPUTIME_ID,
+};
+use crate::imp::resources::Usage;
+
+/// The number of clock ticks per second, in which [`sys_times`] reports the
+/// times.
+pub(crate) const CLK_TCK: u64 = 100;
 
 impl From<ctypes::timespec> for Duration {
     fn from(ts: ctypes::timespec) -> Self {
@@ -44,6 +51,8 @@ pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespe
         let now = match clk as u32 {
             CLOCK_REALTIME => axhal::time::wall_time().into(),
             CLOCK_MONOTONIC => axhal::time::monotonic_time().into(),
+            CLOCK_PROCESS_CPUTIME_ID => Usage::get(false).cpu_time().into(),
+            CLOCK_THREAD_CPUTIME_ID => Usage::get(true).cpu_time().into(),
             _ => {
                 warn!("Called sys_clock_gettime for unsupported clock {}", clk);
                 return Err(LinuxError::EINVAL);
@@ -55,6 +64,30 @@ pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespe
     })
 }
 
+/// Get the CPU times of the process, in clock ticks
+///
+/// Returns the clock ticks since booting.
+pub unsafe fn sys_times(buf: *mut ctypes::tms) -> ctypes::clock_t {
+    fn to_ticks(d: Duration) -> ctypes::clock_t {
+        (d.as_nanos() * CLK_TCK as u128 / 1_000_000_000) as ctypes::clock_t
+    }
+
+    syscall_body!(sys_times, {
+        if !buf.is_null() {
+            let usage = Usage::get(false);
+            unsafe {
+                *buf = ctypes::tms {
+                    tms_utime: to_ticks(usage.user_time),
+                    tms_stime: to_ticks(usage.kernel_time),
+                    tms_cutime: 0,
+                    tms_cstime: 0,
+                };
+            }
+        }
+        Ok(to_ticks(axhal::time::monotonic_time()))
+    })
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno
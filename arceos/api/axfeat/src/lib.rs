@@ -19,9 +19,17 @@
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//!     - `sched_rt`: Use the CFS along with real-time (FIFO and round-robin) tasks
+//!       with static priorities.
+//!     - `sched_edf`: Use the CFS along with periodic deadline tasks scheduled by
+//!       the earliest deadline first (EDF).
 //! - Upperlayer stacks (fs, net, display)
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext4fs`: Use an ext2/ext4 filesystem instead of FAT as the root filesystem.
+//!     - `initramfs`: Unpack a cpio archive embedded at build time into a ramfs root, which
+//!       needs no block device.
+//!     - `overlay-root`: Keep writes to the root filesystem in memory, leaving the disk intact.
 //!     - `net`: Enable networking support.
 //!     - `display`: Enable graphics support.
 //! - Device drivers
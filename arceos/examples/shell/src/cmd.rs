@@ -19,6 +19,8 @@ type CmdHandler = fn(&str);
 const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("cat", do_cat),
     ("cd", do_cd),
+    #[cfg(feature = "axstd")]
+    ("df", do_df),
     ("echo", do_echo),
     ("exit", do_exit),
     ("help", do_help),
@@ -123,6 +125,59 @@ fn do_ls(args: &str) {
     }
 }
 
+/// Shows the capacity and usage of the filesystems of the given paths, or of
+/// all mounted filesystems, as listed in `/proc/mounts`.
+#[cfg(feature = "axstd")]
+fn do_df(args: &str) {
+    fn show_one(source: &str, path: &str) -> io::Result<()> {
+        let stats = fs::statfs(path)?;
+        let kb = |blocks: u64| blocks * stats.block_size / 1024;
+        let used = stats.blocks.saturating_sub(stats.free_blocks);
+        let usage = match used + stats.avail_blocks {
+            0 => String::from("-"),
+            total => format!("{}%", (used * 100).div_ceil(total)),
+        };
+        println!(
+            "{:<12} {:>10} {:>10} {:>10} {:>4} {}",
+            source,
+            kb(stats.blocks),
+            kb(used),
+            kb(stats.avail_blocks),
+            usage,
+            path
+        );
+        Ok(())
+    }
+
+    let mounts = if args.is_empty() {
+        match fs::read_to_string("/proc/mounts") {
+            // source, target, fstype, options...
+            Ok(mounts) => mounts
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    Some((String::from(fields.next()?), String::from(fields.next()?)))
+                })
+                .collect(),
+            Err(_) => vec![(String::from("rootfs"), String::from("/"))],
+        }
+    } else {
+        args.split_whitespace()
+            .map(|path| (String::from("-"), String::from(path)))
+            .collect::<Vec<_>>()
+    };
+
+    println!(
+        "{:<12} {:>10} {:>10} {:>10} {:>4} Mounted on",
+        "Filesystem", "1K-blocks", "Used", "Available", "Use%"
+    );
+    for (source, path) in mounts {
+        if let Err(e) = show_one(&source, &path) {
+            print_err!("df", path, e);
+        }
+    }
+}
+
 fn do_cat(args: &str) {
     if args.is_empty() {
         print_err!("cat", "no file specified");
@@ -20,8 +20,6 @@ const SYS_EXIT_GROUP: usize = 94;
 const SYS_SET_TID_ADDRESS: usize = 96;
 const SYS_MMAP: usize = 222;
 
-const AT_FDCWD: i32 = -100;
-
 /// Macro to generate syscall body
 ///
 /// It will receive a function which return Result<_, LinuxError> and convert it to
@@ -144,8 +142,7 @@ fn sys_mmap(
 }
 
 fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
-    assert_eq!(dfd, AT_FDCWD);
-    api::sys_open(fname, flags, mode) as isize
+    api::sys_openat(dfd, fname, flags, mode) as isize
 }
 
 fn sys_close(fd: i32) -> isize {
@@ -127,6 +127,21 @@ fn gen_config_rs(config_path: &Path) -> Result<Vec<u8>> {
                         writeln!(output, "pub const {var_name}: &str = \"{s}\";")?;
                     }
                 }
+                Value::Array(mounts) if key == "automount" => {
+                    writeln!(output, "{comments}")?;
+                    writeln!(output, "pub const {var_name}: &[(&str, &str, &str)] = &[")?;
+                    for m in mounts.iter() {
+                        let m = m.as_array().unwrap();
+                        writeln!(
+                            output,
+                            "    ({:?}, {:?}, {:?}),",
+                            m.get(0).unwrap().as_str().unwrap(),
+                            m.get(1).unwrap().as_str().unwrap(),
+                            m.get(2).unwrap().as_str().unwrap()
+                        )?;
+                    }
+                    writeln!(output, "];")?;
+                }
                 Value::Array(regions) => {
                     if key != "mmio-regions" && key != "virtio-mmio-regions" && key != "pci-ranges"
                     {
@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+/// Copies the cpio archive at `AX_INITRAMFS` to `OUT_DIR`, so that it can be
+/// embedded by `include_bytes!`. An empty archive is used if it is not set.
+fn main() {
+    let out_path = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("initramfs.cpio");
+    println!("cargo:rerun-if-env-changed=AX_INITRAMFS");
+
+    let archive = match std::env::var("AX_INITRAMFS") {
+        Ok(path) if !path.is_empty() && std::env::var("CARGO_FEATURE_INITRAMFS").is_ok() => {
+            let mut path = PathBuf::from(path);
+            if path.is_relative() {
+                // relative to the root of the workspace, like `make` does
+                let mut root_dir = PathBuf::from(std::env!("CARGO_MANIFEST_DIR"));
+                root_dir.extend(["..", ".."]);
+                path = root_dir.join(path);
+            }
+            println!("cargo:rerun-if-changed={}", path.display());
+            std::fs::read(&path)
+                .unwrap_or_else(|e| panic!("failed to read initramfs {}: {}", path.display(), e))
+        }
+        _ => Vec::new(),
+    };
+    std::fs::write(out_path, archive).unwrap();
+}
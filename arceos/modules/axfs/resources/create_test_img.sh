@@ -26,5 +26,24 @@ create_test_img() {
 	sudo umount mnt
 }
 
+create_ext2_img() {
+	local name=$1
+	local blkcount=$2
+	local root=`mktemp -d`
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$root/long.txt"
+	done
+	echo "Rust is cool!" >>"$root/short.txt"
+	mkdir -p "$root/very/long/path"
+	echo "Rust is cool!" >>"$root/very/long/path/test.txt"
+	mkdir -p "$root/very-long-dir-name"
+	echo "Rust is cool!" >>"$root/very-long-dir-name/very-long-file-name.txt"
+	# ext2 with 1K blocks, so that indirect blocks are used by small files
+	dd if=/dev/zero of="$name" bs=1024 count=$blkcount
+	mke2fs -q -t ext2 -b 1024 -L "Test!" -d "$root" "$name"
+	rm -rf "$root"
+}
+
 create_test_img "$CUR_DIR/fat16.img" 2500 16
 create_test_img "$CUR_DIR/fat32.img" 34000 32
+create_ext2_img "$CUR_DIR/ext2.img" 4096
@@ -1,7 +1,8 @@
 use axio::{prelude::*, Result, SeekFrom};
-use core::fmt;
+use core::{fmt, time::Duration};
 
 use crate::fops;
+use crate::lock::LockType;
 
 /// A structure representing a type of file with accessors for each file type.
 /// It is returned by [`Metadata::file_type`] method.
@@ -16,7 +17,7 @@ pub struct File {
 }
 
 /// Metadata information about a file.
-pub struct Metadata(fops::FileAttr);
+pub struct Metadata(pub(super) fops::FileAttr);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -64,6 +65,13 @@ impl OpenOptions {
         self
     }
 
+    /// Sets the permissions of the file if it is created, which are masked by
+    /// the umask. It is `0o666` by default.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.0.mode(mode);
+        self
+    }
+
     /// Opens a file at `path` with the options specified by `self`.
     pub fn open(&self, path: &str) -> Result<File> {
         fops::File::open(path, &self.0).map(|inner| File { inner })
@@ -90,6 +98,14 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link. It can only be
+    /// `true` for the metadata returned by [`symlink_metadata`].
+    ///
+    /// [`symlink_metadata`]: super::symlink_metadata
+    pub const fn is_symlink(&self) -> bool {
+        self.0.file_type().is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -110,6 +126,34 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the last access time of the file, as the duration since the
+    /// Unix epoch.
+    pub const fn accessed(&self) -> Duration {
+        self.0.atime()
+    }
+
+    /// Returns the last modification time of the file, as the duration since
+    /// the Unix epoch.
+    pub const fn modified(&self) -> Duration {
+        self.0.mtime()
+    }
+
+    /// Returns the inode number of the file, or 0 if the filesystem does not
+    /// have inode numbers.
+    pub const fn ino(&self) -> u64 {
+        self.0.ino()
+    }
+
+    /// Returns the user ID of the owner of the file.
+    pub const fn uid(&self) -> u32 {
+        self.0.uid()
+    }
+
+    /// Returns the group ID of the owner of the file.
+    pub const fn gid(&self) -> u32 {
+        self.0.gid()
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -158,10 +202,91 @@ impl File {
         self.inner.truncate(size)
     }
 
+    /// Allocates the storage of the `len` bytes at `offset`, extending the file
+    /// if they are beyond its end, unless `keep_size` is `true`.
+    ///
+    /// See `fallocate(2)`.
+    pub fn allocate(&self, offset: u64, len: u64, keep_size: bool) -> Result<()> {
+        self.inner.allocate(offset, len, keep_size)
+    }
+
+    /// Deallocates the `len` bytes at `offset`, which then read as zeros,
+    /// without changing the size of the file.
+    ///
+    /// See `FALLOC_FL_PUNCH_HOLE` in `fallocate(2)`.
+    pub fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.inner.punch_hole(offset, len)
+    }
+
+    /// Seeks to the first byte of data at or after `offset`.
+    ///
+    /// See `SEEK_DATA` in `lseek(2)`.
+    pub fn seek_data(&mut self, offset: u64) -> Result<u64> {
+        self.inner.seek_data(offset)
+    }
+
+    /// Seeks to the first hole at or after `offset`, or to the end of the
+    /// file.
+    ///
+    /// See `SEEK_HOLE` in `lseek(2)`.
+    pub fn seek_hole(&mut self, offset: u64) -> Result<u64> {
+        self.inner.seek_hole(offset)
+    }
+
+    /// Changes the permissions of the underlying file.
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        self.inner.set_perm(perm)
+    }
+
+    /// Changes the owner and the group of the underlying file, unless they are
+    /// `None`.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.inner.set_owner(uid, gid)
+    }
+
     /// Queries metadata about the underlying file.
     pub fn metadata(&self) -> Result<Metadata> {
         self.inner.get_attr().map(Metadata)
     }
+
+    /// Acquires an exclusive advisory lock on the file, blocking until it can
+    /// be acquired.
+    pub fn lock(&self) -> Result<()> {
+        self.inner.locks().lock(LockType::Write, true)
+    }
+
+    /// Acquires a shared advisory lock on the file, blocking until it can be
+    /// acquired.
+    pub fn lock_shared(&self) -> Result<()> {
+        self.inner.locks().lock(LockType::Read, true)
+    }
+
+    /// Tries to acquire an exclusive advisory lock on the file. Returns
+    /// `false` if another handle holds a lock on it.
+    pub fn try_lock(&self) -> Result<bool> {
+        try_lock(self.inner.locks().lock(LockType::Write, false))
+    }
+
+    /// Tries to acquire a shared advisory lock on the file. Returns `false` if
+    /// another handle holds an exclusive lock on it.
+    pub fn try_lock_shared(&self) -> Result<bool> {
+        try_lock(self.inner.locks().lock(LockType::Read, false))
+    }
+
+    /// Releases the advisory lock held by this handle, which is also released
+    /// when the file is closed.
+    pub fn unlock(&self) -> Result<()> {
+        self.inner.locks().unlock();
+        Ok(())
+    }
+}
+
+fn try_lock(res: Result<()>) -> Result<bool> {
+    match res {
+        Ok(()) => Ok(true),
+        Err(axio::Error::WouldBlock) => Ok(false),
+        Err(e) => Err(e),
+    }
 }
 
 impl Read for File {
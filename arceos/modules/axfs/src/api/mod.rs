@@ -5,6 +5,7 @@ mod file;
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use crate::fops::FsStats;
 
 use alloc::{string::String, vec::Vec};
 use axio::{self as io, prelude::*};
@@ -15,9 +16,20 @@ pub fn read_dir(path: &str) -> io::Result<ReadDir> {
 }
 
 /// Returns the canonical, absolute form of a path with all intermediate
-/// components normalized.
+/// components normalized and symbolic links resolved.
 pub fn canonicalize(path: &str) -> io::Result<String> {
-    crate::root::absolute_path(path)
+    crate::root::canonicalize(path)
+}
+
+/// Returns whether the last failed operation of the calling task hit a loop of
+/// symbolic links, and clears the record.
+///
+/// Such failures are reported as [`BadState`](io::Error::BadState), which this
+/// tells apart from other failures, e.g. to report them as `ELOOP`. Loops are
+/// too many levels of symbolic links in a path, or a symbolic link opened
+/// without following it.
+pub fn take_symlink_loop() -> bool {
+    crate::root::take_symlink_loop()
 }
 
 /// Returns the current working directory as a [`String`].
@@ -59,6 +71,58 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     File::open(path)?.metadata()
 }
 
+/// Changes the permissions found on a file or a directory.
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    crate::fops::set_perm(path, perm)
+}
+
+/// Changes the owner and the group of a file or a directory, unless they are
+/// `None`, following symbolic links.
+pub fn chown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    crate::fops::set_owner(path, uid, gid, true)
+}
+
+/// Changes the owner and the group of a file or a directory like [`chown`],
+/// without following the symbolic link at the end of `path`.
+pub fn lchown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    crate::fops::set_owner(path, uid, gid, false)
+}
+
+/// Sets the umask of the calling task, i.e. the permissions cleared from new
+/// files and directories, and returns the old one.
+pub fn umask(mask: u32) -> u32 {
+    crate::root::set_umask(mask)
+}
+
+/// Returns the capacity and usage of the filesystem that the file at `path`
+/// resides in.
+pub fn statfs(path: &str) -> io::Result<FsStats> {
+    crate::fops::statfs(path)
+}
+
+/// Queries the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    crate::fops::symlink_attr(path).map(Metadata)
+}
+
+/// Reads a symbolic link, returning the path that the link points to.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(path)
+}
+
+/// Creates a new symbolic link at `link` that points to `original`.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::symlink(original, link)
+}
+
+/// Creates a new hard link at `link` to the file at `original`.
+///
+/// Hard links are only supported in in-memory filesystems such as `ramfs`, and
+/// both paths must be in the same one.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::link(original, link)
+}
+
 /// Creates a new, empty directory at the provided path.
 pub fn create_dir(path: &str) -> io::Result<()> {
     DirBuilder::new().create(path)
@@ -72,12 +136,12 @@ pub fn create_dir_all(path: &str) -> io::Result<()> {
 
 /// Removes an empty directory.
 pub fn remove_dir(path: &str) -> io::Result<()> {
-    crate::root::remove_dir(None, path)
+    crate::root::remove_dir(path)
 }
 
 /// Removes a file from the filesystem.
 pub fn remove_file(path: &str) -> io::Result<()> {
-    crate::root::remove_file(None, path)
+    crate::root::remove_file(path)
 }
 
 /// Rename a file or directory to a new name.
@@ -87,3 +151,35 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     crate::root::rename(old, new)
 }
+
+/// Mounts a filesystem of type `fstype` at `target`.
+///
+/// The meaning of `source` depends on the filesystem type, and it is ignored
+/// by in-memory filesystems such as `ramfs`.
+pub fn mount(source: &str, target: &str, fstype: &str) -> io::Result<()> {
+    crate::root::mount(source, target, fstype, "")
+}
+
+/// Mounts a filesystem of type `fstype` at `target`, with comma-separated
+/// filesystem-specific `options`.
+///
+/// The only option supported so far is `size=<bytes>` of `ramfs` and `tmpfs`,
+/// which limits the total size of their files. The size may have a `k`, `m`
+/// or `g` suffix. Unknown options fail with
+/// [`InvalidInput`](io::Error::InvalidInput).
+pub fn mount_with_options(
+    source: &str,
+    target: &str,
+    fstype: &str,
+    options: &str,
+) -> io::Result<()> {
+    crate::root::mount(source, target, fstype, options)
+}
+
+/// Unmounts the filesystem mounted at `target`.
+///
+/// Returns [`ResourceBusy`](io::Error::ResourceBusy) if there are still opened
+/// files or nested mount points under it, or the current directory is in it.
+pub fn umount(target: &str) -> io::Result<()> {
+    crate::root::umount(target)
+}
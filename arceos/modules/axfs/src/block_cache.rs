@@ -0,0 +1,268 @@
+//! A buffer cache of block devices.
+//!
+//! Blocks are cached in memory and written back when they are evicted or the
+//! device is flushed. Blocks missing in the cache are read with read-ahead on
+//! sequential access, and consecutive blocks are read or written back in one
+//! request to the device.
+
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use axdriver::prelude::*;
+
+/// Maximum number of blocks in one request to the device.
+const MAX_BATCH_BLOCKS: usize = 128;
+
+struct Block {
+    data: Box<[u8]>,
+    dirty: bool,
+    tick: u64,
+}
+
+/// A block device with a buffer cache.
+pub(crate) struct BlockCache {
+    dev: AxBlockDevice,
+    block_size: usize,
+    num_blocks: u64,
+    /// Maximum number of cached blocks.
+    capacity: usize,
+    /// Number of blocks read ahead on sequential reads.
+    read_ahead: usize,
+    blocks: BTreeMap<u64, Block>,
+    /// Cached blocks in least-recently-used order, indexed by their ticks.
+    lru: BTreeMap<u64, u64>,
+    tick: u64,
+    /// The block after the last read, to detect sequential reads.
+    next_block: u64,
+}
+
+impl BlockCache {
+    /// Creates a buffer cache for `dev`, whose sizes are given by
+    /// [`axconfig::BLOCK_CACHE_SIZE`] and [`axconfig::BLOCK_READ_AHEAD`].
+    pub fn new(dev: AxBlockDevice) -> Self {
+        let block_size = dev.block_size();
+        let num_blocks = dev.num_blocks();
+        let mut cache = Self {
+            dev,
+            block_size,
+            num_blocks,
+            capacity: 1,
+            read_ahead: 0,
+            blocks: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            next_block: 0,
+        };
+        cache.set_read_ahead(axconfig::BLOCK_READ_AHEAD);
+        // nothing is cached yet, cannot fail
+        cache.set_capacity(axconfig::BLOCK_CACHE_SIZE).ok();
+        cache
+    }
+
+    /// Returns the underlying block device.
+    pub fn device(&self) -> &AxBlockDevice {
+        &self.dev
+    }
+
+    /// Returns the block size of the device in bytes.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the number of blocks of the device.
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    /// Returns the size of the device in bytes.
+    pub fn size(&self) -> u64 {
+        self.num_blocks * self.block_size as u64
+    }
+
+    /// Returns the size of the cache in bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity * self.block_size
+    }
+
+    /// Sets the size of the cache in bytes. At least one block is cached.
+    ///
+    /// Blocks exceeding the new size are evicted, and written back if dirty.
+    pub fn set_capacity(&mut self, bytes: usize) -> DevResult {
+        self.capacity = (bytes / self.block_size).max(1);
+        while self.blocks.len() > self.capacity {
+            self.evict_one()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the maximum size of data read ahead in bytes.
+    pub fn read_ahead(&self) -> usize {
+        self.read_ahead * self.block_size
+    }
+
+    /// Sets the maximum size of data read ahead on sequential reads in bytes.
+    pub fn set_read_ahead(&mut self, bytes: usize) {
+        self.read_ahead = bytes / self.block_size;
+    }
+
+    fn touch(&mut self, block_id: u64) {
+        self.tick += 1;
+        if let Some(block) = self.blocks.get_mut(&block_id) {
+            self.lru.remove(&block.tick);
+            block.tick = self.tick;
+            self.lru.insert(self.tick, block_id);
+        }
+    }
+
+    fn insert(&mut self, block_id: u64, data: Box<[u8]>, dirty: bool) -> DevResult {
+        while self.blocks.len() >= self.capacity {
+            self.evict_one()?;
+        }
+        self.tick += 1;
+        let block = Block {
+            data,
+            dirty,
+            tick: self.tick,
+        };
+        self.blocks.insert(block_id, block);
+        self.lru.insert(self.tick, block_id);
+        Ok(())
+    }
+
+    fn evict_one(&mut self) -> DevResult {
+        let Some((&tick, &block_id)) = self.lru.first_key_value() else {
+            return Ok(());
+        };
+        if self.blocks[&block_id].dirty {
+            self.write_back_from(block_id)?;
+        }
+        self.lru.remove(&tick);
+        self.blocks.remove(&block_id);
+        Ok(())
+    }
+
+    /// Writes back the run of consecutive dirty blocks starting from
+    /// `block_id` in one request.
+    fn write_back_from(&mut self, block_id: u64) -> DevResult {
+        let mut buf = Vec::new();
+        let mut end = block_id;
+        while buf.len() < MAX_BATCH_BLOCKS * self.block_size {
+            match self.blocks.get(&end) {
+                Some(block) if block.dirty => buf.extend_from_slice(&block.data),
+                _ => break,
+            }
+            end += 1;
+        }
+        if buf.is_empty() {
+            return Ok(());
+        }
+        self.dev.write_block(block_id, &buf)?;
+        for id in block_id..end {
+            self.blocks.get_mut(&id).unwrap().dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Reads the blocks missing in the cache starting from `block_id` (which
+    /// must be missing) and before `end` in one request.
+    fn fetch(&mut self, block_id: u64, end: u64) -> DevResult {
+        let max_count = self.capacity.min(MAX_BATCH_BLOCKS) as u64;
+        let mut count = 1;
+        while count < max_count
+            && block_id + count < end
+            && !self.blocks.contains_key(&(block_id + count))
+        {
+            count += 1;
+        }
+        let mut buf = vec![0u8; count as usize * self.block_size];
+        self.dev.read_block(block_id, &mut buf)?;
+        for (i, data) in buf.chunks_exact(self.block_size).enumerate() {
+            self.insert(block_id + i as u64, data.into(), false)?;
+        }
+        Ok(())
+    }
+
+    /// Reads data from the device at `pos`, returns the number of bytes read,
+    /// which is less than `buf.len()` only at the end of the device.
+    pub fn read_at(&mut self, pos: u64, buf: &mut [u8]) -> DevResult<usize> {
+        let len = buf.len().min(self.size().saturating_sub(pos) as usize);
+        if len == 0 {
+            return Ok(0);
+        }
+        let bs = self.block_size as u64;
+        let first = pos / bs;
+        let last = (pos + len as u64).div_ceil(bs);
+        // a read is sequential if it starts from the last block read, or the
+        // one after it
+        let end = if first == self.next_block || first + 1 == self.next_block {
+            (last + self.read_ahead as u64).min(self.num_blocks)
+        } else {
+            last
+        };
+        self.next_block = last;
+
+        let mut read_len = 0;
+        while read_len < len {
+            let cur = pos + read_len as u64;
+            let block_id = cur / bs;
+            let offset = (cur % bs) as usize;
+            let count = (len - read_len).min(self.block_size - offset);
+            if !self.blocks.contains_key(&block_id) {
+                self.fetch(block_id, end)?;
+            }
+            let data = &self.blocks[&block_id].data;
+            buf[read_len..read_len + count].copy_from_slice(&data[offset..offset + count]);
+            self.touch(block_id);
+            read_len += count;
+        }
+        Ok(read_len)
+    }
+
+    /// Writes data to the device at `pos`, returns the number of bytes
+    /// written, which is less than `buf.len()` only at the end of the device.
+    ///
+    /// Data is only written to the cache, call [`flush`](Self::flush) to write
+    /// it to the device.
+    pub fn write_at(&mut self, pos: u64, buf: &[u8]) -> DevResult<usize> {
+        let len = buf.len().min(self.size().saturating_sub(pos) as usize);
+        let bs = self.block_size as u64;
+        let mut write_len = 0;
+        while write_len < len {
+            let cur = pos + write_len as u64;
+            let block_id = cur / bs;
+            let offset = (cur % bs) as usize;
+            let count = (len - write_len).min(self.block_size - offset);
+            let src = &buf[write_len..write_len + count];
+            if let Some(block) = self.blocks.get_mut(&block_id) {
+                block.data[offset..offset + count].copy_from_slice(src);
+                block.dirty = true;
+                self.touch(block_id);
+            } else if count == self.block_size {
+                // whole block, no need to read it
+                self.insert(block_id, src.into(), true)?;
+            } else {
+                self.fetch(block_id, block_id + 1)?;
+                let block = self.blocks.get_mut(&block_id).unwrap();
+                block.data[offset..offset + count].copy_from_slice(src);
+                block.dirty = true;
+            }
+            write_len += count;
+        }
+        Ok(write_len)
+    }
+
+    /// Writes back all dirty blocks and flushes the device.
+    pub fn flush(&mut self) -> DevResult {
+        let dirty = self
+            .blocks
+            .iter()
+            .filter(|(_, block)| block.dirty)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        for id in dirty {
+            // may have been written back with the previous blocks
+            if self.blocks[&id].dirty {
+                self.write_back_from(id)?;
+            }
+        }
+        self.dev.flush()
+    }
+}
@@ -0,0 +1,138 @@
+//! Credentials of tasks, i.e. the user and the groups that they access files
+//! as.
+//!
+//! Accesses to a file are allowed by its owner, group or other permission
+//! bits, whichever class the caller falls in. In filesystems that keep owners,
+//! such as ramfs and ext2/ext4, root (uid 0) can also read and write any file
+//! and search any directory, and execute files that are executable by anyone.
+//! In other filesystems, such as FAT, procfs and sysfs, everything is owned by
+//! root, and the permissions tell what the filesystem supports, so they bind
+//! root as well.
+//!
+//! Only root can change the owner of a file, and the owner can only change its
+//! group to one that it is in. Only root and the owner can change the
+//! permissions of a file.
+//!
+//! With the `multitask` feature, a task starts with the credentials of the
+//! task that spawned it, and [`set_current`] only changes those of the calling
+//! task. Otherwise, the credentials are global. They are root's until changed.
+
+use alloc::{sync::Arc, vec::Vec};
+use axfs_vfs::VfsNodePerm;
+use axsync::Mutex;
+use cap_access::Cap;
+
+/// The user and the groups that a task accesses files as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    /// The user ID.
+    pub uid: u32,
+    /// The primary group ID, which new files belong to.
+    pub gid: u32,
+    /// The supplementary group IDs.
+    pub groups: Vec<u32>,
+}
+
+/// The credentials of tasks that have no credentials of their own, or of the
+/// whole system without the `multitask` feature. `None` stands for root.
+static GLOBAL_CRED: Mutex<Option<Arc<Credentials>>> = Mutex::new(None);
+
+static ROOT: Credentials = Credentials::root();
+
+impl Credentials {
+    /// Creates the credentials of the user `uid` in the group `gid`, without
+    /// supplementary groups.
+    pub const fn new(uid: u32, gid: u32) -> Self {
+        Self {
+            uid,
+            gid,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Returns the credentials of root.
+    pub const fn root() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// Whether the user is root.
+    pub const fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    /// Whether the user is in the group `gid`, as its primary group or as a
+    /// supplementary one.
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+
+    /// Returns the accesses allowed to a file with permissions `perm`, owned
+    /// by `owner`, or `None` if the filesystem does not keep owners. `is_dir`
+    /// tells whether the file is a directory, which root can always search.
+    pub(crate) fn access(&self, perm: VfsNodePerm, is_dir: bool, owner: Option<(u32, u32)>) -> Cap {
+        let bits = perm.bits();
+        if self.is_root() && owner.is_some() {
+            let mut cap = Cap::READ | Cap::WRITE;
+            if is_dir || bits & 0o111 != 0 {
+                cap |= Cap::EXECUTE;
+            }
+            return cap;
+        }
+        let (uid, gid) = owner.unwrap_or((0, 0));
+        let bits = if self.uid == uid {
+            bits >> 6
+        } else if self.in_group(gid) {
+            bits >> 3
+        } else {
+            bits
+        };
+        let mut cap = Cap::empty();
+        if bits & 0o4 != 0 {
+            cap |= Cap::READ;
+        }
+        if bits & 0o2 != 0 {
+            cap |= Cap::WRITE;
+        }
+        if bits & 0o1 != 0 {
+            cap |= Cap::EXECUTE;
+        }
+        cap
+    }
+}
+
+impl Default for Credentials {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+#[cfg(feature = "multitask")]
+fn current_task_cred() -> Option<Arc<Credentials>> {
+    crate::task_state::current()?.cred.clone()
+}
+
+/// Calls `f` with the credentials of the calling task.
+pub(crate) fn with_current<R>(f: impl FnOnce(&Credentials) -> R) -> R {
+    #[cfg(feature = "multitask")]
+    if let Some(cred) = current_task_cred() {
+        return f(&cred);
+    }
+    let cred = GLOBAL_CRED.lock().clone();
+    f(cred.as_deref().unwrap_or(&ROOT))
+}
+
+/// Returns the credentials of the calling task.
+pub fn current() -> Credentials {
+    with_current(Credentials::clone)
+}
+
+/// Replaces the credentials of the calling task. Tasks that it spawns later
+/// start with the new ones.
+pub fn set_current(cred: Credentials) {
+    #[cfg(feature = "multitask")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        crate::task_state::update(&curr, |state| state.cred = Some(Arc::new(cred)));
+        return;
+    }
+    *GLOBAL_CRED.lock() = Some(Arc::new(cred));
+}
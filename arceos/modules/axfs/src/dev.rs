@@ -1,92 +1,274 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::{string::String, sync::Arc, vec::Vec};
 use axdriver::prelude::*;
+use axsync::Mutex;
 
-const BLOCK_SIZE: usize = 512;
+#[cfg(all(any(feature = "fatfs", feature = "ext4fs"), not(feature = "myfs")))]
+use axerrno::{ax_err, AxResult};
+#[cfg(feature = "devfs")]
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+
+use crate::block_cache::BlockCache;
+
+/// A block device shared by [`Disk`]s and its device file, accessed through
+/// its buffer cache.
+type SharedBlockDevice = Arc<Mutex<BlockCache>>;
+
+/// Block devices other than the one of the root filesystem, indexed by their
+/// names in `/dev`.
+static BLOCK_DEVICES: Mutex<BTreeMap<String, SharedBlockDevice>> = Mutex::new(BTreeMap::new());
+
+/// Names of the block devices in [`BLOCK_DEVICES`] that are opened by a
+/// filesystem, which can not be mounted again until it is dropped.
+static OPENED_DEVICES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// The block device of the root filesystem.
+static ROOT_DEVICE: Mutex<Option<SharedBlockDevice>> = Mutex::new(None);
 
 /// A disk device with a cursor.
+///
+/// Data is read and written through the buffer cache of the device, and is
+/// written to the device on [`Disk::flush`] or when evicted.
 pub struct Disk {
-    block_id: u64,
-    offset: usize,
-    dev: AxBlockDevice,
+    pos: u64,
+    dev: SharedBlockDevice,
+    /// The name in [`OPENED_DEVICES`], if opened by [`open_block_device`].
+    opened: Option<String>,
+}
+
+/// The device file of a block device, e.g. `/dev/vdb`.
+#[cfg(feature = "devfs")]
+pub(crate) struct BlockDeviceFile {
+    dev: SharedBlockDevice,
 }
 
 impl Disk {
     /// Create a new disk.
     pub fn new(dev: AxBlockDevice) -> Self {
-        assert_eq!(BLOCK_SIZE, dev.block_size());
+        Self::from_shared(Arc::new(Mutex::new(BlockCache::new(dev))))
+    }
+
+    fn from_shared(dev: SharedBlockDevice) -> Self {
         Self {
-            block_id: 0,
-            offset: 0,
+            pos: 0,
             dev,
+            opened: None,
         }
     }
 
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.dev.lock().size()
+    }
+
+    /// Get the block size of the disk.
+    pub fn block_size(&self) -> usize {
+        self.dev.lock().block_size()
     }
 
     /// Get the position of the cursor.
     pub fn position(&self) -> u64 {
-        self.block_id * BLOCK_SIZE as u64 + self.offset as u64
+        self.pos
     }
 
     /// Set the position of the cursor.
     pub fn set_position(&mut self, pos: u64) {
-        self.block_id = pos / BLOCK_SIZE as u64;
-        self.offset = pos as usize % BLOCK_SIZE;
+        self.pos = pos;
+    }
+
+    /// Read at the cursor, returns the number of bytes read, which is less
+    /// than `buf.len()` only at the end of the disk.
+    pub fn read(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+        let read_len = self.dev.lock().read_at(self.pos, buf)?;
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+
+    /// Write at the cursor, returns the number of bytes written, which is
+    /// less than `buf.len()` only at the end of the disk.
+    pub fn write(&mut self, buf: &[u8]) -> DevResult<usize> {
+        let write_len = self.dev.lock().write_at(self.pos, buf)?;
+        self.pos += write_len as u64;
+        Ok(write_len)
     }
 
     /// Read within one block, returns the number of bytes read.
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
-        let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev
-                .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(read_size)
+        let len = self.len_in_block(buf.len());
+        self.read(&mut buf[..len])
     }
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
-        let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(write_size)
+        let len = self.len_in_block(buf.len());
+        self.write(&buf[..len])
+    }
+
+    fn len_in_block(&self, len: usize) -> usize {
+        let block_size = self.block_size();
+        len.min(block_size - self.pos as usize % block_size)
+    }
+
+    /// Write all cached data to the device.
+    pub fn flush(&mut self) -> DevResult {
+        self.dev.lock().flush()
+    }
+}
+
+impl Drop for Disk {
+    fn drop(&mut self) {
+        if let Some(name) = self.opened.take() {
+            OPENED_DEVICES.lock().remove(&name);
+        }
+    }
+}
+
+#[cfg(feature = "devfs")]
+impl VfsNodeOps for BlockDeviceFile {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = self.dev.lock().size();
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o660),
+            VfsNodeType::BlockDevice,
+            size,
+            size / 512,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.dev
+            .lock()
+            .read_at(offset, buf)
+            .map_err(|_| VfsError::Io)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.dev
+            .lock()
+            .write_at(offset, buf)
+            .map_err(|_| VfsError::Io)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.dev.lock().flush().map_err(|_| VfsError::Io)
+    }
+}
+
+/// Sets up the block device of the root filesystem, and returns it as a disk.
+#[cfg_attr(feature = "initramfs", allow(dead_code))]
+pub(crate) fn init_root_device(dev: AxBlockDevice) -> Disk {
+    let disk = Disk::new(dev);
+    *ROOT_DEVICE.lock() = Some(disk.dev.clone());
+    disk
+}
+
+/// Writes the cached data of all block devices to the devices.
+pub(crate) fn sync() -> DevResult {
+    let root = ROOT_DEVICE.lock().clone();
+    let devices = BLOCK_DEVICES.lock().values().cloned().collect::<Vec<_>>();
+    for dev in root.into_iter().chain(devices) {
+        dev.lock().flush()?;
+    }
+    Ok(())
+}
+
+/// Returns the name of the block device at `idx` in `/dev`, i.e. `vda` to
+/// `vdz`, then `vdaa`, `vdab` and so on, as Linux names virtio disks.
+pub(crate) fn block_device_name(mut idx: usize) -> String {
+    let mut suffix = Vec::new();
+    loop {
+        suffix.push(b'a' + (idx % 26) as u8);
+        if idx < 26 {
+            break;
+        }
+        idx = idx / 26 - 1;
+    }
+    suffix.reverse();
+    String::from("vd") + core::str::from_utf8(&suffix).unwrap()
+}
+
+/// Registers a block device other than the one of the root filesystem, so that
+/// it can be accessed at `/dev/<name>` and mounted at runtime.
+pub(crate) fn register_block_device(name: String, dev: AxBlockDevice) {
+    let dev = Arc::new(Mutex::new(BlockCache::new(dev)));
+    #[cfg(feature = "sysfs")]
+    publish_block_attrs(&name, &dev);
+    #[cfg(feature = "devfs")]
+    crate::fs::devfs::register_device(&name, Arc::new(BlockDeviceFile { dev: dev.clone() }))
+        .expect("failed to publish the block device in devfs");
+    BLOCK_DEVICES.lock().insert(name, dev);
+}
+
+/// Publishes the attributes of a block device in `/sys/block/<name>`.
+#[cfg(feature = "sysfs")]
+fn publish_block_attrs(name: &str, dev: &SharedBlockDevice) {
+    use crate::fs::sysfs::{register_attr, SysAttr};
+    use alloc::format;
+    use axerrno::ax_err;
+
+    let size_dev = dev.clone();
+    let driver_dev = dev.clone();
+    let block_size_dev = dev.clone();
+    let (show_dev, store_dev) = (dev.clone(), dev.clone());
+    let attrs = [
+        (
+            "size",
+            // in 512-byte sectors, as Linux does
+            SysAttr::read_only(move || format!("{}\n", size_dev.lock().size() / 512)),
+        ),
+        (
+            "driver",
+            SysAttr::read_only(move || format!("{}\n", driver_dev.lock().device().device_name())),
+        ),
+        (
+            "queue/logical_block_size",
+            SysAttr::read_only(move || format!("{}\n", block_size_dev.lock().block_size())),
+        ),
+        (
+            "queue/read_ahead_kb",
+            SysAttr::read_write(
+                move || format!("{}\n", show_dev.lock().read_ahead() / 1024),
+                move |kb| match kb.parse::<usize>() {
+                    Ok(kb) => {
+                        store_dev.lock().set_read_ahead(kb * 1024);
+                        Ok(())
+                    }
+                    Err(_) => ax_err!(InvalidInput, "invalid read-ahead size"),
+                },
+            ),
+        ),
+    ];
+    for (attr_name, attr) in attrs {
+        if let Err(e) = register_attr(&format!("block/{}/{}", name, attr_name), attr) {
+            warn!(
+                "failed to publish /sys/block/{}/{}: {:?}",
+                name, attr_name, e
+            );
+        }
+    }
+}
+
+/// Opens the registered block device at `path` (e.g. `/dev/vdb`) as a disk.
+///
+/// Returns [`ResourceBusy`](axerrno::AxError::ResourceBusy) if the device is
+/// already opened, i.e. mounted. It is released when the returned disk is
+/// dropped with its filesystem, e.g. on unmounting.
+#[cfg(all(any(feature = "fatfs", feature = "ext4fs"), not(feature = "myfs")))]
+pub(crate) fn open_block_device(path: &str) -> AxResult<Disk> {
+    let name = match path.strip_prefix("/dev/") {
+        Some(name) => name,
+        None => return ax_err!(NotFound, "not a block device"),
+    };
+    let dev = match BLOCK_DEVICES.lock().get(name) {
+        Some(dev) => dev.clone(),
+        None => return ax_err!(NotFound, "block device not found"),
+    };
+    if !OPENED_DEVICES.lock().insert(String::from(name)) {
+        return ax_err!(ResourceBusy, "block device is already mounted");
     }
+    let mut disk = Disk::from_shared(dev);
+    disk.opened = Some(String::from(name));
+    Ok(disk)
 }
@@ -1,10 +1,23 @@
 //! Low-level filesystem operations.
 
+use alloc::{
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
-use axfs_vfs::{VfsError, VfsNodeRef};
+use axfs_vfs::{VfsNodeAttr, VfsNodeRef};
 use axio::SeekFrom;
+use axsync::Mutex;
 use cap_access::{Cap, WithCap};
-use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::{fmt, time::Duration};
+
+use crate::lock::{self, LockHandle};
+use crate::notify::{self, EventMask};
+use crate::page_cache::CachedFile;
+use crate::root::{FileKey, MountPoint};
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
@@ -15,16 +28,69 @@ pub use crate::fs::myfs::MyFileSystemIf;
 pub type FileType = axfs_vfs::VfsNodeType;
 /// Alias of [`axfs_vfs::VfsDirEntry`].
 pub type DirEntry = axfs_vfs::VfsDirEntry;
-/// Alias of [`axfs_vfs::VfsNodeAttr`].
-pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
 
+/// Timestamps of a file, as durations since the Unix epoch.
+///
+/// They are all zero if the filesystem does not keep timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileTimes {
+    /// Time of the last access.
+    pub atime: Duration,
+    /// Time of the last modification.
+    pub mtime: Duration,
+    /// Time of the last status change.
+    pub ctime: Duration,
+}
+
+/// File attributes, i.e. [`axfs_vfs::VfsNodeAttr`] with the timestamps, the
+/// inode number and the owner.
+#[derive(Debug, Clone, Copy)]
+pub struct FileAttr {
+    attr: VfsNodeAttr,
+    times: FileTimes,
+    ino: u64,
+    uid: u32,
+    gid: u32,
+}
+
+/// Capacity and usage of a filesystem, as reported by `statfs`.
+///
+/// Sizes are counted in blocks of `block_size` bytes. Counts that the
+/// filesystem does not keep, such as the number of files in FAT, are zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsStats {
+    /// The type of the filesystem, as the magic numbers of Linux `statfs`.
+    pub magic: u64,
+    /// The size of blocks, in bytes.
+    pub block_size: u64,
+    /// The total number of blocks.
+    pub blocks: u64,
+    /// The number of free blocks.
+    pub free_blocks: u64,
+    /// The number of free blocks available to unprivileged users.
+    pub avail_blocks: u64,
+    /// The total number of files, i.e. of inodes.
+    pub files: u64,
+    /// The number of free inodes.
+    pub free_files: u64,
+    /// The maximum length of file names.
+    pub name_max: u64,
+}
+
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
     is_append: bool,
     offset: u64,
+    cache: Option<Arc<CachedFile>>,
+    /// The absolute path of the file, for [`notify`](crate::notify).
+    path: OpenedPath,
+    mount: Option<Arc<MountPoint>>,
+    /// Identifies the opened file as the owner of its whole-file lock.
+    id: u64,
+    lock_key: FileKey,
 }
 
 /// An opened directory object, with open permissions and a cursor for
@@ -32,6 +98,41 @@ pub struct File {
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
     entry_idx: usize,
+    path: OpenedPath,
+    mount: Option<Arc<MountPoint>>,
+}
+
+/// The absolute path of an opened file or directory, which follows it when it
+/// or one of its ancestors is renamed.
+struct OpenedPath(Arc<Mutex<String>>);
+
+/// The paths of all opened files and directories, see [`rename_opened`].
+static OPENED_PATHS: Mutex<Vec<Weak<Mutex<String>>>> = Mutex::new(Vec::new());
+
+impl OpenedPath {
+    fn new(path: String) -> Self {
+        let path = Arc::new(Mutex::new(path));
+        let mut paths = OPENED_PATHS.lock();
+        paths.retain(|p| p.strong_count() > 0);
+        paths.push(Arc::downgrade(&path));
+        Self(path)
+    }
+
+    fn get(&self) -> String {
+        self.0.lock().clone()
+    }
+}
+
+/// Updates the paths of opened files and directories after `old` is renamed
+/// to `new`.
+pub(crate) fn rename_opened(old: &str, new: &str) {
+    let (old, new) = (old.trim_end_matches('/'), new.trim_end_matches('/'));
+    for path in OPENED_PATHS.lock().iter().filter_map(Weak::upgrade) {
+        let mut path = path.lock();
+        if crate::page_cache::is_under(&path, old) {
+            *path = String::from(new) + &path[old.len()..];
+        }
+    }
 }
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -44,9 +145,10 @@ pub struct OpenOptions {
     truncate: bool,
     create: bool,
     create_new: bool,
+    no_follow: bool,
     // system-specific
     _custom_flags: i32,
-    _mode: u32,
+    mode: u32,
 }
 
 impl OpenOptions {
@@ -60,9 +162,10 @@ impl OpenOptions {
             truncate: false,
             create: false,
             create_new: false,
+            no_follow: false,
             // system-specific
             _custom_flags: 0,
-            _mode: 0o666,
+            mode: 0o666,
         }
     }
     /// Sets the option for read access.
@@ -89,6 +192,16 @@ impl OpenOptions {
     pub fn create_new(&mut self, create_new: bool) {
         self.create_new = create_new;
     }
+    /// Sets the option to fail if the last component of the path is a
+    /// symbolic link, instead of following it.
+    pub fn no_follow(&mut self, no_follow: bool) {
+        self.no_follow = no_follow;
+    }
+    /// Sets the permissions of the file if it is created, which are masked by
+    /// the umask. It is `0o666` by default.
+    pub fn mode(&mut self, mode: u32) {
+        self.mode = mode;
+    }
 
     const fn is_valid(&self) -> bool {
         if !self.read && !self.write && !self.append {
@@ -116,50 +229,73 @@ impl File {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(dir: Option<&Directory>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
         }
 
-        let node_option = crate::root::lookup(dir, path);
-        let node = if opts.create || opts.create_new {
-            match node_option {
-                Ok(node) => {
-                    // already exists
-                    if opts.create_new {
-                        return ax_err!(AlreadyExists);
-                    }
-                    node
+        let path = path_at(dir, path)?;
+        let (real_path, node_option) = crate::root::resolve_path(&path, !opts.no_follow)?;
+        let (node, created) = match node_option {
+            Some(node) => {
+                // already exists
+                if opts.create_new {
+                    return ax_err!(AlreadyExists);
                 }
-                // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
-                Err(e) => return Err(e),
+                (node, false)
             }
-        } else {
-            // just open the existing
-            node_option?
+            // not exists, create new
+            None if opts.create || opts.create_new => {
+                (crate::root::create_file(&path, opts.mode)?, true)
+            }
+            None => return ax_err!(NotFound),
         };
+        let mount = crate::root::mount_point_of(&real_path);
 
         let attr = node.get_attr()?;
+        if attr.file_type() == FileType::SymLink {
+            return Err(crate::root::symlink_loop());
+        } else if path.ends_with('/') && !attr.is_dir() {
+            return ax_err!(NotADirectory);
+        }
         if attr.is_dir()
             && (opts.create || opts.create_new || opts.write || opts.append || opts.truncate)
         {
             return ax_err!(IsADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        // a new file can be written even if its permissions do not allow it
+        if !created && !crate::root::access(mount.as_deref(), &node, &attr).contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
 
+        let key = crate::root::file_key(mount.as_deref(), &real_path, &node);
+        let cache = if attr.is_file() && crate::root::is_page_cached(&real_path) {
+            Some(crate::page_cache::open(&key, node.clone())?)
+        } else {
+            None
+        };
+
+        static ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+
         node.open()?;
         if opts.truncate {
-            node.truncate(0)?;
+            match &cache {
+                Some(cache) => cache.truncate(0)?,
+                None => node.truncate(0)?,
+            }
+            notify::notify(&real_path, EventMask::MODIFY, false);
         }
         Ok(Self {
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
+            cache,
+            path: OpenedPath::new(real_path),
+            mount,
+            id: ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            lock_key: key,
         })
     }
 
@@ -171,7 +307,58 @@ impl File {
 
     /// Truncates the file to the specified size.
     pub fn truncate(&self, size: u64) -> AxResult {
-        self.access_node(Cap::WRITE)?.truncate(size)?;
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.truncate(size)?,
+            None => node.truncate(size)?,
+        }
+        notify::notify(&self.path.get(), EventMask::MODIFY, false);
+        Ok(())
+    }
+
+    /// Allocates the storage of the `len` bytes at `offset`, so that writing
+    /// them does not fail for lack of space. The file is extended if they are
+    /// beyond its end, unless `keep_size` is `true`.
+    ///
+    /// Filesystems without sparse files store all bytes of their files, so it
+    /// only extends the file there, and fails with [`AxError::Unsupported`] if
+    /// the bytes are beyond the end and `keep_size` is `true`.
+    pub fn allocate(&self, offset: u64, len: u64, keep_size: bool) -> AxResult {
+        let node = self.access_node(Cap::WRITE)?;
+        let end = match offset.checked_add(len) {
+            Some(end) if len > 0 => end,
+            _ => return ax_err!(InvalidInput),
+        };
+        match crate::root::allocate(self.mount.as_deref(), node, offset, len, keep_size) {
+            Err(AxError::Unsupported) => {
+                if end <= self.size()? {
+                    Ok(())
+                } else if keep_size {
+                    ax_err!(Unsupported, "cannot allocate beyond the end of file")
+                } else {
+                    self.truncate(end)
+                }
+            }
+            res => {
+                res?;
+                notify::notify(&self.path.get(), EventMask::MODIFY, false);
+                Ok(())
+            }
+        }
+    }
+
+    /// Deallocates the `len` bytes at `offset`, which then read as zeros. The
+    /// size of the file does not change.
+    ///
+    /// Fails with [`AxError::Unsupported`] if the filesystem does not have
+    /// sparse files.
+    pub fn punch_hole(&self, offset: u64, len: u64) -> AxResult {
+        let node = self.access_node(Cap::WRITE)?;
+        if len == 0 || offset.checked_add(len).is_none() {
+            return ax_err!(InvalidInput);
+        }
+        crate::root::punch_hole(self.mount.as_deref(), node, offset, len)?;
+        notify::notify(&self.path.get(), EventMask::MODIFY, false);
         Ok(())
     }
 
@@ -180,8 +367,7 @@ impl File {
     ///
     /// After the read, the cursor will be advanced by the number of bytes read.
     pub fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(self.offset, buf)?;
+        let read_len = self.read_at(self.offset, buf)?;
         self.offset += read_len as u64;
         Ok(read_len)
     }
@@ -191,8 +377,10 @@ impl File {
     /// It does not update the file cursor.
     pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(offset, buf)?;
-        Ok(read_len)
+        match &self.cache {
+            Some(cache) => cache.read_at(offset, buf),
+            None => node.read_at(offset, buf),
+        }
     }
 
     /// Writes the file at the current position. Returns the number of bytes
@@ -202,12 +390,11 @@ impl File {
     /// written.
     pub fn write(&mut self, buf: &[u8]) -> AxResult<usize> {
         let offset = if self.is_append {
-            self.get_attr()?.size()
+            self.size()?
         } else {
             self.offset
         };
-        let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        let write_len = self.write_at(offset, buf)?;
         self.offset = offset + write_len as u64;
         Ok(write_len)
     }
@@ -218,20 +405,44 @@ impl File {
     /// It does not update the file cursor.
     pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        let write_len = match &self.cache {
+            Some(cache) => cache.write_at(offset, buf)?,
+            None => node.write_at(offset, buf)?,
+        };
+        if write_len > 0 {
+            notify::notify(&self.path.get(), EventMask::MODIFY, false);
+        }
         Ok(write_len)
     }
 
-    /// Flushes the file, writes all buffered data to the underlying device.
+    /// Flushes the file, writes all buffered data (including the dirty pages
+    /// in the page cache) to the underlying device.
     pub fn flush(&self) -> AxResult {
-        self.access_node(Cap::WRITE)?.fsync()?;
-        Ok(())
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.flush(),
+            None => node.fsync(),
+        }
+    }
+
+    fn size(&self) -> AxResult<u64> {
+        let node = self.access_node(Cap::empty())?;
+        match &self.cache {
+            Some(cache) => Ok(cache.size()),
+            None => Ok(node.get_attr()?.size()),
+        }
+    }
+
+    /// Returns the cached pages of the file, which can be mapped into address
+    /// spaces, or `None` if the file is not accessed through the page cache.
+    pub fn cached_file(&self) -> Option<&Arc<CachedFile>> {
+        self.cache.as_ref()
     }
 
     /// Sets the cursor of the file to the specified offset. Returns the new
     /// position after the seek.
     pub fn seek(&mut self, pos: SeekFrom) -> AxResult<u64> {
-        let size = self.get_attr()?.size();
+        let size = self.size()?;
         let new_offset = match pos {
             SeekFrom::Start(pos) => Some(pos),
             SeekFrom::Current(off) => self.offset.checked_add_signed(off),
@@ -242,9 +453,67 @@ impl File {
         Ok(new_offset)
     }
 
+    /// Sets the cursor of the file to the first byte of data at or after
+    /// `offset`. Returns the new position, or [`AxError::NotFound`] if there is
+    /// no data from `offset` to the end of the file.
+    ///
+    /// All bytes are data if the filesystem does not have sparse files.
+    pub fn seek_data(&mut self, offset: u64) -> AxResult<u64> {
+        self.seek_extent(offset, false)
+    }
+
+    /// Sets the cursor of the file to the first hole at or after `offset`,
+    /// where the end of the file counts as a hole. Returns the new position,
+    /// or [`AxError::NotFound`] if `offset` is beyond the end of the file.
+    pub fn seek_hole(&mut self, offset: u64) -> AxResult<u64> {
+        self.seek_extent(offset, true)
+    }
+
+    fn seek_extent(&mut self, offset: u64, hole: bool) -> AxResult<u64> {
+        let node = self.access_node(Cap::empty())?;
+        let size = self.size()?;
+        let pos = match crate::root::seek_extent(self.mount.as_deref(), node, offset, hole) {
+            Err(AxError::Unsupported) if offset >= size => None,
+            Err(AxError::Unsupported) => Some(if hole { size } else { offset }),
+            res => res?,
+        };
+        self.offset = pos.ok_or(AxError::NotFound)?;
+        Ok(self.offset)
+    }
+
     /// Gets the file attributes.
     pub fn get_attr(&self) -> AxResult<FileAttr> {
-        self.access_node(Cap::empty())?.get_attr()
+        let node = self.access_node(Cap::empty())?;
+        node_attr(node, self.mount.as_deref(), self.cache.as_deref())
+    }
+
+    /// Returns the capacity and usage of the filesystem the file resides in.
+    pub fn statfs(&self) -> AxResult<FsStats> {
+        crate::root::statfs(self.mount.as_deref())
+    }
+
+    /// Changes the permissions of the file. Only root and the owner of the
+    /// file can do it.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        let node = self.access_node(Cap::empty())?;
+        crate::root::set_perm(self.mount.as_deref(), node, perm)
+    }
+
+    /// Changes the owner and the group of the file, unless they are `None`.
+    /// See [`cred`](crate::cred) for who can do it.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+        let node = self.access_node(Cap::empty())?;
+        crate::root::set_owner(self.mount.as_deref(), node, uid, gid)
+    }
+
+    /// Returns the handle to place and remove the locks of the file.
+    pub fn locks(&self) -> LockHandle {
+        LockHandle::new(
+            self.lock_key.clone(),
+            self.id,
+            self.access_node(Cap::READ).is_ok(),
+            self.access_node(Cap::WRITE).is_ok(),
+        )
     }
 }
 
@@ -253,7 +522,7 @@ impl Directory {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(dir: Option<&Directory>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -262,28 +531,38 @@ impl Directory {
             return ax_err!(InvalidInput);
         }
 
-        let node = crate::root::lookup(dir, path)?;
+        let path = path_at(dir, path)?;
+        let (real_path, node) = crate::root::resolve_path(&path, !opts.no_follow)?;
+        let node = node.ok_or(AxError::NotFound)?;
+        let mount = crate::root::mount_point_of(&real_path);
         let attr = node.get_attr()?;
         if !attr.is_dir() {
             return ax_err!(NotADirectory);
         }
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        let perm_cap = crate::root::access(mount.as_deref(), &node, &attr);
+        if !perm_cap.contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
+        // searching in the directory is allowed by its permissions
+        let access_cap = access_cap | (perm_cap & Cap::EXECUTE);
 
         node.open()?;
         Ok(Self {
             node: WithCap::new(node, access_cap),
             entry_idx: 0,
+            path: OpenedPath::new(real_path),
+            mount,
         })
     }
 
-    fn access_at(&self, path: &str) -> AxResult<Option<&VfsNodeRef>> {
+    /// Joins `path` to the path of this directory, unless it is absolute.
+    fn path_at(&self, path: &str) -> AxResult<String> {
         if path.starts_with('/') {
-            Ok(None)
+            Ok(path.into())
         } else {
-            Ok(Some(self.access_node(Cap::EXECUTE)?))
+            self.access_node(Cap::EXECUTE)?;
+            Ok(format!("{}/{}", self.path.get(), path))
         }
     }
 
@@ -296,33 +575,35 @@ impl Directory {
     /// Opens a directory at the path relative to this directory. Returns a
     /// [`Directory`] object.
     pub fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts)
+        Self::_open_dir_at(Some(self), path, opts)
     }
 
     /// Opens a file at the path relative to this directory. Returns a [`File`]
     /// object.
     pub fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts)
+        File::_open_at(Some(self), path, opts)
     }
 
-    /// Creates an empty file at the path relative to this directory.
-    pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+    /// Creates an empty file at the path relative to this directory, with the
+    /// permissions `mode` masked by the umask.
+    pub fn create_file(&self, path: &str, mode: u32) -> AxResult<VfsNodeRef> {
+        crate::root::create_file(&self.path_at(path)?, mode)
     }
 
-    /// Creates an empty directory at the path relative to this directory.
-    pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+    /// Creates an empty directory at the path relative to this directory, with
+    /// the permissions `mode` masked by the umask.
+    pub fn create_dir(&self, path: &str, mode: u32) -> AxResult {
+        crate::root::create_dir(&self.path_at(path)?, mode)
     }
 
     /// Removes a file at the path relative to this directory.
     pub fn remove_file(&self, path: &str) -> AxResult {
-        crate::root::remove_file(self.access_at(path)?, path)
+        crate::root::remove_file(&self.path_at(path)?)
     }
 
     /// Removes a directory at the path relative to this directory.
     pub fn remove_dir(&self, path: &str) -> AxResult {
-        crate::root::remove_dir(self.access_at(path)?, path)
+        crate::root::remove_dir(&self.path_at(path)?)
     }
 
     /// Reads directory entries starts from the current position into the
@@ -343,12 +624,200 @@ impl Directory {
     ///
     /// This only works then the new path is in the same mounted fs.
     pub fn rename(&self, old: &str, new: &str) -> AxResult {
-        crate::root::rename(old, new)
+        crate::root::rename(&self.path_at(old)?, &self.path_at(new)?)
+    }
+
+    /// Returns the absolute path of this directory, with symbolic links
+    /// resolved. It follows the directory when it is renamed.
+    pub fn path(&self) -> String {
+        self.path.get()
+    }
+
+    /// Get the attributes of the directory.
+    pub fn get_attr(&self) -> AxResult<FileAttr> {
+        let node = self.access_node(Cap::empty())?;
+        node_attr(node, self.mount.as_deref(), None)
+    }
+
+    /// Returns the capacity and usage of the filesystem the directory resides
+    /// in.
+    pub fn statfs(&self) -> AxResult<FsStats> {
+        crate::root::statfs(self.mount.as_deref())
+    }
+}
+
+/// Gets the attributes of the file at `path`, following symbolic links.
+pub fn attr(path: &str) -> AxResult<FileAttr> {
+    path_attr(path, true)
+}
+
+/// Gets the attributes of the file at `path`, without following the symbolic
+/// link at the end of it.
+pub fn symlink_attr(path: &str) -> AxResult<FileAttr> {
+    path_attr(path, false)
+}
+
+/// Changes the permissions of the file at `path`, following symbolic links.
+/// Only root and the owner of the file can do it.
+pub fn set_perm(path: &str, perm: FilePerm) -> AxResult {
+    let (path, node) = crate::root::resolve_path(path, true)?;
+    let node = node.ok_or(AxError::NotFound)?;
+    let mount = crate::root::mount_point_of(&path);
+    crate::root::set_perm(mount.as_deref(), &node, perm)
+}
+
+/// Changes the owner and the group of the file at `path`, unless they are
+/// `None`. Symbolic links are followed if `follow` is `true`. See
+/// [`cred`](crate::cred) for who can do it.
+pub fn set_owner(path: &str, uid: Option<u32>, gid: Option<u32>, follow: bool) -> AxResult {
+    let (path, node) = crate::root::resolve_path(path, follow)?;
+    let node = node.ok_or(AxError::NotFound)?;
+    let mount = crate::root::mount_point_of(&path);
+    crate::root::set_owner(mount.as_deref(), &node, uid, gid)
+}
+
+/// Returns the capacity and usage of the filesystem that the file at `path`
+/// resides in, following symbolic links.
+pub fn statfs(path: &str) -> AxResult<FsStats> {
+    let (path, node) = crate::root::resolve_path(path, true)?;
+    node.ok_or(AxError::NotFound)?;
+    crate::root::statfs(crate::root::mount_point_of(&path).as_deref())
+}
+
+fn path_attr(path: &str, follow: bool) -> AxResult<FileAttr> {
+    let (path, node) = crate::root::resolve_path(path, follow)?;
+    let node = node.ok_or(AxError::NotFound)?;
+    let mount = crate::root::mount_point_of(&path);
+    let key = crate::root::file_key(mount.as_deref(), &path, &node);
+    let cache = crate::page_cache::cached_file(&key);
+    node_attr(&node, mount.as_deref(), cache.as_deref())
+}
+
+/// Returns the attributes of `node` in the mount point `mount`. The size and
+/// the modification time are taken from the page cache if it is cached, which
+/// may include the data not written back.
+fn node_attr(
+    node: &VfsNodeRef,
+    mount: Option<&MountPoint>,
+    cache: Option<&CachedFile>,
+) -> AxResult<FileAttr> {
+    let attr = node.get_attr()?;
+    let mut times = crate::root::node_times(mount, node);
+    let ino = crate::root::node_ino(mount, node);
+    let (uid, gid) = crate::root::node_owner(mount, node).unwrap_or((0, 0));
+    let Some(cache) = cache else {
+        return Ok(FileAttr::new(attr, times)
+            .with_ino(ino)
+            .with_owner(uid, gid));
+    };
+    let size = cache.size();
+    if let Some(mtime) = cache.modified() {
+        times.mtime = mtime;
+        times.ctime = mtime;
+    }
+    let attr = VfsNodeAttr::new(attr.perm(), attr.file_type(), size, size.div_ceil(512));
+    Ok(FileAttr::new(attr, times)
+        .with_ino(ino)
+        .with_owner(uid, gid))
+}
+
+impl FileAttr {
+    /// Creates file attributes from the attributes of a node and its
+    /// timestamps.
+    pub const fn new(attr: VfsNodeAttr, times: FileTimes) -> Self {
+        Self {
+            attr,
+            times,
+            ino: 0,
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    /// Sets the inode number of the file.
+    pub const fn with_ino(self, ino: u64) -> Self {
+        Self { ino, ..self }
+    }
+
+    /// Sets the owner and the group of the file.
+    pub const fn with_owner(self, uid: u32, gid: u32) -> Self {
+        Self { uid, gid, ..self }
+    }
+
+    /// Returns the permissions of the file.
+    pub const fn perm(&self) -> FilePerm {
+        self.attr.perm()
+    }
+
+    /// Returns the type of the file.
+    pub const fn file_type(&self) -> FileType {
+        self.attr.file_type()
+    }
+
+    /// Whether the file is a directory.
+    pub const fn is_dir(&self) -> bool {
+        self.attr.is_dir()
+    }
+
+    /// Whether the file is a regular file.
+    pub const fn is_file(&self) -> bool {
+        self.attr.is_file()
+    }
+
+    /// Returns the size of the file in bytes.
+    pub const fn size(&self) -> u64 {
+        self.attr.size()
+    }
+
+    /// Returns the number of blocks allocated to the file, in 512-byte units.
+    pub const fn blocks(&self) -> u64 {
+        self.attr.blocks()
+    }
+
+    /// Returns the timestamps of the file.
+    pub const fn times(&self) -> FileTimes {
+        self.times
+    }
+
+    /// Returns the time of the last access.
+    pub const fn atime(&self) -> Duration {
+        self.times.atime
+    }
+
+    /// Returns the time of the last modification.
+    pub const fn mtime(&self) -> Duration {
+        self.times.mtime
+    }
+
+    /// Returns the time of the last status change.
+    pub const fn ctime(&self) -> Duration {
+        self.times.ctime
+    }
+
+    /// Returns the inode number of the file, or 0 if the filesystem does not
+    /// have inode numbers.
+    pub const fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the user ID of the owner of the file, which is root if the
+    /// filesystem does not keep owners.
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the group ID of the file.
+    pub const fn gid(&self) -> u32 {
+        self.gid
     }
 }
 
 impl Drop for File {
     fn drop(&mut self) {
+        if let Some(cache) = self.cache.take() {
+            crate::page_cache::close(cache);
+        }
+        lock::unlock_file(&self.lock_key, self.id);
         unsafe { self.node.access_unchecked().release().ok() };
     }
 }
@@ -397,16 +866,11 @@ impl From<&OpenOptions> for Cap {
     }
 }
 
-fn perm_to_cap(perm: FilePerm) -> Cap {
-    let mut cap = Cap::empty();
-    if perm.owner_readable() {
-        cap |= Cap::READ;
-    }
-    if perm.owner_writable() {
-        cap |= Cap::WRITE;
-    }
-    if perm.owner_executable() {
-        cap |= Cap::EXECUTE;
+/// Returns the path of `path` relative to `dir`, or `path` itself if `dir` is
+/// `None`.
+fn path_at(dir: Option<&Directory>, path: &str) -> AxResult<String> {
+    match dir {
+        Some(dir) => dir.path_at(path),
+        None => Ok(path.into()),
     }
-    cap
 }
@@ -0,0 +1,311 @@
+//! Device files, usually mounted on `/dev`.
+//!
+//! The filesystem is [`DeviceFileSystem`], filled with the devices of the
+//! kernel: `null`, `zero`, `random`, `urandom`, `console`, `tty`, and the block
+//! devices such as `vdb`. Drivers and applications can publish their own
+//! devices with [`register_device`], which also adds them to the devfs
+//! instances already mounted.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
+use axsync::{Mutex, MutexGuard};
+
+pub use axfs_devfs::*;
+
+/// Devices published in every devfs instance, indexed by their paths.
+static DEVICES: Mutex<BTreeMap<String, VfsNodeRef>> = Mutex::new(BTreeMap::new());
+
+/// The devfs instances created by [`new_devfs`].
+static INSTANCES: Mutex<Vec<Instance>> = Mutex::new(Vec::new());
+
+/// A devfs instance, with its directories indexed by their paths, since
+/// [`DirNode`] does not allow to look up its subdirectories.
+struct Instance {
+    fs: Weak<DeviceFileSystem>,
+    dirs: BTreeMap<String, Arc<DirNode>>,
+}
+
+impl Instance {
+    /// Adds `node` at `path`, or returns `false` if the instance has been
+    /// dropped.
+    fn add(&mut self, path: &str, node: VfsNodeRef) -> bool {
+        let fs = match self.fs.upgrade() {
+            Some(fs) => fs,
+            None => return false,
+        };
+        let (dir_path, name) = path.rsplit_once('/').unwrap_or(("", path));
+        // names in `axfs_devfs` are static, and devices are never removed
+        let name: &'static str = name.to_string().leak();
+        if dir_path.is_empty() {
+            fs.add(name, node);
+        } else {
+            self.get_or_create_dir(&fs, dir_path).add(name, node);
+        }
+        true
+    }
+
+    fn get_or_create_dir(&mut self, fs: &DeviceFileSystem, path: &str) -> Arc<DirNode> {
+        if let Some(dir) = self.dirs.get(path) {
+            return dir.clone();
+        }
+        let name: &'static str = path.rsplit('/').next().unwrap().to_string().leak();
+        let dir = match path.rsplit_once('/') {
+            Some((parent, _)) => self.get_or_create_dir(fs, parent).mkdir(name),
+            None => fs.mkdir(name),
+        };
+        self.dirs.insert(path.into(), dir.clone());
+        dir
+    }
+}
+
+/// Creates a devfs instance with all devices published so far.
+pub(crate) fn new_devfs() -> Arc<DeviceFileSystem> {
+    let devfs = Arc::new(DeviceFileSystem::new());
+    let mut instance = Instance {
+        fs: Arc::downgrade(&devfs),
+        dirs: BTreeMap::new(),
+    };
+    for (path, node) in devices().iter() {
+        instance.add(path, node.clone());
+    }
+    let mut instances = INSTANCES.lock();
+    instances.retain(|instance| instance.fs.strong_count() > 0);
+    instances.push(instance);
+    devfs
+}
+
+/// Publishes a device at `path` relative to the devfs root, e.g. `fb0` or
+/// `input/event0`. Missing parent directories are created.
+///
+/// Returns [`AlreadyExists`](axerrno::AxError::AlreadyExists) if there is
+/// already a device or a directory at `path`, or if one of its parents is a
+/// device.
+pub fn register_device(path: &str, node: VfsNodeRef) -> AxResult {
+    let names = path
+        .split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .collect::<Vec<_>>();
+    if names.is_empty() || names.contains(&"..") {
+        return ax_err!(InvalidInput);
+    }
+    let path = names.join("/");
+
+    let mut devices = devices();
+    let is_parent = |parent: &str, child: &str| {
+        child.len() > parent.len()
+            && child.starts_with(parent)
+            && child.as_bytes()[parent.len()] == b'/'
+    };
+    if devices
+        .keys()
+        .any(|other| *other == path || is_parent(other, &path) || is_parent(&path, other))
+    {
+        return ax_err!(AlreadyExists);
+    }
+    devices.insert(path.clone(), node.clone());
+    drop(devices);
+
+    INSTANCES
+        .lock()
+        .retain_mut(|instance| instance.add(&path, node.clone()));
+    Ok(())
+}
+
+/// Returns the published devices, starting with the default ones.
+fn devices() -> MutexGuard<'static, BTreeMap<String, VfsNodeRef>> {
+    let mut devices = DEVICES.lock();
+    if devices.is_empty() {
+        let defaults: [(&str, VfsNodeRef); 7] = [
+            ("null", Arc::new(NullDev)),
+            ("zero", Arc::new(ZeroDev)),
+            ("random", Arc::new(RandomDev)),
+            ("urandom", Arc::new(RandomDev)),
+            ("console", Arc::new(ConsoleDev)),
+            ("tty", Arc::new(ConsoleDev)),
+            ("foo/bar", Arc::new(ZeroDev)),
+        ];
+        for (path, node) in defaults {
+            devices.insert(path.into(), node);
+        }
+    }
+    devices
+}
+
+fn char_device_attr() -> VfsNodeAttr {
+    VfsNodeAttr::new(
+        VfsNodePerm::from_bits_truncate(0o666),
+        VfsNodeType::CharDevice,
+        0,
+        0,
+    )
+}
+
+/// A device that produces pseudo-random bytes, such as `/dev/random` and
+/// `/dev/urandom`. Reads never block, and writes are ignored.
+pub struct RandomDev;
+
+impl VfsNodeOps for RandomDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(char_device_attr())
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        for chunk in buf.chunks_mut(16) {
+            let random = axhal::misc::random().to_le_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The console of the kernel, such as `/dev/console` and `/dev/tty`.
+///
+/// Reads wait until at least one byte is available, and return the bytes
+/// received so far, with `\r` translated to `\n`.
+pub struct ConsoleDev;
+
+impl VfsNodeOps for ConsoleDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(char_device_attr())
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            match axhal::console::getchar() {
+                Some(c) => {
+                    buf[read_len] = if c == b'\r' { b'\n' } else { c };
+                    read_len += 1;
+                }
+                None if read_len > 0 => break,
+                None => {
+                    #[cfg(feature = "multitask")]
+                    axtask::yield_now();
+                    #[cfg(not(feature = "multitask"))]
+                    core::hint::spin_loop();
+                }
+            }
+        }
+        Ok(read_len)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        axhal::console::write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The framebuffer of [`axdisplay`], usually `/dev/fb0`.
+///
+/// Its contents are the pixels of the screen, which is refreshed after each
+/// write.
+#[cfg(feature = "fbdev")]
+pub struct FramebufferDev {
+    info: axdisplay::DisplayInfo,
+}
+
+#[cfg(feature = "fbdev")]
+impl FramebufferDev {
+    /// Creates the device of the framebuffer. The display must have been
+    /// initialized.
+    pub fn new() -> Self {
+        Self {
+            info: axdisplay::framebuffer_info(),
+        }
+    }
+
+    fn range(&self, offset: u64, len: usize) -> core::ops::Range<usize> {
+        let start = (offset as usize).min(self.info.fb_size);
+        start..(start + len).min(self.info.fb_size)
+    }
+
+    fn pixels(&self) -> *mut u8 {
+        self.info.fb_base_vaddr as *mut u8
+    }
+}
+
+#[cfg(feature = "fbdev")]
+impl Default for FramebufferDev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "fbdev")]
+impl VfsNodeOps for FramebufferDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = self.info.fb_size as u64;
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o660),
+            VfsNodeType::CharDevice,
+            size,
+            size.div_ceil(512),
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let range = self.range(offset, buf.len());
+        // SAFETY: the framebuffer is mapped by the display driver, and `range`
+        // is within it.
+        unsafe {
+            let src = self.pixels().add(range.start);
+            core::ptr::copy_nonoverlapping(src, buf.as_mut_ptr(), range.len());
+        }
+        Ok(range.len())
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let range = self.range(offset, buf.len());
+        // SAFETY: the framebuffer is mapped by the display driver, and `range`
+        // is within it.
+        unsafe {
+            let dst = self.pixels().add(range.start);
+            core::ptr::copy_nonoverlapping(buf.as_ptr(), dst, range.len());
+        }
+        axdisplay::framebuffer_flush();
+        Ok(range.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    fn fsync(&self) -> VfsResult {
+        axdisplay::framebuffer_flush();
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// Publishes the framebuffer of [`axdisplay`] at `/dev/fb0`. It must be called
+/// after the display is initialized.
+#[cfg(feature = "fbdev")]
+pub fn register_framebuffer() -> AxResult {
+    register_device("fb0", Arc::new(FramebufferDev::new()))
+}
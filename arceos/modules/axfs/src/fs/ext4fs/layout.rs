@@ -0,0 +1,499 @@
+//! On-disk structures of ext2/3/4, which are kept as raw little-endian bytes
+//! and accessed by field.
+
+use alloc::vec::Vec;
+use axfs_vfs::VfsNodeType;
+
+/// Byte offset of the superblock on the device.
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+/// Size of the superblock.
+pub const SUPERBLOCK_SIZE: usize = 1024;
+/// Magic number of the superblock.
+pub const EXT2_MAGIC: u16 = 0xef53;
+/// Inode number of the root directory.
+pub const ROOT_INO: u32 = 2;
+/// Number of block pointers in an inode.
+pub const N_BLOCKS: usize = 15;
+/// Number of direct block pointers in an inode.
+pub const N_DIRECT: usize = 12;
+/// Maximum length of a file name.
+pub const NAME_LEN: usize = 255;
+/// Symbolic links shorter than this are stored in the inode itself.
+pub const FAST_SYMLINK_LEN: usize = N_BLOCKS * 4;
+
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_EXTENTS: u32 = 0x40;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_MMP: u32 = 0x100;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub const INCOMPAT_LARGEDIR: u32 = 0x4000;
+
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+
+/// Incompatible features that are supported for reading.
+pub const INCOMPAT_READ: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_MMP
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+/// Incompatible features that are supported for writing, i.e. ext2.
+pub const INCOMPAT_WRITE: u32 = INCOMPAT_FILETYPE;
+/// Read-only compatible features that are supported for writing. Others,
+/// such as checksums, make the filesystem read-only.
+pub const RO_COMPAT_WRITE: u32 =
+    RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE | RO_COMPAT_DIR_NLINK | RO_COMPAT_EXTRA_ISIZE;
+
+/// Inode flag of hashed-indexed directories.
+pub const INDEX_FL: u32 = 0x1000;
+/// Inode flag of files mapped by extents.
+pub const EXTENTS_FL: u32 = 0x80000;
+/// Inode flag of files with inline data.
+pub const INLINE_DATA_FL: u32 = 0x1000_0000;
+
+/// Magic number of extent tree nodes.
+pub const EXTENT_MAGIC: u16 = 0xf30a;
+/// Extents longer than this are uninitialized, and read as zeros.
+pub const EXTENT_INIT_MAX_LEN: u16 = 32768;
+
+pub const S_IFMT: u16 = 0o170000;
+pub const S_IFDIR: u16 = 0o040000;
+pub const S_IFREG: u16 = 0o100000;
+pub const S_IFLNK: u16 = 0o120000;
+
+pub fn le16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+pub fn le32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+pub fn set_le16(buf: &mut [u8], off: usize, val: u16) {
+    buf[off..off + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+pub fn set_le32(buf: &mut [u8], off: usize, val: u32) {
+    buf[off..off + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+/// The superblock.
+pub struct Superblock(pub [u8; SUPERBLOCK_SIZE]);
+
+impl Superblock {
+    pub fn inodes_count(&self) -> u32 {
+        le32(&self.0, 0)
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        self.hi_lo(0x150, 4)
+    }
+
+    pub fn r_blocks_count(&self) -> u64 {
+        self.hi_lo(0x154, 8)
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        self.hi_lo(0x158, 12)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u64) {
+        set_le32(&mut self.0, 12, count as u32);
+        if self.is_64bit() {
+            set_le32(&mut self.0, 0x158, (count >> 32) as u32);
+        }
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        le32(&self.0, 16)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        set_le32(&mut self.0, 16, count);
+    }
+
+    pub fn first_data_block(&self) -> u32 {
+        le32(&self.0, 20)
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << le32(&self.0, 24)
+    }
+
+    pub fn blocks_per_group(&self) -> u32 {
+        le32(&self.0, 32)
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        le32(&self.0, 40)
+    }
+
+    pub fn set_wtime(&mut self, time: u32) {
+        set_le32(&mut self.0, 48, time);
+    }
+
+    pub fn magic(&self) -> u16 {
+        le16(&self.0, 56)
+    }
+
+    pub fn rev_level(&self) -> u32 {
+        le32(&self.0, 76)
+    }
+
+    pub fn first_ino(&self) -> u32 {
+        match self.rev_level() {
+            0 => 11,
+            _ => le32(&self.0, 84),
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        match self.rev_level() {
+            0 => 128,
+            _ => le16(&self.0, 88) as usize,
+        }
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        le32(&self.0, 96)
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        le32(&self.0, 100)
+    }
+
+    pub fn is_64bit(&self) -> bool {
+        self.feature_incompat() & INCOMPAT_64BIT != 0
+    }
+
+    pub fn desc_size(&self) -> usize {
+        if self.is_64bit() {
+            (le16(&self.0, 0xfe) as usize).max(32)
+        } else {
+            32
+        }
+    }
+
+    fn hi_lo(&self, hi: usize, lo: usize) -> u64 {
+        let hi = if self.is_64bit() {
+            le32(&self.0, hi)
+        } else {
+            0
+        };
+        ((hi as u64) << 32) | le32(&self.0, lo) as u64
+    }
+}
+
+/// A block group descriptor.
+pub struct GroupDesc(pub Vec<u8>);
+
+impl GroupDesc {
+    pub fn block_bitmap(&self) -> u64 {
+        self.hi_lo32(0x20, 0)
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        self.hi_lo32(0x24, 4)
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        self.hi_lo32(0x28, 8)
+    }
+
+    pub fn free_blocks_count(&self) -> u32 {
+        self.hi_lo16(0x2c, 12)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u32) {
+        self.set_hi_lo16(0x2c, 12, count);
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        self.hi_lo16(0x2e, 14)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        self.set_hi_lo16(0x2e, 14, count);
+    }
+
+    pub fn used_dirs_count(&self) -> u32 {
+        self.hi_lo16(0x30, 16)
+    }
+
+    pub fn set_used_dirs_count(&mut self, count: u32) {
+        self.set_hi_lo16(0x30, 16, count);
+    }
+
+    fn hi_lo32(&self, hi: usize, lo: usize) -> u64 {
+        let hi = if self.0.len() >= 64 {
+            le32(&self.0, hi)
+        } else {
+            0
+        };
+        ((hi as u64) << 32) | le32(&self.0, lo) as u64
+    }
+
+    fn hi_lo16(&self, hi: usize, lo: usize) -> u32 {
+        let hi = if self.0.len() >= 64 {
+            le16(&self.0, hi)
+        } else {
+            0
+        };
+        ((hi as u32) << 16) | le16(&self.0, lo) as u32
+    }
+
+    fn set_hi_lo16(&mut self, hi: usize, lo: usize, val: u32) {
+        set_le16(&mut self.0, lo, val as u16);
+        if self.0.len() >= 64 {
+            set_le16(&mut self.0, hi, (val >> 16) as u16);
+        }
+    }
+}
+
+/// An inode.
+#[derive(Clone)]
+pub struct Inode(pub Vec<u8>);
+
+impl Inode {
+    pub fn mode(&self) -> u16 {
+        le16(&self.0, 0)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        set_le16(&mut self.0, 0, mode);
+    }
+
+    /// Returns the owner, whose high 16 bits are in the Linux-specific area.
+    pub fn uid(&self) -> u32 {
+        ((le16(&self.0, 120) as u32) << 16) | le16(&self.0, 2) as u32
+    }
+
+    pub fn set_uid(&mut self, uid: u32) {
+        set_le16(&mut self.0, 2, uid as u16);
+        set_le16(&mut self.0, 120, (uid >> 16) as u16);
+    }
+
+    /// Returns the group, whose high 16 bits are in the Linux-specific area.
+    pub fn gid(&self) -> u32 {
+        ((le16(&self.0, 122) as u32) << 16) | le16(&self.0, 24) as u32
+    }
+
+    pub fn set_gid(&mut self, gid: u32) {
+        set_le16(&mut self.0, 24, gid as u16);
+        set_le16(&mut self.0, 122, (gid >> 16) as u16);
+    }
+
+    pub fn node_type(&self) -> VfsNodeType {
+        mode_to_type(self.mode())
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode() & S_IFMT == S_IFDIR
+    }
+
+    pub fn size(&self) -> u64 {
+        ((le32(&self.0, 108) as u64) << 32) | le32(&self.0, 4) as u64
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        set_le32(&mut self.0, 4, size as u32);
+        set_le32(&mut self.0, 108, (size >> 32) as u32);
+    }
+
+    pub fn atime(&self) -> u32 {
+        le32(&self.0, 8)
+    }
+
+    pub fn set_atime(&mut self, time: u32) {
+        set_le32(&mut self.0, 8, time);
+    }
+
+    pub fn ctime(&self) -> u32 {
+        le32(&self.0, 12)
+    }
+
+    pub fn set_ctime(&mut self, time: u32) {
+        set_le32(&mut self.0, 12, time);
+    }
+
+    pub fn mtime(&self) -> u32 {
+        le32(&self.0, 16)
+    }
+
+    pub fn set_mtime(&mut self, time: u32) {
+        set_le32(&mut self.0, 16, time);
+    }
+
+    pub fn set_dtime(&mut self, time: u32) {
+        set_le32(&mut self.0, 20, time);
+    }
+
+    pub fn links_count(&self) -> u16 {
+        le16(&self.0, 26)
+    }
+
+    pub fn set_links_count(&mut self, count: u16) {
+        set_le16(&mut self.0, 26, count);
+    }
+
+    /// Returns the number of 512-byte sectors allocated to the inode.
+    pub fn blocks(&self) -> u64 {
+        ((le16(&self.0, 116) as u64) << 32) | le32(&self.0, 28) as u64
+    }
+
+    pub fn set_blocks(&mut self, blocks: u64) {
+        set_le32(&mut self.0, 28, blocks as u32);
+        set_le16(&mut self.0, 116, (blocks >> 32) as u16);
+    }
+
+    pub fn flags(&self) -> u32 {
+        le32(&self.0, 32)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        set_le32(&mut self.0, 32, flags);
+    }
+
+    /// Returns the `i`-th block pointer.
+    pub fn block(&self, i: usize) -> u32 {
+        le32(&self.0, 40 + i * 4)
+    }
+
+    pub fn set_block(&mut self, i: usize, block: u32) {
+        set_le32(&mut self.0, 40 + i * 4, block);
+    }
+
+    /// Returns the area of block pointers, which holds the extent tree root or
+    /// the target of a fast symbolic link instead in some inodes.
+    pub fn block_area(&self) -> &[u8] {
+        &self.0[40..40 + N_BLOCKS * 4]
+    }
+
+    pub fn block_area_mut(&mut self) -> &mut [u8] {
+        &mut self.0[40..40 + N_BLOCKS * 4]
+    }
+
+    pub fn file_acl(&self) -> u32 {
+        le32(&self.0, 104)
+    }
+}
+
+/// A directory entry parsed from a directory block.
+pub struct DirEntry {
+    /// Inode number, 0 for unused entries.
+    pub ino: u32,
+    /// Offset of the entry in the directory data.
+    pub offset: u64,
+    pub rec_len: usize,
+    pub name: Vec<u8>,
+    pub file_type: u8,
+}
+
+impl DirEntry {
+    /// Returns the size actually used by the entry.
+    pub fn used_len(&self) -> usize {
+        if self.ino == 0 {
+            0
+        } else {
+            dir_rec_len(self.name.len())
+        }
+    }
+}
+
+/// Returns the minimum record length of a directory entry with a name of
+/// `name_len` bytes.
+pub const fn dir_rec_len(name_len: usize) -> usize {
+    (8 + name_len + 3) & !3
+}
+
+/// Parses the directory entries in a directory block at `offset` of the
+/// directory data. Returns `None` if the block is corrupted.
+pub fn parse_dir_block(block: &[u8], offset: u64, filetype: bool) -> Option<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= block.len() {
+        let rec_len = le16(block, pos + 4) as usize;
+        let name_len = if filetype {
+            block[pos + 6] as usize
+        } else {
+            le16(block, pos + 6) as usize
+        };
+        if rec_len < 8 || rec_len % 4 != 0 || pos + rec_len > block.len() {
+            return None;
+        }
+        let name_len = name_len.min(rec_len - 8);
+        entries.push(DirEntry {
+            ino: le32(block, pos),
+            offset: offset + pos as u64,
+            rec_len,
+            name: block[pos + 8..pos + 8 + name_len].into(),
+            file_type: if filetype { block[pos + 7] } else { 0 },
+        });
+        pos += rec_len;
+    }
+    Some(entries)
+}
+
+/// Writes a directory entry at `pos` of a directory block.
+pub fn write_dir_entry(
+    block: &mut [u8],
+    pos: usize,
+    ino: u32,
+    rec_len: usize,
+    name: &[u8],
+    ty: u8,
+) {
+    set_le32(block, pos, ino);
+    set_le16(block, pos + 4, rec_len as u16);
+    block[pos + 6] = name.len() as u8;
+    block[pos + 7] = ty;
+    block[pos + 8..pos + 8 + name.len()].copy_from_slice(name);
+}
+
+/// Converts the file mode of an inode to the node type.
+pub fn mode_to_type(mode: u16) -> VfsNodeType {
+    match mode & S_IFMT {
+        0o010000 => VfsNodeType::Fifo,
+        0o020000 => VfsNodeType::CharDevice,
+        S_IFDIR => VfsNodeType::Dir,
+        0o060000 => VfsNodeType::BlockDevice,
+        S_IFLNK => VfsNodeType::SymLink,
+        0o140000 => VfsNodeType::Socket,
+        _ => VfsNodeType::File,
+    }
+}
+
+/// Converts the node type to the file type in directory entries.
+pub fn type_to_dirent_type(ty: VfsNodeType) -> u8 {
+    match ty {
+        VfsNodeType::File => 1,
+        VfsNodeType::Dir => 2,
+        VfsNodeType::CharDevice => 3,
+        VfsNodeType::BlockDevice => 4,
+        VfsNodeType::Fifo => 5,
+        VfsNodeType::Socket => 6,
+        VfsNodeType::SymLink => 7,
+    }
+}
+
+/// Converts the file type in directory entries to the node type, or `None` if
+/// unknown.
+pub fn dirent_type_to_type(ty: u8) -> Option<VfsNodeType> {
+    Some(match ty {
+        1 => VfsNodeType::File,
+        2 => VfsNodeType::Dir,
+        3 => VfsNodeType::CharDevice,
+        4 => VfsNodeType::BlockDevice,
+        5 => VfsNodeType::Fifo,
+        6 => VfsNodeType::Socket,
+        7 => VfsNodeType::SymLink,
+        _ => return None,
+    })
+}
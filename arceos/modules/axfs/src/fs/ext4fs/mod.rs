@@ -0,0 +1,301 @@
+//! An ext2/3/4 filesystem.
+//!
+//! Volumes with only ext2 features (and ext3 ones that need no journal
+//! replay) are mounted read-write. Volumes with ext4 features such as extents,
+//! 64-bit block numbers or metadata checksums are mounted read-only, and
+//! modifications fail with [`VfsError::PermissionDenied`].
+//!
+//! Unlike FAT, files keep their Unix permissions, owners, inode numbers,
+//! timestamps and link counts. Symbolic links are created by [`VfsNodeOps::create`] with
+//! [`VfsNodeType::SymLink`], and their targets are set by writing to them.
+//! Hard links are created by [`Ext4Node::link`].
+
+mod layout;
+mod volume;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axsync::Mutex;
+
+use self::layout::ROOT_INO;
+use self::volume::Volume;
+use crate::dev::Disk;
+use crate::fops::{FileTimes, FsStats};
+
+/// An ext2/3/4 filesystem on a disk.
+pub struct Ext4FileSystem {
+    vol: Arc<Mutex<Volume>>,
+}
+
+/// A node of [`Ext4FileSystem`], identified by its inode number. The type of
+/// the node is given by the inode.
+pub struct Ext4Node {
+    vol: Arc<Mutex<Volume>>,
+    ino: u32,
+}
+
+impl Ext4FileSystem {
+    /// Opens the filesystem on `disk`.
+    pub fn new(disk: Disk) -> VfsResult<Self> {
+        let vol = Volume::open(disk)?;
+        let mode = if vol.is_writable() {
+            "read-write"
+        } else {
+            "read-only"
+        };
+        info!("ext4fs: block size {}, {}", vol.block_size(), mode);
+        Ok(Self {
+            vol: Arc::new(Mutex::new(vol)),
+        })
+    }
+}
+
+impl VfsOps for Ext4FileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        Ext4Node::new(self.vol.clone(), ROOT_INO)
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.vol.lock().flush()
+    }
+}
+
+impl Ext4Node {
+    fn new(vol: Arc<Mutex<Volume>>, ino: u32) -> Arc<Self> {
+        Arc::new(Self { vol, ino })
+    }
+
+    /// Returns the inode number of the node.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// Returns the timestamps of the node.
+    pub fn times(&self) -> VfsResult<FileTimes> {
+        let inode = self.vol.lock().read_inode(self.ino)?;
+        let secs = |t: u32| Duration::from_secs(t as u64);
+        Ok(FileTimes {
+            atime: secs(inode.atime()),
+            mtime: secs(inode.mtime()),
+            ctime: secs(inode.ctime()),
+        })
+    }
+
+    /// Returns the owner and the group of the node.
+    pub fn owner(&self) -> VfsResult<(u32, u32)> {
+        let inode = self.vol.lock().read_inode(self.ino)?;
+        Ok((inode.uid(), inode.gid()))
+    }
+
+    /// Returns the number of hard links to the node, which is 0 once it has
+    /// been removed.
+    pub fn links(&self) -> VfsResult<u16> {
+        Ok(self.vol.lock().read_inode(self.ino)?.links_count())
+    }
+
+    /// Sets the permissions of the node.
+    pub fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        self.vol.lock().set_perm(self.ino, perm.bits())
+    }
+
+    /// Sets the owner and the group of the node.
+    pub fn set_owner(&self, uid: u32, gid: u32) -> VfsResult {
+        self.vol.lock().set_owner(self.ino, uid, gid)
+    }
+
+    /// Creates a hard link to `node` at `path` relative to this directory.
+    ///
+    /// `node` must be a regular file or a symbolic link of the same
+    /// filesystem, hard links to directories are not allowed.
+    pub fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        let node = node
+            .as_any()
+            .downcast_ref::<Ext4Node>()
+            .filter(|node| Arc::ptr_eq(&node.vol, &self.vol))
+            .ok_or(VfsError::Unsupported)?;
+        let mut vol = self.vol.lock();
+        let (dir, name) = self.parent_of(&mut vol, path)?;
+        vol.link(dir, name, node.ino)
+    }
+
+    /// Returns the inode number of the node at `path` relative to this node.
+    fn lookup_ino(&self, vol: &mut Volume, path: &str) -> VfsResult<u32> {
+        let mut ino = self.ino;
+        for name in path.split('/') {
+            if name.is_empty() || name == "." {
+                continue;
+            }
+            let inode = vol.read_inode(ino)?;
+            if !inode.is_dir() {
+                return Err(VfsError::NotADirectory);
+            }
+            ino = vol.lookup(&inode, name)?;
+        }
+        Ok(ino)
+    }
+
+    /// Returns the inode number of the directory that contains the last
+    /// component of `path`, together with the name of that component.
+    fn parent_of<'a>(&self, vol: &mut Volume, path: &'a str) -> VfsResult<(u32, &'a str)> {
+        let path = path.trim_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let dir = self.lookup_ino(vol, parent)?;
+        if !vol.read_inode(dir)?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok((dir, name))
+    }
+}
+
+impl VfsNodeOps for Ext4Node {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let inode = self.vol.lock().read_inode(self.ino)?;
+        let perm = VfsNodePerm::from_bits_truncate(inode.mode() & 0o777);
+        Ok(VfsNodeAttr::new(
+            perm,
+            inode.node_type(),
+            inode.size(),
+            inode.blocks(),
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        match inode.node_type() {
+            VfsNodeType::Dir => Err(VfsError::IsADirectory),
+            VfsNodeType::SymLink => vol.read_link(&inode, offset, buf),
+            _ => {
+                let len = vol.read_data(&inode, offset, buf)?;
+                vol.touch_accessed(self.ino)?;
+                Ok(len)
+            }
+        }
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        match vol.read_inode(self.ino)?.node_type() {
+            VfsNodeType::Dir => Err(VfsError::IsADirectory),
+            VfsNodeType::SymLink if offset == 0 => vol.write_link(self.ino, buf),
+            VfsNodeType::SymLink => Err(VfsError::InvalidInput),
+            _ => vol.write_data(self.ino, offset, buf),
+        }
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let mut vol = self.vol.lock();
+        match vol.read_inode(self.ino)?.node_type() {
+            VfsNodeType::Dir => Err(VfsError::IsADirectory),
+            VfsNodeType::File => vol.truncate(self.ino, size),
+            _ => Err(VfsError::InvalidInput),
+        }
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.vol.lock().flush()
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.ino == ROOT_INO {
+            return None;
+        }
+        let ino = self.lookup_ino(&mut self.vol.lock(), "..").ok()?;
+        Some(Ext4Node::new(self.vol.clone(), ino))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at ext4fs: {}", path);
+        let ino = self.lookup_ino(&mut self.vol.lock(), path)?;
+        if ino == self.ino {
+            Ok(self)
+        } else {
+            Ok(Ext4Node::new(self.vol.clone(), ino))
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ext4fs: {}", ty, path);
+        let mut vol = self.vol.lock();
+        let (dir, name) = self.parent_of(&mut vol, path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Ok(()); // already exists
+        }
+        vol.create(dir, name, ty).map(|_| ())
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ext4fs: {}", path);
+        let mut vol = self.vol.lock();
+        let (dir, name) = self.parent_of(&mut vol, path)?;
+        vol.remove(dir, name)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        if !inode.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let entries = vol.read_dir(&inode)?;
+        let mut count = 0;
+        for ((name, ty), ent) in entries.iter().skip(start_idx).zip(dirents.iter_mut()) {
+            *ent = VfsDirEntry::new(&String::from_utf8_lossy(name), *ty);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at ext4fs: {} -> {}", src_path, dst_path);
+        let mut vol = self.vol.lock();
+        let (src_dir, src_name) = self.parent_of(&mut vol, src_path)?;
+        let (dst_dir, dst_name) = self.parent_of(&mut vol, dst_path)?;
+        if [src_name, dst_name]
+            .iter()
+            .any(|name| name.is_empty() || *name == "." || *name == "..")
+        {
+            return Err(VfsError::InvalidInput);
+        }
+        vol.rename(src_dir, src_name, dst_dir, dst_name)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// Returns the timestamps of a node in [`Ext4FileSystem`].
+pub(crate) fn node_times(node: &VfsNodeRef) -> Option<FileTimes> {
+    node.as_any().downcast_ref::<Ext4Node>()?.times().ok()
+}
+
+/// Returns the owner and the group of a node in [`Ext4FileSystem`].
+pub(crate) fn node_owner(node: &VfsNodeRef) -> Option<(u32, u32)> {
+    node.as_any().downcast_ref::<Ext4Node>()?.owner().ok()
+}
+
+/// Returns the number of hard links to a node in [`Ext4FileSystem`].
+pub(crate) fn node_links(node: &VfsNodeRef) -> Option<u64> {
+    let node = node.as_any().downcast_ref::<Ext4Node>()?;
+    node.links().ok().map(u64::from)
+}
+
+/// Returns the capacity and usage of the [`Ext4FileSystem`] whose root
+/// directory is `root`.
+pub(crate) fn statfs(root: &VfsNodeRef) -> AxResult<FsStats> {
+    match root.as_any().downcast_ref::<Ext4Node>() {
+        Some(root) => Ok(root.vol.lock().stats()),
+        None => ax_err!(Unsupported),
+    }
+}
+
+/// Returns the inode number of a node in [`Ext4FileSystem`].
+pub(crate) fn node_ino(node: &VfsNodeRef) -> Option<u64> {
+    Some(node.as_any().downcast_ref::<Ext4Node>()?.ino() as u64)
+}
@@ -1,22 +1,53 @@
+use alloc::string::String;
 use alloc::sync::Arc;
-use core::cell::UnsafeCell;
+use core::time::Duration;
 
+use axerrno::{ax_err, AxResult};
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, LossyOemCpConverter, Read, Seek, SeekFrom, Time, TimeProvider, Write};
 
 use crate::dev::Disk;
+use crate::fops::{FileTimes, FsStats};
 
 const BLOCK_SIZE: usize = 512;
 
+/// The magic number of FAT in Linux `statfs`.
+const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+
+type FatFs = fatfs::FileSystem<Disk, AxTimeProvider, LossyOemCpConverter>;
+type FatDir<'a> = fatfs::Dir<'a, Disk, AxTimeProvider, LossyOemCpConverter>;
+type FatFile<'a> = fatfs::File<'a, Disk, AxTimeProvider, LossyOemCpConverter>;
+type FatDirEntry<'a> = fatfs::DirEntry<'a, Disk, AxTimeProvider, LossyOemCpConverter>;
+
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
-    root_dir: UnsafeCell<Option<VfsNodeRef>>,
+    root_dir: VfsNodeRef,
+}
+
+/// A file, with its entry in the parent directory.
+///
+/// Files and directories borrow the filesystem, and keep it alive by the last
+/// field, which is dropped after the borrowing ones. So the filesystem is freed
+/// once it is unmounted and all its nodes are dropped.
+pub struct FileWrapper<'a>(Mutex<FatFile<'a>>, Option<EntryRef<'a>>, Arc<FatFs>);
+/// A directory, with its entry in the parent directory. The root directory has
+/// no entry.
+pub struct DirWrapper<'a>(FatDir<'a>, Option<EntryRef<'a>>, Arc<FatFs>);
+
+/// The location of the directory entry of a file or directory, where its
+/// timestamps are stored.
+struct EntryRef<'a> {
+    dir: FatDir<'a>,
+    name: String,
 }
 
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+/// A [`TimeProvider`] that reads the wall clock, i.e. the RTC if the `rtc`
+/// feature of `axhal` is enabled.
+///
+/// FAT timestamps are in local time, which is always treated as UTC.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AxTimeProvider;
 
 unsafe impl Sync for FatFileSystem {}
 unsafe impl Send for FatFileSystem {}
@@ -28,37 +59,83 @@ unsafe impl<'a> Sync for DirWrapper<'a> {}
 impl FatFileSystem {
     #[cfg(feature = "use-ramdisk")]
     pub fn new(mut disk: Disk) -> Self {
-        let opts = fatfs::FormatVolumeOptions::new();
+        let opts = fatfs::FormatVolumeOptions::new().bytes_per_sector(disk.block_size() as u16);
         fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
-            inner,
-            root_dir: UnsafeCell::new(None),
-        }
+        Self::try_new(disk).expect("failed to initialize FAT filesystem")
     }
 
     #[cfg(not(feature = "use-ramdisk"))]
     pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
-            inner,
-            root_dir: UnsafeCell::new(None),
-        }
+        Self::try_new(disk).expect("failed to initialize FAT filesystem")
     }
 
-    pub fn init(&'static self) {
-        // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+    /// Opens the FAT filesystem on `disk`, without formatting it.
+    ///
+    /// Returns [`InvalidData`](VfsError::InvalidData) if the disk does not
+    /// hold a valid FAT filesystem, e.g. if its boot sector is corrupted or
+    /// the volume is larger than the disk.
+    pub fn try_new(mut disk: Disk) -> VfsResult<Self> {
+        check_volume_size(&mut disk)?;
+        let opts = fatfs::FsOptions::new().time_provider(AxTimeProvider);
+        let inner = fatfs::FileSystem::new(disk, opts).map_err(|e| {
+            warn!("invalid FAT filesystem: {:?}", e);
+            VfsError::InvalidData
+        })?;
+        let inner = Arc::new(inner);
+        // The filesystem is not moved in the `Arc`, and outlives the nodes.
+        let fs: &'static FatFs = unsafe { &*Arc::as_ptr(&inner) };
+        Ok(Self {
+            root_dir: Arc::new(DirWrapper(fs.root_dir(), None, inner)),
+        })
     }
 
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
-        Arc::new(FileWrapper(Mutex::new(file)))
+    fn new_file<'a>(
+        file: FatFile<'a>,
+        entry: Option<EntryRef<'a>>,
+        fs: &Arc<FatFs>,
+    ) -> Arc<FileWrapper<'a>> {
+        Arc::new(FileWrapper(Mutex::new(file), entry, fs.clone()))
     }
 
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
-        Arc::new(DirWrapper(dir))
+    fn new_dir<'a>(
+        dir: FatDir<'a>,
+        entry: Option<EntryRef<'a>>,
+        fs: &Arc<FatFs>,
+    ) -> Arc<DirWrapper<'a>> {
+        Arc::new(DirWrapper(dir, entry, fs.clone()))
+    }
+}
+
+impl<'a> EntryRef<'a> {
+    /// Locates the entry at `path` relative to `dir`.
+    fn new(dir: &FatDir<'a>, path: &str) -> Option<Self> {
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (dir.open_dir(parent).ok()?, name),
+            None => (dir.clone(), path),
+        };
+        Some(Self {
+            dir,
+            name: name.into(),
+        })
+    }
+
+    /// Reads the timestamps from the entry. FAT does not keep the status
+    /// change time, which is reported as the modification time.
+    fn times(&self) -> Option<FileTimes> {
+        let entry = self
+            .dir
+            .iter()
+            .filter_map(Result::ok)
+            .find(|e: &FatDirEntry| {
+                e.file_name().eq_ignore_ascii_case(&self.name)
+                    || e.short_file_name().eq_ignore_ascii_case(&self.name)
+            })?;
+        let mtime = from_fat_date_time(entry.modified());
+        Some(FileTimes {
+            atime: from_fat_date_time(DateTime::new(entry.accessed(), Time::new(0, 0, 0, 0))),
+            mtime,
+            ctime: mtime,
+        })
     }
 }
 
@@ -87,9 +164,28 @@ impl VfsNodeOps for FileWrapper<'static> {
 
     fn truncate(&self, size: u64) -> VfsResult {
         let mut file = self.0.lock();
-        file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
+        let len = file.seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
+        if size > len {
+            // FAT has no holes, and files cannot be seeked beyond their end, so
+            // they are extended by writing zeros
+            let zeros = [0; BLOCK_SIZE];
+            let mut remaining = size - len;
+            while remaining > 0 {
+                let n = remaining.min(BLOCK_SIZE as u64) as usize;
+                match file.write(&zeros[..n]).map_err(as_vfs_err)? {
+                    0 => return Err(VfsError::StorageFull),
+                    n => remaining -= n as u64,
+                }
+            }
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?;
         file.truncate().map_err(as_vfs_err)
     }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
 }
 
 impl VfsNodeOps for DirWrapper<'static> {
@@ -108,7 +204,7 @@ impl VfsNodeOps for DirWrapper<'static> {
     fn parent(&self) -> Option<VfsNodeRef> {
         self.0
             .open_dir("..")
-            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir)))
+            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir, None, &self.2)))
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
@@ -123,9 +219,11 @@ impl VfsNodeOps for DirWrapper<'static> {
 
         // TODO: use `fatfs::Dir::find_entry`, but it's not public.
         if let Ok(file) = self.0.open_file(path) {
-            Ok(FatFileSystem::new_file(file))
+            let entry = EntryRef::new(&self.0, path);
+            Ok(FatFileSystem::new_file(file, entry, &self.2))
         } else if let Ok(dir) = self.0.open_dir(path) {
-            Ok(FatFileSystem::new_dir(dir))
+            let entry = EntryRef::new(&self.0, path);
+            Ok(FatFileSystem::new_dir(dir, entry, &self.2))
         } else {
             Err(VfsError::NotFound)
         }
@@ -196,54 +294,168 @@ impl VfsNodeOps for DirWrapper<'static> {
             .rename(src_path, &self.0, dst_path)
             .map_err(as_vfs_err)
     }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
 }
 
 impl VfsOps for FatFileSystem {
     fn root_dir(&self) -> VfsNodeRef {
-        let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
-        root_dir.clone()
+        self.root_dir.clone()
+    }
+}
+
+impl TimeProvider for AxTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        to_fat_date_time(axhal::time::wall_time())
     }
 }
 
+/// Returns the timestamps of a node in [`FatFileSystem`]. The root directory
+/// has no timestamps.
+pub(crate) fn node_times(node: &VfsNodeRef) -> Option<FileTimes> {
+    let any = node.as_any();
+    let entry = if let Some(file) = any.downcast_ref::<FileWrapper<'static>>() {
+        file.1.as_ref()
+    } else {
+        any.downcast_ref::<DirWrapper<'static>>()?.1.as_ref()
+    };
+    entry?.times()
+}
+
+/// Returns the capacity and usage of the [`FatFileSystem`] whose root
+/// directory is `root`, counted in clusters. FAT has no inodes, so the number
+/// of files is not reported.
+pub(crate) fn statfs(root: &VfsNodeRef) -> AxResult<FsStats> {
+    let fs = match root.as_any().downcast_ref::<DirWrapper<'static>>() {
+        Some(DirWrapper(_, _, fs)) => fs,
+        _ => return ax_err!(Unsupported),
+    };
+    let stats = fs.stats().map_err(as_vfs_err)?;
+    let free = stats.free_clusters() as u64;
+    Ok(FsStats {
+        magic: MSDOS_SUPER_MAGIC,
+        block_size: stats.cluster_size() as u64,
+        blocks: stats.total_clusters() as u64,
+        free_blocks: free,
+        avail_blocks: free,
+        name_max: 255,
+        ..Default::default()
+    })
+}
+
+/// Converts a time since the epoch to a FAT timestamp, which is clamped to the
+/// range FAT supports (1980 to 2107).
+fn to_fat_date_time(time: Duration) -> DateTime {
+    const SECS_PER_DAY: u64 = 86400;
+    let secs = time.as_secs();
+    let (year, month, day) = civil_from_days(secs / SECS_PER_DAY);
+    if year < 1980 {
+        return DateTime::new(Date::new(1980, 1, 1), Time::new(0, 0, 0, 0));
+    } else if year > 2107 {
+        return DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999));
+    }
+    let secs_of_day = secs % SECS_PER_DAY;
+    DateTime::new(
+        Date::new(year as u16, month as u16, day as u16),
+        Time::new(
+            (secs_of_day / 3600) as u16,
+            (secs_of_day / 60 % 60) as u16,
+            (secs_of_day % 60) as u16,
+            time.subsec_millis() as u16,
+        ),
+    )
+}
+
+/// Converts a FAT timestamp to a time since the epoch.
+fn from_fat_date_time(dt: DateTime) -> Duration {
+    let days = days_from_civil(
+        dt.date.year as u64,
+        dt.date.month as u64,
+        dt.date.day as u64,
+    );
+    let secs =
+        days * 86400 + dt.time.hour as u64 * 3600 + dt.time.min as u64 * 60 + dt.time.sec as u64;
+    Duration::from_secs(secs) + Duration::from_millis(dt.time.millis as u64)
+}
+
+/// Returns the date (year, month, day) of the given number of days since the
+/// epoch, in the proleptic Gregorian calendar.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+/// Returns the number of days since the epoch of the given date, which must
+/// not be earlier than the epoch.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = year - (month <= 2) as u64;
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Checks that the volume described by the boot sector of `disk` fits in the
+/// disk, since `fatfs` only finds out when accessing the missing sectors.
+fn check_volume_size(disk: &mut Disk) -> VfsResult {
+    let mut boot_sector = [0; BLOCK_SIZE];
+    disk.set_position(0);
+    let read_len = disk.read(&mut boot_sector).map_err(|_| VfsError::Io)?;
+    disk.set_position(0);
+    if read_len < BLOCK_SIZE {
+        warn!("invalid FAT filesystem: the disk is smaller than a boot sector");
+        return Err(VfsError::InvalidData);
+    }
+    let u16_at = |pos: usize| u16::from_le_bytes([boot_sector[pos], boot_sector[pos + 1]]);
+    let bytes_per_sector = u16_at(0x0b) as u64;
+    let total_sectors = match u16_at(0x13) {
+        0 => u32::from_le_bytes(boot_sector[0x20..0x24].try_into().unwrap()) as u64,
+        sectors => sectors as u64,
+    };
+    if bytes_per_sector * total_sectors > disk.size() {
+        warn!(
+            "invalid FAT filesystem: the volume has {} bytes, but the disk has only {}",
+            bytes_per_sector * total_sectors,
+            disk.size()
+        );
+        return Err(VfsError::InvalidData);
+    }
+    Ok(())
+}
+
 impl fatfs::IoBase for Disk {
     type Error = ();
 }
 
 impl Read for Disk {
-    fn read(&mut self, mut buf: &mut [u8]) -> Result<usize, Self::Error> {
-        let mut read_len = 0;
-        while !buf.is_empty() {
-            match self.read_one(buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    let tmp = buf;
-                    buf = &mut tmp[n..];
-                    read_len += n;
-                }
-                Err(_) => return Err(()),
-            }
-        }
-        Ok(read_len)
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Disk::read(self, buf).map_err(|_| ())
     }
 }
 
 impl Write for Disk {
-    fn write(&mut self, mut buf: &[u8]) -> Result<usize, Self::Error> {
-        let mut write_len = 0;
-        while !buf.is_empty() {
-            match self.write_one(buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    buf = &buf[n..];
-                    write_len += n;
-                }
-                Err(_) => return Err(()),
-            }
-        }
-        Ok(write_len)
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Disk::write(self, buf).map_err(|_| ())
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
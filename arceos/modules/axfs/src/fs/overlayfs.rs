@@ -0,0 +1,498 @@
+//! An overlay (union) filesystem, which stacks a writable upper filesystem over
+//! a lower one that is never modified.
+//!
+//! Lookups see the upper layer first, then the lower one. Writing to a file of
+//! the lower layer first copies it up, together with its parent directories.
+//! Removing a file or directory of the lower layer records a whiteout, which
+//! hides it and everything below it in the lower layer, so that a new file or
+//! directory of the same name only shows the contents of the upper layer.
+//! Directories present in both layers are merged by `read_dir`.
+//!
+//! Whiteouts are kept in memory rather than in the upper layer, so the upper
+//! layer is expected to be volatile as well, such as a ramfs.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsNodePerm, VfsOps, VfsResult};
+use axsync::Mutex;
+
+use crate::fops::{FileTimes, FsStats};
+
+/// The magic number of overlayfs in Linux `statfs`.
+const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c_7630;
+
+/// The buffer size used to copy files.
+const COPY_BUF_SIZE: usize = 4096;
+
+/// An overlay filesystem.
+pub struct OverlayFileSystem {
+    inner: Arc<Overlay>,
+}
+
+/// A node of [`OverlayFileSystem`], identified by its path relative to the
+/// root of the filesystem. It is resolved to a node of either layer on every
+/// operation.
+pub struct OverlayNode {
+    fs: Arc<Overlay>,
+    path: String,
+}
+
+struct Overlay {
+    lower: Arc<dyn VfsOps>,
+    upper: Arc<dyn VfsOps>,
+    lower_type: String,
+    /// Paths hidden in the lower layer, together with everything below them.
+    whiteouts: Mutex<BTreeSet<String>>,
+    /// The parent of the mount point, if the filesystem is not mounted at `/`.
+    parent: Mutex<Option<Weak<dyn VfsNodeOps>>>,
+    /// Held while copying nodes up, so that a node being copied up by one
+    /// task is neither copied again nor modified half-copied by another.
+    copy_up_lock: Mutex<()>,
+}
+
+impl OverlayFileSystem {
+    /// Creates an overlay of `upper` over `lower`, whose filesystem type is
+    /// `lower_type`.
+    pub fn new(lower: Arc<dyn VfsOps>, upper: Arc<dyn VfsOps>, lower_type: &str) -> Self {
+        Self {
+            inner: Arc::new(Overlay {
+                lower,
+                upper,
+                lower_type: lower_type.into(),
+                whiteouts: Mutex::new(BTreeSet::new()),
+                parent: Mutex::new(None),
+                copy_up_lock: Mutex::new(()),
+            }),
+        }
+    }
+}
+
+impl VfsOps for OverlayFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            *self.inner.parent.lock() = Some(Arc::downgrade(&parent));
+        }
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        OverlayNode::new(self.inner.clone(), String::new())
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.inner.upper.umount()?;
+        self.inner.lower.umount()
+    }
+}
+
+impl Overlay {
+    fn is_whited_out(&self, path: &str) -> bool {
+        let whiteouts = self.whiteouts.lock();
+        ancestors(path).any(|p| whiteouts.contains(p))
+    }
+
+    fn upper_node(&self, path: &str) -> Option<VfsNodeRef> {
+        self.upper.root_dir().lookup(path).ok()
+    }
+
+    fn lower_node(&self, path: &str) -> Option<VfsNodeRef> {
+        if self.is_whited_out(path) {
+            return None;
+        }
+        self.lower.root_dir().lookup(path).ok()
+    }
+
+    /// Returns the node at `path` in the upper layer if it exists there, or
+    /// in the lower layer otherwise. The flag tells whether it is in the
+    /// upper layer.
+    fn real_node(&self, path: &str) -> VfsResult<(VfsNodeRef, bool)> {
+        match self.upper.root_dir().lookup(path) {
+            Ok(node) => return Ok((node, true)),
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        match self.lower_node(path) {
+            Some(node) => Ok((node, false)),
+            None => Err(VfsError::NotFound),
+        }
+    }
+
+    /// Copies the node at `path` from the lower layer to the upper one if it
+    /// is not there, and returns the node in the upper layer. Directories are
+    /// copied without their contents.
+    fn copy_up(&self, path: &str) -> VfsResult<VfsNodeRef> {
+        let _guard = self.copy_up_lock.lock();
+        self.copy_up_locked(path)
+    }
+
+    fn copy_up_locked(&self, path: &str) -> VfsResult<VfsNodeRef> {
+        if let Some(node) = self.upper_node(path) {
+            return Ok(node);
+        }
+        let lower = self.lower_node(path).ok_or(VfsError::NotFound)?;
+        if let Some((parent, _)) = path.rsplit_once('/') {
+            self.copy_up_locked(parent)?;
+        }
+
+        let ty = lower.get_attr()?.file_type();
+        debug!("overlayfs: copy up {:?} {}", ty, path);
+        self.upper.root_dir().create(path, ty)?;
+        let upper = self.upper.root_dir().lookup(path)?;
+        if ty != VfsNodeType::Dir {
+            if let Err(e) = copy_data(&lower, &upper) {
+                self.upper.root_dir().remove(path).ok();
+                return Err(e);
+            }
+        }
+        // keep the permissions and the owner if the upper layer can, unlike
+        // the timestamps
+        if ty != VfsNodeType::SymLink {
+            crate::mounts::set_perm("ramfs", &upper, lower.get_attr()?.perm()).ok();
+        }
+        let (uid, gid) = crate::mounts::node_owner(&self.lower_type, &lower).unwrap_or((0, 0));
+        crate::mounts::set_owner("ramfs", &upper, uid, gid).ok();
+        Ok(upper)
+    }
+
+    /// Copies the directory at `path` and everything below it to the upper
+    /// layer.
+    fn copy_up_tree(&self, path: &str) -> VfsResult {
+        let node = self.copy_up(path)?;
+        if node.get_attr()?.is_dir() {
+            for (name, _) in self.merged_entries(path)? {
+                self.copy_up_tree(&join(path, &name))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the entries of the directory at `path` in both layers, except
+    /// `.` and `..`. Entries in the upper layer take precedence.
+    fn merged_entries(&self, path: &str) -> VfsResult<BTreeMap<String, VfsNodeType>> {
+        let (node, in_upper) = self.real_node(path)?;
+        if !node.get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let mut entries = BTreeMap::new();
+        if in_upper {
+            read_all_entries(&node, &mut entries)?;
+        }
+        if let Some(lower) = self.lower_node(path) {
+            if lower.get_attr()?.is_dir() {
+                let mut lower_entries = BTreeMap::new();
+                read_all_entries(&lower, &mut lower_entries)?;
+                for (name, ty) in lower_entries {
+                    if !entries.contains_key(&name) && !self.is_whited_out(&join(path, &name)) {
+                        entries.insert(name, ty);
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Hides `path` in the lower layer, if it is visible there.
+    fn white_out(&self, path: &str) {
+        if self.lower_node(path).is_some() {
+            self.whiteouts.lock().insert(path.into());
+        }
+    }
+}
+
+impl OverlayNode {
+    fn new(fs: Arc<Overlay>, path: String) -> Arc<Self> {
+        Arc::new(Self { fs, path })
+    }
+
+    /// Returns the node of the upper or the lower layer that this node
+    /// currently refers to, and whether it is in the upper layer.
+    pub fn real_node(&self) -> VfsResult<(VfsNodeRef, bool)> {
+        self.fs.real_node(&self.path)
+    }
+
+    /// Returns the path relative to this node as a path relative to the root.
+    fn resolve(&self, path: &str) -> VfsResult<String> {
+        let mut components: Vec<&str> = self.path.split('/').filter(|s| !s.is_empty()).collect();
+        for name in path.split('/') {
+            match name {
+                "" | "." => {}
+                ".." => {
+                    components.pop().ok_or(VfsError::NotFound)?;
+                }
+                _ => components.push(name),
+            }
+        }
+        Ok(components.join("/"))
+    }
+}
+
+impl VfsNodeOps for OverlayNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.real_node()?.0.get_attr()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.real_node()?.0.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.fs.copy_up(&self.path)?.write_at(offset, buf)
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.fs.copy_up(&self.path)?.truncate(size)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        match self.fs.upper_node(&self.path) {
+            Some(node) => node.fsync(),
+            None => Ok(()),
+        }
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        match self.path.rsplit_once('/') {
+            Some((parent, _)) => Some(OverlayNode::new(self.fs.clone(), parent.into())),
+            None if !self.path.is_empty() => Some(OverlayNode::new(self.fs.clone(), String::new())),
+            None => self.fs.parent.lock().as_ref().and_then(Weak::upgrade),
+        }
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(_) => return self.parent().ok_or(VfsError::NotFound), // ".." of the root
+        };
+        if path == self.path {
+            return Ok(self);
+        }
+        self.fs.real_node(&path)?;
+        Ok(OverlayNode::new(self.fs.clone(), path))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at overlayfs: {}", ty, path);
+        let path = self.resolve(path)?;
+        if self.fs.real_node(&path).is_ok() {
+            return if path.is_empty() {
+                Ok(()) // already exists
+            } else {
+                Err(VfsError::AlreadyExists)
+            };
+        }
+        let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+        if !self.fs.real_node(parent)?.0.get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        self.fs.copy_up(parent)?;
+        self.fs.upper.root_dir().create(&path, ty)
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at overlayfs: {}", path);
+        let path = self.resolve(path)?;
+        if path.is_empty() {
+            return Err(VfsError::InvalidInput);
+        }
+        let (node, in_upper) = self.fs.real_node(&path)?;
+        if node.get_attr()?.is_dir() && !self.fs.merged_entries(&path)?.is_empty() {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        if in_upper {
+            self.fs.upper.root_dir().remove(&path)?;
+        }
+        self.fs.white_out(&path);
+        Ok(())
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.fs.merged_entries(&self.path)?;
+        let all = [(".", VfsNodeType::Dir), ("..", VfsNodeType::Dir)]
+            .into_iter()
+            .chain(entries.iter().map(|(name, ty)| (name.as_str(), *ty)));
+        let mut count = 0;
+        for ((name, ty), ent) in all.skip(start_idx).zip(dirents.iter_mut()) {
+            *ent = VfsDirEntry::new(name, ty);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at overlayfs: {} -> {}", src_path, dst_path);
+        let src = self.resolve(src_path)?;
+        let dst = self.resolve(dst_path)?;
+        if src.is_empty() || dst.is_empty() {
+            return Err(VfsError::InvalidInput);
+        }
+        if src == dst {
+            return Ok(());
+        }
+        let dst_parent = dst.rsplit_once('/').map_or("", |(parent, _)| parent);
+        if !self.fs.real_node(dst_parent)?.0.get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+
+        self.fs.copy_up_tree(&src)?;
+        self.fs.copy_up(dst_parent)?;
+        self.fs.upper.root_dir().rename(&src, &dst)?;
+        self.fs.white_out(&src);
+        self.fs.white_out(&dst);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// Returns the timestamps of a node in [`OverlayFileSystem`], as kept by the
+/// layer that it is in.
+pub(crate) fn node_times(node: &VfsNodeRef) -> Option<FileTimes> {
+    let node = node.as_any().downcast_ref::<OverlayNode>()?;
+    let (real, in_upper) = node.real_node().ok()?;
+    let fstype = if in_upper {
+        "ramfs"
+    } else {
+        &node.fs.lower_type
+    };
+    crate::mounts::node_times(fstype, &real)
+}
+
+/// Returns the owner and the group of a node in [`OverlayFileSystem`], as kept
+/// by the layer that it is in.
+pub(crate) fn node_owner(node: &VfsNodeRef) -> Option<(u32, u32)> {
+    let node = node.as_any().downcast_ref::<OverlayNode>()?;
+    let (real, in_upper) = node.real_node().ok()?;
+    let fstype = if in_upper {
+        "ramfs"
+    } else {
+        &node.fs.lower_type
+    };
+    crate::mounts::node_owner(fstype, &real)
+}
+
+/// Sets the permissions of a node in [`OverlayFileSystem`], in the upper
+/// layer after copying it up.
+pub(crate) fn set_perm(node: &VfsNodeRef, perm: VfsNodePerm) -> AxResult {
+    let upper = copy_up(node)?;
+    crate::mounts::set_perm("ramfs", &upper, perm)
+}
+
+/// Sets the owner and the group of a node in [`OverlayFileSystem`], in the
+/// upper layer after copying it up.
+pub(crate) fn set_owner(node: &VfsNodeRef, uid: u32, gid: u32) -> AxResult {
+    let upper = copy_up(node)?;
+    crate::mounts::set_owner("ramfs", &upper, uid, gid)
+}
+
+/// Returns the capacity and usage of the [`OverlayFileSystem`] whose root
+/// directory is `root`, i.e. of its upper layer, which takes all writes.
+pub(crate) fn statfs(root: &VfsNodeRef) -> AxResult<FsStats> {
+    let Some(root) = root.as_any().downcast_ref::<OverlayNode>() else {
+        return ax_err!(Unsupported);
+    };
+    let stats = crate::mounts::statfs("ramfs", &root.fs.upper.root_dir())?;
+    Ok(FsStats {
+        magic: OVERLAYFS_SUPER_MAGIC,
+        ..stats
+    })
+}
+
+/// Allocates the storage of a range of a file in [`OverlayFileSystem`], in
+/// the upper layer after copying it up.
+pub(crate) fn allocate(node: &VfsNodeRef, offset: u64, len: u64, keep_size: bool) -> AxResult {
+    let upper = copy_up(node)?;
+    crate::mounts::allocate("ramfs", &upper, offset, len, keep_size)
+}
+
+/// Punches a hole in a file in [`OverlayFileSystem`], in the upper layer
+/// after copying it up.
+pub(crate) fn punch_hole(node: &VfsNodeRef, offset: u64, len: u64) -> AxResult {
+    let upper = copy_up(node)?;
+    crate::mounts::punch_hole("ramfs", &upper, offset, len)
+}
+
+/// Finds the next hole or data in a file in [`OverlayFileSystem`], as
+/// reported by the layer that it is in.
+pub(crate) fn seek_extent(node: &VfsNodeRef, offset: u64, hole: bool) -> AxResult<Option<u64>> {
+    let Some(node) = node.as_any().downcast_ref::<OverlayNode>() else {
+        return ax_err!(Unsupported);
+    };
+    let (real, in_upper) = node.real_node()?;
+    let fstype = if in_upper {
+        "ramfs"
+    } else {
+        &node.fs.lower_type
+    };
+    crate::mounts::seek_extent(fstype, &real, offset, hole)
+}
+
+fn copy_up(node: &VfsNodeRef) -> AxResult<VfsNodeRef> {
+    match node.as_any().downcast_ref::<OverlayNode>() {
+        Some(node) => Ok(node.fs.copy_up(&node.path)?),
+        None => ax_err!(Unsupported),
+    }
+}
+
+/// Returns `path` and its ancestors, from the longest to the shortest.
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    let mut next = Some(path);
+    core::iter::from_fn(move || {
+        let path = next?;
+        next = path.rsplit_once('/').map(|(parent, _)| parent);
+        Some(path)
+    })
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.into()
+    } else {
+        alloc::format!("{}/{}", dir, name)
+    }
+}
+
+fn read_all_entries(dir: &VfsNodeRef, entries: &mut BTreeMap<String, VfsNodeType>) -> VfsResult {
+    const EMPTY: VfsDirEntry = VfsDirEntry::default();
+    let mut dirents = [EMPTY; 16];
+    let mut start = 0;
+    loop {
+        let n = dir.read_dir(start, &mut dirents)?;
+        for ent in &dirents[..n] {
+            let name = String::from_utf8_lossy(ent.name_as_bytes());
+            if name != "." && name != ".." {
+                entries.insert(name.into_owned(), ent.entry_type());
+            }
+        }
+        if n < dirents.len() {
+            return Ok(());
+        }
+        start += n;
+    }
+}
+
+fn copy_data(src: &VfsNodeRef, dst: &VfsNodeRef) -> VfsResult {
+    let mut buf = alloc::vec![0; COPY_BUF_SIZE];
+    if src.get_attr()?.file_type() == VfsNodeType::SymLink {
+        // the target of a symbolic link is written as a whole
+        let mut target = alloc::vec![0; src.get_attr()?.size() as usize];
+        let len = src.read_at(0, &mut target)?;
+        dst.write_at(0, &target[..len])?;
+        return Ok(());
+    }
+    let mut offset = 0;
+    loop {
+        let n = src.read_at(offset, &mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        dst.write_at(offset, &buf[..n])?;
+        offset += n as u64;
+    }
+}
@@ -0,0 +1,389 @@
+//! A dynamic filesystem that exposes kernel states, usually mounted on `/proc`.
+//!
+//! The contents of the files are generated from the current kernel states every
+//! time they are read.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, format, vec::Vec};
+use core::fmt::Write;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axsync::Mutex;
+
+const PAGE_SIZE: usize = 0x1000;
+
+type Generator = Box<dyn Fn() -> String + Send + Sync>;
+
+/// A dynamic filesystem that exposes kernel states.
+pub struct ProcFileSystem {
+    root: Arc<ProcDir>,
+}
+
+/// A read-only file whose content is generated on read.
+pub struct ProcFile {
+    generate: Generator,
+}
+
+/// A directory in procfs.
+///
+/// Besides its fixed entries, the root directory also contains a directory
+/// for each task, named by the task ID, and the `self` directory for the
+/// current task.
+pub struct ProcDir {
+    this: Weak<ProcDir>,
+    parent: Mutex<Option<Weak<dyn VfsNodeOps>>>,
+    entries: BTreeMap<&'static str, VfsNodeRef>,
+    is_root: bool,
+}
+
+impl ProcFileSystem {
+    /// Create a new procfs with the default files.
+    pub fn new() -> Self {
+        let mut entries: BTreeMap<&'static str, VfsNodeRef> = BTreeMap::new();
+        entries.insert("meminfo", ProcFile::new(meminfo));
+        entries.insert("uptime", ProcFile::new(uptime));
+        entries.insert("mounts", ProcFile::new(mounts));
+        entries.insert("interrupts", ProcFile::new(interrupts));
+        entries.insert("sys", sys_dir());
+        Self {
+            root: ProcDir::new(entries, true),
+        }
+    }
+}
+
+impl VfsOps for ProcFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            self.root.set_parent(&parent);
+        }
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl Default for ProcFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcFile {
+    fn new<F>(generate: F) -> Arc<Self>
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            generate: Box::new(generate),
+        })
+    }
+}
+
+impl VfsNodeOps for ProcFile {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        // the size is unknown until the content is generated
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.generate)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&content[start..end]);
+        Ok(end - start)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+impl ProcDir {
+    fn new(entries: BTreeMap<&'static str, VfsNodeRef>, is_root: bool) -> Arc<Self> {
+        let dir = Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: Mutex::new(None),
+            entries,
+            is_root,
+        });
+        for node in dir.entries.values() {
+            if let Some(subdir) = node.as_any().downcast_ref::<ProcDir>() {
+                subdir.set_parent(&(dir.clone() as VfsNodeRef));
+            }
+        }
+        dir
+    }
+
+    fn set_parent(&self, parent: &VfsNodeRef) {
+        *self.parent.lock() = Some(Arc::downgrade(parent));
+    }
+
+    fn lookup_entry(&self, name: &str) -> Option<VfsNodeRef> {
+        if let Some(node) = self.entries.get(name) {
+            return Some(node.clone());
+        }
+        if !self.is_root {
+            return None;
+        }
+        let tid = match name {
+            "self" => current_tid()?,
+            _ => name.parse().ok()?,
+        };
+        let dir = task_dir(tid)?;
+        dir.set_parent(&(self.this.upgrade()? as VfsNodeRef));
+        Some(dir)
+    }
+
+    /// Returns the names of all entries, including the task directories.
+    fn entry_names(&self) -> Vec<(String, VfsNodeType)> {
+        let mut names = Vec::new();
+        for (name, node) in self.entries.iter() {
+            let ty = if node.as_any().is::<ProcDir>() {
+                VfsNodeType::Dir
+            } else {
+                VfsNodeType::File
+            };
+            names.push((name.to_string(), ty));
+        }
+        if self.is_root {
+            if current_tid().is_some() {
+                names.push(("self".into(), VfsNodeType::Dir));
+            }
+            for tid in task_ids() {
+                names.push((tid.to_string(), VfsNodeType::Dir));
+            }
+        }
+        names
+    }
+}
+
+impl VfsNodeOps for ProcDir {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o555),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.lock().as_ref().and_then(|p| p.upgrade())
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.lookup_entry(name).ok_or(VfsError::NotFound),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let names = self.entry_names();
+        let mut children = names.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, ty)) = children.next() {
+                        *ent = VfsDirEntry::new(name, *ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
+
+/// `/proc/sys`, the tunables that some applications expect to exist.
+fn sys_dir() -> Arc<ProcDir> {
+    let mut core = BTreeMap::new();
+    core.insert("somaxconn", ProcFile::new(|| "4096\n".into()) as VfsNodeRef);
+    let mut net = BTreeMap::new();
+    net.insert("core", ProcDir::new(core, false) as VfsNodeRef);
+    let mut vm = BTreeMap::new();
+    vm.insert(
+        "overcommit_memory",
+        ProcFile::new(|| "0\n".into()) as VfsNodeRef,
+    );
+
+    let mut sys = BTreeMap::new();
+    sys.insert("net", ProcDir::new(net, false) as VfsNodeRef);
+    sys.insert("vm", ProcDir::new(vm, false) as VfsNodeRef);
+    ProcDir::new(sys, false)
+}
+
+/// `/proc/meminfo`
+fn meminfo() -> String {
+    let allocator = axalloc::global_allocator();
+    let used_pages = allocator.used_pages();
+    let free_pages = allocator.available_pages();
+    let mut s = String::new();
+    let mut kb = |name: &str, bytes: usize| writeln!(s, "{:<16}{:>8} kB", name, bytes / 1024);
+    kb("MemTotal:", (used_pages + free_pages) * PAGE_SIZE).ok();
+    kb("MemFree:", free_pages * PAGE_SIZE).ok();
+    kb("MemAvailable:", free_pages * PAGE_SIZE).ok();
+    kb("Cached:", crate::page_cache::cached_bytes()).ok();
+    kb("HeapUsed:", allocator.used_bytes()).ok();
+    kb("HeapFree:", allocator.available_bytes()).ok();
+    s
+}
+
+/// `/proc/uptime`
+fn uptime() -> String {
+    let now = axhal::time::monotonic_time();
+    // idle time is not tracked
+    format!("{}.{:02} 0.00\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+/// `/proc/mounts`
+fn mounts() -> String {
+    let mut s = String::new();
+    crate::root::for_each_mount(|source, target, fstype| {
+        writeln!(s, "{} {} {} rw 0 0", source, target, fstype).ok();
+    });
+    s
+}
+
+/// `/proc/interrupts`
+fn interrupts() -> String {
+    let mut s = String::new();
+    writeln!(s, "{:>4}  {:>10}", "IRQ", "COUNT").ok();
+    for (irq_num, count) in axhal::irq::irq_counts() {
+        writeln!(s, "{:>4}: {:>10}", irq_num, count).ok();
+    }
+    s
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "multitask")] {
+        use axtask::{AxTaskRef, TaskState};
+
+        fn current_tid() -> Option<u64> {
+            axtask::current_may_uninit().map(|curr| curr.id().as_u64())
+        }
+
+        fn task_ids() -> Vec<u64> {
+            let mut tids = Vec::new();
+            axtask::for_each_task(|task| tids.push(task.id().as_u64()));
+            tids
+        }
+
+        fn find_task(tid: u64) -> Option<AxTaskRef> {
+            let mut found = None;
+            axtask::for_each_task(|task| {
+                if task.id().as_u64() == tid {
+                    found = Some(task.clone());
+                }
+            });
+            found
+        }
+
+        /// `/proc/<tid>`, which contains the `stat` and `status` files of the
+        /// task. The task is looked up again on every read, so the files become
+        /// empty after the task is dropped.
+        fn task_dir(tid: u64) -> Option<Arc<ProcDir>> {
+            find_task(tid)?;
+            let mut entries = BTreeMap::new();
+            entries.insert(
+                "stat",
+                ProcFile::new(move || find_task(tid).map_or(String::new(), |t| task_stat(&t)))
+                    as VfsNodeRef,
+            );
+            entries.insert(
+                "status",
+                ProcFile::new(move || find_task(tid).map_or(String::new(), |t| task_status(&t)))
+                    as VfsNodeRef,
+            );
+            Some(ProcDir::new(entries, false))
+        }
+
+        fn state_char(state: TaskState) -> char {
+            match state {
+                TaskState::Running | TaskState::Ready => 'R',
+                TaskState::Blocked => 'S',
+                TaskState::Exited => 'Z',
+            }
+        }
+
+        /// `/proc/<tid>/stat`, the fields that are not tracked are zeros.
+        fn task_stat(task: &AxTaskRef) -> String {
+            format!(
+                "{} ({}) {} 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1\n",
+                task.id().as_u64(),
+                task.name(),
+                state_char(task.state()),
+            )
+        }
+
+        /// `/proc/<tid>/status`
+        fn task_status(task: &AxTaskRef) -> String {
+            let state = match task.state() {
+                TaskState::Running | TaskState::Ready => "R (running)",
+                TaskState::Blocked => "S (sleeping)",
+                TaskState::Exited => "Z (zombie)",
+            };
+            let tid = task.id().as_u64();
+            let mut s = String::new();
+            writeln!(s, "Name:\t{}", task.name()).ok();
+            writeln!(s, "State:\t{}", state).ok();
+            writeln!(s, "Tgid:\t{}", tid).ok();
+            writeln!(s, "Pid:\t{}", tid).ok();
+            writeln!(s, "PPid:\t0").ok();
+            writeln!(s, "Threads:\t1").ok();
+            let stats = task.stats();
+            writeln!(s, "voluntary_ctxt_switches:\t{}", stats.voluntary_switches).ok();
+            writeln!(s, "nonvoluntary_ctxt_switches:\t{}", stats.involuntary_switches).ok();
+            s
+        }
+    } else {
+        fn current_tid() -> Option<u64> {
+            None
+        }
+
+        fn task_ids() -> Vec<u64> {
+            Vec::new()
+        }
+
+        fn task_dir(_tid: u64) -> Option<Arc<ProcDir>> {
+            None
+        }
+    }
+}
@@ -0,0 +1,784 @@
+//! An in-memory filesystem with symbolic and hard link support.
+//!
+//! Symbolic links are created by [`VfsNodeOps::create`] with
+//! [`VfsNodeType::SymLink`], and their targets are set by writing to them.
+//! Hard links are created by [`DirNode::link`].
+//!
+//! All nodes keep their access, modification and status change times, and
+//! their permissions and owners, which can be changed by [`set_perm`] and
+//! [`set_owner`]. New nodes are owned by root.
+//!
+//! Files are sparse: their contents are stored in chunks of [`CHUNK_SIZE`]
+//! bytes, which are only allocated when written or by
+//! [`FileNode::allocate`]. Holes read as zeros and take no memory.
+//!
+//! The total size of the allocated chunks can be limited by a quota, see
+//! [`RamFileSystem::with_quota`]. Without one, the files can grow until the
+//! kernel heap is exhausted.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axsync::Mutex;
+
+use crate::fops::{FileTimes, FsStats};
+
+const BLOCK_SIZE: u64 = 512;
+
+/// The size of the chunks that file contents are allocated in.
+pub const CHUNK_SIZE: u64 = 4096;
+
+/// The block size reported by [`statfs`].
+const STATFS_BLOCK_SIZE: u64 = CHUNK_SIZE;
+
+/// The magic number of ramfs in Linux `statfs`.
+const RAMFS_MAGIC: u64 = 0x8584_58f6;
+
+/// An in-memory filesystem.
+pub struct RamFileSystem {
+    root: Arc<DirNode>,
+}
+
+/// A directory node of [`RamFileSystem`].
+pub struct DirNode {
+    this: Weak<DirNode>,
+    parent: Mutex<Option<Weak<dyn VfsNodeOps>>>,
+    children: Mutex<BTreeMap<String, VfsNodeRef>>,
+    times: Mutex<FileTimes>,
+    owner: Mutex<Owner>,
+    usage: Arc<Usage>,
+}
+
+/// A regular file node of [`RamFileSystem`].
+pub struct FileNode {
+    content: Mutex<Content>,
+    times: Mutex<FileTimes>,
+    owner: Mutex<Owner>,
+    usage: Arc<Usage>,
+}
+
+/// A symbolic link node of [`RamFileSystem`], whose content is the target path.
+pub struct SymlinkNode {
+    target: Mutex<String>,
+    times: Mutex<FileTimes>,
+    owner: Mutex<Owner>,
+}
+
+/// The permissions and the owner of a node.
+#[derive(Clone, Copy)]
+struct Owner {
+    perm: VfsNodePerm,
+    uid: u32,
+    gid: u32,
+}
+
+/// The contents of a [`FileNode`]. Chunks that are not allocated are holes.
+struct Content {
+    size: u64,
+    /// Allocated chunks by their indexes. There may be chunks beyond the end
+    /// of the file, allocated with [`FileNode::allocate`].
+    chunks: BTreeMap<u64, Box<[u8]>>,
+}
+
+/// The total size of the allocated chunks of the files of a
+/// [`RamFileSystem`], shared by all its nodes.
+struct Usage {
+    used: AtomicU64,
+    quota: Option<u64>,
+}
+
+impl RamFileSystem {
+    /// Creates a new, empty filesystem.
+    pub fn new() -> Self {
+        Self::new_with_usage(None)
+    }
+
+    /// Creates a new, empty filesystem whose files take at most `quota` bytes
+    /// of memory in total. Writes beyond it fail with [`VfsError::StorageFull`].
+    pub fn with_quota(quota: u64) -> Self {
+        Self::new_with_usage(Some(quota))
+    }
+
+    fn new_with_usage(quota: Option<u64>) -> Self {
+        let usage = Arc::new(Usage {
+            used: AtomicU64::new(0),
+            quota,
+        });
+        Self {
+            root: DirNode::new(None, usage),
+        }
+    }
+
+    /// Returns the total size of the allocated chunks of the files in bytes.
+    pub fn used_bytes(&self) -> u64 {
+        self.root.usage.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the root directory node.
+    pub fn root_dir_node(&self) -> Arc<DirNode> {
+        self.root.clone()
+    }
+}
+
+impl Default for RamFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Usage {
+    /// Accounts for `size` more bytes of file content, or fails if that
+    /// exceeds the quota.
+    fn charge(&self, size: u64) -> VfsResult {
+        let quota = self.quota.unwrap_or(u64::MAX);
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(size).filter(|&used| used <= quota)
+            })
+            .map(|_| ())
+            .map_err(|_| VfsError::StorageFull)
+    }
+
+    fn release(&self, size: u64) {
+        self.used.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Returns the capacity and usage of the filesystem. Without a quota, the
+    /// capacity is what the files could take from the kernel heap, i.e. their
+    /// current size plus the free memory.
+    fn stats(&self) -> FsStats {
+        let used = self.used.load(Ordering::Relaxed);
+        let (total, free) = match self.quota {
+            Some(quota) => (quota, quota.saturating_sub(used)),
+            None => {
+                let free = free_memory();
+                (used + free, free)
+            }
+        };
+        FsStats {
+            magic: RAMFS_MAGIC,
+            block_size: STATFS_BLOCK_SIZE,
+            blocks: total / STATFS_BLOCK_SIZE,
+            free_blocks: free / STATFS_BLOCK_SIZE,
+            avail_blocks: free / STATFS_BLOCK_SIZE,
+            name_max: 255,
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns the number of bytes that can still be allocated from the kernel
+/// heap, including the free pages it can grow into.
+fn free_memory() -> u64 {
+    let allocator = axalloc::global_allocator();
+    let pages = allocator.available_pages() as u64;
+    allocator.available_bytes() as u64 + pages * axhal::mem::PAGE_SIZE_4K as u64
+}
+
+impl VfsOps for RamFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            *self.root.parent.lock() = Some(Arc::downgrade(&parent));
+        }
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl DirNode {
+    fn new(parent: Option<Weak<dyn VfsNodeOps>>, usage: Arc<Usage>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: Mutex::new(parent),
+            children: Mutex::new(BTreeMap::new()),
+            times: Mutex::new(new_times()),
+            owner: Mutex::new(Owner::new(0o755)),
+            usage,
+        })
+    }
+
+    /// Returns the timestamps of the directory.
+    pub fn times(&self) -> FileTimes {
+        *self.times.lock()
+    }
+
+    fn create_node(&self, name: &str, ty: VfsNodeType) -> VfsResult {
+        if name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut children = self.children.lock();
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        let node: VfsNodeRef = match ty {
+            VfsNodeType::File => Arc::new(FileNode::new(self.usage.clone())),
+            VfsNodeType::Dir => DirNode::new(Some(self.this.clone()), self.usage.clone()),
+            VfsNodeType::SymLink => Arc::new(SymlinkNode::new()),
+            _ => return Err(VfsError::Unsupported),
+        };
+        children.insert(name.into(), node);
+        touch_modified(&self.times);
+        Ok(())
+    }
+
+    fn remove_node(&self, name: &str) -> VfsResult {
+        if name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut children = self.children.lock();
+        let node = children.get(name).ok_or(VfsError::NotFound)?;
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            if !dir.children.lock().is_empty() {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+        }
+        children.remove(name);
+        touch_modified(&self.times);
+        Ok(())
+    }
+
+    /// Returns the directory that contains the last component of `path`,
+    /// together with the name of that component.
+    fn parent_dir_of<'a>(self: &Arc<Self>, path: &'a str) -> VfsResult<(Arc<DirNode>, &'a str)> {
+        let path = path.trim_matches('/');
+        let (parent_path, name) = match path.rfind('/') {
+            Some(n) => (&path[..n], &path[n + 1..]),
+            None => ("", path),
+        };
+        let parent = self.clone().lookup(parent_path)?;
+        match parent.as_any().downcast_ref::<DirNode>() {
+            Some(dir) => Ok((dir.this.upgrade().ok_or(VfsError::NotFound)?, name)),
+            None => Err(VfsError::NotADirectory),
+        }
+    }
+
+    /// Creates a hard link to `node` at `path` relative to this directory.
+    ///
+    /// `node` must be a regular file or a symbolic link of the same
+    /// filesystem, hard links to directories are not allowed.
+    pub fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        if node.as_any().is::<DirNode>() {
+            return Err(VfsError::PermissionDenied);
+        }
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let (dir, name) = this.parent_dir_of(path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        let mut children = dir.children.lock();
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        children.insert(name.into(), node);
+        touch_modified(&dir.times);
+        Ok(())
+    }
+}
+
+impl VfsNodeOps for DirNode {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = self.owner.lock().perm;
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::Dir, 4096, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.lock().as_ref().and_then(|p| p.upgrade())
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self
+                .children
+                .lock()
+                .get(name)
+                .cloned()
+                .ok_or(VfsError::NotFound),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let children = self.children.lock();
+        let mut children = children.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, node)) = children.next() {
+                        *ent = VfsDirEntry::new(name, node.get_attr()?.file_type());
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ramfs: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => {
+                    let subdir = self
+                        .children
+                        .lock()
+                        .get(name)
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.create(rest, ty)
+                }
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            self.create_node(name, ty)
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => {
+                    let subdir = self
+                        .children
+                        .lock()
+                        .get(name)
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.remove(rest)
+                }
+            }
+        } else {
+            self.remove_node(name)
+        }
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at ramfs: {} -> {}", src_path, dst_path);
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let (src_dir, src_name) = this.parent_dir_of(src_path)?;
+        let (dst_dir, dst_name) = this.parent_dir_of(dst_path)?;
+        if [src_name, dst_name]
+            .iter()
+            .any(|name| name.is_empty() || *name == "." || *name == "..")
+        {
+            return Err(VfsError::InvalidInput);
+        }
+
+        let node = src_dir
+            .children
+            .lock()
+            .remove(src_name)
+            .ok_or(VfsError::NotFound)?;
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            *dir.parent.lock() = Some(dst_dir.this.clone());
+        }
+        dst_dir.children.lock().insert(dst_name.to_string(), node);
+        touch_modified(&src_dir.times);
+        touch_modified(&dst_dir.times);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+impl Content {
+    const fn new() -> Self {
+        Self {
+            size: 0,
+            chunks: BTreeMap::new(),
+        }
+    }
+
+    fn allocated_bytes(&self) -> u64 {
+        self.chunks.len() as u64 * CHUNK_SIZE
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        let end = self.size.min(offset.saturating_add(buf.len() as u64));
+        let mut pos = offset;
+        while pos < end {
+            let chunk_offset = (pos % CHUNK_SIZE) as usize;
+            let len = (CHUNK_SIZE - pos % CHUNK_SIZE).min(end - pos) as usize;
+            let dst = &mut buf[(pos - offset) as usize..][..len];
+            match self.chunks.get(&(pos / CHUNK_SIZE)) {
+                Some(chunk) => dst.copy_from_slice(&chunk[chunk_offset..chunk_offset + len]),
+                None => dst.fill(0),
+            }
+            pos += len as u64;
+        }
+        end.saturating_sub(offset) as usize
+    }
+
+    fn write_at(&mut self, usage: &Usage, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or(VfsError::InvalidInput)?;
+        self.alloc_range(usage, offset, end)?;
+        let mut pos = offset;
+        while pos < end {
+            let chunk_offset = (pos % CHUNK_SIZE) as usize;
+            let len = (CHUNK_SIZE - pos % CHUNK_SIZE).min(end - pos) as usize;
+            let chunk = self.chunks.get_mut(&(pos / CHUNK_SIZE)).unwrap();
+            let src = &buf[(pos - offset) as usize..][..len];
+            chunk[chunk_offset..chunk_offset + len].copy_from_slice(src);
+            pos += len as u64;
+        }
+        self.size = self.size.max(end);
+        Ok(buf.len())
+    }
+
+    /// Allocates the missing chunks that overlap the range from `start` to
+    /// `end`, or fails without allocating any if they exceed the quota.
+    fn alloc_range(&mut self, usage: &Usage, start: u64, end: u64) -> VfsResult {
+        if start >= end {
+            return Ok(());
+        }
+        let indexes = start / CHUNK_SIZE..end.div_ceil(CHUNK_SIZE);
+        let allocated = self.chunks.range(indexes.clone()).count() as u64;
+        usage.charge((indexes.end - indexes.start - allocated) * CHUNK_SIZE)?;
+        for index in indexes {
+            self.chunks
+                .entry(index)
+                .or_insert_with(|| vec![0; CHUNK_SIZE as usize].into_boxed_slice());
+        }
+        Ok(())
+    }
+
+    /// Frees the chunks that are entirely in the range from `start` to `end`,
+    /// and zeros the parts of the others in the range.
+    fn punch_hole(&mut self, usage: &Usage, start: u64, end: u64) {
+        let first = start.div_ceil(CHUNK_SIZE);
+        let last = end / CHUNK_SIZE;
+        if first < last {
+            let mut hole = self.chunks.split_off(&first);
+            let mut rest = hole.split_off(&last);
+            usage.release(hole.len() as u64 * CHUNK_SIZE);
+            self.chunks.append(&mut rest);
+        }
+        self.zero_range(start, end.min(first * CHUNK_SIZE));
+        self.zero_range(start.max(last * CHUNK_SIZE), end);
+    }
+
+    /// Zeros the range from `start` to `end` within a single chunk, if it is
+    /// allocated.
+    fn zero_range(&mut self, start: u64, end: u64) {
+        if start < end {
+            if let Some(chunk) = self.chunks.get_mut(&(start / CHUNK_SIZE)) {
+                let offset = (start % CHUNK_SIZE) as usize;
+                chunk[offset..offset + (end - start) as usize].fill(0);
+            }
+        }
+    }
+
+    fn truncate(&mut self, usage: &Usage, size: u64) {
+        if size < self.size {
+            let freed = self.chunks.split_off(&size.div_ceil(CHUNK_SIZE));
+            usage.release(freed.len() as u64 * CHUNK_SIZE);
+            // zero the tail of the last chunk, which is read again if the file
+            // is extended
+            self.zero_range(size, size.next_multiple_of(CHUNK_SIZE));
+        }
+        self.size = size;
+    }
+
+    fn next_data(&self, offset: u64) -> Option<u64> {
+        if offset >= self.size {
+            return None;
+        }
+        let (&index, _) = self.chunks.range(offset / CHUNK_SIZE..).next()?;
+        let pos = (index * CHUNK_SIZE).max(offset);
+        (pos < self.size).then_some(pos)
+    }
+
+    fn next_hole(&self, offset: u64) -> Option<u64> {
+        if offset >= self.size {
+            return None;
+        }
+        let mut index = offset / CHUNK_SIZE;
+        for (&allocated, _) in self.chunks.range(index..) {
+            if allocated != index {
+                break;
+            }
+            index += 1;
+        }
+        // there is always a hole at the end of the file
+        Some((index * CHUNK_SIZE).clamp(offset, self.size))
+    }
+}
+
+impl FileNode {
+    fn new(usage: Arc<Usage>) -> Self {
+        Self {
+            content: Mutex::new(Content::new()),
+            times: Mutex::new(new_times()),
+            owner: Mutex::new(Owner::new(0o666)),
+            usage,
+        }
+    }
+
+    /// Returns the timestamps of the file.
+    pub fn times(&self) -> FileTimes {
+        *self.times.lock()
+    }
+
+    /// Allocates the memory for the `len` bytes at `offset`, so that writing
+    /// them never fails for the quota. The file is extended if they are beyond
+    /// its end, unless `keep_size` is `true`.
+    pub fn allocate(&self, offset: u64, len: u64, keep_size: bool) -> VfsResult {
+        let end = offset.checked_add(len).ok_or(VfsError::InvalidInput)?;
+        let mut content = self.content.lock();
+        content.alloc_range(&self.usage, offset, end)?;
+        if !keep_size && end > content.size {
+            content.size = end;
+        }
+        touch_modified(&self.times);
+        Ok(())
+    }
+
+    /// Deallocates the `len` bytes at `offset`, which then read as zeros. The
+    /// size of the file does not change.
+    pub fn punch_hole(&self, offset: u64, len: u64) -> VfsResult {
+        let end = offset.checked_add(len).ok_or(VfsError::InvalidInput)?;
+        self.content.lock().punch_hole(&self.usage, offset, end);
+        touch_modified(&self.times);
+        Ok(())
+    }
+
+    /// Returns the offset of the first data at or after `offset`, or `None`
+    /// if there is no data there before the end of the file.
+    pub fn next_data(&self, offset: u64) -> Option<u64> {
+        self.content.lock().next_data(offset)
+    }
+
+    /// Returns the offset of the first hole at or after `offset`, where the end
+    /// of the file counts as a hole, or `None` if `offset` is beyond the end.
+    pub fn next_hole(&self, offset: u64) -> Option<u64> {
+        self.content.lock().next_hole(offset)
+    }
+}
+
+impl VfsNodeOps for FileNode {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let content = self.content.lock();
+        let blocks = content.allocated_bytes() / BLOCK_SIZE;
+        let perm = self.owner.lock().perm;
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, content.size, blocks))
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.content.lock().truncate(&self.usage, size);
+        touch_modified(&self.times);
+        Ok(())
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // nothing to write back
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let len = self.content.lock().read_at(offset, buf);
+        self.times.lock().atime = axhal::time::wall_time();
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = self.content.lock().write_at(&self.usage, offset, buf)?;
+        touch_modified(&self.times);
+        Ok(len)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+impl Drop for FileNode {
+    fn drop(&mut self) {
+        self.usage.release(self.content.get_mut().allocated_bytes());
+    }
+}
+
+impl SymlinkNode {
+    fn new() -> Self {
+        Self {
+            target: Mutex::new(String::new()),
+            times: Mutex::new(new_times()),
+            owner: Mutex::new(Owner::new(0o777)),
+        }
+    }
+
+    /// Returns the timestamps of the symbolic link.
+    pub fn times(&self) -> FileTimes {
+        *self.times.lock()
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            self.owner.lock().perm,
+            VfsNodeType::SymLink,
+            self.target.lock().len() as u64,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let target = self.target.lock();
+        let target = target.as_bytes();
+        let start = target.len().min(offset as usize);
+        let end = target.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&target[start..end]);
+        Ok(end - start)
+    }
+
+    /// Sets the target path. The target can only be set as a whole.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if offset != 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let target = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        *self.target.lock() = target.into();
+        touch_modified(&self.times);
+        Ok(buf.len())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// Returns the timestamps of a node in [`RamFileSystem`].
+pub(crate) fn node_times(node: &VfsNodeRef) -> Option<FileTimes> {
+    let any = node.as_any();
+    if let Some(dir) = any.downcast_ref::<DirNode>() {
+        Some(dir.times())
+    } else if let Some(file) = any.downcast_ref::<FileNode>() {
+        Some(file.times())
+    } else {
+        any.downcast_ref::<SymlinkNode>().map(SymlinkNode::times)
+    }
+}
+
+impl Owner {
+    const fn new(perm: u16) -> Self {
+        Self {
+            perm: VfsNodePerm::from_bits_truncate(perm),
+            uid: 0,
+            gid: 0,
+        }
+    }
+}
+
+/// Returns the permissions and the owner of a node in [`RamFileSystem`],
+/// together with its timestamps.
+fn node_owner_times(node: &VfsNodeRef) -> Option<(&Mutex<Owner>, &Mutex<FileTimes>)> {
+    let any = node.as_any();
+    if let Some(dir) = any.downcast_ref::<DirNode>() {
+        Some((&dir.owner, &dir.times))
+    } else if let Some(file) = any.downcast_ref::<FileNode>() {
+        Some((&file.owner, &file.times))
+    } else {
+        let link = any.downcast_ref::<SymlinkNode>()?;
+        Some((&link.owner, &link.times))
+    }
+}
+
+/// Returns the owner and the group of a node in [`RamFileSystem`].
+pub(crate) fn node_owner(node: &VfsNodeRef) -> Option<(u32, u32)> {
+    let owner = *node_owner_times(node)?.0.lock();
+    Some((owner.uid, owner.gid))
+}
+
+/// Sets the permissions of a node in [`RamFileSystem`]. Those of symbolic
+/// links are always `0o777`.
+pub(crate) fn set_perm(node: &VfsNodeRef, perm: VfsNodePerm) -> AxResult {
+    if node.as_any().is::<SymlinkNode>() {
+        return ax_err!(Unsupported);
+    }
+    let (owner, times) = node_owner_times(node).ok_or(VfsError::Unsupported)?;
+    owner.lock().perm = perm;
+    times.lock().ctime = axhal::time::wall_time();
+    Ok(())
+}
+
+/// Sets the owner and the group of a node in [`RamFileSystem`].
+pub(crate) fn set_owner(node: &VfsNodeRef, uid: u32, gid: u32) -> AxResult {
+    let (owner, times) = node_owner_times(node).ok_or(VfsError::Unsupported)?;
+    let mut owner = owner.lock();
+    owner.uid = uid;
+    owner.gid = gid;
+    times.lock().ctime = axhal::time::wall_time();
+    Ok(())
+}
+
+/// Returns the capacity and usage of the [`RamFileSystem`] whose root
+/// directory is `root`.
+pub(crate) fn statfs(root: &VfsNodeRef) -> AxResult<FsStats> {
+    match root.as_any().downcast_ref::<DirNode>() {
+        Some(root) => Ok(root.usage.stats()),
+        None => ax_err!(Unsupported),
+    }
+}
+
+fn new_times() -> FileTimes {
+    let now = axhal::time::wall_time();
+    FileTimes {
+        atime: now,
+        mtime: now,
+        ctime: now,
+    }
+}
+
+fn touch_modified(times: &Mutex<FileTimes>) {
+    let now = axhal::time::wall_time();
+    let mut times = times.lock();
+    times.mtime = now;
+    times.ctime = now;
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
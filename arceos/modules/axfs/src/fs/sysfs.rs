@@ -0,0 +1,330 @@
+//! A filesystem of kernel attributes, usually mounted on `/sys`.
+//!
+//! Each file is an attribute backed by a getter and an optional setter, which
+//! are called when the file is read or written. Kernel modules and drivers can
+//! publish their own attributes with [`register_attr`].
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, format, vec::Vec};
+use core::str::FromStr;
+
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axsync::Mutex;
+
+type Getter = Box<dyn Fn() -> String + Send + Sync>;
+type Setter = Box<dyn Fn(&str) -> AxResult + Send + Sync>;
+
+/// The root directory shared by all mounted sysfs instances.
+static SYSFS_ROOT: Mutex<Option<Arc<SysDir>>> = Mutex::new(None);
+
+/// A kernel attribute, backed by a getter and an optional setter.
+pub struct SysAttr {
+    show: Getter,
+    store: Option<Setter>,
+}
+
+/// A filesystem of kernel attributes.
+pub struct SysFileSystem {
+    root: Arc<SysDir>,
+}
+
+struct SysAttrFile {
+    attr: SysAttr,
+}
+
+struct SysDir {
+    this: Weak<SysDir>,
+    parent: Mutex<Option<Weak<dyn VfsNodeOps>>>,
+    children: Mutex<BTreeMap<String, VfsNodeRef>>,
+}
+
+impl SysAttr {
+    /// Creates a read-only attribute, whose content is returned by `show`.
+    pub fn read_only<F>(show: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        Self {
+            show: Box::new(show),
+            store: None,
+        }
+    }
+
+    /// Creates a writable attribute. The written content, with the trailing
+    /// newline removed, is passed to `store`.
+    pub fn read_write<F, G>(show: F, store: G) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+        G: Fn(&str) -> AxResult + Send + Sync + 'static,
+    {
+        Self {
+            show: Box::new(show),
+            store: Some(Box::new(store)),
+        }
+    }
+}
+
+impl SysFileSystem {
+    /// Creates a new sysfs instance, which shares the attributes with all other
+    /// instances.
+    pub fn new() -> Self {
+        Self { root: sysfs_root() }
+    }
+}
+
+impl Default for SysFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for SysFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            *self.root.parent.lock() = Some(Arc::downgrade(&parent));
+        }
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl VfsNodeOps for SysAttrFile {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mode = if self.attr.store.is_some() {
+            0o644
+        } else {
+            0o444
+        };
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(mode),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.attr.show)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&content[start..end]);
+        Ok(end - start)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let store = self.attr.store.as_ref().ok_or(VfsError::PermissionDenied)?;
+        let value = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        store(value.trim_end_matches('\n'))?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // opened with `O_TRUNC` before written
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+impl SysDir {
+    fn new(parent: Option<Weak<dyn VfsNodeOps>>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: Mutex::new(parent),
+            children: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Returns the subdirectory `name`, creating it if it does not exist.
+    fn get_or_create_dir(&self, name: &str) -> AxResult<Arc<SysDir>> {
+        let mut children = self.children.lock();
+        let node = children.entry(name.into()).or_insert_with(|| {
+            let this: Weak<dyn VfsNodeOps> = self.this.clone();
+            SysDir::new(Some(this))
+        });
+        match node.as_any().downcast_ref::<SysDir>() {
+            Some(dir) => dir.this.upgrade().ok_or(VfsError::NotFound),
+            None => ax_err!(NotADirectory),
+        }
+    }
+}
+
+impl VfsNodeOps for SysDir {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o755),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.lock().as_ref().and_then(|p| p.upgrade())
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self
+                .children
+                .lock()
+                .get(name)
+                .cloned()
+                .ok_or(VfsError::NotFound),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let children = self.children.lock();
+        let mut children = children.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, node)) = children.next() {
+                        let ty = if node.as_any().is::<SysDir>() {
+                            VfsNodeType::Dir
+                        } else {
+                            VfsNodeType::File
+                        };
+                        *ent = VfsDirEntry::new(name, ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
+
+fn sysfs_root() -> Arc<SysDir> {
+    let mut root = SYSFS_ROOT.lock();
+    if let Some(root) = root.as_ref() {
+        return root.clone();
+    }
+    let new_root = SysDir::new(None);
+    *root = Some(new_root.clone());
+    drop(root);
+    register_default_attrs();
+    new_root
+}
+
+/// Publishes an attribute at `path` relative to the sysfs root, e.g.
+/// `block/vdb/size`. Missing parent directories are created.
+///
+/// Returns [`AlreadyExists`](axerrno::AxError::AlreadyExists) if there is
+/// already a file at `path`.
+pub fn register_attr(path: &str, attr: SysAttr) -> AxResult {
+    let names = path
+        .split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .collect::<Vec<_>>();
+    let (file_name, dir_names) = match names.split_last() {
+        Some(split) => split,
+        None => return ax_err!(InvalidInput),
+    };
+    if dir_names
+        .iter()
+        .chain([file_name])
+        .any(|name| *name == "..")
+    {
+        return ax_err!(InvalidInput);
+    }
+
+    let mut dir = sysfs_root();
+    for name in dir_names {
+        dir = dir.get_or_create_dir(name)?;
+    }
+    let mut children = dir.children.lock();
+    if children.contains_key(*file_name) {
+        return ax_err!(AlreadyExists);
+    }
+    children.insert(file_name.to_string(), Arc::new(SysAttrFile { attr }));
+    Ok(())
+}
+
+fn cpu_list() -> String {
+    match axconfig::SMP {
+        1 => "0\n".into(),
+        n => format!("0-{}\n", n - 1),
+    }
+}
+
+fn register_default_attrs() {
+    let attrs = [
+        (
+            "kernel/log_level",
+            SysAttr::read_write(
+                || format!("{}\n", log::max_level().as_str().to_lowercase()),
+                |level| {
+                    if log::LevelFilter::from_str(level).is_err() {
+                        return ax_err!(InvalidInput, "invalid log level");
+                    }
+                    axlog::set_max_level(level);
+                    Ok(())
+                },
+            ),
+        ),
+        (
+            "kernel/mm/transparent_hugepage/enabled",
+            SysAttr::read_only(|| "always [madvise] never\n".into()),
+        ),
+        ("devices/system/cpu/online", SysAttr::read_only(cpu_list)),
+        ("devices/system/cpu/possible", SysAttr::read_only(cpu_list)),
+        ("devices/system/cpu/present", SysAttr::read_only(cpu_list)),
+        (
+            "devices/system/clocksource/clocksource0/current_clocksource",
+            SysAttr::read_only(|| {
+                let name = if cfg!(target_arch = "x86_64") {
+                    "tsc"
+                } else if cfg!(target_arch = "aarch64") {
+                    "arch_sys_counter"
+                } else if cfg!(target_arch = "riscv64") {
+                    "riscv_clocksource"
+                } else {
+                    "unknown"
+                };
+                format!("{}\n", name)
+            }),
+        ),
+    ];
+    for (path, attr) in attrs {
+        register_attr(path, attr).expect("failed to register default sysfs attributes");
+    }
+}
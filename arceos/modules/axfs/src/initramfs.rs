@@ -0,0 +1,187 @@
+//! Unpacking of [cpio] archives in the "newc" format, as used by Linux for
+//! its initramfs.
+//!
+//! Each entry starts with a 110-byte ASCII header, followed by the
+//! NUL-terminated path name and the file data, both padded to 4 bytes. The
+//! archive ends with an entry named `TRAILER!!!`.
+//!
+//! With the `initramfs` feature, the archive at the path in the `AX_INITRAMFS`
+//! environment variable is embedded in the kernel image at build time as
+//! [`INITRAMFS`], and unpacked into the root ramfs at boot.
+//!
+//! [cpio]: https://www.kernel.org/doc/html/latest/driver-api/early-userspace/buffer-format.html
+
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String};
+use axerrno::{ax_err, AxError, AxResult};
+use axio::Write;
+
+use crate::api;
+
+/// The archive embedded in the kernel image. It is empty if `AX_INITRAMFS` is
+/// not set at build time.
+#[cfg(feature = "initramfs")]
+pub static INITRAMFS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.cpio"));
+
+const MAGIC: &[u8] = b"070701";
+const MAGIC_CRC: &[u8] = b"070702";
+const HEADER_LEN: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// An entry of a cpio archive.
+#[derive(Debug, Clone, Copy)]
+pub struct CpioEntry<'a> {
+    /// The path name, relative to the root of the archive.
+    pub name: &'a str,
+    /// The inode number, shared by hard links to the same file.
+    pub ino: u32,
+    /// The file type and permissions, as in `st_mode`.
+    pub mode: u32,
+    /// The number of links to the file.
+    pub nlink: u32,
+    /// The modification time in seconds since the Unix epoch.
+    pub mtime: u32,
+    /// The file contents, or the target of a symbolic link.
+    pub data: &'a [u8],
+}
+
+/// An iterator over the entries of a cpio archive in the "newc" format.
+///
+/// The iteration stops at the trailer, or after the first malformed entry.
+pub struct CpioReader<'a> {
+    archive: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CpioReader<'a> {
+    /// Creates a reader of `archive`.
+    pub const fn new(archive: &'a [u8]) -> Self {
+        Self { archive, pos: 0 }
+    }
+
+    fn next_entry(&mut self) -> AxResult<Option<CpioEntry<'a>>> {
+        let archive = self.archive;
+        // Trailing zero padding is allowed after the trailer or at the end.
+        if archive[self.pos..].iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+        let header = archive
+            .get(self.pos..self.pos + HEADER_LEN)
+            .ok_or(AxError::InvalidData)?;
+        if &header[..6] != MAGIC && &header[..6] != MAGIC_CRC {
+            return ax_err!(InvalidData, "bad cpio magic");
+        }
+        let field = |idx: usize| {
+            let hex = &header[6 + idx * 8..14 + idx * 8];
+            core::str::from_utf8(hex)
+                .ok()
+                .and_then(|s| u32::from_str_radix(s, 16).ok())
+                .ok_or(AxError::InvalidData)
+        };
+        let (ino, mode, nlink, mtime) = (field(0)?, field(1)?, field(4)?, field(5)?);
+        let file_size = field(6)? as usize;
+        let name_size = field(11)? as usize;
+
+        let name_start = self.pos + HEADER_LEN;
+        let name = archive
+            .get(name_start..name_start + name_size)
+            .and_then(|name| name.strip_suffix(b"\0"))
+            .and_then(|name| core::str::from_utf8(name).ok())
+            .ok_or(AxError::InvalidData)?;
+        let data_start = align4(name_start + name_size);
+        let data = archive
+            .get(data_start..data_start + file_size)
+            .ok_or(AxError::InvalidData)?;
+        self.pos = align4(data_start + file_size).min(archive.len());
+
+        if name == TRAILER {
+            self.pos = archive.len();
+            return Ok(None);
+        }
+        Ok(Some(CpioEntry {
+            name,
+            ino,
+            mode,
+            nlink,
+            mtime,
+            data,
+        }))
+    }
+}
+
+impl<'a> Iterator for CpioReader<'a> {
+    type Item = AxResult<CpioEntry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.next_entry().transpose();
+        if let Some(Err(_)) = entry {
+            self.pos = self.archive.len();
+        }
+        entry
+    }
+}
+
+const fn align4(pos: usize) -> usize {
+    (pos + 3) & !3
+}
+
+/// Unpacks the cpio `archive` into the directory `target`.
+///
+/// Directories, regular files, symbolic links and hard links are created,
+/// and existing directories are reused. Device nodes and other special files
+/// are skipped. Permissions, owners and timestamps in the archive are
+/// ignored.
+pub fn unpack(archive: &[u8], target: &str) -> AxResult {
+    let target = target.trim_end_matches('/');
+    // The first path of each file with more than one link.
+    let mut links: BTreeMap<u32, String> = BTreeMap::new();
+
+    for entry in CpioReader::new(archive) {
+        let entry = entry?;
+        let name = entry.name.trim_start_matches("./").trim_matches('/');
+        if name.is_empty() || name == "." {
+            continue;
+        }
+        let path = format!("{}/{}", target, name);
+        debug!("initramfs: unpack {} ({:#o})", path, entry.mode);
+
+        match entry.mode & S_IFMT {
+            S_IFDIR => match api::create_dir(&path) {
+                Ok(()) | Err(AxError::AlreadyExists) => {}
+                Err(e) => return Err(e),
+            },
+            S_IFLNK => {
+                let link_target =
+                    core::str::from_utf8(entry.data).map_err(|_| AxError::InvalidData)?;
+                api::symlink(link_target, &path)?;
+            }
+            S_IFREG => {
+                // The data of a hard-linked file is stored with its last link.
+                if entry.nlink > 1 {
+                    if let Some(first) = links.get(&entry.ino) {
+                        api::hard_link(first, &path)?;
+                        if !entry.data.is_empty() {
+                            write_file(&path, entry.data)?;
+                        }
+                        continue;
+                    }
+                    links.insert(entry.ino, path.clone());
+                }
+                write_file(&path, entry.data)?;
+            }
+            _ => warn!("initramfs: skip special file {}", path),
+        }
+    }
+    Ok(())
+}
+
+fn write_file(path: &str, data: &[u8]) -> AxResult {
+    let mut file = api::File::create(path)?;
+    file.write_all(data)?;
+    file.flush()
+}
@@ -1,15 +1,50 @@
 //! [ArceOS](https://github.com/arceos-org/arceos) filesystem module.
 //!
-//! It provides unified filesystem operations for various filesystems.
+//! It provides unified filesystem operations for various filesystems. Files on
+//! disk filesystems are accessed through a shared [`page_cache`], which is
+//! written back on [`fops::File::flush`] or when pages are evicted. Block
+//! devices are also accessed through buffer caches, whose sizes are given by
+//! [`axconfig::BLOCK_CACHE_SIZE`] and [`axconfig::BLOCK_READ_AHEAD`].
 //!
 //! # Cargo Features
 //!
 //! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
 //!    is **enabled** by default.
-//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
+//! - `ext4fs`: Use [ext2/ext4] as the main filesystem instead of FAT, which
+//!    keeps Unix permissions, inode numbers and links. ext2 volumes are
+//!    writable, while ext4 volumes are mounted read-only. FAT volumes can still
+//!    be mounted at runtime if `fatfs` is also enabled.
+//! - `devfs`: Mount [`devfs::DeviceFileSystem`] on `/dev`, with devices
+//!    such as `/dev/random`, `/dev/console` and the block devices. Other
+//!    modules can publish their own ones by [`devfs::register_device`]. This
+//!    feature is **enabled** by default.
+//! - `fbdev`: Provide the framebuffer of [`axdisplay`] as a device, which is
+//!    published at `/dev/fb0` by [`devfs::register_framebuffer`].
+//! - `ramfs`: Mount an in-memory filesystem on `/tmp`, which supports symbolic
+//!    and hard links. This feature is **enabled** by default.
+//! - `procfs`: Mount a dynamic filesystem on `/proc`, which exposes kernel
+//!    states such as memory usage, mount points and tasks. This feature is
 //!    **enabled** by default.
-//! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
+//! - `sysfs`: Mount a filesystem of kernel attributes on `/sys`. Attributes
+//!    such as `/sys/kernel/log_level` are writable, and other modules can
+//!    publish their own ones by [`sysfs::register_attr`]. This feature is
 //!    **enabled** by default.
+//! - `initramfs`: Use a ramfs as the main filesystem, and fill it at boot
+//!    from a [cpio] archive embedded in the kernel image, so that no block
+//!    device is needed. The archive is read at build time from the path in
+//!    the `AX_INITRAMFS` environment variable. Block devices can still be
+//!    mounted at runtime. This feature overrides other filesystem selection
+//!    features, including `myfs`.
+//! - `overlayfs`: Provide [`overlayfs::OverlayFileSystem`], which stacks a
+//!    writable filesystem over a read-only one.
+//! - `overlay-root`: Cover the main filesystem with a writable ramfs by an
+//!    overlay, so that writes to `/` are kept in memory and the disk is never
+//!    modified. This is useful to boot the same disk image many times.
+//! - `multitask`: Expose tasks in `/proc/<tid>` and `/proc/self`, keep the
+//!    current directory, the umask and the [`cred`]entials per task (see
+//!    [`FsContext`]), and allow tasks to wait for file [`lock`]s.
+//! - `mmap`: Allow the pages of cached files to be mapped into address spaces,
+//!    by implementing [`axmm::FilePages`] for [`page_cache::CachedFile`].
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
@@ -17,6 +52,8 @@
 //!    both are enabled.
 //!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
+//! [ext2/ext4]: https://en.wikipedia.org/wiki/Ext4
+//! [cpio]: https://www.kernel.org/doc/html/latest/driver-api/early-userspace/buffer-format.html
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
@@ -26,21 +63,71 @@
 extern crate log;
 extern crate alloc;
 
+mod block_cache;
 mod dev;
 mod fs;
 mod mounts;
 mod root;
+#[cfg(feature = "multitask")]
+mod task_state;
 
 pub mod api;
+pub mod cred;
 pub mod fops;
+pub mod initramfs;
+pub mod lock;
+pub mod notify;
+pub mod page_cache;
+
+pub use root::FsContext;
+
+#[cfg(feature = "devfs")]
+pub use fs::devfs;
+
+#[cfg(feature = "sysfs")]
+pub use fs::sysfs;
+
+#[cfg(feature = "overlayfs")]
+pub use fs::overlayfs;
 
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes filesystems by block devices.
+///
+/// The first block device is used for the root filesystem, and the others are
+/// exposed as `/dev/vdb`, `/dev/vdc`, etc. They are mounted at boot if listed
+/// in [`axconfig::AUTOMOUNT`].
+///
+/// With the `initramfs` feature, the root filesystem is unpacked from the
+/// embedded archive instead, so no block device is required, and all block
+/// devices are exposed from `/dev/vda` on.
 pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
     info!("Initialize filesystems...");
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+    #[cfg(not(feature = "initramfs"))]
+    let main_fs = {
+        let dev = blk_devs.take_one().expect("No block device found!");
+        info!("  use block device 0: {:?}", dev.device_name());
+        self::root::new_main_fs(self::dev::init_root_device(dev))
+    };
+    #[cfg(feature = "initramfs")]
+    let main_fs = {
+        info!("  use initramfs ({} bytes)", initramfs::INITRAMFS.len());
+        self::mounts::ramfs()
+    };
+
+    let mut idx = if cfg!(feature = "initramfs") { 0 } else { 1 };
+    while let Some(dev) = blk_devs.take_one() {
+        let name = self::dev::block_device_name(idx);
+        info!(
+            "  use block device {}: {:?} as /dev/{}",
+            idx,
+            dev.device_name(),
+            name
+        );
+        self::dev::register_block_device(name, dev);
+        idx += 1;
+    }
+
+    self::root::init_rootfs(main_fs);
 }
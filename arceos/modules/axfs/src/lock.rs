@@ -0,0 +1,265 @@
+//! Advisory file locks, as the whole-file locks of `flock` and the byte-range
+//! record locks of `fcntl`.
+//!
+//! Locks are kept per file, which is identified by its inode number on its
+//! mount point, or by its path on filesystems without inode numbers (so locks
+//! on such files do not follow them when renamed). The two kinds of locks are
+//! independent of each other, as on Linux:
+//!
+//! - Whole-file locks belong to an opened [`File`](crate::fops::File), and are
+//!   released when it is closed.
+//! - Record locks belong to an owner given by the caller, usually the ID of the
+//!   calling task, and never conflict with locks of the same owner.
+//!
+//! Locks are placed through the [`LockHandle`] of an opened file, which can be
+//! kept without borrowing the file, e.g. while waiting for a lock.
+//!
+//! A task waiting for a lock is blocked until another lock of the file is
+//! released. Waiting requires the `multitask` feature, without which a lock
+//! that would block fails with [`WouldBlock`](AxError::WouldBlock).
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axerrno::{ax_err, AxError, AxResult};
+use axsync::Mutex;
+
+use crate::root::FileKey;
+
+/// The type of a lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockType {
+    /// A shared lock, which can be held by many owners at the same time.
+    Read,
+    /// An exclusive lock.
+    Write,
+}
+
+/// A lock of the bytes in `start..end` of a file. An `end` of [`u64::MAX`]
+/// extends the lock to the end of the file, however it grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLock {
+    /// The type of the lock.
+    pub ty: LockType,
+    /// The first byte of the locked range.
+    pub start: u64,
+    /// The end of the locked range, exclusive.
+    pub end: u64,
+    /// The owner of the lock.
+    pub owner: u64,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn conflicts_with(&self, other: &RecordLock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other.start, other.end)
+            && (self.ty == LockType::Write || other.ty == LockType::Write)
+    }
+}
+
+/// A handle to place and remove the locks of an opened file, returned by
+/// [`File::locks`](crate::fops::File::locks).
+#[derive(Debug, Clone)]
+pub struct LockHandle {
+    key: FileKey,
+    id: u64,
+    readable: bool,
+    writable: bool,
+}
+
+#[derive(Default)]
+struct FileLocks {
+    /// Whole-file locks by the IDs of opened files.
+    whole: BTreeMap<u64, LockType>,
+    records: Vec<RecordLock>,
+}
+
+impl FileLocks {
+    fn is_empty(&self) -> bool {
+        self.whole.is_empty() && self.records.is_empty()
+    }
+
+    /// Removes the range `start..end` from the record locks of `owner`,
+    /// splitting the locks partially in it.
+    fn remove_records(&mut self, owner: u64, start: u64, end: u64) -> bool {
+        let mut changed = false;
+        let mut records = Vec::with_capacity(self.records.len() + 1);
+        for lock in self.records.drain(..) {
+            if lock.owner != owner || !lock.overlaps(start, end) {
+                records.push(lock);
+                continue;
+            }
+            changed = true;
+            if lock.start < start {
+                records.push(RecordLock { end: start, ..lock });
+            }
+            if lock.end > end {
+                records.push(RecordLock { start: end, ..lock });
+            }
+        }
+        self.records = records;
+        changed
+    }
+}
+
+static LOCKS: Mutex<BTreeMap<FileKey, FileLocks>> = Mutex::new(BTreeMap::new());
+
+/// Incremented whenever a lock is released, to wake up waiting tasks.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "multitask")]
+static WAITERS: axtask::WaitQueue = axtask::WaitQueue::new();
+
+/// Calls `f` on the locks of the file `key` until it succeeds. If it fails
+/// with [`WouldBlock`](AxError::WouldBlock) and `wait` is set, waits for a
+/// lock to be released and tries again.
+fn with_locks<F>(key: &FileKey, wait: bool, mut f: F) -> AxResult
+where
+    F: FnMut(&mut FileLocks) -> AxResult,
+{
+    loop {
+        let generation = GENERATION.load(Ordering::Acquire);
+        let mut locks = LOCKS.lock();
+        let file_locks = locks.entry(key.clone()).or_default();
+        let res = f(file_locks);
+        if file_locks.is_empty() {
+            locks.remove(key);
+        }
+        drop(locks);
+        match res {
+            Err(AxError::WouldBlock) if wait => wait_for_release(generation)?,
+            res => return res,
+        }
+    }
+}
+
+fn wait_for_release(generation: u64) -> AxResult {
+    #[cfg(feature = "multitask")]
+    {
+        WAITERS.wait_until(|| GENERATION.load(Ordering::Acquire) != generation);
+        Ok(())
+    }
+    #[cfg(not(feature = "multitask"))]
+    {
+        let _ = generation;
+        ax_err!(WouldBlock, "no other task can release the lock")
+    }
+}
+
+fn notify_released() {
+    GENERATION.fetch_add(1, Ordering::Release);
+    #[cfg(feature = "multitask")]
+    WAITERS.notify_all(false);
+}
+
+/// Removes the whole-file lock of the opened file `id`, when it is closed.
+pub(crate) fn unlock_file(key: &FileKey, id: u64) {
+    with_locks(key, false, |locks| {
+        if locks.whole.remove(&id).is_some() {
+            notify_released();
+        }
+        Ok(())
+    })
+    .ok();
+}
+
+impl LockHandle {
+    pub(crate) const fn new(key: FileKey, id: u64, readable: bool, writable: bool) -> Self {
+        Self {
+            key,
+            id,
+            readable,
+            writable,
+        }
+    }
+
+    /// Places a whole-file lock on the file, as `flock`, or converts the lock
+    /// already placed by the opened file to `ty`.
+    ///
+    /// If other opened files hold conflicting locks, waits until they are
+    /// released if `wait` is set, or returns
+    /// [`WouldBlock`](AxError::WouldBlock) otherwise.
+    pub fn lock(&self, ty: LockType, wait: bool) -> AxResult {
+        let id = self.id;
+        with_locks(&self.key, wait, |locks| {
+            let conflicts = locks.whole.iter().any(|(&other, &other_ty)| {
+                other != id && (ty == LockType::Write || other_ty == LockType::Write)
+            });
+            if conflicts {
+                return Err(AxError::WouldBlock);
+            }
+            if locks.whole.insert(id, ty) == Some(LockType::Write) && ty == LockType::Read {
+                notify_released(); // downgraded
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes the whole-file lock placed by the opened file, if any.
+    pub fn unlock(&self) {
+        unlock_file(&self.key, self.id)
+    }
+
+    /// Places the record lock `lock` on the file, as `F_SETLK` of `fcntl`,
+    /// replacing the locks of the same owner in its range. The file must be
+    /// opened for reading to place read locks, and for writing to place write
+    /// locks.
+    ///
+    /// If other owners hold conflicting locks, waits until they are released
+    /// if `wait` is set, or returns [`WouldBlock`](AxError::WouldBlock)
+    /// otherwise.
+    pub fn set_record_lock(&self, lock: RecordLock, wait: bool) -> AxResult {
+        let permitted = match lock.ty {
+            LockType::Read => self.readable,
+            LockType::Write => self.writable,
+        };
+        if !permitted {
+            return ax_err!(PermissionDenied);
+        }
+        if lock.start >= lock.end {
+            return ax_err!(InvalidInput);
+        }
+        with_locks(&self.key, wait, |locks| {
+            if locks
+                .records
+                .iter()
+                .any(|other| other.conflicts_with(&lock))
+            {
+                return Err(AxError::WouldBlock);
+            }
+            if locks.remove_records(lock.owner, lock.start, lock.end) {
+                notify_released();
+            }
+            locks.records.push(lock);
+            Ok(())
+        })
+    }
+
+    /// Removes the record locks of `owner` in `start..end` of the file.
+    pub fn unlock_records(&self, owner: u64, start: u64, end: u64) {
+        with_locks(&self.key, false, |locks| {
+            if locks.remove_records(owner, start, end) {
+                notify_released();
+            }
+            Ok(())
+        })
+        .ok();
+    }
+
+    /// Returns a record lock of another owner that prevents `lock` from being
+    /// placed, if any, as `F_GETLK` of `fcntl`.
+    pub fn conflicting_record_lock(&self, lock: &RecordLock) -> Option<RecordLock> {
+        let locks = LOCKS.lock();
+        locks
+            .get(&self.key)?
+            .records
+            .iter()
+            .find(|other| other.conflicts_with(lock))
+            .copied()
+    }
+}
@@ -1,19 +1,23 @@
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsNodePerm, VfsNodeRef, VfsOps, VfsResult};
 
+use crate::fops::{FileTimes, FsStats};
 use crate::fs;
 
 #[cfg(feature = "devfs")]
 pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
-    let null = fs::devfs::NullDev;
-    let zero = fs::devfs::ZeroDev;
-    let bar = fs::devfs::ZeroDev;
-    let devfs = fs::devfs::DeviceFileSystem::new();
-    let foo_dir = devfs.mkdir("foo");
-    devfs.add("null", Arc::new(null));
-    devfs.add("zero", Arc::new(zero));
-    foo_dir.add("bar", Arc::new(bar));
-    Arc::new(devfs)
+    fs::devfs::new_devfs()
+}
+
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+pub(crate) fn fatfs(disk: crate::dev::Disk) -> VfsResult<Arc<fs::fatfs::FatFileSystem>> {
+    Ok(Arc::new(fs::fatfs::FatFileSystem::try_new(disk)?))
+}
+
+#[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+pub(crate) fn ext4fs(disk: crate::dev::Disk) -> VfsResult<Arc<fs::ext4fs::Ext4FileSystem>> {
+    Ok(Arc::new(fs::ext4fs::Ext4FileSystem::new(disk)?))
 }
 
 #[cfg(feature = "ramfs")]
@@ -21,60 +25,298 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
     Arc::new(fs::ramfs::RamFileSystem::new())
 }
 
+/// Creates a ramfs whose files take at most `quota` bytes, or without limit
+/// if `quota` is 0.
+#[cfg(feature = "ramfs")]
+pub(crate) fn ramfs_with_quota(quota: u64) -> Arc<fs::ramfs::RamFileSystem> {
+    match quota {
+        0 => ramfs(),
+        quota => Arc::new(fs::ramfs::RamFileSystem::with_quota(quota)),
+    }
+}
+
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
+pub(crate) fn procfs() -> VfsResult<Arc<fs::procfs::ProcFileSystem>> {
+    Ok(Arc::new(fs::procfs::ProcFileSystem::new()))
+}
 
-    // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
+#[cfg(feature = "sysfs")]
+pub(crate) fn sysfs() -> VfsResult<Arc<fs::sysfs::SysFileSystem>> {
+    Ok(Arc::new(fs::sysfs::SysFileSystem::new()))
+}
 
-    // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
+/// Returns whether filesystems of type `fstype` are stored on block devices,
+/// so that their files are accessed through the page cache.
+pub(crate) fn is_disk_fs(fstype: &str) -> bool {
+    matches!(fstype, "vfat" | "fat" | "ext2" | "ext3" | "ext4")
+}
 
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
+/// Returns the timestamps of `node` in a filesystem of type `fstype`, or
+/// `None` if the filesystem does not keep timestamps.
+#[allow(unused_variables)]
+pub(crate) fn node_times(fstype: &str, node: &VfsNodeRef) -> Option<FileTimes> {
+    match fstype {
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        "vfat" | "fat" => fs::fatfs::node_times(node),
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => fs::ext4fs::node_times(node),
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => fs::ramfs::node_times(node),
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::node_times(node),
+        _ => None,
+    }
+}
 
-    Ok(Arc::new(procfs))
+/// Returns the inode number of `node` in a filesystem of type `fstype`, or
+/// `None` if the filesystem does not have inode numbers.
+#[allow(unused_variables)]
+pub(crate) fn node_ino(fstype: &str, node: &VfsNodeRef) -> Option<u64> {
+    match fstype {
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => fs::ext4fs::node_ino(node),
+        _ => None,
+    }
 }
 
-#[cfg(feature = "sysfs")]
-pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let sysfs = fs::ramfs::RamFileSystem::new();
-    let sys_root = sysfs.root_dir();
-
-    // Create /sys/kernel/mm/transparent_hugepage/enabled
-    sys_root.create("kernel", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage/enabled", VfsNodeType::File)?;
-    let file_hp = sys_root
-        .clone()
-        .lookup("./kernel/mm/transparent_hugepage/enabled")?;
-    file_hp.write_at(0, b"always [madvise] never\n")?;
-
-    // Create /sys/devices/system/clocksource/clocksource0/current_clocksource
-    sys_root.create("devices", VfsNodeType::Dir)?;
-    sys_root.create("devices/system", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource/clocksource0", VfsNodeType::Dir)?;
-    sys_root.create(
-        "devices/system/clocksource/clocksource0/current_clocksource",
-        VfsNodeType::File,
-    )?;
-    let file_cc = sys_root
-        .clone()
-        .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
-    file_cc.write_at(0, b"tsc\n")?;
-
-    Ok(Arc::new(sysfs))
+/// Returns the number of hard links to `node` in a filesystem of type
+/// `fstype`, or `None` if the filesystem does not keep link counts.
+#[allow(unused_variables)]
+pub(crate) fn node_links(fstype: &str, node: &VfsNodeRef) -> Option<u64> {
+    match fstype {
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => fs::ext4fs::node_links(node),
+        _ => None,
+    }
+}
+
+/// Returns the owner and the group of `node` in a filesystem of type
+/// `fstype`, or `None` if the filesystem does not keep owners, in which case
+/// everything is owned by root.
+#[allow(unused_variables)]
+pub(crate) fn node_owner(fstype: &str, node: &VfsNodeRef) -> Option<(u32, u32)> {
+    match fstype {
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => fs::ext4fs::node_owner(node),
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => fs::ramfs::node_owner(node),
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::node_owner(node),
+        _ => None,
+    }
+}
+
+/// Sets the permissions of `node` in a filesystem of type `fstype`. Fails with
+/// [`Unsupported`] if the filesystem does not keep permissions.
+///
+/// [`Unsupported`]: axerrno::AxError::Unsupported
+#[allow(unused_variables)]
+pub(crate) fn set_perm(fstype: &str, node: &VfsNodeRef, perm: VfsNodePerm) -> AxResult {
+    match fstype {
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => match node.as_any().downcast_ref::<fs::ext4fs::Ext4Node>() {
+            Some(node) => Ok(node.set_perm(perm)?),
+            None => ax_err!(Unsupported),
+        },
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => fs::ramfs::set_perm(node, perm),
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::set_perm(node, perm),
+        _ => ax_err!(Unsupported, "permissions are not supported"),
+    }
+}
+
+/// Sets the owner and the group of `node` in a filesystem of type `fstype`.
+/// Fails with [`Unsupported`] if the filesystem does not keep owners.
+///
+/// [`Unsupported`]: axerrno::AxError::Unsupported
+#[allow(unused_variables)]
+pub(crate) fn set_owner(fstype: &str, node: &VfsNodeRef, uid: u32, gid: u32) -> AxResult {
+    match fstype {
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => match node.as_any().downcast_ref::<fs::ext4fs::Ext4Node>() {
+            Some(node) => Ok(node.set_owner(uid, gid)?),
+            None => ax_err!(Unsupported),
+        },
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => fs::ramfs::set_owner(node, uid, gid),
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::set_owner(node, uid, gid),
+        _ => ax_err!(Unsupported, "owners are not supported"),
+    }
+}
+
+/// Creates a hard link to `node` at `path` relative to `root`, the root
+/// directory of a filesystem of type `fstype`.
+#[allow(unused_variables)]
+pub(crate) fn link(fstype: &str, root: &VfsNodeRef, path: &str, node: VfsNodeRef) -> AxResult {
+    match fstype {
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => match root.as_any().downcast_ref() {
+            Some(root) => fs::ext4fs::Ext4Node::link(root, path, node),
+            None => ax_err!(Unsupported),
+        },
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => match root.as_any().downcast_ref() {
+            Some(root) => fs::ramfs::DirNode::link(root, path, node),
+            None => ax_err!(Unsupported),
+        },
+        _ => ax_err!(Unsupported, "hard links are not supported"),
+    }
+}
+
+/// Returns the capacity and usage of a filesystem of type `fstype`, whose
+/// root directory is `root`. Filesystems without storage, such as procfs,
+/// report zero blocks.
+#[allow(unused_variables)]
+pub(crate) fn statfs(fstype: &str, root: &VfsNodeRef) -> AxResult<FsStats> {
+    const NAME_MAX: u64 = 255;
+    let empty = |magic| FsStats {
+        magic,
+        name_max: NAME_MAX,
+        ..Default::default()
+    };
+    match fstype {
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        "vfat" | "fat" => fs::fatfs::statfs(root),
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => fs::ext4fs::statfs(root),
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => fs::ramfs::statfs(root),
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::statfs(root),
+        "devfs" => Ok(empty(0x1373)),
+        "proc" | "procfs" => Ok(empty(0x9fa0)),
+        "sysfs" => Ok(empty(0x6265_6572)),
+        _ => Ok(FsStats::default()),
+    }
+}
+
+/// Allocates the storage of the `len` bytes at `offset` of the regular file
+/// `node` in a filesystem of type `fstype`, extending the file unless
+/// `keep_size` is `true`. Fails with [`Unsupported`] if the filesystem does
+/// not have sparse files.
+///
+/// [`Unsupported`]: axerrno::AxError::Unsupported
+#[allow(unused_variables)]
+pub(crate) fn allocate(
+    fstype: &str,
+    node: &VfsNodeRef,
+    offset: u64,
+    len: u64,
+    keep_size: bool,
+) -> AxResult {
+    match fstype {
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => match node.as_any().downcast_ref::<fs::ramfs::FileNode>() {
+            Some(file) => Ok(file.allocate(offset, len, keep_size)?),
+            None => ax_err!(Unsupported),
+        },
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::allocate(node, offset, len, keep_size),
+        _ => ax_err!(Unsupported, "sparse files are not supported"),
+    }
+}
+
+/// Deallocates the `len` bytes at `offset` of the regular file `node` in a
+/// filesystem of type `fstype`, leaving a hole. Fails with [`Unsupported`] if
+/// the filesystem does not have sparse files.
+///
+/// [`Unsupported`]: axerrno::AxError::Unsupported
+#[allow(unused_variables)]
+pub(crate) fn punch_hole(fstype: &str, node: &VfsNodeRef, offset: u64, len: u64) -> AxResult {
+    match fstype {
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => match node.as_any().downcast_ref::<fs::ramfs::FileNode>() {
+            Some(file) => Ok(file.punch_hole(offset, len)?),
+            None => ax_err!(Unsupported),
+        },
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::punch_hole(node, offset, len),
+        _ => ax_err!(Unsupported, "sparse files are not supported"),
+    }
+}
+
+/// Returns the offset of the first hole (if `hole` is `true`) or data at or
+/// after `offset` in the regular file `node` in a filesystem of type `fstype`,
+/// or `None` if there is none before the end of the file. Fails with
+/// [`Unsupported`] if the filesystem cannot report holes.
+///
+/// [`Unsupported`]: axerrno::AxError::Unsupported
+#[allow(unused_variables)]
+pub(crate) fn seek_extent(
+    fstype: &str,
+    node: &VfsNodeRef,
+    offset: u64,
+    hole: bool,
+) -> AxResult<Option<u64>> {
+    match fstype {
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => match node.as_any().downcast_ref::<fs::ramfs::FileNode>() {
+            Some(file) if hole => Ok(file.next_hole(offset)),
+            Some(file) => Ok(file.next_data(offset)),
+            None => ax_err!(Unsupported),
+        },
+        #[cfg(feature = "overlayfs")]
+        "overlay" => fs::overlayfs::seek_extent(node, offset, hole),
+        _ => ax_err!(Unsupported),
+    }
+}
+
+/// Parses the `size=` option of ramfs and tmpfs, in bytes with an optional
+/// `k`, `m` or `g` suffix. Returns 0 if there is no such option.
+#[cfg(feature = "ramfs")]
+fn parse_size_option(options: &str) -> AxResult<u64> {
+    let mut size = 0;
+    for option in options.split(',').filter(|o| !o.is_empty()) {
+        let Some(value) = option.strip_prefix("size=") else {
+            return ax_err!(InvalidInput, "unknown mount option");
+        };
+        let (digits, shift) = match value.as_bytes().last() {
+            Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+            Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+            Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+            _ => (value, 0),
+        };
+        size = match digits.parse::<u64>() {
+            Ok(n) if n > 0 && n.leading_zeros() >= shift => n << shift,
+            _ => return ax_err!(InvalidInput, "invalid size option"),
+        };
+    }
+    Ok(size)
+}
+
+/// Creates a new filesystem of type `fstype` to be mounted at runtime.
+///
+/// `source` is the path of the block device for disk filesystems, and is
+/// ignored by in-memory filesystems. `options` is a comma-separated list of
+/// filesystem-specific options, see [`parse_size_option`].
+#[allow(unused_variables)]
+pub(crate) fn create_fs(source: &str, fstype: &str, options: &str) -> AxResult<Arc<dyn VfsOps>> {
+    #[cfg(feature = "ramfs")]
+    if matches!(fstype, "ramfs" | "tmpfs") {
+        return Ok(ramfs_with_quota(parse_size_option(options)?));
+    }
+    if !options.is_empty() {
+        return ax_err!(InvalidInput, "unknown mount option");
+    }
+    match fstype {
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        "vfat" | "fat" => {
+            let source = crate::root::canonicalize(source)?;
+            Ok(fatfs(crate::dev::open_block_device(&source)?)?)
+        }
+        #[cfg(all(feature = "ext4fs", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => {
+            let source = crate::root::canonicalize(source)?;
+            Ok(ext4fs(crate::dev::open_block_device(&source)?)?)
+        }
+        #[cfg(feature = "devfs")]
+        "devfs" => Ok(devfs()),
+        #[cfg(feature = "procfs")]
+        "proc" | "procfs" => Ok(procfs()?),
+        #[cfg(feature = "sysfs")]
+        "sysfs" => Ok(sysfs()?),
+        _ => ax_err!(Unsupported, "unsupported filesystem type"),
+    }
 }
@@ -0,0 +1,308 @@
+//! Notifications of changes in directories, in the manner of Linux `inotify`.
+//!
+//! A [`Watcher`] watches files and directories by their paths, and queues an
+//! [`Event`] for each change:
+//!
+//! - a watched directory gets events for the entries in it, which are
+//!   created, modified, deleted, or moved from or to it;
+//! - a watched file gets [`MODIFY`](EventMask::MODIFY) events when written or
+//!   truncated.
+//!
+//! Watches follow the files and directories they watch when renamed, and are
+//! removed, with an [`IGNORED`](EventMask::IGNORED) event, when those are
+//! deleted. Changes are only noticed when made through `axfs`, e.g. not by the
+//! host of a mounted disk image.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering};
+
+use axerrno::{ax_err, AxResult};
+use axsync::Mutex;
+
+/// The maximum number of events queued in a [`Watcher`], after which an
+/// [`Q_OVERFLOW`](EventMask::Q_OVERFLOW) event is queued instead.
+pub const MAX_QUEUED_EVENTS: usize = 16384;
+
+bitflags::bitflags! {
+    /// The kinds of events, with the values of Linux `inotify`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EventMask: u32 {
+        /// A file was written or truncated.
+        const MODIFY = 0x2;
+        /// An entry was moved out of the directory.
+        const MOVED_FROM = 0x40;
+        /// An entry was moved into the directory.
+        const MOVED_TO = 0x80;
+        /// An entry was created in the directory.
+        const CREATE = 0x100;
+        /// An entry was deleted from the directory.
+        const DELETE = 0x200;
+        /// Events were dropped since the queue was full.
+        const Q_OVERFLOW = 0x4000;
+        /// The watch was removed.
+        const IGNORED = 0x8000;
+        /// The entry of the event is a directory.
+        const ISDIR = 0x4000_0000;
+    }
+}
+
+impl EventMask {
+    /// The events that can be watched.
+    pub const ALL_EVENTS: Self = Self::MODIFY
+        .union(Self::MOVED_FROM)
+        .union(Self::MOVED_TO)
+        .union(Self::CREATE)
+        .union(Self::DELETE);
+}
+
+/// A change of a watched file or directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// The watch descriptor returned by [`Watcher::add_watch`], or -1 for
+    /// [`Q_OVERFLOW`](EventMask::Q_OVERFLOW) events.
+    pub wd: i32,
+    /// The kind of the event.
+    pub mask: EventMask,
+    /// Relates the [`MOVED_FROM`](EventMask::MOVED_FROM) and
+    /// [`MOVED_TO`](EventMask::MOVED_TO) events of the same rename, and is 0
+    /// for other events.
+    pub cookie: u32,
+    /// The name of the entry in the watched directory, or empty for events of
+    /// the watched file or directory itself.
+    pub name: String,
+}
+
+type EventQueue = Mutex<VecDeque<Event>>;
+
+/// A watch on an absolute path.
+struct Watch {
+    wd: i32,
+    path: String,
+    mask: EventMask,
+    queue: Weak<EventQueue>,
+}
+
+static WATCHES: Mutex<Vec<Watch>> = Mutex::new(Vec::new());
+
+/// The number of watches, to skip locking [`WATCHES`] when there are none.
+static WATCH_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Watches files and directories, and queues their changes.
+pub struct Watcher {
+    queue: Arc<EventQueue>,
+}
+
+impl Watcher {
+    /// Creates a watcher without watches.
+    pub fn new() -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Watches the events in `mask` of the file or directory at `path`,
+    /// following symbolic links. Returns the watch descriptor, which is also
+    /// in the events of the watch.
+    ///
+    /// If the path is already watched, the mask of its watch is replaced and
+    /// the same descriptor is returned.
+    pub fn add_watch(&self, path: &str, mask: EventMask) -> AxResult<i32> {
+        if mask.is_empty() || !EventMask::ALL_EVENTS.contains(mask) {
+            return ax_err!(InvalidInput);
+        }
+        let path = match crate::root::resolve_path(path, true)? {
+            (path, Some(_)) => path,
+            (_, None) => return ax_err!(NotFound),
+        };
+
+        static NEXT_WD: AtomicI32 = AtomicI32::new(1);
+        let mut watches = WATCHES.lock();
+        if let Some(watch) = watches.iter_mut().find(|w| w.path == path && self.owns(w)) {
+            watch.mask = mask;
+            return Ok(watch.wd);
+        }
+        let wd = NEXT_WD.fetch_add(1, Ordering::Relaxed);
+        watches.push(Watch {
+            wd,
+            path,
+            mask,
+            queue: Arc::downgrade(&self.queue),
+        });
+        WATCH_COUNT.store(watches.len(), Ordering::Release);
+        Ok(wd)
+    }
+
+    /// Removes the watch `wd`, after which an [`IGNORED`](EventMask::IGNORED)
+    /// event is queued.
+    pub fn remove_watch(&self, wd: i32) -> AxResult {
+        let mut watches = WATCHES.lock();
+        match watches.iter().position(|w| w.wd == wd && self.owns(w)) {
+            Some(idx) => {
+                let watch = watches.remove(idx);
+                WATCH_COUNT.store(watches.len(), Ordering::Release);
+                push_event(&self.queue, watch.wd, EventMask::IGNORED, 0, "");
+                Ok(())
+            }
+            None => ax_err!(InvalidInput),
+        }
+    }
+
+    /// Takes the oldest queued event, if any.
+    pub fn read_event(&self) -> Option<Event> {
+        self.queue.lock().pop_front()
+    }
+
+    /// Takes the oldest queued event if `f` accepts it, e.g. if it fits in a
+    /// buffer, and leaves it queued otherwise.
+    pub fn read_event_if(&self, f: impl FnOnce(&Event) -> bool) -> Option<Event> {
+        let mut queue = self.queue.lock();
+        if f(queue.front()?) {
+            queue.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether events are queued.
+    pub fn has_events(&self) -> bool {
+        !self.queue.lock().is_empty()
+    }
+
+    fn owns(&self, watch: &Watch) -> bool {
+        core::ptr::eq(watch.queue.as_ptr(), Arc::as_ptr(&self.queue))
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let mut watches = WATCHES.lock();
+        watches.retain(|w| !self.owns(w));
+        WATCH_COUNT.store(watches.len(), Ordering::Release);
+    }
+}
+
+fn push_event(queue: &EventQueue, wd: i32, mask: EventMask, cookie: u32, name: &str) {
+    let mut queue = queue.lock();
+    let event = Event {
+        wd,
+        mask,
+        cookie,
+        name: name.into(),
+    };
+    // identical events in a row are merged, as Linux does
+    if queue.back() == Some(&event) {
+        return;
+    }
+    if queue.len() >= MAX_QUEUED_EVENTS {
+        if queue
+            .back()
+            .map_or(true, |e| e.mask != EventMask::Q_OVERFLOW)
+        {
+            queue.push_back(Event {
+                wd: -1,
+                mask: EventMask::Q_OVERFLOW,
+                cookie: 0,
+                name: String::new(),
+            });
+        }
+        return;
+    }
+    queue.push_back(event);
+}
+
+/// Splits an absolute path into its parent directory and its name.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => ("/", path),
+    }
+}
+
+fn is_in(path: &str, dir: &str) -> bool {
+    path == dir
+        || (path.starts_with(dir) && (dir.ends_with('/') || path.as_bytes()[dir.len()] == b'/'))
+}
+
+/// Queues the event `mask` of the entry at the absolute `path` to the watches
+/// of its parent directory, and [`MODIFY`](EventMask::MODIFY) events also to
+/// the watches of the entry itself.
+fn notify_with_cookie(path: &str, mask: EventMask, is_dir: bool, cookie: u32) {
+    let (parent, name) = split_path(path);
+    let path = path.trim_end_matches('/');
+    let flags = if is_dir {
+        EventMask::ISDIR
+    } else {
+        EventMask::empty()
+    };
+    let mut watches = WATCHES.lock();
+    watches.retain(|w| w.queue.strong_count() > 0);
+    for watch in watches.iter().filter(|w| w.mask.intersects(mask)) {
+        let Some(queue) = watch.queue.upgrade() else {
+            continue;
+        };
+        if watch.path == parent {
+            push_event(&queue, watch.wd, mask | flags, cookie, name);
+        } else if watch.path == path && mask == EventMask::MODIFY {
+            push_event(&queue, watch.wd, mask | flags, cookie, "");
+        }
+    }
+    WATCH_COUNT.store(watches.len(), Ordering::Release);
+}
+
+/// Queues the event `mask` of the entry at the absolute `path`.
+pub(crate) fn notify(path: &str, mask: EventMask, is_dir: bool) {
+    if WATCH_COUNT.load(Ordering::Acquire) > 0 {
+        notify_with_cookie(path, mask, is_dir, 0);
+    }
+}
+
+/// Queues the events of deleting the entry at the absolute `path`, and
+/// removes the watches on it and in it.
+pub(crate) fn notify_deleted(path: &str, is_dir: bool) {
+    if WATCH_COUNT.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    notify_with_cookie(path, EventMask::DELETE, is_dir, 0);
+    let path = path.trim_end_matches('/');
+    let mut watches = WATCHES.lock();
+    watches.retain(|w| {
+        if !is_in(&w.path, path) {
+            return true;
+        }
+        if let Some(queue) = w.queue.upgrade() {
+            push_event(&queue, w.wd, EventMask::IGNORED, 0, "");
+        }
+        false
+    });
+    WATCH_COUNT.store(watches.len(), Ordering::Release);
+}
+
+/// Queues the events of renaming the entry at the absolute path `old` to
+/// `new`, and moves the watches on it and in it.
+pub(crate) fn notify_renamed(old: &str, new: &str, is_dir: bool) {
+    if WATCH_COUNT.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    notify_with_cookie(old, EventMask::MOVED_FROM, is_dir, cookie);
+    notify_with_cookie(new, EventMask::MOVED_TO, is_dir, cookie);
+
+    let (old, new) = (old.trim_end_matches('/'), new.trim_end_matches('/'));
+    for watch in WATCHES.lock().iter_mut() {
+        if is_in(&watch.path, old) {
+            watch.path = String::from(new) + &watch.path[old.len()..];
+        }
+    }
+}
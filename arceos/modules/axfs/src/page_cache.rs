@@ -0,0 +1,582 @@
+//! A page cache between file operations and disk filesystems.
+//!
+//! Regular files on disk filesystems are cached in pages of [`PAGE_SIZE`]
+//! bytes, keyed by the file and the page index. Writes only modify the cached
+//! pages, which are written back when the file is flushed, when they are
+//! evicted, or when the filesystem is unmounted. Pages are evicted in
+//! least-recently-used order once the cache grows beyond its capacity, see
+//! [`set_capacity`]. Pages missing from the cache are read from the files
+//! without holding the lock of the cache.
+//!
+//! Page frames are page-aligned, so that they can also be mapped into address
+//! spaces for file-backed memory mappings, by pinning them with
+//! [`CachedFile::pin_page`].
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, vec::Vec};
+use core::alloc::Layout;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+use axerrno::{ax_err, AxError, AxResult};
+use axfs_vfs::VfsNodeRef;
+use axsync::{Mutex, MutexGuard};
+
+use crate::root::FileKey;
+
+/// The size of a cached page.
+pub const PAGE_SIZE: usize = 0x1000;
+
+static PAGE_CACHE: Mutex<PageCache> = Mutex::new(PageCache::new());
+
+/// A regular file whose contents are accessed through the page cache.
+///
+/// All opened instances of the same file share one [`CachedFile`].
+pub struct CachedFile {
+    id: u64,
+    this: Weak<CachedFile>,
+    node: VfsNodeRef,
+    /// The file size, including the data in dirty pages.
+    size: AtomicU64,
+    /// Set if the file has been removed, so that its pages are never written
+    /// back. They are kept in the cache until the file is no longer opened or
+    /// mapped.
+    removed: AtomicBool,
+    /// Time of the last modification through the cache in nanoseconds since
+    /// the epoch, or 0 if not modified.
+    modified: AtomicU64,
+    /// Serializes writes and truncations, which may lock the cache several
+    /// times.
+    write_lock: Mutex<()>,
+}
+
+#[repr(C, align(4096))]
+struct PageFrame([u8; PAGE_SIZE]);
+
+struct Page {
+    frame: Box<PageFrame>,
+    /// The file that the page belongs to, which is kept alive as long as it
+    /// has cached pages.
+    file: Arc<CachedFile>,
+    index: u64,
+    dirty: bool,
+    /// Number of pins, pinned pages are never evicted.
+    pins: usize,
+    /// The last access time, as the key in [`PageCache::lru`].
+    tick: u64,
+}
+
+struct PageCache {
+    /// Cached files by their inode numbers on their mount points, or by their
+    /// absolute paths on filesystems without inode numbers.
+    files: BTreeMap<FileKey, Weak<CachedFile>>,
+    /// Cached pages by the file ID and the page index.
+    pages: BTreeMap<(u64, u64), Page>,
+    /// Keys of cached pages by their last access time.
+    lru: BTreeMap<u64, (u64, u64)>,
+    /// Keys of pages being read from their files, which are not cached yet.
+    loading: BTreeSet<(u64, u64)>,
+    tick: u64,
+    /// Maximum number of cached pages, 0 for the default.
+    capacity: usize,
+}
+
+impl PageFrame {
+    fn alloc_zeroed() -> Option<Box<Self>> {
+        let ptr = unsafe { alloc::alloc::alloc_zeroed(Layout::new::<Self>()) } as *mut Self;
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { Box::from_raw(ptr) })
+        }
+    }
+}
+
+impl PageCache {
+    const fn new() -> Self {
+        Self {
+            files: BTreeMap::new(),
+            pages: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            loading: BTreeSet::new(),
+            tick: 0,
+            capacity: 0,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        match self.capacity {
+            // an eighth of the physical memory by default
+            0 => axconfig::PHYS_MEMORY_SIZE / 8 / PAGE_SIZE,
+            n => n,
+        }
+    }
+
+    fn touch(&mut self, key: (u64, u64)) {
+        if let Some(page) = self.pages.get_mut(&key) {
+            self.lru.remove(&page.tick);
+            self.tick += 1;
+            page.tick = self.tick;
+            self.lru.insert(self.tick, key);
+        }
+    }
+
+    /// Returns the cached page at `index` of `file`, which must be locked by
+    /// [`CachedFile::lock_page`].
+    fn page(&mut self, file: &CachedFile, index: u64) -> &mut Page {
+        self.pages.get_mut(&(file.id, index)).unwrap()
+    }
+
+    /// Allocates a page frame, evicting pages if the cache is full or the
+    /// memory is exhausted.
+    fn alloc_frame(&mut self) -> AxResult<Box<PageFrame>> {
+        while self.pages.len() >= self.capacity() && self.evict_one() {}
+        loop {
+            if let Some(frame) = PageFrame::alloc_zeroed() {
+                return Ok(frame);
+            }
+            if !self.evict_one() {
+                return ax_err!(NoMemory, "no memory for the page cache");
+            }
+        }
+    }
+
+    /// Evicts the least recently used page that is not pinned, or the pages of
+    /// removed files that are no longer used. Returns `false` if there is no
+    /// such page.
+    fn evict_one(&mut self) -> bool {
+        let victim = self
+            .lru
+            .values()
+            .find(|key| {
+                let page = &self.pages[*key];
+                page.pins == 0 && !page.file.removed.load(Ordering::Acquire)
+            })
+            .copied();
+        let Some((id, index)) = victim else {
+            return self.reclaim_removed();
+        };
+        if let Err(e) = self.write_back(id, index) {
+            warn!(
+                "failed to write back page {} of file {}: {:?}",
+                index, id, e
+            );
+        }
+        let page = self.pages.remove(&(id, index)).unwrap();
+        self.lru.remove(&page.tick);
+        true
+    }
+
+    /// Writes back the dirty pages of the file `id`, up to the page at `last`.
+    ///
+    /// Pages are written in ascending order, so that the file grows
+    /// contiguously, as some filesystems (e.g. FAT) cannot write beyond the end
+    /// of files.
+    fn write_back(&mut self, id: u64, last: u64) -> AxResult {
+        for page in self.pages.range_mut((id, 0)..=(id, last)).map(|(_, p)| p) {
+            if page.dirty {
+                page.file.write_page(page.index, &page.frame.0)?;
+                page.dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether `file` is removed and referenced only by its pages and
+    /// by the caller, so that its pages can never be accessed again.
+    fn is_orphan(&self, file: &Arc<CachedFile>) -> bool {
+        let pages = self.pages.range((file.id, 0)..=(file.id, u64::MAX)).count();
+        file.removed.load(Ordering::Acquire) && Arc::strong_count(file) <= pages + 1
+    }
+
+    /// Drops the cached pages of removed files that are no longer opened or
+    /// mapped. Returns `false` if there are no such pages.
+    fn reclaim_removed(&mut self) -> bool {
+        let mut orphans = Vec::new();
+        let mut last_id = None;
+        for page in self.pages.values() {
+            if last_id == Some(page.file.id) || !page.file.removed.load(Ordering::Acquire) {
+                continue;
+            }
+            last_id = Some(page.file.id);
+            // the clone is the reference of the caller
+            if self.is_orphan(&page.file.clone()) {
+                orphans.push(page.file.id);
+            }
+        }
+        for &id in &orphans {
+            self.discard(id, 0);
+        }
+        !orphans.is_empty()
+    }
+
+    /// Drops the cached pages of the file `id` from the page at `first`,
+    /// without writing them back.
+    fn discard(&mut self, id: u64, first: u64) {
+        let keys = self
+            .pages
+            .range((id, first)..=(id, u64::MAX))
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in keys {
+            let page = self.pages.remove(&key).unwrap();
+            self.lru.remove(&page.tick);
+        }
+    }
+}
+
+impl CachedFile {
+    /// Returns the size of the file, including the data not written back.
+    pub fn size(&self) -> u64 {
+        self.size.load(Ordering::Acquire)
+    }
+
+    /// Returns the time of the last modification through the cache, which may
+    /// not be written back yet.
+    pub fn modified(&self) -> Option<Duration> {
+        match self.modified.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    fn touch_modified(&self) {
+        let now = axhal::time::wall_time_nanos();
+        self.modified.store(now, Ordering::Relaxed);
+    }
+
+    /// Extends the file to `size` on disk, after writing back its dirty
+    /// pages, so that the gap takes no pages in the cache. Cached pages are
+    /// always zero beyond the end of the file, so they need not be updated.
+    fn extend(&self, cache: &mut PageCache, size: u64) -> AxResult {
+        if self.removed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        cache.write_back(self.id, u64::MAX)?;
+        self.node.truncate(size)
+    }
+
+    /// Locks the page cache with the page at `index` cached, and returns the
+    /// guard. If the page is not cached, it is read from the file without
+    /// holding the lock if `load` is `true`, or filled with zeros otherwise.
+    fn lock_page(&self, index: u64, load: bool) -> AxResult<MutexGuard<'static, PageCache>> {
+        let key = (self.id, index);
+        loop {
+            let mut cache = PAGE_CACHE.lock();
+            if cache.pages.contains_key(&key) {
+                cache.touch(key);
+                return Ok(cache);
+            }
+            if cache.loading.contains(&key) {
+                // wait for the task reading the page
+                drop(cache);
+                #[cfg(feature = "multitask")]
+                axtask::yield_now();
+                #[cfg(not(feature = "multitask"))]
+                core::hint::spin_loop();
+                continue;
+            }
+            let mut frame = cache.alloc_frame()?;
+            if load {
+                cache.loading.insert(key);
+                drop(cache);
+                let res = self.read_page(index, &mut frame.0);
+                cache = PAGE_CACHE.lock();
+                cache.loading.remove(&key);
+                res?;
+                // the file may be truncated during the read
+                let offset = index * PAGE_SIZE as u64;
+                let len = self.size().saturating_sub(offset).min(PAGE_SIZE as u64);
+                frame.0[len as usize..].fill(0);
+            }
+            let page = Page {
+                frame,
+                file: self.this.upgrade().unwrap(),
+                index,
+                dirty: false,
+                pins: 0,
+                tick: 0,
+            };
+            cache.pages.insert(key, page);
+            cache.touch(key);
+            return Ok(cache);
+        }
+    }
+
+    fn read_page(&self, index: u64, buf: &mut [u8; PAGE_SIZE]) -> AxResult {
+        let offset = index * PAGE_SIZE as u64;
+        let mut pos = 0;
+        while pos < PAGE_SIZE {
+            match self.node.read_at(offset + pos as u64, &mut buf[pos..])? {
+                0 => break,
+                n => pos += n,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_page(&self, index: u64, buf: &[u8; PAGE_SIZE]) -> AxResult {
+        if self.removed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let offset = index * PAGE_SIZE as u64;
+        let len = self.size().saturating_sub(offset).min(PAGE_SIZE as u64) as usize;
+        let mut pos = 0;
+        while pos < len {
+            match self.node.write_at(offset + pos as u64, &buf[pos..len])? {
+                0 => return ax_err!(StorageFull),
+                n => pos += n,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the file at `offset` into `buf` through the page cache.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let end = self.size().min(offset.saturating_add(buf.len() as u64));
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / PAGE_SIZE as u64;
+            let page_offset = pos as usize % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min((end - pos) as usize);
+            let mut cache = self.lock_page(index, true)?;
+            let page = cache.page(self, index);
+            let dst = (pos - offset) as usize;
+            buf[dst..dst + len].copy_from_slice(&page.frame.0[page_offset..page_offset + len]);
+            pos += len as u64;
+        }
+        Ok(end.saturating_sub(offset) as usize)
+    }
+
+    /// Writes `buf` to the file at `offset` through the page cache. The data
+    /// is written back later.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let _guard = self.write_lock.lock();
+        self.touch_modified();
+        let end = offset + buf.len() as u64;
+        // update the size first, as pages may be written back during the write
+        let size = self.size.fetch_max(end, Ordering::AcqRel);
+        if offset > size {
+            self.extend(&mut PAGE_CACHE.lock(), offset)?;
+        }
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / PAGE_SIZE as u64;
+            let page_offset = pos as usize % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min((end - pos) as usize);
+            // no need to read the page if it is overwritten entirely
+            let load = index * (PAGE_SIZE as u64) < size && len < PAGE_SIZE;
+            let mut cache = self.lock_page(index, load)?;
+            let page = cache.page(self, index);
+            let src = (pos - offset) as usize;
+            page.frame.0[page_offset..page_offset + len].copy_from_slice(&buf[src..src + len]);
+            page.dirty = true;
+            pos += len as u64;
+        }
+        Ok(buf.len())
+    }
+
+    /// Truncates or extends the file to `size`.
+    pub fn truncate(&self, size: u64) -> AxResult {
+        let _guard = self.write_lock.lock();
+        self.touch_modified();
+        let mut cache = PAGE_CACHE.lock();
+        let old_size = self.size();
+        if size > old_size {
+            self.size.store(size, Ordering::Release);
+            return self.extend(&mut cache, size);
+        }
+        cache.discard(self.id, size.div_ceil(PAGE_SIZE as u64));
+        let tail = size as usize % PAGE_SIZE;
+        if let Some(page) = cache.pages.get_mut(&(self.id, size / PAGE_SIZE as u64)) {
+            page.frame.0[tail..].fill(0);
+        }
+        // the file on disk may be shorter than `size` before written back
+        cache.write_back(self.id, u64::MAX)?;
+        self.node.truncate(size)?;
+        self.size.store(size, Ordering::Release);
+        Ok(())
+    }
+
+    /// Writes back all dirty pages of the file, and flushes the file.
+    pub fn flush(&self) -> AxResult {
+        PAGE_CACHE.lock().write_back(self.id, u64::MAX)?;
+        self.node.fsync()
+    }
+
+    /// Pins the page at `index` in the cache, and returns the address of the
+    /// page frame. The page is read from the file if it is not cached.
+    ///
+    /// Pinned pages are never evicted, until [`unpin_page`] is called as many
+    /// times as this function.
+    ///
+    /// [`unpin_page`]: CachedFile::unpin_page
+    pub fn pin_page(&self, index: u64) -> AxResult<usize> {
+        let load = index * (PAGE_SIZE as u64) < self.size();
+        let mut cache = self.lock_page(index, load)?;
+        let page = cache.page(self, index);
+        page.pins += 1;
+        Ok(page.frame.0.as_ptr() as usize)
+    }
+
+    /// Unpins the page at `index`, and marks it dirty if it has been modified
+    /// through the pinned frame.
+    pub fn unpin_page(&self, index: u64, dirty: bool) {
+        if let Some(page) = PAGE_CACHE.lock().pages.get_mut(&(self.id, index)) {
+            page.pins = page.pins.saturating_sub(1);
+            page.dirty |= dirty;
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl axmm::FilePages for CachedFile {
+    fn pin_page(&self, index: u64) -> Option<axhal::mem::VirtAddr> {
+        CachedFile::pin_page(self, index).ok().map(Into::into)
+    }
+
+    fn unpin_page(&self, index: u64, dirty: bool) {
+        CachedFile::unpin_page(self, index, dirty)
+    }
+}
+
+/// Returns the cached file identified by `key`, whose node is `node`.
+pub(crate) fn open(key: &FileKey, node: VfsNodeRef) -> AxResult<Arc<CachedFile>> {
+    static ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+    let mut cache = PAGE_CACHE.lock();
+    if let Some(file) = cache.files.get(key).and_then(Weak::upgrade) {
+        return Ok(file);
+    }
+    let size = node.get_attr()?.size();
+    let file = Arc::new_cyclic(|this| CachedFile {
+        id: ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+        this: this.clone(),
+        node,
+        size: AtomicU64::new(size),
+        removed: AtomicBool::new(false),
+        modified: AtomicU64::new(0),
+        write_lock: Mutex::new(()),
+    });
+    cache.files.insert(key.clone(), Arc::downgrade(&file));
+    Ok(file)
+}
+
+/// Returns the cached file identified by `key`, or `None` if it is not cached.
+pub(crate) fn cached_file(key: &FileKey) -> Option<Arc<CachedFile>> {
+    PAGE_CACHE.lock().files.get(key).and_then(Weak::upgrade)
+}
+
+/// Returns whether `path` is `prefix` or under the directory `prefix`.
+pub(crate) fn is_under(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Updates the paths of cached files on filesystems without inode numbers
+/// after `old` is renamed to `new`, which may be a directory. Files keyed by
+/// inode numbers are not affected by renames.
+pub(crate) fn rename(old: &str, new: &str) {
+    let mut cache = PAGE_CACHE.lock();
+    let moved = cache
+        .files
+        .keys()
+        .filter_map(|key| match key {
+            FileKey::Path(path) if is_under(path, old) => Some(path.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for path in moved {
+        let file = cache.files.remove(&FileKey::Path(path.clone())).unwrap();
+        let new_path =
+            String::from(new.trim_end_matches('/')) + &path[old.trim_end_matches('/').len()..];
+        cache.files.insert(FileKey::Path(new_path), file);
+    }
+}
+
+/// Marks the file identified by `key` as removed, once no links to it are
+/// left, so that it is never written back.
+///
+/// The cached pages are dropped if the file is not opened or mapped, or kept
+/// until it is closed by [`close`] otherwise, since the removed file can no
+/// longer be read from the filesystem.
+pub(crate) fn remove(key: &FileKey) {
+    let mut cache = PAGE_CACHE.lock();
+    if let Some(file) = cache.files.remove(key).and_then(|f| f.upgrade()) {
+        file.removed.store(true, Ordering::Release);
+        if cache.is_orphan(&file) {
+            cache.discard(file.id, 0);
+        }
+    }
+}
+
+/// Drops a reference to `file` when an opened file is closed, with the cached
+/// pages if the file is removed and no longer opened or mapped.
+pub(crate) fn close(file: Arc<CachedFile>) {
+    let mut cache = PAGE_CACHE.lock();
+    if cache.is_orphan(&file) {
+        cache.discard(file.id, 0);
+    }
+}
+
+/// Writes back and drops the cached pages of all files of the filesystem
+/// mounted at `path`, before it is unmounted.
+pub(crate) fn umount(path: &str) -> AxResult {
+    let mut cache = PAGE_CACHE.lock();
+    let files = cache
+        .files
+        .iter()
+        .filter(|(key, _)| match key {
+            FileKey::Inode(mount_path, _) => is_under(mount_path, path),
+            FileKey::Path(p) => is_under(p, path),
+        })
+        .map(|(key, f)| (key.clone(), f.upgrade()))
+        .collect::<Vec<_>>();
+    for (key, file) in files {
+        if let Some(file) = file {
+            cache.write_back(file.id, u64::MAX)?;
+            cache.discard(file.id, 0);
+        }
+        cache.files.remove(&key);
+    }
+    Ok(())
+}
+
+/// Writes back all dirty pages in the cache, and writes the cached data of
+/// all block devices to the devices.
+pub fn sync() -> AxResult {
+    let mut cache = PAGE_CACHE.lock();
+    let ids = cache
+        .pages
+        .iter()
+        .filter(|(_, page)| page.dirty)
+        .map(|((id, _), _)| *id)
+        .collect::<Vec<_>>();
+    for id in ids {
+        cache.write_back(id, u64::MAX)?;
+    }
+    for file in cache.files.values().filter_map(Weak::upgrade) {
+        file.node.fsync()?;
+    }
+    drop(cache);
+    crate::dev::sync().map_err(|_| AxError::Io)
+}
+
+/// Sets the maximum size of the page cache in bytes, and evicts pages if it
+/// is exceeded. 0 restores the default, an eighth of the physical memory.
+pub fn set_capacity(bytes: usize) {
+    let mut cache = PAGE_CACHE.lock();
+    cache.capacity = bytes.div_ceil(PAGE_SIZE);
+    while cache.pages.len() > cache.capacity() && cache.evict_one() {}
+}
+
+/// Returns the total size of the cached pages in bytes.
+pub fn cached_bytes() -> usize {
+    PAGE_CACHE.lock().pages.len() * PAGE_SIZE
+}
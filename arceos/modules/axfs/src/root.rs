@@ -1,73 +1,344 @@
 //! Root directory of the filesystem
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! Mount points are organized as a tree keyed on path components, so that
+//! nested mounts (e.g. `/mnt/a` and `/mnt/a/b`) are supported, and paths such
+//! as `/mnt2` never match a mount point at `/mnt`.
+//!
+//! Paths are resolved one component at a time, so that symbolic links can be
+//! followed wherever they appear. Operations on the filesystems are then done
+//! with the resolved absolute paths.
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{ax_err, AxError, AxResult};
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
 use axsync::Mutex;
+use cap_access::Cap;
+use core::sync::atomic::{AtomicU32, Ordering};
 use lazyinit::LazyInit;
 
-use crate::{api::FileType, fs, mounts};
+use crate::fops::{FileTimes, FsStats};
+use crate::notify::{self, EventMask};
+use crate::{api::FileType, cred, fs, mounts};
+
+/// Maximum number of symbolic links followed in one path resolution, the same
+/// as Linux.
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+/// IDs of the tasks whose last failed operation hit a loop of symbolic links,
+/// see [`take_symlink_loop`].
+static SYMLINK_LOOPS: Mutex<BTreeSet<u64>> = Mutex::new(BTreeSet::new());
+
+/// The umask of the initial context, the same as most Linux systems.
+const DEFAULT_UMASK: u32 = 0o022;
 
-static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
-static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
+/// Type of the main filesystem mounted on `/`.
+const MAIN_FSTYPE: &str = if cfg!(feature = "initramfs") {
+    "ramfs"
+} else if cfg!(feature = "overlay-root") {
+    "overlay"
+} else {
+    DISK_FSTYPE
+};
+
+/// Type of the filesystem on the root block device.
+const DISK_FSTYPE: &str = if cfg!(feature = "myfs") {
+    "myfs"
+} else if cfg!(feature = "ext4fs") {
+    "ext4"
+} else {
+    "vfat"
+};
+
+/// The context of tasks that have no context of their own, or of the whole
+/// system without the `multitask` feature.
+static GLOBAL_CONTEXT: FsContext = FsContext::new(String::new(), DEFAULT_UMASK, true);
+
+/// The filesystem context of a task, i.e. its current directory and its umask.
+///
+/// With the `multitask` feature, a task starts with the context of the task
+/// that spawned it, and changing its current directory does not affect other
+/// tasks. Kernels with processes can create a context by
+/// [`FsContext::new_shared`] and install it into every thread of a process
+/// with [`FsContext::install`], so that the current directory is shared by the
+/// whole process.
+pub struct FsContext {
+    cwd: Mutex<String>,
+    /// The permissions cleared from new files and directories.
+    umask: AtomicU32,
+    #[cfg_attr(not(feature = "multitask"), allow(dead_code))]
+    shared: bool,
+}
 
-struct MountPoint {
-    path: &'static str,
+/// A filesystem mounted at some path.
+///
+/// Opened files and directories hold a reference to the mount point they
+/// reside in, so that it cannot be unmounted while they are in use.
+pub(crate) struct MountPoint {
+    path: String,
+    source: String,
+    fstype: String,
     fs: Arc<dyn VfsOps>,
 }
 
+/// A node of the mount tree. Each node corresponds to a path component, and
+/// may have a filesystem mounted on it.
+struct MountNode {
+    mount: Option<Arc<MountPoint>>,
+    children: BTreeMap<String, MountNode>,
+}
+
 struct RootDirectory {
     main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+    mounts: Mutex<MountNode>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
+impl FsContext {
+    const fn new(cwd: String, umask: u32, shared: bool) -> Self {
+        Self {
+            cwd: Mutex::new(cwd),
+            umask: AtomicU32::new(umask),
+            shared,
+        }
+    }
+
+    /// Creates a context starting at the current directory and with the umask
+    /// of the calling task, which are changed for all tasks sharing it.
+    pub fn new_shared() -> Arc<Self> {
+        Arc::new(with_current_context(|ctx| ctx.copy(true)))
+    }
+
+    /// Makes `task` use this context, e.g. before it is spawned as a thread of
+    /// a process sharing the context.
+    #[cfg(feature = "multitask")]
+    pub fn install(self: &Arc<Self>, task: &axtask::TaskInner) {
+        crate::task_state::update(task, |state| state.context = Some(self.clone()));
+    }
+
+    /// Returns the current directory of this context.
+    pub fn current_dir(&self) -> String {
+        self.cwd.lock().clone()
+    }
+
+    /// Returns the umask of this context.
+    pub fn umask(&self) -> u32 {
+        self.umask.load(Ordering::Relaxed)
+    }
+
+    fn copy(&self, shared: bool) -> Self {
+        Self::new(self.current_dir(), self.umask(), shared)
+    }
+}
+
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new(path: String, source: &str, fstype: &str, fs: Arc<dyn VfsOps>) -> Self {
+        Self {
+            path,
+            source: source.into(),
+            fstype: fstype.into(),
+            fs,
+        }
+    }
+
+    /// Creates a hard link to `node` at `path` relative to the mount point.
+    fn link(&self, path: &str, node: VfsNodeRef) -> AxResult {
+        mounts::link(&self.fstype, &self.fs.root_dir(), path, node)
     }
 }
 
 impl Drop for MountPoint {
     fn drop(&mut self) {
+        debug!("umount {}", self.path);
         self.fs.umount().ok();
     }
 }
 
+impl MountNode {
+    const fn new() -> Self {
+        Self {
+            mount: None,
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn get<S: AsRef<str>>(&self, components: &[S]) -> Option<&MountNode> {
+        components
+            .iter()
+            .try_fold(self, |node, name| node.children.get(name.as_ref()))
+    }
+
+    fn get_or_insert(&mut self, components: &[&str]) -> &mut MountNode {
+        components.iter().fold(self, |node, name| {
+            node.children
+                .entry(String::from(*name))
+                .or_insert_with(MountNode::new)
+        })
+    }
+
+    /// Finds the mount point that has the longest match with `components`.
+    /// Returns it and the number of matched components, or `None` if the path
+    /// is not under any mount point.
+    fn longest_match(&self, components: &[&str]) -> Option<(&Arc<MountPoint>, usize)> {
+        let mut matched = None;
+        let mut node = self;
+        for (i, name) in components.iter().enumerate() {
+            match node.children.get(*name) {
+                Some(child) => node = child,
+                None => break,
+            }
+            if let Some(mp) = &node.mount {
+                matched = Some((mp, i + 1));
+            }
+        }
+        matched
+    }
+
+    /// Visits all mount points in the subtree, parents before children.
+    #[cfg(feature = "procfs")]
+    fn for_each_mount(&self, f: &mut impl FnMut(&MountPoint)) {
+        if let Some(mp) = &self.mount {
+            f(mp);
+        }
+        for child in self.children.values() {
+            child.for_each_mount(f);
+        }
+    }
+
+    /// Takes the mount point at `components` out of the tree, and removes the
+    /// nodes that no longer lead to any mount point.
+    fn take_mount(&mut self, components: &[&str]) -> Option<Arc<MountPoint>> {
+        match components.split_first() {
+            None => self.mount.take(),
+            Some((name, rest)) => {
+                let child = self.children.get_mut(*name)?;
+                let mp = child.take_mount(rest);
+                if child.mount.is_none() && child.children.is_empty() {
+                    self.children.remove(*name);
+                }
+                mp
+            }
+        }
+    }
+}
+
 impl RootDirectory {
     pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
         Self {
             main_fs,
-            mounts: Vec::new(),
+            mounts: Mutex::new(MountNode::new()),
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
-        if path == "/" {
-            return ax_err!(InvalidInput, "cannot mount root filesystem");
-        }
+    pub fn mount(&self, source: &str, path: &str, fstype: &str, fs: Arc<dyn VfsOps>) -> AxResult {
         if !path.starts_with('/') {
             return ax_err!(InvalidInput, "mount path must start with '/'");
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
+        let components = path_components(path);
+        if components.is_empty() {
+            return ax_err!(InvalidInput, "cannot mount root filesystem");
+        }
+
+        let mut mounts = self.mounts.lock();
+        if mounts.get(&components).is_some_and(|n| n.mount.is_some()) {
             return ax_err!(InvalidInput, "mount point already exists");
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+
+        // create the mount point in the filesystem that covers it, if it does
+        // not exist
+        let (parent_fs, rest_path) = self.resolve(&mounts, &components);
+        let parent_root = parent_fs.root_dir();
+        let mount_point = match parent_root.clone().lookup(&rest_path) {
+            Ok(node) => node,
+            Err(AxError::NotFound) => {
+                parent_root.create(&rest_path, FileType::Dir)?;
+                parent_root.lookup(&rest_path)?
+            }
+            Err(e) => return Err(e),
+        };
+        if !mount_point.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory, "mount point is not a directory");
+        }
+
+        let path = String::from("/") + &components.join("/");
+        fs.mount(&path, mount_point)?;
+        let mp = MountPoint::new(path, source, fstype, fs);
+        mounts.get_or_insert(&components).mount = Some(Arc::new(mp));
         Ok(())
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    pub fn umount(&self, path: &str) -> AxResult {
+        let components = path_components(path);
+        if components.is_empty() {
+            return ax_err!(InvalidInput, "cannot unmount root filesystem");
+        }
+
+        let mut mounts = self.mounts.lock();
+        let node = match mounts.get(&components) {
+            Some(node) if node.mount.is_some() => node,
+            _ => return ax_err!(InvalidInput, "not a mount point"),
+        };
+        if !node.children.is_empty() {
+            return ax_err!(ResourceBusy, "has nested mount points");
+        }
+        if node
+            .mount
+            .as_ref()
+            .is_some_and(|mp| Arc::strong_count(mp) > 1)
+        {
+            return ax_err!(ResourceBusy, "has opened files");
+        }
+        let in_mount = |ctx: &FsContext| path_components(&ctx.cwd.lock()).starts_with(&components);
+        if any_context(in_mount) {
+            return ax_err!(ResourceBusy, "is the current directory");
+        }
+        crate::page_cache::umount(path)?;
+        mounts.take_mount(&components);
+        Ok(())
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+        self.mounts
+            .lock()
+            .get(&path_components(path))
+            .is_some_and(|n| n.mount.is_some())
+    }
+
+    /// Returns the mount point that `path` resides in, or `None` if it is in
+    /// the main filesystem.
+    pub fn mount_point_of(&self, path: &str) -> Option<Arc<MountPoint>> {
+        let mounts = self.mounts.lock();
+        mounts
+            .longest_match(&path_components(path))
+            .map(|(mp, _)| mp.clone())
+    }
+
+    /// Finds the filesystem that has the longest mounted path match, and
+    /// returns it with the rest of the path relative to its root.
+    fn resolve(&self, mounts: &MountNode, components: &[&str]) -> (Arc<dyn VfsOps>, String) {
+        match mounts.longest_match(components) {
+            Some((mp, depth)) => (mp.fs.clone(), components[depth..].join("/")),
+            None => (self.main_fs.clone(), components.join("/")),
+        }
+    }
+
+    /// Looks up the last one of `components` in `parent`, which is the node of
+    /// the other components. Returns the root of the mounted filesystem instead
+    /// if there is one.
+    fn lookup_child(&self, parent: &VfsNodeRef, components: &[String]) -> AxResult<VfsNodeRef> {
+        if let Some(mp) = self
+            .mounts
+            .lock()
+            .get(components)
+            .and_then(|n| n.mount.as_ref())
+        {
+            return Ok(mp.fs.root_dir());
+        }
+        match components.last() {
+            Some(name) => parent.clone().lookup(name),
+            None => Ok(self.main_fs.root_dir()),
+        }
     }
 
     fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
@@ -75,29 +346,8 @@ impl RootDirectory {
         F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
     {
         debug!("lookup at root: {}", path);
-        let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
-        }
-
-        let mut idx = 0;
-        let mut max_len = 0;
-
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
-            }
-        }
-
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
-        }
+        let (fs, rest_path) = self.resolve(&self.mounts.lock(), &path_components(path));
+        f(fs, &rest_path)
     }
 }
 
@@ -133,79 +383,232 @@ impl VfsNodeOps for RootDirectory {
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let (dst_fs, dst_rest) =
+            self.lookup_mounted_fs(dst_path, |fs, rest_path| Ok((fs, String::from(rest_path))))?;
         self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
+            if rest_path.is_empty() || dst_rest.is_empty() {
                 ax_err!(PermissionDenied) // cannot rename mount points
+            } else if !Arc::ptr_eq(&fs, &dst_fs) {
+                ax_err!(Unsupported, "cannot rename across mount points")
             } else {
-                fs.root_dir().rename(rest_path, dst_path)
+                fs.root_dir().rename(rest_path, &dst_rest)
             }
         })
     }
 }
 
-pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
+/// Creates the main filesystem on `disk`, according to the filesystem
+/// selection features. With the `overlay-root` feature, it is covered by a
+/// writable ramfs, so that the disk is never modified.
+#[cfg(not(feature = "initramfs"))]
+pub(crate) fn new_main_fs(disk: crate::dev::Disk) -> Arc<dyn VfsOps> {
     cfg_if::cfg_if! {
         if #[cfg(feature = "myfs")] { // override the default filesystem
             let main_fs = fs::myfs::new_myfs(disk);
+        } else if #[cfg(feature = "ext4fs")] {
+            let ext4fs = fs::ext4fs::Ext4FileSystem::new(disk);
+            let main_fs = Arc::new(ext4fs.expect("failed to initialize ext4 filesystem"));
         } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
+            let main_fs = Arc::new(fs::fatfs::FatFileSystem::new(disk));
         }
     }
+    #[cfg(feature = "overlay-root")]
+    let main_fs = Arc::new(fs::overlayfs::OverlayFileSystem::new(
+        main_fs,
+        mounts::ramfs(),
+        DISK_FSTYPE,
+    ));
+    main_fs
+}
 
-    let mut root_dir = RootDirectory::new(main_fs);
+pub(crate) fn init_rootfs(main_fs: Arc<dyn VfsOps>) {
+    let root_dir = RootDirectory::new(main_fs);
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount("devfs", "/dev", "devfs", mounts::devfs())
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
     root_dir
-        .mount("/tmp", mounts::ramfs())
+        .mount(
+            "ramfs",
+            "/tmp",
+            "ramfs",
+            mounts::ramfs_with_quota(axconfig::TMP_SIZE as u64),
+        )
         .expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
     root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+        .mount("proc", "/proc", "procfs", mounts::procfs().unwrap())
         .expect("fail to mount procfs at /proc");
 
-    // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
     root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+        .mount("sysfs", "/sys", "sysfs", mounts::sysfs().unwrap())
         .expect("fail to mount sysfs at /sys");
 
     ROOT_DIR.init_once(Arc::new(root_dir));
-    CURRENT_DIR.init_once(Mutex::new(ROOT_DIR.clone()));
-    *CURRENT_DIR_PATH.lock() = "/".into();
+    *GLOBAL_CONTEXT.cwd.lock() = "/".into();
+
+    #[cfg(feature = "initramfs")]
+    if let Err(e) = crate::initramfs::unpack(crate::initramfs::INITRAMFS, "/") {
+        warn!("failed to unpack initramfs: {:?}", e);
+    }
+
+    for &(source, target, fstype) in axconfig::AUTOMOUNT {
+        info!("  mount {} at {} ({})", source, target, fstype);
+        if let Err(e) = mount(source, target, fstype, "") {
+            warn!("failed to mount {} at {}: {:?}", source, target, e);
+        }
+    }
+}
+
+/// Splits `path` into components, resolving `.` and `..` lexically. `..` at
+/// the root stays at the root.
+fn path_components(path: &str) -> Vec<&str> {
+    let mut components = Vec::new();
+    for name in path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(name),
+        }
+    }
+    components
 }
 
-fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
-    if path.starts_with('/') {
-        ROOT_DIR.clone()
+/// Resolves `path`, which is absolute or relative to the current directory,
+/// following symbolic links in it. The last component is not followed if
+/// `follow_last` is false.
+///
+/// Returns the resolved absolute path and its node, or `None` as the node if
+/// only the last component does not exist. Fails by [`symlink_loop`] if there
+/// are too many levels of symbolic links.
+pub(crate) fn resolve_path(
+    path: &str,
+    follow_last: bool,
+) -> AxResult<(String, Option<VfsNodeRef>)> {
+    take_symlink_loop();
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let path = if path.starts_with('/') {
+        String::from(path)
     } else {
-        dir.cloned().unwrap_or_else(|| CURRENT_DIR.lock().clone())
+        current_dir()? + path
+    };
+
+    // the components to be resolved, in reverse order
+    let mut pending: Vec<String> = path.split('/').rev().map(String::from).collect();
+    let mut resolved: Vec<String> = Vec::new();
+    let mut node = ROOT_DIR.main_fs.root_dir();
+    let mut follows = 0;
+    while let Some(name) = pending.pop() {
+        match name.as_str() {
+            "" | "." => continue,
+            ".." => {
+                resolved.pop();
+                node = ROOT_DIR.clone().lookup(&resolved.join("/"))?;
+                continue;
+            }
+            _ => {}
+        }
+        check_search(&node, &resolved)?;
+        resolved.push(name);
+
+        let child = match ROOT_DIR.lookup_child(&node, &resolved) {
+            Ok(child) => child,
+            Err(AxError::NotFound) if pending.iter().all(|n| n.is_empty() || n == ".") => {
+                return Ok((String::from("/") + &resolved.join("/"), None));
+            }
+            Err(e) => return Err(e),
+        };
+        // a trailing slash also makes the last component followed
+        let follow = follow_last || !pending.is_empty();
+        if follow && child.get_attr()?.file_type() == VfsNodeType::SymLink {
+            follows += 1;
+            if follows > MAX_SYMLINK_FOLLOWS {
+                return Err(symlink_loop());
+            }
+            resolved.pop();
+            let target = read_link_node(&child)?;
+            if target.starts_with('/') {
+                resolved.clear();
+                node = ROOT_DIR.main_fs.root_dir();
+            }
+            pending.extend(target.split('/').rev().map(String::from));
+        } else {
+            node = child;
+        }
     }
+    Ok((String::from("/") + &resolved.join("/"), Some(node)))
 }
 
-pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
-    if path.starts_with('/') {
-        Ok(axfs_vfs::path::canonicalize(path))
+/// Checks that the calling task can search `dir` at the path made of
+/// `components`, if it is a directory.
+fn check_search(dir: &VfsNodeRef, components: &[String]) -> AxResult {
+    let attr = dir.get_attr()?;
+    if !attr.is_dir() {
+        return Ok(()); // fails in the lookup
+    }
+    let mount = ROOT_DIR.mount_point_of(&(String::from("/") + &components.join("/")));
+    if access(mount.as_deref(), dir, &attr).contains(Cap::EXECUTE) {
+        Ok(())
     } else {
-        let path = CURRENT_DIR_PATH.lock().clone() + path;
-        Ok(axfs_vfs::path::canonicalize(&path))
+        ax_err!(PermissionDenied)
     }
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
-    if path.is_empty() {
-        return ax_err!(NotFound);
+/// Checks that the calling task can add and remove entries in the directory
+/// that contains the resolved absolute `path`.
+fn check_parent_writable(path: &str) -> AxResult {
+    let parent = match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    };
+    let dir = ROOT_DIR.clone().lookup(parent)?;
+    let attr = dir.get_attr()?;
+    let mount = ROOT_DIR.mount_point_of(parent);
+    if access(mount.as_deref(), &dir, &attr).contains(Cap::WRITE | Cap::EXECUTE) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
     }
-    let node = parent_node_of(dir, path).lookup(path)?;
+}
+
+/// Makes the calling task the owner of the new node at the resolved absolute
+/// `path`, and sets its permissions to `mode` without the bits in the umask.
+///
+/// Filesystems without owners or permissions keep their own.
+fn init_new_node(path: &str, node: &VfsNodeRef, mode: Option<u32>) {
+    let mount = ROOT_DIR.mount_point_of(path);
+    let fstype = mount.as_deref().map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    let (uid, gid) = cred::with_current(|cred| (cred.uid, cred.gid));
+    mounts::set_owner(fstype, node, uid, gid).ok();
+    if let Some(mode) = mode {
+        let perm = mode & !with_current_context(|ctx| ctx.umask());
+        mounts::set_perm(fstype, node, VfsNodePerm::from_bits_truncate(perm as u16)).ok();
+    }
+}
+
+fn read_link_node(node: &VfsNodeRef) -> AxResult<String> {
+    let mut buf = vec![0; node.get_attr()?.size() as usize];
+    let len = node.read_at(0, &mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+/// Returns the absolute path of `path` with all symbolic links resolved.
+pub(crate) fn canonicalize(path: &str) -> AxResult<String> {
+    resolve_path(path, true).map(|(path, _)| path)
+}
+
+pub(crate) fn lookup(path: &str, follow: bool) -> AxResult<VfsNodeRef> {
+    let node = resolve_path(path, follow)?.1.ok_or(AxError::NotFound)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
     } else {
@@ -213,38 +616,61 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     }
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
-    if path.is_empty() {
-        return ax_err!(NotFound);
-    } else if path.ends_with('/') {
+/// Creates a regular file with the permissions `mode`, without the bits in
+/// the umask.
+pub(crate) fn create_file(path: &str, mode: u32) -> AxResult<VfsNodeRef> {
+    if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    match resolve_path(path, true)? {
+        (_, Some(_)) => ax_err!(AlreadyExists),
+        (path, None) => {
+            check_parent_writable(&path)?;
+            ROOT_DIR.create(&path, VfsNodeType::File)?;
+            let node = ROOT_DIR.clone().lookup(&path)?;
+            init_new_node(&path, &node, Some(mode));
+            notify::notify(&path, EventMask::CREATE, false);
+            Ok(node)
+        }
+    }
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
-        Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
-        Err(e) => Err(e),
+/// Creates a directory with the permissions `mode`, without the bits in the
+/// umask.
+pub(crate) fn create_dir(path: &str, mode: u32) -> AxResult {
+    match resolve_path(path, false)? {
+        (_, Some(_)) => ax_err!(AlreadyExists),
+        (path, None) => {
+            check_parent_writable(&path)?;
+            ROOT_DIR.create(&path, VfsNodeType::Dir)?;
+            let node = ROOT_DIR.clone().lookup(&path)?;
+            init_new_node(&path, &node, Some(mode));
+            notify::notify(&path, EventMask::CREATE, true);
+            Ok(())
+        }
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
-    let attr = node.get_attr()?;
-    if attr.is_dir() {
+pub(crate) fn remove_file(path: &str) -> AxResult {
+    let (path, node) = resolve_path(path, false)?;
+    let node = node.ok_or(AxError::NotFound)?;
+    if node.get_attr()?.is_dir() {
         ax_err!(IsADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        check_parent_writable(&path)?;
+        let mount = ROOT_DIR.mount_point_of(&path);
+        let key = file_key(mount.as_deref(), &path, &node);
+        ROOT_DIR.remove(&path)?;
+        // the file may still be reachable by other hard links
+        if node_links(mount.as_deref(), &node) == 0 {
+            crate::page_cache::remove(&key);
+        }
+        notify::notify_deleted(&path, false);
+        Ok(())
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_dir(path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -258,53 +684,372 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
-    if ROOT_DIR.contains(&absolute_path(path)?) {
+
+    let (path, node) = resolve_path(path, false)?;
+    if ROOT_DIR.contains(&path) {
         return ax_err!(PermissionDenied);
     }
-
-    let node = lookup(dir, path)?;
-    let attr = node.get_attr()?;
+    let attr = node.ok_or(AxError::NotFound)?.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        check_parent_writable(&path)?;
+        ROOT_DIR.remove(&path)?;
+        notify::notify_deleted(&path, true);
+        Ok(())
     }
 }
 
-pub(crate) fn current_dir() -> AxResult<String> {
-    Ok(CURRENT_DIR_PATH.lock().clone())
+fn current_task_id() -> u64 {
+    #[cfg(feature = "multitask")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        return curr.id().as_u64();
+    }
+    0
 }
 
-pub(crate) fn set_current_dir(path: &str) -> AxResult {
-    let mut abs_path = absolute_path(path)?;
-    if !abs_path.ends_with('/') {
-        abs_path += "/";
+/// Returns the error of hitting a loop of symbolic links, i.e. too many levels
+/// of them, or a symbolic link opened without following it.
+///
+/// It is [`BadState`](AxError::BadState), which also reports other failures,
+/// so the loop is recorded for the calling task, until it is taken by
+/// [`take_symlink_loop`] or another path is resolved.
+pub(crate) fn symlink_loop() -> AxError {
+    SYMLINK_LOOPS.lock().insert(current_task_id());
+    AxError::BadState
+}
+
+/// Returns whether the last failed operation of the calling task hit a loop of
+/// symbolic links, and clears the record.
+pub(crate) fn take_symlink_loop() -> bool {
+    SYMLINK_LOOPS.lock().remove(&current_task_id())
+}
+
+#[cfg(feature = "multitask")]
+fn current_task_context() -> Option<Arc<FsContext>> {
+    crate::task_state::current()?.context.clone()
+}
+
+/// Returns whether `f` holds for the global context or the context of any
+/// task, e.g. whether any current directory is in a mount point.
+fn any_context(mut f: impl FnMut(&FsContext) -> bool) -> bool {
+    #[cfg(feature = "multitask")]
+    if crate::task_state::any_context(&mut f) {
+        return true;
+    }
+    f(&GLOBAL_CONTEXT)
+}
+
+fn with_current_context<R>(f: impl FnOnce(&FsContext) -> R) -> R {
+    #[cfg(feature = "multitask")]
+    if let Some(ctx) = current_task_context() {
+        return f(&ctx);
     }
-    if abs_path == "/" {
-        *CURRENT_DIR.lock() = ROOT_DIR.clone();
-        *CURRENT_DIR_PATH.lock() = "/".into();
-        return Ok(());
+    f(&GLOBAL_CONTEXT)
+}
+
+pub(crate) fn current_dir() -> AxResult<String> {
+    Ok(with_current_context(|ctx| ctx.current_dir()))
+}
+
+/// Changes the context of the calling task by `f`.
+///
+/// A shared context is changed in place. Otherwise the task gets a new context
+/// of its own, so that tasks sharing the old one are not affected.
+fn update_current_context(f: impl FnOnce(&FsContext)) {
+    #[cfg(feature = "multitask")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        match current_task_context() {
+            Some(ctx) if ctx.shared => f(&ctx),
+            ctx => {
+                let ctx = ctx.as_deref().unwrap_or(&GLOBAL_CONTEXT).copy(false);
+                f(&ctx);
+                crate::task_state::update(&curr, |state| state.context = Some(Arc::new(ctx)));
+            }
+        }
+        return;
     }
+    f(&GLOBAL_CONTEXT)
+}
 
-    let node = lookup(None, &abs_path)?;
+pub(crate) fn set_current_dir(path: &str) -> AxResult {
+    let (mut path, node) = resolve_path(path, true)?;
+    let node = node.ok_or(AxError::NotFound)?;
     let attr = node.get_attr()?;
+    let mount = ROOT_DIR.mount_point_of(&path);
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_executable() {
+    } else if !access(mount.as_deref(), &node, &attr).contains(Cap::EXECUTE) {
         ax_err!(PermissionDenied)
     } else {
-        *CURRENT_DIR.lock() = node;
-        *CURRENT_DIR_PATH.lock() = abs_path;
+        if !path.ends_with('/') {
+            path += "/";
+        }
+        update_current_context(|ctx| *ctx.cwd.lock() = path);
         Ok(())
     }
 }
 
+/// Sets the umask of the calling task to `mask`, and returns the old one.
+pub(crate) fn set_umask(mask: u32) -> u32 {
+    let old = with_current_context(|ctx| ctx.umask());
+    update_current_context(|ctx| ctx.umask.store(mask & 0o777, Ordering::Relaxed));
+    old
+}
+
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
+    let (old, node) = resolve_path(old, false)?;
+    let is_dir = node.ok_or(AxError::NotFound)?.get_attr()?.is_dir();
+    check_parent_writable(&old)?;
+    if resolve_path(new, false)?.1.is_some() {
         warn!("dst file already exist, now remove it");
-        remove_file(None, new)?;
+        remove_file(new)?;
     }
-    parent_node_of(None, old).rename(old, new)
+    let (new, _) = resolve_path(new, false)?;
+    check_parent_writable(&new)?;
+    ROOT_DIR.rename(&old, &new)?;
+    crate::page_cache::rename(&old, &new);
+    crate::fops::rename_opened(&old, &new);
+    notify::notify_renamed(&old, &new, is_dir);
+    Ok(())
+}
+
+/// Creates a symbolic link at `path`, which points to `target`.
+pub(crate) fn symlink(target: &str, path: &str) -> AxResult {
+    if target.is_empty() {
+        return ax_err!(NotFound);
+    } else if path.ends_with('/') {
+        return ax_err!(NotADirectory);
+    }
+    let path = match resolve_path(path, false)? {
+        (_, Some(_)) => return ax_err!(AlreadyExists),
+        (path, None) => path,
+    };
+    check_parent_writable(&path)?;
+    ROOT_DIR.create(&path, VfsNodeType::SymLink)?;
+    let node = ROOT_DIR.clone().lookup(&path)?;
+    if let Err(e) = node.write_at(0, target.as_bytes()) {
+        ROOT_DIR.remove(&path).ok();
+        return Err(e);
+    }
+    init_new_node(&path, &node, None);
+    notify::notify(&path, EventMask::CREATE, false);
+    Ok(())
+}
+
+/// Returns the target of the symbolic link at `path`.
+pub(crate) fn read_link(path: &str) -> AxResult<String> {
+    let node = resolve_path(path, false)?.1.ok_or(AxError::NotFound)?;
+    if node.get_attr()?.file_type() != VfsNodeType::SymLink {
+        return ax_err!(InvalidInput, "not a symbolic link");
+    }
+    read_link_node(&node)
+}
+
+/// Creates a hard link at `new` to the file at `old`, without following the
+/// symbolic link at the end of `old`.
+///
+/// Only supported if both paths are in the same filesystem, which is ramfs or
+/// ext2/ext4.
+pub(crate) fn link(old: &str, new: &str) -> AxResult {
+    let (old, node) = resolve_path(old, false)?;
+    let node = node.ok_or(AxError::NotFound)?;
+    if node.get_attr()?.is_dir() {
+        return ax_err!(
+            PermissionDenied,
+            "hard links to directories are not allowed"
+        );
+    }
+    if new.ends_with('/') {
+        return ax_err!(NotADirectory);
+    }
+    let new = match resolve_path(new, false)? {
+        (_, Some(_)) => return ax_err!(AlreadyExists),
+        (new, None) => new,
+    };
+    check_parent_writable(&new)?;
+
+    match (ROOT_DIR.mount_point_of(&old), ROOT_DIR.mount_point_of(&new)) {
+        (Some(mp), Some(new_mp)) if Arc::ptr_eq(&mp, &new_mp) => {
+            mp.link(&new[mp.path.len()..], node)?
+        }
+        (None, None) => mounts::link(MAIN_FSTYPE, &ROOT_DIR.main_fs.root_dir(), &new, node)?,
+        _ => return ax_err!(Unsupported, "cannot link across mount points"),
+    }
+    notify::notify(&new, EventMask::CREATE, false);
+    Ok(())
+}
+
+pub(crate) fn mount(source: &str, target: &str, fstype: &str, options: &str) -> AxResult {
+    let fs = mounts::create_fs(source, fstype, options)?;
+    ROOT_DIR.mount(source, &canonicalize(target)?, fstype, fs)
+}
+
+pub(crate) fn umount(target: &str) -> AxResult {
+    ROOT_DIR.umount(&canonicalize(target)?)
+}
+
+/// Calls `f` with the source, target path and filesystem type of each mount
+/// point, including the root filesystem.
+#[cfg(feature = "procfs")]
+pub(crate) fn for_each_mount(mut f: impl FnMut(&str, &str, &str)) {
+    f("rootfs", "/", "rootfs");
+    ROOT_DIR
+        .mounts
+        .lock()
+        .for_each_mount(&mut |mp| f(&mp.source, &mp.path, &mp.fstype));
+}
+
+/// Returns whether the file at `path` is accessed through the page cache, i.e.
+/// it resides in a disk filesystem. `path` must be resolved by
+/// [`resolve_path`].
+pub(crate) fn is_page_cached(path: &str) -> bool {
+    match ROOT_DIR.mount_point_of(path) {
+        Some(mp) => mounts::is_disk_fs(&mp.fstype),
+        None => mounts::is_disk_fs(MAIN_FSTYPE),
+    }
+}
+
+/// Returns the timestamps of `node` in the mount point `mount`, or in the main
+/// filesystem if `mount` is `None`.
+pub(crate) fn node_times(mount: Option<&MountPoint>, node: &VfsNodeRef) -> FileTimes {
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::node_times(fstype, node).unwrap_or_default()
+}
+
+/// Returns the inode number of `node` in the mount point `mount`, or in the
+/// main filesystem if `mount` is `None`. Returns 0 if the filesystem does not
+/// have inode numbers.
+pub(crate) fn node_ino(mount: Option<&MountPoint>, node: &VfsNodeRef) -> u64 {
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::node_ino(fstype, node).unwrap_or(0)
+}
+
+/// Returns the number of hard links to `node` in the mount point `mount`, or in
+/// the main filesystem if `mount` is `None`, or 0 if the filesystem does not
+/// keep link counts.
+fn node_links(mount: Option<&MountPoint>, node: &VfsNodeRef) -> u64 {
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::node_links(fstype, node).unwrap_or(0)
+}
+
+/// Returns the owner and the group of `node` in the mount point `mount`, or in
+/// the main filesystem if `mount` is `None`, or `None` if the filesystem does
+/// not keep owners.
+pub(crate) fn node_owner(mount: Option<&MountPoint>, node: &VfsNodeRef) -> Option<(u32, u32)> {
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::node_owner(fstype, node)
+}
+
+/// Returns the accesses to `node` with the attributes `attr` in the mount
+/// point `mount` that the calling task is allowed, see [`cred`].
+pub(crate) fn access(mount: Option<&MountPoint>, node: &VfsNodeRef, attr: &VfsNodeAttr) -> Cap {
+    let owner = node_owner(mount, node);
+    cred::with_current(|cred| cred.access(attr.perm(), attr.is_dir(), owner))
+}
+
+/// Sets the permissions of `node` in the mount point `mount`, or in the main
+/// filesystem if `mount` is `None`. Only root and the owner can do it.
+pub(crate) fn set_perm(mount: Option<&MountPoint>, node: &VfsNodeRef, perm: VfsNodePerm) -> AxResult {
+    let (uid, _) = node_owner(mount, node).unwrap_or((0, 0));
+    if !cred::with_current(|cred| cred.is_root() || cred.uid == uid) {
+        return ax_err!(PermissionDenied);
+    }
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::set_perm(fstype, node, perm)
+}
+
+/// Sets the owner and the group of `node` in the mount point `mount`, or in
+/// the main filesystem if `mount` is `None`. Those that are `None` are not
+/// changed.
+///
+/// Only root can change the owner, and the owner can only change the group to
+/// one that it is in.
+pub(crate) fn set_owner(
+    mount: Option<&MountPoint>,
+    node: &VfsNodeRef,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> AxResult {
+    let (old_uid, old_gid) = node_owner(mount, node).unwrap_or((0, 0));
+    let (uid, gid) = (uid.unwrap_or(old_uid), gid.unwrap_or(old_gid));
+    let allowed = cred::with_current(|cred| {
+        cred.is_root()
+            || (cred.uid == old_uid && uid == old_uid && (gid == old_gid || cred.in_group(gid)))
+    });
+    if !allowed {
+        return ax_err!(PermissionDenied);
+    }
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::set_owner(fstype, node, uid, gid)
+}
+
+/// Identifies a file, e.g. for its locks and its cached pages, regardless of
+/// the path it is accessed by.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum FileKey {
+    /// The inode number on the mount point at the path.
+    Inode(String, u64),
+    /// The absolute path of a file on a filesystem without inode numbers.
+    Path(String),
+}
+
+/// Returns the key of `node` at the resolved path `path`.
+pub(crate) fn file_key(mount: Option<&MountPoint>, path: &str, node: &VfsNodeRef) -> FileKey {
+    match node_ino(mount, node) {
+        0 => FileKey::Path(path.into()),
+        ino => FileKey::Inode(mount.map_or("/", |mp| mp.path.as_str()).into(), ino),
+    }
+}
+
+/// Allocates the storage of the `len` bytes at `offset` of `node` in the
+/// mount point `mount`, or in the main filesystem if `mount` is `None`.
+pub(crate) fn allocate(
+    mount: Option<&MountPoint>,
+    node: &VfsNodeRef,
+    offset: u64,
+    len: u64,
+    keep_size: bool,
+) -> AxResult {
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::allocate(fstype, node, offset, len, keep_size)
+}
+
+/// Punches a hole of `len` bytes at `offset` in `node` in the mount point
+/// `mount`, or in the main filesystem if `mount` is `None`.
+pub(crate) fn punch_hole(
+    mount: Option<&MountPoint>,
+    node: &VfsNodeRef,
+    offset: u64,
+    len: u64,
+) -> AxResult {
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::punch_hole(fstype, node, offset, len)
+}
+
+/// Finds the next hole or data at or after `offset` in `node` in the mount
+/// point `mount`, or in the main filesystem if `mount` is `None`.
+pub(crate) fn seek_extent(
+    mount: Option<&MountPoint>,
+    node: &VfsNodeRef,
+    offset: u64,
+    hole: bool,
+) -> AxResult<Option<u64>> {
+    let fstype = mount.map_or(MAIN_FSTYPE, |mp| mp.fstype.as_str());
+    mounts::seek_extent(fstype, node, offset, hole)
+}
+
+/// Returns the capacity and usage of the filesystem of the mount point
+/// `mount`, or of the main filesystem if `mount` is `None`.
+pub(crate) fn statfs(mount: Option<&MountPoint>) -> AxResult<FsStats> {
+    match mount {
+        Some(mp) => mounts::statfs(&mp.fstype, &mp.fs.root_dir()),
+        None => mounts::statfs(MAIN_FSTYPE, &ROOT_DIR.main_fs.root_dir()),
+    }
+}
+
+/// Returns the mount point that the file at `path` resides in. `path` must be
+/// resolved by [`resolve_path`].
+pub(crate) fn mount_point_of(path: &str) -> Option<Arc<MountPoint>> {
+    ROOT_DIR.mount_point_of(path)
 }
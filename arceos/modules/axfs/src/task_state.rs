@@ -0,0 +1,53 @@
+//! The filesystem state of tasks, kept in [`axtask::TaskInner::fs_state`].
+//!
+//! This is the only place that knows how the state is stored in tasks. The
+//! state is shared by a task and the tasks it spawns, until one of them changes
+//! it, which replaces its own state by an updated copy.
+
+use alloc::sync::Arc;
+
+use axtask::{TaskInner, TaskState};
+
+use crate::cred::Credentials;
+use crate::FsContext;
+
+/// The filesystem state of a task. Missing parts are taken from the global
+/// ones.
+#[derive(Clone, Default)]
+pub(crate) struct TaskFsState {
+    pub context: Option<Arc<FsContext>>,
+    pub cred: Option<Arc<Credentials>>,
+}
+
+fn state_of(task: &TaskInner) -> Option<Arc<TaskFsState>> {
+    task.fs_state()?.downcast::<TaskFsState>().ok()
+}
+
+/// Returns the filesystem state of the calling task, or `None` if it has none
+/// or tasks have not been initialized.
+pub(crate) fn current() -> Option<Arc<TaskFsState>> {
+    state_of(&axtask::current_may_uninit()?)
+}
+
+/// Changes the filesystem state of `task` by `f`, without affecting the tasks
+/// sharing it.
+pub(crate) fn update(task: &TaskInner, f: impl FnOnce(&mut TaskFsState)) {
+    let mut state = state_of(task).map_or_else(TaskFsState::default, |s| (*s).clone());
+    f(&mut state);
+    task.set_fs_state(Some(Arc::new(state)));
+}
+
+/// Returns whether `f` holds for the filesystem context of any task that has
+/// not exited. Tasks without a context of their own are skipped.
+pub(crate) fn any_context(mut f: impl FnMut(&FsContext) -> bool) -> bool {
+    let mut found = false;
+    axtask::for_each_task(|task| {
+        if found || task.state() == TaskState::Exited {
+            return;
+        }
+        if let Some(ctx) = state_of(task).and_then(|s| s.context.clone()) {
+            found = f(&ctx);
+        }
+    });
+    found
+}
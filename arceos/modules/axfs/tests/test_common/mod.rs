@@ -198,6 +198,35 @@ fn test_devfs_ramfs() -> Result<()> {
         .collect::<Vec<_>>();
     assert!(dirents.contains(&"null".into()));
     assert!(dirents.contains(&"zero".into()));
+    assert!(dirents.contains(&"urandom".into()));
+    assert!(dirents.contains(&"tty".into()));
+
+    // read /dev/random
+    let mut file = File::open("/dev/random")?;
+    assert_eq!(file.read(&mut buf)?, N);
+    let md = fs::metadata("/dev/console")?;
+    assert_eq!(md.file_type(), FileType::CharDevice);
+
+    // devices published at runtime
+    let event = std::sync::Arc::new(axfs::devfs::ZeroDev);
+    assert_eq!(
+        axfs::devfs::register_device("input/event0", event.clone()),
+        Ok(())
+    );
+    assert_err!(
+        axfs::devfs::register_device("/input//event0", event.clone()),
+        AlreadyExists
+    );
+    assert_err!(
+        axfs::devfs::register_device("null/event0", event.clone()),
+        AlreadyExists
+    );
+    assert_err!(
+        axfs::devfs::register_device("../event0", event),
+        InvalidInput
+    );
+    let md = fs::metadata("/dev/input/event0")?;
+    assert_eq!(md.file_type(), FileType::CharDevice);
 
     // stat /dev
     let dname = "/dev";
@@ -231,8 +260,11 @@ fn test_devfs_ramfs() -> Result<()> {
     // parent of '/dev'
     assert_eq!(fs::create_dir("///dev//..//233//"), Ok(()));
     assert_eq!(fs::write(".///dev//..//233//.///test.txt", "test"), Ok(()));
-    assert_err!(fs::remove_file("./dev//../..//233//.///test.txt"), NotFound);
-    assert_eq!(fs::remove_file("./dev//..//233//../233/./test.txt"), Ok(()));
+    assert_eq!(fs::remove_file("./dev//../..//233//.///test.txt"), Ok(()));
+    assert_err!(
+        fs::remove_file("./dev//..//233//../233/./test.txt"),
+        NotFound
+    );
     assert_eq!(fs::remove_dir("dev//foo/../foo/../.././/233"), Ok(()));
     assert_err!(fs::remove_dir("very/../dev//"), PermissionDenied);
 
@@ -242,16 +274,699 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(fs::read_dir("tmp").unwrap().count(), 1);
     assert_eq!(fs::write(".///tmp///dir//.///test.txt", "test"), Ok(()));
     assert_eq!(fs::read("tmp//././/dir//.///test.txt"), Ok("test".into()));
-    // assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty); // TODO
+    assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty);
     assert_err!(fs::remove_dir("/tmp/dir/../dir"), DirectoryNotEmpty);
     assert_eq!(fs::remove_file("./tmp//dir//test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("tmp/dir/.././dir///"), Ok(()));
     assert_eq!(fs::read_dir("tmp").unwrap().count(), 0);
 
+    // '..' crosses mount boundaries
+    assert_eq!(fs::create_dir("/tmp/dev2"), Ok(()));
+    assert_eq!(
+        fs::metadata("/tmp/../dev/zero")?.file_type(),
+        FileType::CharDevice
+    );
+    assert_err!(fs::metadata("/tmp/dev2/../../dev2"), NotFound);
+    assert_eq!(fs::set_current_dir("/tmp/dev2"), Ok(()));
+    assert_eq!(
+        fs::metadata("../../dev/null")?.file_type(),
+        FileType::CharDevice
+    );
+    assert_eq!(fs::set_current_dir("/"), Ok(()));
+    assert_eq!(fs::remove_dir("/tmp/dev2"), Ok(()));
+
     println!("test_devfs_ramfs() OK!");
     Ok(())
 }
 
+fn test_mount_umount() -> Result<()> {
+    let mnt = "/tmp/mnt";
+    println!("test mount {:?}:", mnt);
+
+    // nested mount in /tmp
+    assert_eq!(fs::mount("", mnt, "ramfs"), Ok(()));
+    assert_eq!(fs::write("/tmp/mnt/test.txt", "test"), Ok(()));
+    assert_eq!(fs::read_dir("/tmp/mnt/..")?.count(), 1);
+    assert_err!(fs::mount("", "tmp/./mnt/", "ramfs"), InvalidInput);
+    assert_err!(fs::mount("", "/tmp/mnt/test.txt", "ramfs"), NotADirectory);
+    assert_err!(fs::mount("", "/tmp/mnt2", "unknownfs"), Unsupported);
+    assert_err!(fs::remove_dir(mnt), PermissionDenied);
+    assert!(fs::read_to_string("/proc/mounts")?.contains("ramfs /tmp/mnt ramfs"));
+
+    // busy if there are opened files, or the current directory is in it
+    let file = File::open("/tmp/mnt/test.txt")?;
+    assert_err!(fs::umount(mnt), ResourceBusy);
+    drop(file);
+    fs::set_current_dir("/tmp/mnt")?;
+    assert_err!(fs::umount(mnt), ResourceBusy);
+    fs::set_current_dir("/")?;
+    #[cfg(feature = "multitask")]
+    {
+        // or the current directory of another task is in it
+        use core::sync::atomic::{AtomicBool, Ordering};
+        static IN_MNT: AtomicBool = AtomicBool::new(false);
+        let task = axtask::spawn(|| {
+            fs::set_current_dir("/tmp/mnt").unwrap();
+            IN_MNT.store(true, Ordering::Release);
+            while IN_MNT.load(Ordering::Acquire) {
+                axtask::yield_now();
+            }
+        });
+        while !IN_MNT.load(Ordering::Acquire) {
+            axtask::yield_now();
+        }
+        assert_err!(fs::umount(mnt), ResourceBusy);
+        IN_MNT.store(false, Ordering::Release);
+        task.join();
+    }
+
+    assert_eq!(fs::umount(mnt), Ok(()));
+    assert!(!fs::read_to_string("/proc/mounts")?.contains("/tmp/mnt"));
+    assert_err!(fs::metadata("/tmp/mnt/test.txt"), NotFound);
+    assert_err!(fs::umount(mnt), InvalidInput);
+    assert_err!(fs::umount("/"), InvalidInput);
+    assert_eq!(fs::remove_dir(mnt), Ok(()));
+
+    println!("test_mount_umount() OK!");
+    Ok(())
+}
+
+fn test_statfs() -> Result<()> {
+    let mnt = "/tmp/quota";
+    println!("test statfs and quotas in {:?}:", mnt);
+
+    let root = fs::statfs("/")?;
+    assert!(root.free_blocks <= root.blocks && root.avail_blocks <= root.free_blocks);
+    let proc = fs::statfs("/proc/mounts")?;
+    assert_eq!((proc.magic, proc.blocks), (0x9fa0, 0));
+    assert_err!(fs::statfs("/tmp/not-exist"), NotFound);
+
+    fs::create_dir(mnt)?;
+    let mount = |fstype, options| fs::mount_with_options("", mnt, fstype, options);
+    assert_err!(mount("ramfs", "size=0"), InvalidInput);
+    assert_err!(mount("ramfs", "mode=755"), InvalidInput);
+    assert_err!(mount("sysfs", "size=8k"), InvalidInput);
+    assert_eq!(mount("tmpfs", "size=8k"), Ok(()));
+    let stats = fs::statfs(mnt)?;
+    assert_eq!(stats.block_size, 4096);
+    assert_eq!((stats.blocks, stats.free_blocks), (2, 2));
+
+    // writes beyond the quota fail, and removing files frees space
+    fs::write("/tmp/quota/a", [1; 4096])?;
+    assert_eq!(fs::statfs("/tmp/quota/a")?.free_blocks, 1);
+    assert_err!(fs::write("/tmp/quota/b", [2; 8192]), StorageFull);
+    let mut file = File::create("/tmp/quota/b")?;
+    assert_eq!(file.write(&[2; 4096])?, 4096);
+    assert_err!(file.write(&[2]), StorageFull);
+    assert_eq!(fs::statfs(mnt)?.avail_blocks, 0);
+    file.set_len(1024)?;
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 0); // 3 KiB are free
+    fs::remove_file("/tmp/quota/a")?;
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 1);
+    fs::remove_file("/tmp/quota/b")?;
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 1); // still opened
+    drop(file);
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 2);
+
+    fs::umount(mnt)?;
+    fs::remove_dir(mnt)?;
+    println!("test_statfs() OK!");
+    Ok(())
+}
+
+fn test_links() -> Result<()> {
+    let dir = "/tmp/links";
+    println!("test symbolic and hard links in {:?}:", dir);
+
+    fs::create_dir(dir)?;
+    fs::write("/tmp/links/file", "hello")?;
+
+    // symbolic links, relative or absolute, even to other mounts
+    assert_eq!(fs::symlink("file", "/tmp/links/sym"), Ok(()));
+    assert_eq!(fs::symlink("/tmp/links", "/tmp/links/self"), Ok(()));
+    assert_eq!(fs::symlink("/dev/foo", "/tmp/links/foo"), Ok(()));
+    assert_err!(fs::symlink("file", "/tmp/links/sym"), AlreadyExists);
+    assert_eq!(fs::read_link("/tmp/links/sym")?, "file");
+    assert_err!(fs::read_link("/tmp/links/file"), InvalidInput);
+    assert_eq!(fs::read_to_string("/tmp/links/self/self/sym")?, "hello");
+    assert!(fs::metadata("/tmp/links/sym")?.is_file());
+    assert!(fs::symlink_metadata("/tmp/links/sym")?.is_symlink());
+    assert_eq!(fs::canonicalize("/tmp/links/self/sym")?, "/tmp/links/file");
+    assert_eq!(
+        fs::metadata("/tmp/links/foo/bar")?.file_type(),
+        FileType::CharDevice
+    );
+    assert_eq!(
+        fs::metadata("/tmp/links/foo/../null")?.file_type(),
+        FileType::CharDevice
+    );
+
+    // dangling links and loops
+    assert_eq!(fs::symlink("new", "/tmp/links/dangling"), Ok(()));
+    assert_err!(fs::read("/tmp/links/dangling"), NotFound);
+    assert_eq!(fs::write("/tmp/links/dangling", "new"), Ok(()));
+    assert_eq!(fs::read_to_string("/tmp/links/new")?, "new");
+    assert_eq!(fs::symlink("loop2", "/tmp/links/loop1"), Ok(()));
+    assert_eq!(fs::symlink("loop1", "/tmp/links/loop2"), Ok(()));
+    assert_err!(fs::read("/tmp/links/loop1"), BadState);
+    assert!(fs::take_symlink_loop());
+    assert!(!fs::take_symlink_loop());
+    // other failures are not loops
+    assert_err!(fs::read_link("/tmp/links/file"), InvalidInput);
+    assert!(!fs::take_symlink_loop());
+    let mut opts = axfs::fops::OpenOptions::new();
+    opts.read(true);
+    opts.no_follow(true);
+    assert!(axfs::fops::File::open("/tmp/links/file", &opts).is_ok());
+    assert_err!(axfs::fops::File::open("/tmp/links/sym", &opts), BadState);
+    assert!(fs::take_symlink_loop());
+
+    // hard links share the content
+    assert_eq!(fs::hard_link("/tmp/links/file", "/tmp/links/hard"), Ok(()));
+    assert_eq!(fs::write("/tmp/links/hard", "world"), Ok(()));
+    assert_eq!(fs::read_to_string("/tmp/links/file")?, "world");
+    assert_eq!(fs::remove_file("/tmp/links/file"), Ok(()));
+    assert_eq!(fs::read_to_string("/tmp/links/hard")?, "world");
+    assert_err!(fs::hard_link(dir, "/tmp/links2"), PermissionDenied);
+    assert_err!(fs::hard_link("/tmp/links/hard", "/dev/hard"), Unsupported);
+
+    // removing links does not remove their targets
+    for name in [
+        "sym", "self", "foo", "dangling", "new", "loop1", "loop2", "hard",
+    ] {
+        fs::remove_file(&format!("/tmp/links/{}", name))?;
+    }
+    assert_eq!(fs::metadata("/dev/foo")?.file_type(), FileType::Dir);
+    assert_eq!(fs::remove_dir(dir), Ok(()));
+
+    println!("test_links() OK!");
+    Ok(())
+}
+
+fn test_dir_relative() -> Result<()> {
+    let dir = "/tmp/rel";
+    println!("test paths relative to opened directories in {:?}:", dir);
+
+    fs::create_dir(dir)?;
+    let mut opts = axfs::fops::OpenOptions::new();
+    opts.read(true);
+    let tmp = axfs::fops::Directory::open_dir("/tmp", &opts)?;
+    let rel = tmp.open_dir_at("rel", &opts)?;
+    assert_eq!(rel.path(), dir);
+    assert!(rel.get_attr()?.is_dir());
+
+    // create, rename and remove relative to `rel`
+    assert_eq!(rel.create_dir("sub"), Ok(()));
+    let mut file_opts = axfs::fops::OpenOptions::new();
+    file_opts.write(true);
+    file_opts.create(true);
+    let mut file = rel.open_file_at("sub/f1", &file_opts)?;
+    assert_eq!(file.write(b"relative")?, 8);
+    drop(file);
+    assert_eq!(rel.rename("sub/f1", "f2"), Ok(()));
+    assert_eq!(fs::read_to_string("/tmp/rel/f2")?, "relative");
+
+    // the current directory
+    let cwd = fs::current_dir()?;
+    assert_eq!(fs::set_current_dir("/tmp/rel/sub"), Ok(()));
+    assert_eq!(fs::read_to_string("../f2")?, "relative");
+    assert_eq!(axfs::FsContext::new_shared().current_dir(), "/tmp/rel/sub/");
+    assert_eq!(fs::set_current_dir(&cwd), Ok(()));
+
+    // opened directories follow renames of themselves and their parents
+    let sub = rel.open_dir_at("sub", &opts)?;
+    assert_eq!(fs::rename(dir, "/tmp/rel2"), Ok(()));
+    assert_eq!(rel.path(), "/tmp/rel2");
+    assert_eq!(sub.path(), "/tmp/rel2/sub");
+    assert_eq!(sub.create_dir("moved"), Ok(()));
+    assert!(fs::metadata("/tmp/rel2/sub/moved")?.is_dir());
+    assert_eq!(sub.remove_dir("moved"), Ok(()));
+    drop(sub);
+    assert_eq!(fs::rename("/tmp/rel2", dir), Ok(()));
+    assert_eq!(rel.path(), dir);
+
+    assert_eq!(rel.remove_file("f2"), Ok(()));
+    assert_eq!(rel.remove_dir("sub"), Ok(()));
+    drop(rel);
+    assert_eq!(tmp.remove_dir("rel"), Ok(()));
+
+    println!("test_dir_relative() OK!");
+    Ok(())
+}
+
+fn test_page_cache() -> Result<()> {
+    let fname = "/page_cache.txt";
+    println!("test page cache with {:?}:", fname);
+
+    // force pages to be evicted and written back
+    axfs::page_cache::set_capacity(2 * axfs::page_cache::PAGE_SIZE);
+
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(fname)?;
+    let data = (0..5000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    assert_eq!(file.write(&data)?, 5000);
+    // leave a hole across pages
+    assert_eq!(file.seek(io::SeekFrom::Start(12000))?, 12000);
+    assert_eq!(file.write(b"tail")?, 4);
+    assert_eq!(file.metadata()?.len(), 12004);
+    #[cfg(not(feature = "myfs"))] // custom filesystems may not support `fsync`
+    file.flush()?;
+    assert!(axfs::page_cache::cached_bytes() <= 2 * axfs::page_cache::PAGE_SIZE);
+
+    let contents = fs::read(fname)?;
+    assert_eq!(contents.len(), 12004);
+    assert_eq!(&contents[..5000], &data[..]);
+    assert!(contents[5000..12000].iter().all(|&b| b == 0));
+    assert_eq!(&contents[12000..], b"tail");
+
+    // shrink in the middle of a page, then extend again
+    file.set_len(3000)?;
+    file.set_len(6000)?;
+    let contents = fs::read(fname)?;
+    assert_eq!(contents.len(), 6000);
+    assert_eq!(&contents[..3000], &data[..3000]);
+    assert!(contents[3000..].iter().all(|&b| b == 0));
+    drop(file);
+
+    // cached data follows the file when renamed
+    assert_eq!(fs::write(fname, "Hello, cache!"), Ok(()));
+    assert_eq!(fs::rename(fname, "/page_cache2.txt"), Ok(()));
+    assert_eq!(fs::metadata("/page_cache2.txt")?.len(), 13);
+    assert_eq!(fs::read_to_string("/page_cache2.txt")?, "Hello, cache!");
+    assert_eq!(fs::remove_file("/page_cache2.txt"), Ok(()));
+    assert_err!(fs::metadata(fname), NotFound);
+
+    // a removed file keeps its data in the cache until closed
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(fname)?;
+    assert_eq!(file.write(&data)?, 5000);
+    assert_eq!(fs::remove_file(fname), Ok(()));
+    assert_eq!(file.write(&data)?, 5000);
+    assert_eq!(file.seek(io::SeekFrom::Start(0))?, 0);
+    let mut contents = Vec::new();
+    assert_eq!(file.read_to_end(&mut contents)?, 10000);
+    assert_eq!(&contents[..5000], &data[..]);
+    assert_eq!(&contents[5000..], &data[..]);
+    drop(file);
+    assert!(axfs::page_cache::cached_bytes() <= 2 * axfs::page_cache::PAGE_SIZE);
+
+    axfs::page_cache::set_capacity(0);
+    assert_eq!(axfs::page_cache::sync(), Ok(()));
+    println!("test_page_cache() OK!");
+    Ok(())
+}
+
+fn test_sparse_files() -> Result<()> {
+    let mnt = "/tmp/sparse";
+    println!("test sparse files in {:?}:", mnt);
+
+    fs::create_dir(mnt)?;
+    fs::mount_with_options("", mnt, "tmpfs", "size=16k")?;
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open("/tmp/sparse/image")?;
+
+    // holes take no space, and read as zeros
+    file.set_len(1 << 30)?;
+    assert_eq!(file.seek(io::SeekFrom::Start(8192))?, 8192);
+    assert_eq!(file.write(&[1; 100])?, 100);
+    let meta = file.metadata()?;
+    assert_eq!((meta.len(), meta.blocks()), (1 << 30, 8));
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 3);
+    let mut buf = [0xff; 4];
+    assert_eq!(file.seek(io::SeekFrom::Start(8190))?, 8190);
+    assert_eq!(file.read(&mut buf)?, 4);
+    assert_eq!(buf, [0, 0, 1, 1]);
+
+    assert_eq!(file.seek_data(0)?, 8192);
+    assert_eq!(file.seek_hole(0)?, 0);
+    assert_eq!(file.seek_hole(8192)?, 12288);
+    assert_err!(file.seek_data(12288), NotFound);
+    assert_err!(file.seek_hole(1 << 30), NotFound);
+
+    // allocated space is reserved, even beyond the end of the file
+    file.allocate(0, 8192, false)?;
+    assert_eq!(file.seek_hole(0)?, 12288);
+    file.allocate(1 << 30, 4096, true)?;
+    assert_eq!(file.metadata()?.len(), 1 << 30);
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 0);
+    assert_err!(file.allocate(1 << 20, 1, false), StorageFull);
+    assert_err!(file.allocate(0, 0, false), InvalidInput);
+    assert_eq!(file.seek(io::SeekFrom::Start(100))?, 100);
+    assert_eq!(file.write(&[2; 100])?, 100);
+
+    // punching holes frees the space
+    file.punch_hole(4000, 8292)?;
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 2);
+    assert_eq!(file.metadata()?.len(), 1 << 30);
+    assert_eq!((file.seek_data(0)?, file.seek_hole(0)?), (0, 4096));
+    assert_err!(file.seek_data(4096), NotFound);
+    assert_eq!(file.seek(io::SeekFrom::Start(8190))?, 8190);
+    assert_eq!(file.read(&mut buf)?, 4);
+    assert_eq!(buf, [0; 4]);
+    assert_eq!(file.seek(io::SeekFrom::Start(198))?, 198);
+    assert_eq!(file.read(&mut buf)?, 4);
+    assert_eq!(buf, [2, 2, 0, 0]);
+    file.set_len(0)?;
+    assert_eq!(fs::statfs(mnt)?.free_blocks, 4);
+    drop(file);
+    fs::umount(mnt)?;
+    fs::remove_dir(mnt)?;
+
+    // filesystems without holes store the extended part as zeros
+    let fname = "/sparse.img";
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(fname)?;
+    assert_eq!(file.write(b"head")?, 4);
+    file.allocate(0, 20000, false)?;
+    assert_eq!(file.metadata()?.len(), 20000);
+    assert_eq!(file.seek_data(0)?, 0);
+    assert!(file.seek_hole(0)? >= 4);
+    file.set_len(100000)?;
+    drop(file);
+    let contents = fs::read(fname)?;
+    assert_eq!(contents.len(), 100000);
+    assert_eq!(&contents[..4], b"head");
+    assert!(contents[4..].iter().all(|&b| b == 0));
+    fs::remove_file(fname)?;
+
+    println!("test_sparse_files() OK!");
+    Ok(())
+}
+
+fn test_locks() -> Result<()> {
+    use axfs::fops::{File as RawFile, OpenOptions as RawOptions};
+    use axfs::lock::{LockType, RecordLock};
+
+    let fname = "/locks.txt";
+    println!("test file locks on {:?}:", fname);
+    fs::write(fname, "locks")?;
+
+    // whole-file locks conflict between opened files, and are released on close
+    let file1 = File::open(fname)?;
+    let file2 = File::open(fname)?;
+    assert!(file1.try_lock_shared()?);
+    assert!(file2.try_lock_shared()?);
+    assert!(!file1.try_lock()?);
+    file2.unlock()?;
+    assert!(file1.try_lock()?); // converted to an exclusive lock
+    assert!(!file2.try_lock_shared()?);
+    #[cfg(not(feature = "multitask"))] // no other task to release it
+    assert_err!(file2.lock(), WouldBlock);
+    drop(file1);
+    assert!(file2.try_lock()?);
+    drop(file2);
+
+    // record locks conflict between owners in overlapping ranges
+    let mut opts = RawOptions::new();
+    opts.read(true);
+    opts.write(true);
+    let file = RawFile::open(fname, &opts)?;
+    let locks = file.locks();
+    let lock = |ty, start, end, owner| RecordLock {
+        ty,
+        start,
+        end,
+        owner,
+    };
+    assert_eq!(
+        locks.set_record_lock(lock(LockType::Read, 0, 10, 1), false),
+        Ok(())
+    );
+    assert_eq!(
+        locks.set_record_lock(lock(LockType::Read, 5, 20, 2), false),
+        Ok(())
+    );
+    assert_err!(
+        locks.set_record_lock(lock(LockType::Write, 8, 9, 3), false),
+        WouldBlock
+    );
+    assert_eq!(
+        locks.conflicting_record_lock(&lock(LockType::Write, 0, 3, 2)),
+        Some(lock(LockType::Read, 0, 10, 1))
+    );
+    assert_eq!(
+        locks.conflicting_record_lock(&lock(LockType::Write, 0, 3, 1)),
+        None
+    );
+
+    // unlocking the middle of a range splits it
+    locks.unlock_records(2, 8, 12);
+    locks.unlock_records(1, 4, 10);
+    assert_eq!(
+        locks.set_record_lock(lock(LockType::Write, 4, 5, 3), false),
+        Ok(())
+    );
+    assert_err!(
+        locks.set_record_lock(lock(LockType::Write, 12, u64::MAX, 3), false),
+        WouldBlock
+    );
+    for owner in 1..=3 {
+        locks.unlock_records(owner, 0, u64::MAX);
+    }
+    assert_eq!(
+        locks.conflicting_record_lock(&lock(LockType::Write, 0, u64::MAX, 4)),
+        None
+    );
+
+    // write locks need the file to be opened for writing
+    let mut opts = RawOptions::new();
+    opts.read(true);
+    let file = RawFile::open(fname, &opts)?;
+    assert_err!(
+        file.locks()
+            .set_record_lock(lock(LockType::Write, 0, 1, 1), false),
+        PermissionDenied
+    );
+    drop(file);
+    assert_eq!(fs::remove_file(fname), Ok(()));
+    println!("test_locks() OK!");
+    Ok(())
+}
+
+fn test_timestamps() -> Result<()> {
+    println!("test file timestamps:");
+
+    #[cfg(not(any(
+        feature = "myfs",
+        feature = "ext4fs",
+        feature = "initramfs",
+        feature = "overlay-root"
+    )))]
+    {
+        // FAT timestamps start from 1980-01-01
+        const FAT_EPOCH_SECS: u64 = 315532800;
+        let fname = "/timestamps.txt";
+        assert_eq!(fs::write(fname, "Hello, time!"), Ok(()));
+        let meta = fs::metadata(fname)?;
+        assert!(meta.modified().as_secs() >= FAT_EPOCH_SECS);
+        assert!(meta.accessed().as_secs() >= FAT_EPOCH_SECS);
+        assert_eq!(fs::remove_file(fname), Ok(()));
+    }
+
+    let fname = "/tmp/timestamps.txt";
+    assert_eq!(fs::write(fname, "Hello, time!"), Ok(()));
+    let created = fs::metadata(fname)?.modified();
+    let dir_modified = fs::metadata("/tmp")?.modified();
+    assert!(dir_modified >= created);
+    let mut file = File::options().append(true).open(fname)?;
+    assert_eq!(file.write(b"!")?, 1);
+    assert!(file.metadata()?.modified() >= created);
+    drop(file);
+    assert_eq!(fs::remove_file(fname), Ok(()));
+    assert!(fs::metadata("/tmp")?.modified() >= dir_modified);
+
+    println!("test_timestamps() OK!");
+    Ok(())
+}
+
+fn test_sysfs() -> Result<()> {
+    println!("test sysfs attributes:");
+
+    assert!(fs::read_to_string("/sys/devices/system/cpu/online")?.starts_with('0'));
+    assert_err!(
+        fs::write("/sys/devices/system/cpu/online", "0-3"),
+        PermissionDenied
+    );
+
+    let log_level = fs::read_to_string("/sys/kernel/log_level")?;
+    assert_eq!(fs::write("/sys/kernel/log_level", "warn\n"), Ok(()));
+    assert_eq!(fs::read_to_string("/sys/kernel/log_level")?, "warn\n");
+    assert_err!(fs::write("/sys/kernel/log_level", "verbose"), InvalidInput);
+    assert_eq!(fs::read_to_string("/sys/kernel/log_level")?, "warn\n");
+    assert_eq!(fs::write("/sys/kernel/log_level", log_level), Ok(()));
+
+    println!("test_sysfs() OK!");
+    Ok(())
+}
+
+fn test_notify() -> Result<()> {
+    use axfs::notify::{EventMask, Watcher};
+
+    let dir = "/notify-dir";
+    println!("test notifications in {:?}:", dir);
+    fs::create_dir(dir)?;
+    let watcher = Watcher::new();
+    let wd = watcher.add_watch(dir, EventMask::ALL_EVENTS)?;
+    let events = || {
+        let mut events = Vec::new();
+        while let Some(event) = watcher.read_event() {
+            events.push(event);
+        }
+        events
+    };
+    let kinds = |events: &[axfs::notify::Event]| {
+        events
+            .iter()
+            .map(|e| (e.wd, e.mask, e.name.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    // events of the entries in the watched directory
+    fs::write("/notify-dir/a.txt", "a")?;
+    fs::create_dir("/notify-dir/sub")?;
+    fs::rename("/notify-dir/a.txt", "/notify-dir/sub/b.txt")?;
+    assert_eq!(
+        kinds(&events()),
+        [
+            (wd, EventMask::CREATE, "a.txt".into()),
+            (wd, EventMask::MODIFY, "a.txt".into()),
+            (wd, EventMask::CREATE | EventMask::ISDIR, "sub".into()),
+            (wd, EventMask::MOVED_FROM, "a.txt".into()),
+        ]
+    );
+
+    // watches of files follow them when renamed
+    let file_wd = watcher.add_watch("/notify-dir/sub/b.txt", EventMask::MODIFY)?;
+    assert_ne!(file_wd, wd);
+    fs::rename("/notify-dir/sub", "/notify-dir/sub2")?;
+    let moves = events();
+    assert_eq!(
+        kinds(&moves),
+        [
+            (wd, EventMask::MOVED_FROM | EventMask::ISDIR, "sub".into()),
+            (wd, EventMask::MOVED_TO | EventMask::ISDIR, "sub2".into()),
+        ]
+    );
+    assert_ne!(moves[0].cookie, 0);
+    assert_eq!(moves[0].cookie, moves[1].cookie);
+    fs::write("/notify-dir/sub2/b.txt", "b")?;
+    assert_eq!(
+        kinds(&events()),
+        [(file_wd, EventMask::MODIFY, String::new())]
+    );
+
+    // watches are removed with the watched files
+    fs::remove_file("/notify-dir/sub2/b.txt")?;
+    fs::remove_dir("/notify-dir/sub2")?;
+    assert_eq!(
+        kinds(&events()),
+        [
+            (file_wd, EventMask::IGNORED, String::new()),
+            (wd, EventMask::DELETE | EventMask::ISDIR, "sub2".into()),
+        ]
+    );
+    assert_eq!(watcher.remove_watch(wd), Ok(()));
+    assert_err!(watcher.remove_watch(wd), InvalidInput);
+    assert_eq!(kinds(&events()), [(wd, EventMask::IGNORED, String::new())]);
+    fs::remove_dir(dir)?;
+    assert!(!watcher.has_events());
+
+    assert_err!(watcher.add_watch(dir, EventMask::CREATE), NotFound);
+    assert_err!(watcher.add_watch("/", EventMask::empty()), InvalidInput);
+    println!("test_notify() OK!");
+    Ok(())
+}
+
+fn test_owners() -> Result<()> {
+    use axfs::cred::{self, Credentials};
+    use fs::{DirBuilder, Permissions};
+
+    let dir = "/tmp/owners";
+    println!("test owners and permissions in {:?}:", dir);
+    let perm = |path: &str| fs::metadata(path).map(|meta| meta.permissions().bits());
+
+    // new files belong to the creator, and the umask is applied to the mode
+    let old_umask = fs::umask(0o027);
+    DirBuilder::new().mode(0o777).create(dir)?;
+    assert_eq!(perm(dir)?, 0o750);
+    assert_eq!(fs::umask(0o022), 0o027);
+    fs::write("/tmp/owners/shared.txt", "shared")?;
+    assert_eq!(perm("/tmp/owners/shared.txt")?, 0o644);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .mode(0o600)
+        .open("/tmp/owners/private.txt")?;
+    assert_eq!(file.metadata()?.permissions().bits(), 0o600);
+    assert_eq!(file.write(b"private")?, 7);
+    drop(file);
+    let meta = fs::metadata(dir)?;
+    assert_eq!((meta.uid(), meta.gid()), (0, 0));
+    assert_eq!(fs::chown(dir, Some(1000), Some(1000)), Ok(()));
+    let meta = fs::metadata(dir)?;
+    assert_eq!((meta.uid(), meta.gid()), (1000, 1000));
+
+    // other users are bound by the permissions
+    cred::set_current(Credentials::new(1000, 1000));
+    assert_eq!(fs::read_to_string("/tmp/owners/shared.txt")?, "shared");
+    assert_err!(fs::write("/tmp/owners/shared.txt", "x"), PermissionDenied);
+    assert_err!(fs::read("/tmp/owners/private.txt"), PermissionDenied);
+    assert_err!(
+        fs::set_permissions("/tmp/owners/shared.txt", Permissions::all()),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::chown("/tmp/owners/shared.txt", Some(1000), None),
+        PermissionDenied
+    );
+    fs::write("/tmp/owners/user.txt", "user")?;
+    let meta = fs::metadata("/tmp/owners/user.txt")?;
+    assert_eq!((meta.uid(), meta.gid()), (1000, 1000));
+    assert_err!(
+        fs::chown("/tmp/owners/user.txt", Some(0), None),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::chown("/tmp/owners/user.txt", None, Some(0)),
+        PermissionDenied
+    );
+    assert_err!(fs::write("/tmp/user.txt", "user"), PermissionDenied);
+
+    // directories need write access to change entries, and search access to
+    // reach them
+    let read_only = Permissions::from_bits_truncate(0o550);
+    assert_eq!(fs::set_permissions(dir, read_only), Ok(()));
+    assert_err!(fs::write("/tmp/owners/new.txt", "new"), PermissionDenied);
+    assert_err!(fs::remove_file("/tmp/owners/user.txt"), PermissionDenied);
+    assert_eq!(fs::set_permissions(dir, Permissions::empty()), Ok(()));
+    assert_err!(fs::metadata("/tmp/owners/user.txt"), PermissionDenied);
+    assert_err!(fs::read_dir(dir), PermissionDenied);
+
+    // root is not bound by the permissions
+    cred::set_current(Credentials::root());
+    assert_eq!(fs::read_to_string("/tmp/owners/user.txt")?, "user");
+    assert_eq!(fs::remove_file("/tmp/owners/user.txt"), Ok(()));
+    assert_eq!(fs::remove_file("/tmp/owners/shared.txt"), Ok(()));
+    assert_eq!(fs::remove_file("/tmp/owners/private.txt"), Ok(()));
+    assert_eq!(fs::remove_dir(dir), Ok(()));
+    fs::umask(old_umask);
+
+    println!("test_owners() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
@@ -259,4 +974,15 @@ pub fn test_all() {
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_mount_umount().expect("test_mount_umount() failed");
+    test_statfs().expect("test_statfs() failed");
+    test_links().expect("test_links() failed");
+    test_dir_relative().expect("test_dir_relative() failed");
+    test_page_cache().expect("test_page_cache() failed");
+    test_sparse_files().expect("test_sparse_files() failed");
+    test_locks().expect("test_locks() failed");
+    test_notify().expect("test_notify() failed");
+    test_timestamps().expect("test_timestamps() failed");
+    test_sysfs().expect("test_sysfs() failed");
+    test_owners().expect("test_owners() failed");
 }
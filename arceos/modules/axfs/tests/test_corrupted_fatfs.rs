@@ -0,0 +1,44 @@
+//! Mounting corrupted FAT images. The root filesystem is unpacked from an
+//! initramfs, so that the only block device is free to be corrupted.
+#![cfg(all(feature = "initramfs", feature = "fatfs"))]
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::Result;
+
+/// Returns a disk whose boot sector describes a FAT volume of 1000 sectors,
+/// much larger than the disk itself.
+fn make_truncated_disk() -> RamDisk {
+    let mut image = vec![0; 0x10000];
+    image[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes()); // bytes per sector
+    image[0x13..0x15].copy_from_slice(&1000u16.to_le_bytes()); // total sectors
+    image[0x1fe..0x200].copy_from_slice(&[0x55, 0xaa]);
+    RamDisk::from(&image)
+}
+
+fn test_truncated() -> Result<()> {
+    println!("test mounting a truncated FAT image:");
+
+    fs::create_dir("/mnt")?;
+    let res = fs::mount("/dev/vda", "/mnt", "vfat");
+    assert_eq!(res.err(), Some(axio::Error::InvalidData));
+    assert_eq!(fs::read_dir("/mnt")?.count(), 0);
+    // the failed mount does not keep the device busy
+    let res = fs::mount("/dev/vda", "/mnt", "vfat");
+    assert_eq!(res.err(), Some(axio::Error::InvalidData));
+    fs::remove_dir("/mnt")?;
+
+    println!("test_truncated() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_fatfs() {
+    println!("Testing corrupted fatfs ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(make_truncated_disk()));
+
+    test_truncated().expect("test_truncated() failed");
+}
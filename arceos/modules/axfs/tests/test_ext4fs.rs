@@ -0,0 +1,92 @@
+#![cfg(all(feature = "ext4fs", not(any(feature = "myfs", feature = "initramfs"))))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::{Result, Write};
+
+const IMG_PATH: &str = "resources/ext2.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_unix_attrs() -> Result<()> {
+    println!("test permissions, inode numbers and links on ext2:");
+
+    // new files and directories get the default permissions
+    fs::write("/attrs.txt", "attrs")?;
+    fs::create_dir("/attrs-dir")?;
+    assert_eq!(fs::metadata("/attrs.txt")?.permissions().bits(), 0o644);
+    assert_eq!(fs::metadata("/attrs-dir")?.permissions().bits(), 0o755);
+
+    // inode numbers are stable and unique, and shared by hard links
+    let ino = fs::metadata("/attrs.txt")?.ino();
+    assert!(ino > 2);
+    assert_eq!(fs::metadata("/")?.ino(), 2);
+    assert_ne!(fs::metadata("/attrs-dir")?.ino(), ino);
+    assert_eq!(fs::hard_link("/attrs.txt", "/attrs-dir/hard"), Ok(()));
+    assert_eq!(fs::metadata("/attrs-dir/hard")?.ino(), ino);
+    assert_eq!(fs::rename("/attrs.txt", "/attrs-dir/moved"), Ok(()));
+    assert_eq!(fs::metadata("/attrs-dir/moved")?.ino(), ino);
+    assert_eq!(fs::remove_file("/attrs-dir/moved"), Ok(()));
+    assert_eq!(fs::read_to_string("/attrs-dir/hard")?, "attrs");
+
+    // hard links share the cached pages, even across renames
+    fs::hard_link("/attrs-dir/hard", "/attrs-dir/hard2")?;
+    let mut file = fs::File::options().write(true).open("/attrs-dir/hard")?;
+    file.write_all(b"ATTRS")?;
+    assert_eq!(fs::read_to_string("/attrs-dir/hard2")?, "ATTRS");
+    fs::rename("/attrs-dir/hard2", "/attrs-dir/hard3")?;
+    file.write_all(b"!")?;
+    assert_eq!(fs::read_to_string("/attrs-dir/hard3")?, "ATTRS!");
+    assert_eq!(fs::remove_file("/attrs-dir/hard3"), Ok(()));
+    drop(file);
+    assert_eq!(fs::read_to_string("/attrs-dir/hard")?, "ATTRS!");
+
+    // short and long symbolic links
+    let long_target = "very/long/path/".repeat(8) + "test.txt";
+    assert_eq!(
+        fs::symlink("very/long/path/test.txt", "/short-link"),
+        Ok(())
+    );
+    assert_eq!(fs::symlink(&long_target, "/long-link"), Ok(()));
+    assert_eq!(fs::read_link("/short-link")?, "very/long/path/test.txt");
+    assert_eq!(fs::read_link("/long-link")?, long_target);
+    assert!(fs::read_to_string("/short-link")?.starts_with("Rust is cool!"));
+    assert_eq!(fs::remove_file("/short-link"), Ok(()));
+    assert_eq!(fs::remove_file("/long-link"), Ok(()));
+
+    // files large enough to use double indirect blocks
+    let data = (0..400_000).map(|i| (i % 253) as u8).collect::<Vec<_>>();
+    fs::write("/attrs-dir/large", &data)?;
+    axfs::page_cache::set_capacity(0); // read from the disk
+    assert_eq!(fs::read("/attrs-dir/large")?, data);
+    assert_eq!(fs::remove_file("/attrs-dir/large"), Ok(()));
+
+    let err = fs::remove_dir("/attrs-dir").err();
+    assert_eq!(err, Some(axio::Error::DirectoryNotEmpty));
+    assert_eq!(fs::remove_file("/attrs-dir/hard"), Ok(()));
+    assert_eq!(fs::remove_dir("/attrs-dir"), Ok(()));
+
+    println!("test_unix_attrs() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_ext4fs() {
+    println!("Testing ext4fs with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+    test_unix_attrs().expect("test_unix_attrs() failed");
+}
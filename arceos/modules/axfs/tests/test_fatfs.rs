@@ -1,4 +1,9 @@
-#![cfg(not(feature = "myfs"))]
+#![cfg(not(any(
+    feature = "myfs",
+    feature = "ext4fs",
+    feature = "initramfs",
+    feature = "overlay-root"
+)))]
 
 mod test_common;
 
@@ -0,0 +1,111 @@
+#![cfg(feature = "initramfs")]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs::initramfs::{self, CpioReader};
+use axio::Result;
+
+const DIR_MODE: u32 = 0o040755;
+const FILE_MODE: u32 = 0o100644;
+const LINK_MODE: u32 = 0o120777;
+
+/// Appends an entry in the "newc" format to `archive`.
+fn push_entry(archive: &mut Vec<u8>, name: &str, ino: u32, mode: u32, nlink: u32, data: &[u8]) {
+    let fields = [ino, mode, 0, 0, nlink, 0, data.len() as u32, 0, 0, 0, 0];
+    archive.extend_from_slice(b"070701");
+    for field in fields {
+        archive.extend_from_slice(format!("{:08x}", field).as_bytes());
+    }
+    archive.extend_from_slice(format!("{:08x}{:08x}", name.len() + 1, 0).as_bytes());
+    archive.extend_from_slice(name.as_bytes());
+    archive.push(0);
+    archive.resize((archive.len() + 3) & !3, 0);
+    archive.extend_from_slice(data);
+    archive.resize((archive.len() + 3) & !3, 0);
+}
+
+fn make_archive() -> Vec<u8> {
+    const TEXT: &[u8] = b"Rust is cool!\n";
+    const ORIGIN: &[u8] = b"\x13\x05\x00\x00";
+    let long = TEXT.repeat(100);
+    let entries: &[(&str, u32, u32, &[u8])] = &[
+        (".", DIR_MODE, 2, b""),
+        ("short.txt", FILE_MODE, 1, TEXT),
+        ("long.txt", FILE_MODE, 1, &long),
+        ("very-long-dir-name", DIR_MODE, 2, b""),
+        (
+            "very-long-dir-name/very-long-file-name.txt",
+            FILE_MODE,
+            1,
+            TEXT,
+        ),
+        ("./very", DIR_MODE, 3, b""),
+        ("very/long", DIR_MODE, 3, b""),
+        ("very/long/path", DIR_MODE, 2, b""),
+        ("very/long/path/test.txt", FILE_MODE, 1, TEXT),
+        ("dev", DIR_MODE, 2, b""), // already mounted
+        ("sbin", DIR_MODE, 2, b""),
+        ("sbin/origin", FILE_MODE, 2, b""), // data is stored with the last link
+        ("sbin/origin.bin", FILE_MODE, 2, ORIGIN),
+        ("sbin/init", LINK_MODE, 1, b"origin"),
+        ("TRAILER!!!", 0, 1, b""),
+    ];
+
+    let mut archive = Vec::new();
+    for (idx, &(name, mode, nlink, data)) in entries.iter().enumerate() {
+        // hard links share the inode number of the first link
+        let ino = if name == "sbin/origin.bin" {
+            idx
+        } else {
+            idx + 1
+        };
+        push_entry(&mut archive, name, ino as u32, mode, nlink, data);
+    }
+    archive.resize(archive.len().next_multiple_of(512), 0);
+    archive
+}
+
+fn test_unpacked() -> Result<()> {
+    println!("test files unpacked from initramfs:");
+
+    assert_eq!(fs::read_to_string("/short.txt")?, "Rust is cool!\n");
+    assert_eq!(fs::metadata("/long.txt")?.len(), 1400);
+    assert!(fs::metadata("/very/long/path")?.is_dir());
+    assert_eq!(fs::read("/sbin/origin")?, b"\x13\x05\x00\x00");
+    assert_eq!(fs::read("/sbin/origin.bin")?, b"\x13\x05\x00\x00");
+    assert_eq!(fs::read_link("/sbin/init")?, "origin");
+    assert_eq!(fs::read("/sbin/init")?, b"\x13\x05\x00\x00");
+
+    // hard links share the same file
+    fs::write("/sbin/origin", "changed")?;
+    assert_eq!(fs::read_to_string("/sbin/origin.bin")?, "changed");
+    fs::remove_file("/sbin/init")?;
+    fs::remove_file("/sbin/origin")?;
+    fs::remove_file("/sbin/origin.bin")?;
+    fs::remove_dir("/sbin")?;
+
+    // malformed archives are rejected
+    let archive = make_archive();
+    assert_eq!(CpioReader::new(&archive).count(), 14);
+    assert!(initramfs::unpack(&archive[..200], "/tmp").is_err());
+    assert!(initramfs::unpack(b"070707garbage", "/tmp").is_err());
+    assert!(initramfs::unpack(&[], "/tmp").is_ok());
+
+    println!("test_unpacked() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_initramfs() {
+    println!("Testing initramfs ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::default()); // no block device at all
+
+    initramfs::unpack(&make_archive(), "/").expect("failed to unpack initramfs");
+    test_unpacked().expect("test_unpacked() failed");
+
+    test_common::test_all();
+}
@@ -0,0 +1,80 @@
+#![cfg(all(
+    feature = "overlay-root",
+    not(any(feature = "myfs", feature = "ext4fs", feature = "initramfs"))
+))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::Result;
+
+const IMG_PATH: &str = "resources/fat16.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_layers() -> Result<()> {
+    println!("test overlay layers:");
+
+    // files of the lower layer are copied up on write
+    assert_eq!(
+        fs::read_to_string("/very/long/path/test.txt")?,
+        "Rust is cool!\n"
+    );
+    fs::write("/very/long/path/test.txt", "Overlay is cool!\n")?;
+    assert_eq!(
+        fs::read_to_string("/very/long/path/test.txt")?,
+        "Overlay is cool!\n"
+    );
+
+    // whiteouts hide removed files and directories
+    fs::remove_file("/very/long/path/test.txt")?;
+    fs::remove_dir("/very/long/path")?;
+    assert!(fs::metadata("/very/long/path/test.txt").is_err());
+    assert_eq!(fs::read_dir("/very/long")?.count(), 0);
+    fs::create_dir("/very/long/path")?;
+    assert_eq!(fs::read_dir("/very/long/path")?.count(), 0);
+
+    // merged entries of both layers
+    fs::write("/upper.txt", "upper")?;
+    let names = fs::read_dir("/")?
+        .map(|entry| entry.map(|e| e.file_name()))
+        .collect::<Result<Vec<_>>>()?;
+    assert!(names.iter().any(|name| name == "upper.txt"));
+    assert!(names.iter().any(|name| name == "short.txt"));
+    assert_eq!(names.iter().filter(|name| *name == "very").count(), 1);
+
+    // renaming a lower directory copies up its subtree
+    fs::rename("/very-long-dir-name", "/renamed-dir")?;
+    assert!(fs::metadata("/very-long-dir-name").is_err());
+    assert_eq!(
+        fs::read_to_string("/renamed-dir/very-long-file-name.txt")?,
+        "Rust is cool!\n"
+    );
+
+    // restore the files used by the common tests
+    fs::rename("/renamed-dir", "/very-long-dir-name")?;
+    fs::write("/very/long/path/test.txt", "Rust is cool!\n")?;
+    fs::remove_file("/upper.txt")?;
+    println!("test_layers() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_overlayfs() {
+    println!("Testing overlayfs over fatfs ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_layers().expect("test_layers() failed");
+    test_common::test_all();
+}
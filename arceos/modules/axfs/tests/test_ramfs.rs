@@ -1,4 +1,4 @@
-#![cfg(feature = "myfs")]
+#![cfg(all(feature = "myfs", not(feature = "initramfs")))]
 
 mod test_common;
 
@@ -35,6 +35,10 @@ fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bo
 
 #[no_mangle]
 fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
+    #[cfg(feature = "uspace")]
+    if from_user {
+        crate::trap::handle_user_mode_switch(false);
+    }
     let scause = scause::read();
     match scause.cause() {
         #[cfg(feature = "uspace")]
@@ -60,4 +64,8 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if from_user {
+        crate::trap::handle_user_mode_switch(true);
+    }
 }
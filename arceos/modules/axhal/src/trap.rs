@@ -22,6 +22,12 @@ pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 #[def_trap_handler]
 pub static SYSCALL: [fn(&TrapFrame, usize) -> isize];
 
+/// A slice of functions called when the CPU enters user space (with `true`),
+/// or traps from user space into the kernel (with `false`).
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static USER_MODE_SWITCH: [fn(bool)];
+
 #[allow(unused_macros)]
 macro_rules! handle_trap {
     ($trap:ident, $($args:tt)*) => {{
@@ -43,3 +49,11 @@ macro_rules! handle_trap {
 pub(crate) fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     SYSCALL[0](tf, syscall_num)
 }
+
+/// Call all the handlers of switching between user space and the kernel.
+#[cfg(feature = "uspace")]
+pub(crate) fn handle_user_mode_switch(to_user: bool) {
+    for func in USER_MODE_SWITCH.iter() {
+        func(to_user);
+    }
+}
@@ -9,9 +9,10 @@ use memory_addr::{
     is_aligned_4k, pa, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
 };
 use memory_set::{MemoryArea, MemorySet};
-use crate::backend::Backend;
+use crate::backend::{Backend, FilePages};
 use crate::paging_err_to_ax_err;
 use crate::mapping_err_to_ax_err;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 /// The virtual memory address space.
@@ -158,6 +159,35 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Add a new file mapping, which maps the pages of `file` starting from the
+    /// page at `index` to the range `[start, start + size)`.
+    ///
+    /// See [`Backend`] for more details about the mapping backends.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<dyn FilePages>,
+        index: u64,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let area = MemoryArea::new(start, size, flags, Backend::new_file(file, start, index));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
     /// Removes mappings within the specified virtual address range.
     ///
     /// Returns an error if the address range is out of the address space or not
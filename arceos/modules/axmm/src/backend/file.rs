@@ -0,0 +1,99 @@
+use alloc::sync::Arc;
+use axhal::mem::virt_to_phys;
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{MemoryAddr, PageIter4K, VirtAddr, PAGE_SIZE_4K};
+
+use super::Backend;
+
+/// Pages of a file that can be mapped into address spaces, such as the pages
+/// in the page cache of a filesystem.
+pub trait FilePages: Send + Sync {
+    /// Pins the page at `index` of the file in memory, and returns the
+    /// (kernel) virtual address of the page frame.
+    ///
+    /// The frame must stay valid until it is unpinned.
+    fn pin_page(&self, index: u64) -> Option<VirtAddr>;
+
+    /// Unpins the page at `index`, which may have been modified if `dirty` is
+    /// `true`.
+    fn unpin_page(&self, index: u64, dirty: bool);
+}
+
+impl Backend {
+    /// Creates a new file mapping backend, which maps the virtual address
+    /// `start` to the page at `index` of `file`.
+    pub fn new_file(file: Arc<dyn FilePages>, start: VirtAddr, index: u64) -> Self {
+        Self::File { file, start, index }
+    }
+
+    fn file_page_index(start: VirtAddr, index: u64, vaddr: VirtAddr) -> u64 {
+        index + ((vaddr - start) / PAGE_SIZE_4K) as u64
+    }
+
+    pub(crate) fn map_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        debug!("map_file: [{:#x}, {:#x}) {:?}", start, start + size, flags);
+        // Map to a empty entry, the pages are mapped on demand.
+        let flags = MappingFlags::empty();
+        pt.map_region(start, |_| 0.into(), size, flags, false, false)
+            .map(|tlb| tlb.ignore())
+            .is_ok()
+    }
+
+    pub(crate) fn unmap_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        file: &Arc<dyn FilePages>,
+        file_start: VirtAddr,
+        index: u64,
+    ) -> bool {
+        debug!("unmap_file: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            // Pages in writable mappings are assumed to be modified.
+            let dirty = match pt.query(addr) {
+                Ok((_, flags, _)) => flags.contains(MappingFlags::WRITE),
+                Err(_) => continue, // not mapped yet
+            };
+            if let Ok((_, page_size, tlb)) = pt.unmap(addr) {
+                if page_size.is_huge() {
+                    return false;
+                }
+                tlb.flush();
+                // The page frame belongs to the file, just unpin it.
+                file.unpin_page(Self::file_page_index(file_start, index, addr), dirty);
+            }
+        }
+        true
+    }
+
+    pub(crate) fn handle_page_fault_file(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        file: &Arc<dyn FilePages>,
+        file_start: VirtAddr,
+        index: u64,
+    ) -> bool {
+        let index = Self::file_page_index(file_start, index, vaddr.align_down_4k());
+        if let Some(page) = file.pin_page(index) {
+            // Map the page in the file to the fault address.
+            if pt
+                .remap(vaddr, virt_to_phys(page), orig_flags)
+                .map(|(_, tlb)| tlb.flush())
+                .is_ok()
+            {
+                return true;
+            }
+            file.unpin_page(index, false);
+        }
+        false
+    }
+}
@@ -1,21 +1,27 @@
 //! Memory mapping backends.
 #![allow(dead_code)]
 
+use ::alloc::sync::Arc;
 use axhal::paging::{MappingFlags, PageTable};
 use memory_addr::VirtAddr;
 use memory_set::MappingBackend;
 
 mod alloc;
+mod file;
 mod linear;
 
+pub use self::file::FilePages;
+
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, three backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **File**: used for file-backed mappings. The target physical frames are
+///   the pages of a file, e.g. in the page cache, and are mapped on demand.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -37,6 +43,19 @@ pub enum Backend {
         /// Whether to populate the physical frames when creating the mapping.
         populate: bool,
     },
+    /// File mapping backend.
+    ///
+    /// The pages of `file` are pinned and mapped on demand (by handling page
+    /// faults), and unpinned when unmapped. The mapping is shared, i.e.
+    /// modifications are seen by other mappings and reads of the file.
+    File {
+        /// The mapped file.
+        file: Arc<dyn FilePages>,
+        /// The virtual address mapped to the page at `index`.
+        start: VirtAddr,
+        /// The page index in the file mapped at `start`.
+        index: u64,
+    },
 }
 
 impl MappingBackend for Backend {
@@ -47,6 +66,7 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.map_linear(start, size, flags, pt, pa_va_offset),
             Self::Alloc { populate } => self.map_alloc(start, size, flags, pt, populate),
+            Self::File { .. } => self.map_file(start, size, flags, pt),
         }
     }
 
@@ -54,6 +74,11 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate } => self.unmap_alloc(start, size, pt, populate),
+            Self::File {
+                ref file,
+                start: file_start,
+                index,
+            } => self.unmap_file(start, size, pt, file, file_start, index),
         }
     }
 
@@ -83,6 +108,11 @@ impl Backend {
             Self::Alloc { populate } => {
                 self.handle_page_fault_alloc(vaddr, orig_flags, page_table, populate)
             }
+            Self::File {
+                ref file,
+                start,
+                index,
+            } => self.handle_page_fault_file(vaddr, orig_flags, page_table, file, start, index),
         }
     }
 }
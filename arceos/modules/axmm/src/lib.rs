@@ -10,6 +10,7 @@ mod aspace;
 mod backend;
 
 pub use self::aspace::AddrSpace;
+pub use self::backend::FilePages;
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
@@ -166,6 +166,11 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
+
+        #[cfg(all(feature = "fs", feature = "display"))]
+        if let Err(e) = axfs::devfs::register_framebuffer() {
+            warn!("failed to publish /dev/fb0: {:?}", e);
+        }
     }
 
     #[cfg(feature = "smp")]
@@ -235,7 +240,10 @@ fn init_allocator() {
     use axhal::mem::{memory_regions, phys_to_virt, MemRegionFlags};
 
     info!("Initialize global memory allocator...");
-    info!("  use {} allocator.", alt_axalloc::global_allocator().name());
+    info!(
+        "  use {} allocator.",
+        alt_axalloc::global_allocator().name()
+    );
 
     let mut max_region_size = 0;
     let mut max_region_paddr = 0.into();
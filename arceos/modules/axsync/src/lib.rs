@@ -10,6 +10,9 @@
 //! - `multitask`: For use in the multi-threaded environments. If the feature is
 //!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`]. This
 //!   feature is enabled by default.
+//! - `sched_rt`: Use the real-time scheduler of [`axtask`], so that [`Mutex`]
+//!   lends the priorities of real-time waiters to its owner (priority
+//!   inheritance). It also enables the `multitask` feature.
 
 #![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
@@ -13,6 +13,11 @@ use axtask::{current, WaitQueue};
 /// When the mutex is locked, the current task will block and be put into the
 /// wait queue. When the mutex is unlocked, all tasks waiting on the queue
 /// will be woken up.
+///
+/// To avoid priority inversion, the owner of the mutex runs with the priority
+/// of the real-time tasks waiting for it, if higher (priority inheritance),
+/// and the waiters are woken up in the order of their priorities. It requires
+/// the `sched_rt` feature of [`axtask`].
 pub struct Mutex<T: ?Sized> {
     wq: WaitQueue,
     owner_id: AtomicU64,
@@ -87,8 +92,14 @@ impl<T: ?Sized> Mutex<T> {
                         "{} tried to acquire mutex it already owns.",
                         current().id_name()
                     );
-                    // Wait until the lock looks unlocked before retrying
-                    self.wq.wait_until(|| !self.is_locked());
+                    // Lend the priority to the owner if it still holds the
+                    // lock, then wait until the owner changes before retrying,
+                    // when the priority is lent to the new owner if any.
+                    axtask::inherit_priority(owner_id, self.lock_id(), || {
+                        self.owner_id.load(Ordering::Acquire) == owner_id
+                    });
+                    self.wq
+                        .wait_until(|| self.owner_id.load(Ordering::Relaxed) != owner_id);
                 }
             }
         }
@@ -133,7 +144,15 @@ impl<T: ?Sized> Mutex<T> {
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
+        // Priorities lent from now on are refused, as the owner is cleared.
         self.wq.notify_one(true);
+        axtask::restore_priority(self.lock_id());
+    }
+
+    /// Identifies the mutex when lending priorities to its owner.
+    #[inline(always)]
+    fn lock_id(&self) -> usize {
+        &self.wq as *const WaitQueue as usize
     }
 
     /// Returns a mutable reference to the underlying data.
@@ -201,9 +220,11 @@ impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
 mod tests {
     use crate::Mutex;
     use axtask as thread;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Once;
 
     static INIT: Once = Once::new();
+    static SERIAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
     fn may_interrupt() {
         // simulate interrupts
@@ -214,6 +235,7 @@ mod tests {
 
     #[test]
     fn lots_and_lots() {
+        let _lock = SERIAL.lock();
         INIT.call_once(thread::init_scheduler);
 
         const NUM_TASKS: u32 = 10;
@@ -249,4 +271,51 @@ mod tests {
         assert_eq!(*M.lock(), NUM_ITERS * NUM_TASKS * 3);
         println!("Mutex test OK");
     }
+
+    #[test]
+    #[cfg(feature = "sched_rt")]
+    fn priority_inheritance() {
+        use axtask::SchedPolicy;
+
+        let _lock = SERIAL.lock();
+        INIT.call_once(thread::init_scheduler);
+
+        static M: Mutex<Vec<(u8, u8)>> = Mutex::new(Vec::new());
+        static N: Mutex<()> = Mutex::new(());
+        static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+        let curr = thread::current().as_task_ref().clone();
+
+        // Each task records its static and effective priorities while holding
+        // the lock.
+        fn lock_with_priority(prio: u8) {
+            let curr = thread::current().as_task_ref().clone();
+            assert!(thread::set_sched_params(&curr, SchedPolicy::Fifo, prio));
+            WAITING.fetch_add(1, Ordering::Relaxed);
+            let mut order = M.lock();
+            order.push((curr.rt_priority(), curr.effective_rt_priority()));
+        }
+
+        let n = N.lock();
+        let m = M.lock();
+        let tasks = [10, 30, 20].map(|prio| thread::spawn(move || lock_with_priority(prio)));
+        // Real-time tasks run before this normal task, until they block.
+        while WAITING.load(Ordering::Relaxed) < 3 {
+            thread::yield_now();
+        }
+        assert_eq!(curr.effective_rt_priority(), 30);
+
+        // The priority lent for `M` is kept after releasing another lock.
+        drop(n);
+        assert_eq!(curr.effective_rt_priority(), 30);
+
+        // The boost is dropped on unlock, and the lock goes to the waiters in
+        // the order of priorities, however they started waiting.
+        drop(m);
+        assert_eq!(curr.effective_rt_priority(), 0);
+        for task in tasks {
+            assert_eq!(task.join(), Some(0));
+        }
+        assert_eq!(*M.lock(), [(30, 30), (20, 20), (10, 10)]);
+    }
 }
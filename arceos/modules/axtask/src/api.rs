@@ -2,10 +2,19 @@
 
 use alloc::{string::String, sync::Arc};
 
-pub(crate) use crate::run_queue::{AxRunQueue, RUN_QUEUE};
+pub(crate) use crate::run_queue::current_run_queue;
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::cpumask::CpuMask;
+
+#[cfg(feature = "sched_edf")]
+pub use crate::sched_edf::DeadlineParams;
+#[doc(cfg(feature = "multitask"))]
+pub use crate::stats::TaskStats;
+#[doc(cfg(feature = "multitask"))]
+pub use crate::task::{
+    CurrentTask, SchedPolicy, TaskId, TaskInner, TaskState, RT_PRIORITY_MAX, RT_PRIORITY_MIN,
+};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task_ext::{TaskExtMut, TaskExtRef};
 #[doc(cfg(feature = "multitask"))]
@@ -15,7 +24,13 @@ pub use crate::wait_queue::WaitQueue;
 pub type AxTaskRef = Arc<AxTask>;
 
 cfg_if::cfg_if! {
-    if #[cfg(feature = "sched_rr")] {
+    if #[cfg(feature = "sched_edf")] {
+        pub(crate) type AxTask = scheduler::CFSTask<TaskInner>;
+        pub(crate) type Scheduler = crate::sched_edf::EdfScheduler;
+    } else if #[cfg(feature = "sched_rt")] {
+        pub(crate) type AxTask = scheduler::CFSTask<TaskInner>;
+        pub(crate) type Scheduler = crate::sched_rt::RtScheduler;
+    } else if #[cfg(feature = "sched_rr")] {
         const MAX_TIME_SLICE: usize = 5;
         pub(crate) type AxTask = scheduler::RRTask<TaskInner, MAX_TIME_SLICE>;
         pub(crate) type Scheduler = scheduler::RRScheduler<TaskInner, MAX_TIME_SLICE>;
@@ -77,6 +92,8 @@ pub fn init_scheduler() {
 /// Initializes the task scheduler for secondary CPUs.
 pub fn init_scheduler_secondary() {
     crate::run_queue::init_secondary();
+    #[cfg(feature = "irq")]
+    crate::timers::init();
 }
 
 /// Handles periodic timer ticks for the task manager.
@@ -86,13 +103,15 @@ pub fn init_scheduler_secondary() {
 #[doc(cfg(feature = "irq"))]
 pub fn on_timer_tick() {
     crate::timers::check_events();
-    RUN_QUEUE.lock().scheduler_timer_tick();
+    current_run_queue().scheduler_timer_tick();
 }
 
-/// Adds the given task to the run queue, returns the task reference.
+/// Adds the given task to the least loaded run queue among the CPUs that it is
+/// allowed to run on, returns the task reference.
 pub fn spawn_task(task: TaskInner) -> AxTaskRef {
     let task_ref = task.into_arc();
-    RUN_QUEUE.lock().add_task(task_ref.clone());
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    crate::run_queue::least_loaded_run_queue(&task_ref.cpumask()).add_task(task_ref.clone());
     task_ref
 }
 
@@ -119,6 +138,17 @@ where
     spawn_raw(f, "".into(), axconfig::TASK_STACK_SIZE)
 }
 
+/// Calls `f` on each alive task, in the order of task IDs.
+///
+/// The tasks are collected before calling `f`, so tasks spawned or dropped
+/// during the iteration may not be visited.
+pub fn for_each_task<F>(f: F)
+where
+    F: FnMut(&AxTaskRef),
+{
+    crate::task::all_tasks().iter().for_each(f);
+}
+
 /// Set the priority for current task.
 ///
 /// The range of the priority is dependent on the underlying scheduler. For
@@ -129,13 +159,152 @@ where
 ///
 /// [CFS]: https://en.wikipedia.org/wiki/Completely_Fair_Scheduler
 pub fn set_priority(prio: isize) -> bool {
-    RUN_QUEUE.lock().set_current_priority(prio)
+    current_run_queue().set_current_priority(prio)
+}
+
+/// Sets the scheduling policy and the static priority of `task`.
+///
+/// The priority must be from [`RT_PRIORITY_MIN`] to [`RT_PRIORITY_MAX`] for
+/// the real-time policies, which need the `sched_rt` feature, or 0 for
+/// [`SchedPolicy::Normal`].
+///
+/// Returns `true` if they are set successfully.
+pub fn set_sched_params(task: &AxTaskRef, policy: SchedPolicy, rt_priority: u8) -> bool {
+    let valid = match policy {
+        SchedPolicy::Normal => rt_priority == 0,
+        SchedPolicy::Fifo | SchedPolicy::RoundRobin => {
+            cfg!(feature = "sched_rt") && (RT_PRIORITY_MIN..=RT_PRIORITY_MAX).contains(&rt_priority)
+        }
+    };
+    if !valid {
+        return false;
+    }
+    crate::run_queue::update_sched_params(task, || task.set_sched_params(policy, rt_priority));
+    // Go to the back of the queue of the new priority, and let the tasks with
+    // higher priorities run.
+    #[cfg(feature = "sched_rt")]
+    if current().ptr_eq(task) {
+        yield_now();
+    }
+    true
+}
+
+/// Makes `task` a deadline task with `params`, or a normal task if `params`
+/// is `None`.
+///
+/// Returns `false` if the parameters are invalid, i.e. not satisfying
+/// `0 < runtime <= deadline <= period`, or no CPU that the task is allowed to
+/// run on has enough CPU time left to reserve. The task is moved to the CPU
+/// where its CPU time is reserved, and stays there while it is a deadline
+/// task.
+#[cfg(feature = "sched_edf")]
+pub fn set_deadline_params(task: &AxTaskRef, params: Option<DeadlineParams>) -> bool {
+    if !crate::sched_edf::set_params(task, params) {
+        return false;
+    }
+    // the current task is moved when it is switched out
+    let reserved_cpu = task.edf().reserved_cpu();
+    if current().ptr_eq(task) && reserved_cpu.is_some_and(|cpu| cpu != task.cpu_id()) {
+        yield_now();
+    }
+    true
+}
+
+/// Gives up the rest of the runtime of the current deadline task in this
+/// period, and sleeps until the next one.
+#[cfg(feature = "sched_edf")]
+pub fn wait_for_next_period() {
+    crate::sched_edf::yield_runtime(&current());
+    yield_now();
+}
+
+/// Lends the real-time priority of the current task to the task `owner_id`,
+/// which holds the lock `lock` that the current task is waiting for
+/// (priority inheritance).
+///
+/// `lock` can be any value that identifies the lock, such as its address.
+/// The priorities lent for each lock are kept separately, and the owner runs
+/// with the highest one of them until it releases the lock and calls
+/// [`restore_priority`].
+///
+/// `holds_lock` tells whether the owner still holds the lock, and the priority
+/// is lent only if it does. It is checked in a way that a priority lent right
+/// before the owner releases the lock is always dropped, as long as the owner
+/// is cleared before calling [`restore_priority`]. The waiter should lend the
+/// priority again every time it is woken up and finds a new owner.
+///
+/// It does nothing without the `sched_rt` feature.
+#[cfg_attr(not(feature = "sched_rt"), allow(unused_variables))]
+pub fn inherit_priority<F: FnOnce() -> bool>(owner_id: u64, lock: usize, holds_lock: F) {
+    #[cfg(feature = "sched_rt")]
+    {
+        let prio = current().effective_rt_priority();
+        if prio == 0 {
+            return;
+        }
+        if let Some(owner) = crate::task::find_task(owner_id) {
+            crate::run_queue::update_sched_params(&owner, || {
+                if owner.lend_priority(lock, prio, holds_lock) {
+                    debug!("task {} inherits priority {}", owner.id_name(), prio);
+                }
+            });
+        }
+    }
+}
+
+/// Drops the priority that the current task has inherited by
+/// [`inherit_priority`] for the lock `lock`, when it releases the lock.
+///
+/// The priorities inherited for other locks that it still holds are kept. If
+/// its priority is lowered, it lets the tasks with higher priorities run.
+#[cfg_attr(not(feature = "sched_rt"), allow(unused_variables))]
+pub fn restore_priority(lock: usize) {
+    #[cfg(feature = "sched_rt")]
+    {
+        let curr = current();
+        let old_prio = curr.effective_rt_priority();
+        curr.withdraw_priority(lock);
+        if curr.effective_rt_priority() < old_prio {
+            // Preempted as soon as the preemption is enabled.
+            curr.set_preempt_pending(true);
+            drop(kernel_guard::NoPreempt::new());
+        }
+    }
+}
+
+/// Sets the CPU affinity of the current task, i.e. the CPUs that it is allowed
+/// to run on. If the current CPU is not in `cpumask`, the task is moved to
+/// another one before returning.
+///
+/// Returns `false` if `cpumask` is empty.
+pub fn set_affinity(cpumask: CpuMask) -> bool {
+    if !current().set_cpumask(cpumask) {
+        return false;
+    }
+    if !cpumask.get(axhal::cpu::this_cpu_id()) {
+        yield_now();
+    }
+    true
+}
+
+/// Sets the CPU affinity of `task`. If the task is not allowed on its CPU
+/// any more, a ready task is moved to another CPU right away, and a running
+/// one is preempted to be moved (see [`set_affinity`] for the current task).
+///
+/// Returns `false` if `cpumask` is empty, or it excludes the CPU reserved by a
+/// deadline task.
+pub fn set_task_affinity(task: &AxTaskRef, cpumask: CpuMask) -> bool {
+    if !task.set_cpumask(cpumask) {
+        return false;
+    }
+    crate::run_queue::enforce_affinity(task);
+    true
 }
 
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
-    RUN_QUEUE.lock().yield_current();
+    current_run_queue().yield_current();
 }
 
 /// Current task is going to sleep for the given duration.
@@ -150,14 +319,14 @@ pub fn sleep(dur: core::time::Duration) {
 /// If the feature `irq` is not enabled, it uses busy-wait instead.
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
     #[cfg(feature = "irq")]
-    RUN_QUEUE.lock().sleep_until(deadline);
+    current_run_queue().sleep_until(deadline);
     #[cfg(not(feature = "irq"))]
     axhal::time::busy_wait_until(deadline);
 }
 
 /// Exits the current task.
 pub fn exit(exit_code: i32) -> ! {
-    RUN_QUEUE.lock().exit_current(exit_code)
+    current_run_queue().exit_current(exit_code)
 }
 
 /// The idle task routine.
@@ -0,0 +1,95 @@
+//! Sets of CPUs, such as the CPUs that a task is allowed to run on.
+
+use core::fmt;
+
+const BITS_PER_WORD: usize = usize::BITS as usize;
+const WORDS: usize = axconfig::SMP.div_ceil(BITS_PER_WORD);
+
+/// A set of CPUs, e.g. the CPUs that a task is allowed to run on (its CPU
+/// affinity).
+///
+/// Only the CPUs that exist, i.e. whose IDs are less than [`axconfig::SMP`],
+/// can be in the set. Others are ignored.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CpuMask {
+    bits: [usize; WORDS],
+}
+
+impl CpuMask {
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self { bits: [0; WORDS] }
+    }
+
+    /// Creates a set of all CPUs.
+    pub const fn full() -> Self {
+        let mut mask = Self::new();
+        let mut cpu = 0;
+        while cpu < axconfig::SMP {
+            mask.bits[cpu / BITS_PER_WORD] |= 1 << (cpu % BITS_PER_WORD);
+            cpu += 1;
+        }
+        mask
+    }
+
+    /// Creates a set of the single CPU `cpu`.
+    pub const fn one_shot(cpu: usize) -> Self {
+        let mut mask = Self::new();
+        if cpu < axconfig::SMP {
+            mask.bits[cpu / BITS_PER_WORD] = 1 << (cpu % BITS_PER_WORD);
+        }
+        mask
+    }
+
+    /// Whether the CPU `cpu` is in the set.
+    pub const fn get(&self, cpu: usize) -> bool {
+        cpu < axconfig::SMP && self.bits[cpu / BITS_PER_WORD] & (1 << (cpu % BITS_PER_WORD)) != 0
+    }
+
+    /// Adds the CPU `cpu` to the set if `value` is `true`, or removes it
+    /// otherwise.
+    pub fn set(&mut self, cpu: usize, value: bool) {
+        if cpu < axconfig::SMP {
+            let bit = 1 << (cpu % BITS_PER_WORD);
+            if value {
+                self.bits[cpu / BITS_PER_WORD] |= bit;
+            } else {
+                self.bits[cpu / BITS_PER_WORD] &= !bit;
+            }
+        }
+    }
+
+    /// Whether the set has no CPU.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// Returns the number of CPUs in the set.
+    pub fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns an iterator over the CPUs in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..axconfig::SMP).filter(|&cpu| self.get(cpu))
+    }
+}
+
+impl FromIterator<usize> for CpuMask {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut mask = Self::new();
+        for cpu in iter {
+            mask.set(cpu, true);
+        }
+        mask
+    }
+}
+
+impl fmt::Debug for CpuMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
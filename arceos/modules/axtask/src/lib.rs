@@ -13,6 +13,10 @@
 //!    APIs can be used, such as [`sleep`], [`sleep_until`], and
 //!    [`WaitQueue::wait_timeout`].
 //! - `preempt`: Enable preemptive scheduling.
+//! - `smp`: Enable multi-core scheduling. Each CPU has its own run queue, and
+//!   tasks are balanced between them, within their [CPU affinity][`CpuMask`].
+//! - `uspace`: Count the CPU time of tasks in user space and in the kernel
+//!   separately in their [statistics][`TaskStats`].
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -20,6 +24,14 @@
 //!   the `multitask` and `preempt` features if it is enabled.
 //! - `sched_cfs`: Use the [Completely Fair Scheduler][3]. It also enables the
 //!   the `multitask` and `preempt` features if it is enabled.
+//! - `sched_rt`: Use the CFS for normal tasks, along with real-time tasks of
+//!   the [`SchedPolicy::Fifo`] and [`SchedPolicy::RoundRobin`] policies, which
+//!   always run before normal tasks. It also enables the `multitask` and
+//!   `preempt` features if it is enabled.
+//! - `sched_edf`: Use the CFS for normal tasks, along with deadline tasks with
+//!   [`DeadlineParams`], which are scheduled by the earliest deadline first
+//!   and always run before normal tasks. It also enables the `multitask`,
+//!   `preempt` and `irq` features if it is enabled.
 //!
 //! [1]: scheduler::FifoScheduler
 //! [2]: scheduler::RRScheduler
@@ -36,13 +48,18 @@
 #[cfg(test)]
 mod tests;
 
+#[cfg(all(feature = "sched_rt", feature = "sched_edf"))]
+compile_error!("the `sched_rt` and `sched_edf` features cannot be enabled together");
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "multitask")] {
         #[macro_use]
         extern crate log;
         extern crate alloc;
 
+        mod cpumask;
         mod run_queue;
+        mod stats;
         mod task;
         mod task_ext;
         mod api;
@@ -50,6 +67,10 @@ cfg_if::cfg_if! {
 
         #[cfg(feature = "irq")]
         mod timers;
+        #[cfg(feature = "sched_rt")]
+        mod sched_rt;
+        #[cfg(feature = "sched_edf")]
+        mod sched_edf;
 
         #[doc(cfg(feature = "multitask"))]
         pub use self::api::*;
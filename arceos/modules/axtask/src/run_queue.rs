@@ -1,72 +1,336 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use kernel_guard::NoPreemptIrqSave;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use scheduler::BaseScheduler;
 
+#[cfg(feature = "smp")]
+use alloc::{collections::BTreeMap, sync::Weak};
+
 use crate::task::{CurrentTask, TaskState};
-use crate::{AxTaskRef, Scheduler, TaskInner, WaitQueue};
+use crate::{AxTaskRef, CpuMask, Scheduler, TaskInner, WaitQueue};
 
-// TODO: per-CPU
-pub(crate) static RUN_QUEUE: LazyInit<SpinNoIrq<AxRunQueue>> = LazyInit::new();
+/// Interval of the periodic load balancing, in timer ticks.
+#[cfg(all(feature = "smp", feature = "irq"))]
+const BALANCE_INTERVAL_TICKS: usize = 10;
 
-// TODO: per-CPU
-static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
+#[allow(clippy::declare_interior_mutable_const)]
+const RUN_QUEUE_UNINIT: LazyInit<AxRunQueue> = LazyInit::new();
 
-static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
+/// The run queues of all CPUs, indexed by the CPU IDs.
+static RUN_QUEUES: [LazyInit<AxRunQueue>; axconfig::SMP] = [RUN_QUEUE_UNINIT; axconfig::SMP];
 
 #[percpu::def_percpu]
 static IDLE_TASK: LazyInit<AxTaskRef> = LazyInit::new();
 
-pub(crate) struct AxRunQueue {
+/// The task that the current CPU has just switched out of, whose `on_cpu`
+/// flag is cleared by the next task.
+#[cfg(feature = "smp")]
+#[percpu::def_percpu]
+static PREV_TASK: Weak<crate::AxTask> = Weak::new();
+
+/// The task that the current CPU is switching out of, to be moved to another
+/// CPU once the switch is finished, as its affinity no longer allows this one.
+#[cfg(feature = "smp")]
+#[percpu::def_percpu]
+static MIGRATING_TASK: Option<AxTaskRef> = None;
+
+/// The ready tasks of a run queue, in its scheduler.
+///
+/// On SMP, the tasks are also indexed by their IDs, so that other CPUs can
+/// steal them without disturbing the order of the scheduler, and only remove
+/// tasks that are in the scheduler.
+struct ReadyQueue {
     scheduler: Scheduler,
+    #[cfg(feature = "smp")]
+    tasks: BTreeMap<u64, AxTaskRef>,
+}
+
+impl ReadyQueue {
+    fn new() -> Self {
+        Self {
+            scheduler: Scheduler::new(),
+            #[cfg(feature = "smp")]
+            tasks: BTreeMap::new(),
+        }
+    }
+
+    fn add_task(&mut self, task: AxTaskRef) {
+        #[cfg(feature = "smp")]
+        self.tasks.insert(task.id().as_u64(), task.clone());
+        self.scheduler.add_task(task);
+    }
+
+    fn put_prev_task(&mut self, prev: AxTaskRef, preempt: bool) {
+        #[cfg(feature = "smp")]
+        self.tasks.insert(prev.id().as_u64(), prev.clone());
+        self.scheduler.put_prev_task(prev, preempt);
+    }
+
+    fn pick_next_task(&mut self) -> Option<AxTaskRef> {
+        let task = self.scheduler.pick_next_task()?;
+        #[cfg(feature = "smp")]
+        self.tasks.remove(&task.id().as_u64());
+        Some(task)
+    }
+
+    /// Removes `task` if it is in the queue.
+    fn remove_task(&mut self, task: &AxTaskRef) -> Option<AxTaskRef> {
+        #[cfg(feature = "smp")]
+        self.tasks.remove(&task.id().as_u64())?;
+        self.scheduler.remove_task(task)
+    }
+
+    /// Removes a task that is allowed to run on the CPU `cpu_id`. The task that
+    /// this CPU is switching out of is left.
+    #[cfg(feature = "smp")]
+    fn take_task_for(&mut self, cpu_id: usize) -> Option<AxTaskRef> {
+        let id = self
+            .tasks
+            .values()
+            .find(|task| !task.on_cpu() && can_move_to(task, cpu_id))?
+            .id()
+            .as_u64();
+        let task = self.tasks.remove(&id)?;
+        self.scheduler.remove_task(&task)
+    }
+}
+
+/// The run queue of a CPU.
+///
+/// Each CPU picks the tasks to run from its own run queue. A task is put back
+/// into the run queue of the CPU that it last ran on when it is woken up, as
+/// long as its CPU affinity allows it. Idle CPUs steal ready tasks from the
+/// busiest run queue, and run queues are balanced periodically.
+pub(crate) struct AxRunQueue {
+    cpu_id: usize,
+    ready: SpinNoIrq<ReadyQueue>,
+    /// The number of ready tasks in the scheduler.
+    nr_ready: AtomicUsize,
+    /// Whether the CPU is running a task other than the idle task.
+    busy: AtomicBool,
+    exited_tasks: SpinNoIrq<VecDeque<AxTaskRef>>,
+    wait_for_exit: WaitQueue,
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    ticks: AtomicUsize,
+}
+
+/// The run queue of the current CPU, with IRQs and preemption disabled.
+///
+/// The methods that switch out of the current task take `self`, as the task
+/// may run on another CPU when it is switched back.
+pub(crate) struct CurrentRunQueueRef {
+    inner: &'static AxRunQueue,
+    _guard: NoPreemptIrqSave,
 }
 
 impl AxRunQueue {
-    pub fn new() -> SpinNoIrq<Self> {
-        let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE).into_arc();
-        let mut scheduler = Scheduler::new();
-        scheduler.add_task(gc_task);
-        SpinNoIrq::new(Self { scheduler })
+    fn new(cpu_id: usize) -> Self {
+        Self {
+            cpu_id,
+            ready: SpinNoIrq::new(ReadyQueue::new()),
+            nr_ready: AtomicUsize::new(0),
+            busy: AtomicBool::new(false),
+            exited_tasks: SpinNoIrq::new(VecDeque::new()),
+            wait_for_exit: WaitQueue::new(),
+            #[cfg(all(feature = "smp", feature = "irq"))]
+            ticks: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of ready tasks, plus the running one unless the CPU
+    /// is idle.
+    fn load(&self) -> usize {
+        self.nr_ready.load(Ordering::Relaxed) + self.busy.load(Ordering::Relaxed) as usize
     }
 
-    pub fn add_task(&mut self, task: AxTaskRef) {
-        debug!("task spawn: {}", task.id_name());
+    pub fn add_task(&self, task: AxTaskRef) {
+        debug!("task add: {} to CPU {}", task.id_name(), self.cpu_id);
         assert!(task.is_ready());
-        self.scheduler.add_task(task);
+        task.set_cpu_id(self.cpu_id);
+        #[cfg(any(feature = "sched_rt", feature = "sched_edf"))]
+        self.check_preempt_current(&task);
+        self.ready.lock().add_task(task);
+        self.nr_ready.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Makes the current task of this CPU to be preempted if `task` should
+    /// run before it. Other CPUs notice it at the next timer tick.
+    #[cfg(any(feature = "sched_rt", feature = "sched_edf"))]
+    fn check_preempt_current(&self, task: &AxTaskRef) {
+        if self.cpu_id != axhal::cpu::this_cpu_id() {
+            return;
+        }
+        if let Some(curr) = crate::current_may_uninit() {
+            if Scheduler::should_preempt(task, curr.as_task_ref()) {
+                curr.set_preempt_pending(true);
+            }
+        }
+    }
+
+    /// Puts the current task of this CPU back, which is about to be switched
+    /// out of.
+    fn put_prev_task(&self, prev: AxTaskRef, preempt: bool) {
+        #[cfg(feature = "smp")]
+        if !core::ptr::eq(select_run_queue(&prev), self) {
+            // It must not run on other CPUs until the switch is finished.
+            unsafe { *MIGRATING_TASK.current_ref_mut_raw() = Some(prev) };
+            return;
+        }
+        self.ready.lock().put_prev_task(prev, preempt);
+        self.nr_ready.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Picks the next task to run on this CPU. Tasks that are no longer
+    /// allowed on this CPU are moved to other run queues.
+    fn pick_next_task(&self) -> Option<AxTaskRef> {
+        loop {
+            let task = self.ready.lock().pick_next_task()?;
+            self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+            let rq = select_run_queue(&task);
+            if core::ptr::eq(rq, self) {
+                return Some(task);
+            }
+            #[cfg(feature = "smp")]
+            if crate::current().ptr_eq(&task) {
+                // It must not run on other CPUs until the switch is finished.
+                unsafe { *MIGRATING_TASK.current_ref_mut_raw() = Some(task) };
+                continue;
+            }
+            rq.add_task(task);
+        }
+    }
+
+    /// Takes a ready task that is allowed to run on the CPU `cpu_id` out of
+    /// this run queue.
+    #[cfg(feature = "smp")]
+    fn take_task_for(&self, cpu_id: usize) -> Option<AxTaskRef> {
+        let task = self.ready.lock().take_task_for(cpu_id)?;
+        self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+        Some(task)
+    }
+
+    /// Steals a ready task from the busiest run queue of other CPUs, if its
+    /// load exceeds that of this one by at least `min_imbalance`.
+    #[cfg(feature = "smp")]
+    fn steal_task(&self, min_imbalance: usize) -> Option<AxTaskRef> {
+        let busiest = RUN_QUEUES
+            .iter()
+            .filter(|rq| rq.is_inited() && rq.cpu_id != self.cpu_id)
+            .map(|rq| &**rq)
+            .max_by_key(|rq| rq.nr_ready.load(Ordering::Relaxed))?;
+        if busiest.nr_ready.load(Ordering::Relaxed) == 0
+            || busiest.load() < self.load() + min_imbalance
+        {
+            return None;
+        }
+        let task = busiest.take_task_for(self.cpu_id)?;
+        debug!(
+            "task steal: {} from CPU {} to CPU {}",
+            task.id_name(),
+            busiest.cpu_id,
+            self.cpu_id
+        );
+        Some(task)
+    }
+
+    /// Switches from `prev_task` to `next_task`, `involuntary` if `prev_task`
+    /// is preempted.
+    fn switch_to(&self, prev_task: CurrentTask, next_task: AxTaskRef, involuntary: bool) {
+        trace!(
+            "context switch: {} -> {}",
+            prev_task.id_name(),
+            next_task.id_name()
+        );
+        #[cfg(feature = "preempt")]
+        next_task.set_preempt_pending(false);
+        next_task.set_state(TaskState::Running);
+        if prev_task.ptr_eq(&next_task) {
+            return;
+        }
+
+        // Tasks are put into the run queues of other CPUs only after being
+        // switched out of, but wait in case.
+        #[cfg(feature = "smp")]
+        {
+            while next_task.on_cpu() {
+                core::hint::spin_loop();
+            }
+            next_task.set_on_cpu(true);
+        }
+        next_task.set_cpu_id(self.cpu_id);
+        self.busy.store(!next_task.is_idle(), Ordering::Relaxed);
+
+        let now = axhal::time::monotonic_time();
+        prev_task.stats_state().switch_out(now, involuntary);
+        next_task.stats_state().switch_in(now, self.cpu_id);
+
+        unsafe {
+            let prev_ctx_ptr = prev_task.ctx_mut_ptr();
+            let next_ctx_ptr = next_task.ctx_mut_ptr();
+
+            // The strong reference count of `prev_task` will be decremented by 1,
+            // but won't be dropped until `gc_entry()` is called.
+            assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
+            assert!(Arc::strong_count(&next_task) >= 1);
+
+            #[cfg(feature = "smp")]
+            {
+                *PREV_TASK.current_ref_mut_raw() = Arc::downgrade(prev_task.as_task_ref());
+            }
+            CurrentTask::set_current(prev_task, next_task);
+            (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
+
+            // Now we may be on another CPU.
+            #[cfg(feature = "smp")]
+            finish_task_switch();
+        }
     }
+}
 
+impl CurrentRunQueueRef {
     #[cfg(feature = "irq")]
-    pub fn scheduler_timer_tick(&mut self) {
+    pub fn scheduler_timer_tick(&self) {
         let curr = crate::current();
-        if !curr.is_idle() && self.scheduler.task_tick(curr.as_task_ref()) {
+        let ready = &self.inner.ready;
+        if !curr.is_idle() && ready.lock().scheduler.task_tick(curr.as_task_ref()) {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
+
+        #[cfg(feature = "smp")]
+        if self.inner.ticks.fetch_add(1, Ordering::Relaxed) % BALANCE_INTERVAL_TICKS == 0 {
+            if let Some(task) = self.inner.steal_task(2) {
+                self.inner.add_task(task);
+            }
+        }
     }
 
-    pub fn yield_current(&mut self) {
+    pub fn yield_current(mut self) {
         let curr = crate::current();
         trace!("task yield: {}", curr.id_name());
         assert!(curr.is_running());
         self.resched(false);
     }
 
-    pub fn set_current_priority(&mut self, prio: isize) -> bool {
-        self.scheduler
+    pub fn set_current_priority(&self, prio: isize) -> bool {
+        self.inner
+            .ready
+            .lock()
+            .scheduler
             .set_priority(crate::current().as_task_ref(), prio)
     }
 
     #[cfg(feature = "preempt")]
-    pub fn preempt_resched(&mut self) {
+    pub fn preempt_resched(mut self) {
         let curr = crate::current();
         assert!(curr.is_running());
 
-        // When we get the mutable reference of the run queue, we must
-        // have held the `SpinNoIrq` lock with both IRQs and preemption
-        // disabled. So we need to set `current_disable_count` to 1 in
-        // `can_preempt()` to obtain the preemption permission before
-        //  locking the run queue.
+        // When we get the reference of the run queue, we must have disabled
+        // both IRQs and preemption. So we need to set `current_disable_count`
+        // to 1 in `can_preempt()` to obtain the preemption permission.
         let can_preempt = curr.can_preempt(1);
 
         debug!(
@@ -81,25 +345,27 @@ impl AxRunQueue {
         }
     }
 
-    pub fn exit_current(&mut self, exit_code: i32) -> ! {
+    pub fn exit_current(mut self, exit_code: i32) -> ! {
         let curr = crate::current();
         debug!("task exit: {}, exit_code={}", curr.id_name(), exit_code);
         assert!(curr.is_running());
         assert!(!curr.is_idle());
         if curr.is_init() {
-            EXITED_TASKS.lock().clear();
+            self.inner.exited_tasks.lock().clear();
             axhal::misc::terminate();
         } else {
+            #[cfg(feature = "sched_edf")]
+            crate::sched_edf::exit_current(&curr);
             curr.set_state(TaskState::Exited);
-            curr.notify_exit(exit_code, self);
-            EXITED_TASKS.lock().push_back(curr.clone());
-            WAIT_FOR_EXIT.notify_one_locked(false, self);
+            curr.notify_exit(exit_code);
+            self.inner.exited_tasks.lock().push_back(curr.clone());
+            self.inner.wait_for_exit.notify_one(false);
             self.resched(false);
         }
         unreachable!("task exited!");
     }
 
-    pub fn block_current<F>(&mut self, wait_queue_push: F)
+    pub fn block_current<F>(mut self, wait_queue_push: F)
     where
         F: FnOnce(AxTaskRef),
     {
@@ -108,29 +374,18 @@ impl AxRunQueue {
         assert!(curr.is_running());
         assert!(!curr.is_idle());
 
+        curr.set_state(TaskState::Blocked);
+        wait_queue_push(curr.clone());
+
         // we must not block current task with preemption disabled.
         #[cfg(feature = "preempt")]
         assert!(curr.can_preempt(1));
 
-        curr.set_state(TaskState::Blocked);
-        wait_queue_push(curr.clone());
         self.resched(false);
     }
 
-    pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
-        debug!("task unblock: {}", task.id_name());
-        if task.is_blocked() {
-            task.set_state(TaskState::Ready);
-            self.scheduler.add_task(task); // TODO: priority
-            if resched {
-                #[cfg(feature = "preempt")]
-                crate::current().set_preempt_pending(true);
-            }
-        }
-    }
-
     #[cfg(feature = "irq")]
-    pub fn sleep_until(&mut self, deadline: axhal::time::TimeValue) {
+    pub fn sleep_until(mut self, deadline: axhal::time::TimeValue) {
         let curr = crate::current();
         debug!("task sleep: {}, deadline={:?}", curr.id_name(), deadline);
         assert!(curr.is_running());
@@ -143,61 +398,194 @@ impl AxRunQueue {
             self.resched(false);
         }
     }
-}
 
-impl AxRunQueue {
     /// Common reschedule subroutine. If `preempt`, keep current task's time
     /// slice, otherwise reset it.
     fn resched(&mut self, preempt: bool) {
         let prev = crate::current();
+        let involuntary = preempt && prev.is_running();
+        #[cfg(feature = "sched_edf")]
+        if prev.is_running() {
+            if let Some(next_period) = crate::sched_edf::throttle_current(&prev) {
+                // It has used up its runtime, sleep until the next period.
+                prev.set_state(TaskState::Blocked);
+                crate::timers::set_alarm_replenish(next_period, prev.clone());
+            }
+        }
         if prev.is_running() {
             prev.set_state(TaskState::Ready);
             if !prev.is_idle() {
-                self.scheduler.put_prev_task(prev.clone(), preempt);
+                self.inner.put_prev_task(prev.clone(), preempt);
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
+        let next = self.inner.pick_next_task();
+        #[cfg(feature = "smp")]
+        let next = next.or_else(|| self.inner.steal_task(0));
+        let next = next.unwrap_or_else(|| unsafe {
             // Safety: IRQs must be disabled at this time.
             IDLE_TASK.current_ref_raw().get_unchecked().clone()
         });
-        self.switch_to(prev, next);
+        self.inner.switch_to(prev, next, involuntary);
     }
+}
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
-        trace!(
-            "context switch: {} -> {}",
-            prev_task.id_name(),
-            next_task.id_name()
-        );
-        #[cfg(feature = "preempt")]
-        next_task.set_preempt_pending(false);
-        next_task.set_state(TaskState::Running);
-        if prev_task.ptr_eq(&next_task) {
-            return;
-        }
+/// Returns the run queue of the current CPU.
+pub(crate) fn current_run_queue() -> CurrentRunQueueRef {
+    let guard = NoPreemptIrqSave::new();
+    CurrentRunQueueRef {
+        inner: &*RUN_QUEUES[axhal::cpu::this_cpu_id()],
+        _guard: guard,
+    }
+}
 
-        unsafe {
-            let prev_ctx_ptr = prev_task.ctx_mut_ptr();
-            let next_ctx_ptr = next_task.ctx_mut_ptr();
+/// Selects the run queue to put `task` into: that of the CPU it last ran on
+/// if it is allowed there, otherwise the least loaded one that it is allowed
+/// in.
+pub(crate) fn select_run_queue(task: &AxTaskRef) -> &'static AxRunQueue {
+    #[cfg(feature = "sched_edf")]
+    if let Some(cpu_id) = task.edf().reserved_cpu() {
+        return &RUN_QUEUES[cpu_id];
+    }
+    let cpumask = task.cpumask();
+    let cpu_id = task.cpu_id();
+    if cpumask.get(cpu_id) && RUN_QUEUES[cpu_id].is_inited() {
+        &*RUN_QUEUES[cpu_id]
+    } else {
+        least_loaded_run_queue(&cpumask)
+    }
+}
 
-            // The strong reference count of `prev_task` will be decremented by 1,
-            // but won't be dropped until `gc_entry()` is called.
-            assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
-            assert!(Arc::strong_count(&next_task) >= 1);
+/// Whether the ready task `task` can be moved to the CPU `cpu_id`. Deadline
+/// tasks stay on the CPUs whose bandwidth they reserve.
+#[cfg(feature = "smp")]
+fn can_move_to(task: &AxTaskRef, cpu_id: usize) -> bool {
+    #[cfg(feature = "sched_edf")]
+    if task.edf().reserved_cpu().is_some() {
+        return false;
+    }
+    task.cpumask().get(cpu_id)
+}
 
-            CurrentTask::set_current(prev_task, next_task);
-            (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
+/// Whether the CPU `cpu_id` has started running tasks.
+#[cfg(feature = "sched_edf")]
+pub(crate) fn is_cpu_started(cpu_id: usize) -> bool {
+    RUN_QUEUES[cpu_id].is_inited()
+}
+
+/// Returns the least loaded run queue among the CPUs in `cpumask`, preferring
+/// the current CPU. Falls back to the current CPU if none of them has started.
+pub(crate) fn least_loaded_run_queue(cpumask: &CpuMask) -> &'static AxRunQueue {
+    let this_cpu = axhal::cpu::this_cpu_id();
+    (0..axconfig::SMP)
+        .map(|i| (this_cpu + i) % axconfig::SMP)
+        .filter(|&cpu| cpumask.get(cpu) && RUN_QUEUES[cpu].is_inited())
+        .map(|cpu| &*RUN_QUEUES[cpu])
+        .min_by_key(|rq| rq.load())
+        .unwrap_or(&*RUN_QUEUES[this_cpu])
+}
+
+/// Wakes up `task` if it is blocked, and puts it into a run queue.
+///
+/// If `resched` is true and the task is put into the run queue of the current
+/// CPU, the current task will be preempted when the preemption is enabled.
+pub(crate) fn unblock_task(task: AxTaskRef, resched: bool) {
+    // A task can be woken up by a timer and a notification at the same time,
+    // only the first one puts it into a run queue.
+    if !task.transition_state(TaskState::Blocked, TaskState::Ready) {
+        return;
+    }
+    debug!("task unblock: {}", task.id_name());
+    task.stats_state().wake_up(axhal::time::monotonic_time());
+
+    // The task may not have been switched out yet.
+    #[cfg(feature = "smp")]
+    while task.on_cpu() {
+        core::hint::spin_loop();
+    }
+
+    let _guard = NoPreemptIrqSave::new();
+    let rq = select_run_queue(&task);
+    rq.add_task(task);
+    if resched && rq.cpu_id == axhal::cpu::this_cpu_id() {
+        #[cfg(feature = "preempt")]
+        crate::current().set_preempt_pending(true);
+    }
+}
+
+/// Changes the scheduling parameters of `task` with `update`, and moves it to
+/// the queue of its new class or priority if it is ready.
+pub(crate) fn update_sched_params<F: FnOnce()>(task: &AxTaskRef, update: F) {
+    let _guard = NoPreemptIrqSave::new();
+    loop {
+        let rq = &*RUN_QUEUES[task.cpu_id()];
+        let mut ready = rq.ready.lock();
+        if task.cpu_id() != rq.cpu_id {
+            continue; // it has just been moved to another CPU
+        }
+        // A task that is not in the run queue (running, blocked or being moved)
+        // is queued with the new parameters next time.
+        let queued = ready.remove_task(task);
+        update();
+        if let Some(task) = queued {
+            ready.add_task(task);
+        }
+        break;
+    }
+}
+
+/// Moves `task` to another CPU if its affinity no longer allows the one that
+/// it is on. A ready task is moved right away. A running task is preempted to
+/// be moved, at the next timer tick of its CPU, or when it gives up the CPU
+/// without the `preempt` feature.
+pub(crate) fn enforce_affinity(task: &AxTaskRef) {
+    let _guard = NoPreemptIrqSave::new();
+    loop {
+        let rq = &*RUN_QUEUES[task.cpu_id()];
+        let mut ready = rq.ready.lock();
+        if task.cpu_id() != rq.cpu_id {
+            continue; // it has just been moved to another CPU
+        }
+        if task.cpumask().get(rq.cpu_id) {
+            return;
+        }
+        if let Some(task) = ready.remove_task(task) {
+            rq.nr_ready.fetch_sub(1, Ordering::Relaxed);
+            drop(ready);
+            select_run_queue(&task).add_task(task);
+        } else if task.is_running() {
+            #[cfg(feature = "preempt")]
+            task.set_preempt_pending(true);
         }
+        // Blocked tasks are moved when woken up.
+        return;
+    }
+}
+
+/// Finishes the context switch of the current CPU: clears the `on_cpu` flag of
+/// the task that it has just switched out of, so that other CPUs can run it,
+/// and moves the task to another CPU if needed.
+///
+/// # Safety
+///
+/// IRQs must be disabled, and it must be called right after a context switch.
+#[cfg(feature = "smp")]
+pub(crate) unsafe fn finish_task_switch() {
+    if let Some(prev) = PREV_TASK.current_ref_raw().upgrade() {
+        prev.set_on_cpu(false);
+    }
+    if let Some(task) = MIGRATING_TASK.current_ref_mut_raw().take() {
+        select_run_queue(&task).add_task(task);
     }
 }
 
-fn gc_entry() {
+fn gc_entry(cpu_id: usize) {
+    let rq = &*RUN_QUEUES[cpu_id];
     loop {
         // Drop all exited tasks and recycle resources.
-        let n = EXITED_TASKS.lock().len();
+        let n = rq.exited_tasks.lock().len();
         for _ in 0..n {
             // Do not do the slow drops in the critical section.
-            let task = EXITED_TASKS.lock().pop_front();
+            let task = rq.exited_tasks.lock().pop_front();
             if let Some(task) = task {
                 if Arc::strong_count(&task) == 1 {
                     // If I'm the last holder of the task, drop it immediately.
@@ -205,15 +593,30 @@ fn gc_entry() {
                 } else {
                     // Otherwise (e.g, `switch_to` is not compeleted, held by the
                     // joiner, etc), push it back and wait for them to drop first.
-                    EXITED_TASKS.lock().push_back(task);
+                    rq.exited_tasks.lock().push_back(task);
                 }
             }
         }
-        WAIT_FOR_EXIT.wait();
+        rq.wait_for_exit.wait();
     }
 }
 
+/// Creates the run queue of the current CPU, with its `gc` task, which only
+/// runs on this CPU.
+fn init_run_queue(cpu_id: usize) {
+    RUN_QUEUES[cpu_id].init_once(AxRunQueue::new(cpu_id));
+    let gc_task = TaskInner::new(
+        move || gc_entry(cpu_id),
+        "gc".into(),
+        axconfig::TASK_STACK_SIZE,
+    );
+    gc_task.set_cpumask(CpuMask::one_shot(cpu_id));
+    RUN_QUEUES[cpu_id].add_task(gc_task.into_arc());
+}
+
 pub(crate) fn init() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     // Create the `idle` task (not current task).
     const IDLE_TASK_STACK_SIZE: usize = 4096;
     let idle_task = TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
@@ -224,17 +627,34 @@ pub(crate) fn init() {
     // Put the subsequent execution into the `main` task.
     let main_task = TaskInner::new_init("main".into()).into_arc();
     main_task.set_state(TaskState::Running);
+    main_task.set_cpu_id(cpu_id);
+    main_task
+        .stats_state()
+        .switch_in(axhal::time::monotonic_time(), cpu_id);
+    #[cfg(feature = "smp")]
+    main_task.set_on_cpu(true);
     unsafe { CurrentTask::init_current(main_task) };
 
-    RUN_QUEUE.init_once(AxRunQueue::new());
+    init_run_queue(cpu_id);
+    RUN_QUEUES[cpu_id].busy.store(true, Ordering::Relaxed);
 }
 
 pub(crate) fn init_secondary() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     // Put the subsequent execution into the `idle` task.
     let idle_task = TaskInner::new_init("idle".into()).into_arc();
     idle_task.set_state(TaskState::Running);
+    idle_task.set_cpu_id(cpu_id);
+    idle_task
+        .stats_state()
+        .switch_in(axhal::time::monotonic_time(), cpu_id);
+    #[cfg(feature = "smp")]
+    idle_task.set_on_cpu(true);
     IDLE_TASK.with_current(|i| {
         i.init_once(idle_task.clone());
     });
     unsafe { CurrentTask::init_current(idle_task) }
+
+    init_run_queue(cpu_id);
 }
@@ -0,0 +1,321 @@
+//! An earliest-deadline-first (EDF) scheduler for periodic tasks.
+//!
+//! A deadline task declares its [`DeadlineParams`]: it runs for up to
+//! `runtime` in each `period`, and should finish by the `deadline` relative to
+//! the start of the period. Deadline tasks always run before normal tasks,
+//! which are scheduled by the [CFS], and the one with the earliest deadline
+//! runs first.
+//!
+//! A task that has used up its runtime is throttled: it sleeps until the next
+//! period, so that it cannot delay the other tasks beyond their reservations.
+//!
+//! Each deadline task reserves its bandwidth on one CPU in its affinity, and
+//! stays on that CPU while it is a deadline task, so that the admission test
+//! of each CPU guarantees the deadlines of its tasks.
+//!
+//! [CFS]: scheduler::CFScheduler
+
+use alloc::collections::BTreeMap;
+use core::time::Duration;
+
+use axhal::time::{wall_time, TimeValue};
+use kspin::SpinNoIrq;
+use scheduler::{BaseScheduler, CFScheduler};
+
+use crate::{AxTaskRef, TaskInner};
+
+/// The fraction of the time of each CPU that deadline tasks can reserve, in
+/// [`BW_UNIT`]. The rest is left for normal tasks.
+const BW_LIMIT: u64 = BW_UNIT * 95 / 100;
+
+/// The bandwidth of a task that reserves a whole CPU.
+const BW_UNIT: u64 = 1 << 20;
+
+/// The bandwidth reserved by deadline tasks on each CPU, in [`BW_UNIT`].
+static CPU_BW: SpinNoIrq<[u64; axconfig::SMP]> = SpinNoIrq::new([0; axconfig::SMP]);
+
+/// The scheduling parameters of a deadline task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineParams {
+    /// The CPU time that the task can run for in each period.
+    pub runtime: Duration,
+    /// The length of a period.
+    pub period: Duration,
+    /// The time by which the task should have run for its `runtime` in each
+    /// period, relative to the start of the period.
+    pub deadline: Duration,
+}
+
+impl DeadlineParams {
+    fn is_valid(&self) -> bool {
+        !self.runtime.is_zero() && self.runtime <= self.deadline && self.deadline <= self.period
+    }
+
+    /// Returns the fraction of a CPU reserved by the task, in [`BW_UNIT`].
+    fn bandwidth(&self) -> u64 {
+        ((self.runtime.as_nanos() * BW_UNIT as u128) / self.period.as_nanos()) as u64
+    }
+}
+
+/// The deadline scheduling state of a task.
+#[derive(Default)]
+pub(crate) struct EdfState {
+    params: Option<DeadlineParams>,
+    /// The CPU whose bandwidth the task reserves, if it has `params`.
+    cpu: usize,
+    /// The absolute deadline of the current period.
+    deadline: TimeValue,
+    /// The runtime left in the current period.
+    remaining: Duration,
+    /// When the task last started running, or was last charged.
+    exec_start: TimeValue,
+    /// Whether the task is sleeping until the next period.
+    throttled: bool,
+    /// Whether the task is in the deadline queue of a run queue.
+    queued: bool,
+}
+
+impl EdfState {
+    pub(crate) fn params(&self) -> Option<DeadlineParams> {
+        self.params
+    }
+
+    /// Returns the CPU that the task is placed on, if it is a deadline task.
+    pub(crate) fn reserved_cpu(&self) -> Option<usize> {
+        self.params.map(|_| self.cpu)
+    }
+
+    /// Starts a new period at `now`, with the full runtime.
+    fn start_period(&mut self, now: TimeValue) {
+        if let Some(params) = self.params {
+            self.deadline = now + params.deadline;
+            self.remaining = params.runtime;
+            self.exec_start = now;
+        }
+    }
+
+    /// Starts a new period when the task becomes ready, unless it can finish
+    /// the remaining runtime in the current one without exceeding its
+    /// bandwidth (the CBS wakeup rule).
+    fn wake_up(&mut self, now: TimeValue) {
+        let Some(params) = self.params else {
+            return;
+        };
+        let left = self.deadline.saturating_sub(now);
+        // remaining / left > runtime / period
+        if left.is_zero()
+            || self.remaining.as_nanos() * params.period.as_nanos()
+                > left.as_nanos() * params.runtime.as_nanos()
+        {
+            self.start_period(now);
+        }
+    }
+
+    /// Charges the time that the task has run since it was last charged.
+    fn charge(&mut self, now: TimeValue) {
+        self.remaining = self
+            .remaining
+            .saturating_sub(now.saturating_sub(self.exec_start));
+        self.exec_start = now;
+    }
+}
+
+/// The scheduler of a run queue, with deadline tasks ordered by their
+/// deadlines, and a [`CFScheduler`] for normal tasks.
+pub struct EdfScheduler {
+    edf_queue: BTreeMap<(TimeValue, u64), AxTaskRef>,
+    cfs: CFScheduler<TaskInner>,
+}
+
+impl EdfScheduler {
+    /// Creates a new empty scheduler.
+    pub fn new() -> Self {
+        Self {
+            edf_queue: BTreeMap::new(),
+            cfs: CFScheduler::new(),
+        }
+    }
+
+    /// Gets the name of the scheduler.
+    pub fn scheduler_name() -> &'static str {
+        "EDF+CFS"
+    }
+
+    /// Whether `task`, which becomes ready, should preempt `current`.
+    pub fn should_preempt(task: &AxTaskRef, current: &AxTaskRef) -> bool {
+        let task = task.edf();
+        if task.params.is_none() {
+            return false;
+        }
+        let current = current.edf();
+        current.params.is_none() || task.deadline < current.deadline
+    }
+
+    fn earliest_deadline(&self) -> Option<TimeValue> {
+        self.edf_queue
+            .first_key_value()
+            .map(|((deadline, _), _)| *deadline)
+    }
+
+    fn push_edf_task(&mut self, task: AxTaskRef, deadline: TimeValue) {
+        let key = (deadline, task.id().as_u64());
+        self.edf_queue.insert(key, task);
+    }
+}
+
+impl BaseScheduler for EdfScheduler {
+    type SchedItem = AxTaskRef;
+
+    fn init(&mut self) {
+        self.cfs.init();
+    }
+
+    fn add_task(&mut self, task: AxTaskRef) {
+        let mut edf = task.edf();
+        edf.throttled = false;
+        if edf.params.is_none() {
+            drop(edf);
+            return self.cfs.add_task(task);
+        }
+        edf.wake_up(wall_time());
+        edf.queued = true;
+        let deadline = edf.deadline;
+        drop(edf);
+        self.push_edf_task(task, deadline);
+    }
+
+    fn remove_task(&mut self, task: &AxTaskRef) -> Option<AxTaskRef> {
+        let mut edf = task.edf();
+        if !edf.queued {
+            drop(edf);
+            return self.cfs.remove_task(task);
+        }
+        edf.queued = false;
+        self.edf_queue.remove(&(edf.deadline, task.id().as_u64()))
+    }
+
+    fn pick_next_task(&mut self) -> Option<AxTaskRef> {
+        let Some((_, task)) = self.edf_queue.pop_first() else {
+            return self.cfs.pick_next_task();
+        };
+        let mut edf = task.edf();
+        edf.queued = false;
+        edf.exec_start = wall_time();
+        drop(edf);
+        Some(task)
+    }
+
+    fn put_prev_task(&mut self, prev: AxTaskRef, preempt: bool) {
+        let mut edf = prev.edf();
+        if edf.params.is_none() {
+            drop(edf);
+            return self.cfs.put_prev_task(prev, preempt);
+        }
+        edf.charge(wall_time());
+        edf.queued = true;
+        let deadline = edf.deadline;
+        drop(edf);
+        self.push_edf_task(prev, deadline);
+    }
+
+    fn task_tick(&mut self, current: &AxTaskRef) -> bool {
+        let mut edf = current.edf();
+        if edf.params.is_none() {
+            drop(edf);
+            return self.earliest_deadline().is_some() || self.cfs.task_tick(current);
+        }
+        edf.charge(wall_time());
+        edf.remaining.is_zero() || self.earliest_deadline().is_some_and(|d| d < edf.deadline)
+    }
+
+    fn set_priority(&mut self, task: &AxTaskRef, prio: isize) -> bool {
+        self.cfs.set_priority(task, prio)
+    }
+}
+
+/// Sets the deadline parameters of `task`, or makes it a normal task if
+/// `params` is `None`.
+///
+/// Returns `false` if the parameters are invalid, or no CPU in the affinity of
+/// the task has enough bandwidth left (the admission test). Otherwise the task
+/// is placed on such a CPU, preferring the one it is on.
+pub(crate) fn set_params(task: &AxTaskRef, params: Option<DeadlineParams>) -> bool {
+    if params.is_some_and(|p| !p.is_valid()) {
+        return false;
+    }
+    let mut cpu_bw = CPU_BW.lock();
+    let (old_cpu, old_bw) = {
+        let edf = task.edf();
+        (edf.cpu, edf.params.map_or(0, |p| p.bandwidth()))
+    };
+    cpu_bw[old_cpu] -= old_bw;
+    let cpu = match params {
+        Some(params) => match admit(&cpu_bw, task, params.bandwidth()) {
+            Some(cpu) => {
+                cpu_bw[cpu] += params.bandwidth();
+                cpu
+            }
+            None => {
+                cpu_bw[old_cpu] += old_bw;
+                return false;
+            }
+        },
+        None => old_cpu,
+    };
+
+    // The task is moved to `cpu` the next time it is scheduled.
+    crate::run_queue::update_sched_params(task, || {
+        let mut edf = task.edf();
+        edf.params = params;
+        edf.cpu = cpu;
+        edf.start_period(wall_time());
+    });
+    true
+}
+
+/// Returns the CPU to place a deadline task of bandwidth `bw` on: the CPU that
+/// `task` is on if it has room, or the allowed CPU with the most room.
+fn admit(cpu_bw: &[u64], task: &AxTaskRef, bw: u64) -> Option<usize> {
+    let cpumask = task.cpumask();
+    let fits = |&cpu: &usize| {
+        cpumask.get(cpu) && crate::run_queue::is_cpu_started(cpu) && cpu_bw[cpu] + bw <= BW_LIMIT
+    };
+    Some(task.cpu_id()).filter(fits).or_else(|| {
+        (0..axconfig::SMP)
+            .filter(fits)
+            .min_by_key(|&cpu| cpu_bw[cpu])
+    })
+}
+
+/// Releases the bandwidth of the current task, which is exiting.
+pub(crate) fn exit_current(curr: &TaskInner) {
+    let mut cpu_bw = CPU_BW.lock();
+    let mut edf = curr.edf();
+    if let Some(params) = edf.params.take() {
+        cpu_bw[edf.cpu] -= params.bandwidth();
+    }
+}
+
+/// Gives up the rest of the runtime of the current deadline task in this
+/// period.
+pub(crate) fn yield_runtime(curr: &TaskInner) {
+    curr.edf().remaining = Duration::ZERO;
+}
+
+/// Throttles the current task if it has used up its runtime, and returns the
+/// start of the next period, when it can run again.
+pub(crate) fn throttle_current(curr: &TaskInner) -> Option<TimeValue> {
+    let mut edf = curr.edf();
+    let params = edf.params?;
+    edf.charge(wall_time());
+    if !edf.remaining.is_zero() {
+        return None;
+    }
+    edf.throttled = true;
+    Some(edf.deadline.saturating_sub(params.deadline) + params.period)
+}
+
+/// Whether `task` is sleeping until the next period.
+pub(crate) fn is_throttled(task: &AxTaskRef) -> bool {
+    task.edf().throttled
+}
@@ -0,0 +1,153 @@
+//! A multi-class scheduler with real-time tasks and normal tasks.
+//!
+//! Real-time tasks ([`SchedPolicy::Fifo`] and [`SchedPolicy::RoundRobin`])
+//! have static priorities from 1 to 99, and always run before normal tasks,
+//! which are scheduled by the [CFS].
+//!
+//! [CFS]: scheduler::CFScheduler
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::array;
+
+use scheduler::{BaseScheduler, CFScheduler};
+
+use crate::task::{SchedPolicy, RT_PRIORITY_MAX};
+use crate::{AxTaskRef, TaskInner};
+
+/// The time slice of [`SchedPolicy::RoundRobin`] tasks, in timer ticks.
+const RT_TIME_SLICE: usize = 5;
+
+const NUM_RT_QUEUES: usize = RT_PRIORITY_MAX as usize + 1;
+
+/// The scheduler of a run queue, with a FIFO queue for each real-time
+/// priority, and a [`CFScheduler`] for normal tasks.
+///
+/// The class of a task is determined by its effective priority, which is that
+/// inherited from the tasks waiting for its locks if higher. So a normal task
+/// runs as a real-time task while it blocks a real-time task.
+pub struct RtScheduler {
+    rt_queues: [VecDeque<AxTaskRef>; NUM_RT_QUEUES],
+    /// Bit `i` is set if `rt_queues[i]` is not empty.
+    rt_bitmap: u128,
+    cfs: CFScheduler<TaskInner>,
+}
+
+impl RtScheduler {
+    /// Creates a new empty scheduler.
+    pub fn new() -> Self {
+        Self {
+            rt_queues: array::from_fn(|_| VecDeque::new()),
+            rt_bitmap: 0,
+            cfs: CFScheduler::new(),
+        }
+    }
+
+    /// Gets the name of the scheduler.
+    pub fn scheduler_name() -> &'static str {
+        "RT+CFS"
+    }
+
+    /// Whether `task`, which becomes ready, should preempt `current`.
+    pub fn should_preempt(task: &AxTaskRef, current: &AxTaskRef) -> bool {
+        task.effective_rt_priority() > current.effective_rt_priority()
+    }
+
+    /// Returns the highest priority of the ready real-time tasks, or 0 if
+    /// there is none.
+    fn highest_rt_priority(&self) -> u8 {
+        if self.rt_bitmap == 0 {
+            0
+        } else {
+            (u128::BITS - 1 - self.rt_bitmap.leading_zeros()) as u8
+        }
+    }
+
+    fn push_rt_task(&mut self, task: AxTaskRef, front: bool) {
+        let prio = task.effective_rt_priority() as usize;
+        task.set_rt_queued(true);
+        if front {
+            self.rt_queues[prio].push_front(task);
+        } else {
+            self.rt_queues[prio].push_back(task);
+        }
+        self.rt_bitmap |= 1 << prio;
+    }
+
+    fn pop_rt_task(&mut self, prio: usize, index: usize) -> Option<AxTaskRef> {
+        let task = self.rt_queues[prio].remove(index)?;
+        if self.rt_queues[prio].is_empty() {
+            self.rt_bitmap &= !(1 << prio);
+        }
+        task.set_rt_queued(false);
+        Some(task)
+    }
+}
+
+impl BaseScheduler for RtScheduler {
+    type SchedItem = AxTaskRef;
+
+    fn init(&mut self) {
+        self.cfs.init();
+    }
+
+    fn add_task(&mut self, task: AxTaskRef) {
+        if task.effective_rt_priority() > 0 {
+            task.reset_rt_time_slice(RT_TIME_SLICE);
+            self.push_rt_task(task, false);
+        } else {
+            self.cfs.add_task(task);
+        }
+    }
+
+    fn remove_task(&mut self, task: &AxTaskRef) -> Option<AxTaskRef> {
+        if !task.rt_queued() {
+            return self.cfs.remove_task(task);
+        }
+        // The priority may have changed since it was queued.
+        for prio in 1..NUM_RT_QUEUES {
+            if let Some(index) = self.rt_queues[prio]
+                .iter()
+                .position(|t| Arc::ptr_eq(t, task))
+            {
+                return self.pop_rt_task(prio, index);
+            }
+        }
+        None
+    }
+
+    fn pick_next_task(&mut self) -> Option<AxTaskRef> {
+        match self.highest_rt_priority() {
+            0 => self.cfs.pick_next_task(),
+            prio => self.pop_rt_task(prio as usize, 0),
+        }
+    }
+
+    fn put_prev_task(&mut self, prev: AxTaskRef, preempt: bool) {
+        if prev.effective_rt_priority() == 0 {
+            self.cfs.put_prev_task(prev, preempt);
+        } else if prev.rt_time_slice() == 0 {
+            // A round-robin task that used up its time slice goes to the back.
+            prev.reset_rt_time_slice(RT_TIME_SLICE);
+            self.push_rt_task(prev, false);
+        } else {
+            // A preempted task stays at the front of the queue of its
+            // priority, and a yielding one goes to the back.
+            self.push_rt_task(prev, preempt);
+        }
+    }
+
+    fn task_tick(&mut self, current: &AxTaskRef) -> bool {
+        let prio = current.effective_rt_priority();
+        if prio == 0 {
+            return self.highest_rt_priority() > 0 || self.cfs.task_tick(current);
+        }
+        if self.highest_rt_priority() > prio {
+            return true;
+        }
+        current.sched_policy() == SchedPolicy::RoundRobin && current.tick_rt_time_slice()
+    }
+
+    fn set_priority(&mut self, task: &AxTaskRef, prio: isize) -> bool {
+        self.cfs.set_priority(task, prio)
+    }
+}
@@ -0,0 +1,114 @@
+//! Runtime statistics of tasks.
+
+use core::time::Duration;
+
+use axhal::time::TimeValue;
+
+/// A snapshot of the runtime statistics of a task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskStats {
+    /// The CPU time spent in user space.
+    ///
+    /// It is always zero without the `uspace` feature, where all the time is
+    /// counted as kernel time.
+    pub user_time: Duration,
+    /// The CPU time spent in the kernel.
+    pub kernel_time: Duration,
+    /// The number of times the task gave up the CPU by itself, i.e. it
+    /// blocked, yielded or exited.
+    pub voluntary_switches: u64,
+    /// The number of times the task was preempted.
+    pub involuntary_switches: u64,
+    /// The number of times the task was woken up after being blocked.
+    pub wakeups: u64,
+    /// The total time from being woken up to running, of all the wakeups.
+    pub total_wakeup_latency: Duration,
+    /// The longest time from being woken up to running.
+    pub max_wakeup_latency: Duration,
+    /// The CPU that the task is running on, or last ran on.
+    pub last_cpu: usize,
+}
+
+impl TaskStats {
+    /// Returns the total CPU time, in both user space and the kernel.
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.kernel_time
+    }
+}
+
+/// The runtime statistics of a task, and the states to update them.
+#[derive(Default, Clone)]
+pub(crate) struct StatsState {
+    stats: TaskStats,
+    /// When the CPU time was last charged, while the task is running.
+    exec_start: TimeValue,
+    /// Whether the task is running in user space.
+    in_user: bool,
+    /// When the task was woken up, if it has not run since then.
+    woken_at: Option<TimeValue>,
+}
+
+impl StatsState {
+    /// Charges the time that the task has run since it was last charged.
+    fn charge(&mut self, now: TimeValue) {
+        let delta = now.saturating_sub(self.exec_start);
+        if self.in_user {
+            self.stats.user_time += delta;
+        } else {
+            self.stats.kernel_time += delta;
+        }
+        self.exec_start = now;
+    }
+
+    /// Returns the statistics at `now`, including the time since it was last
+    /// charged if the task is `running`.
+    pub(crate) fn snapshot(&self, now: TimeValue, running: bool) -> TaskStats {
+        let mut state = self.clone();
+        if running {
+            state.charge(now);
+        }
+        state.stats
+    }
+
+    pub(crate) fn wake_up(&mut self, now: TimeValue) {
+        self.woken_at = Some(now);
+    }
+
+    pub(crate) fn switch_in(&mut self, now: TimeValue, cpu_id: usize) {
+        if let Some(woken_at) = self.woken_at.take() {
+            let latency = now.saturating_sub(woken_at);
+            self.stats.wakeups += 1;
+            self.stats.total_wakeup_latency += latency;
+            self.stats.max_wakeup_latency = self.stats.max_wakeup_latency.max(latency);
+        }
+        self.stats.last_cpu = cpu_id;
+        self.exec_start = now;
+    }
+
+    pub(crate) fn switch_out(&mut self, now: TimeValue, involuntary: bool) {
+        self.charge(now);
+        if involuntary {
+            self.stats.involuntary_switches += 1;
+        } else {
+            self.stats.voluntary_switches += 1;
+        }
+    }
+
+    /// Records that the task enters user space (`to_user`), or the kernel.
+    #[cfg(feature = "uspace")]
+    pub(crate) fn switch_mode(&mut self, now: TimeValue, to_user: bool) {
+        self.charge(now);
+        self.in_user = to_user;
+    }
+}
+
+/// Charges the CPU time of the current task to user space or the kernel, when
+/// it switches between them.
+#[cfg(feature = "uspace")]
+#[axhal::trap::register_trap_handler(axhal::trap::USER_MODE_SWITCH)]
+fn user_mode_switch(to_user: bool) {
+    if let Some(curr) = crate::current_may_uninit() {
+        curr.stats_state()
+            .switch_mode(axhal::time::monotonic_time(), to_user);
+    }
+}
@@ -1,19 +1,21 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::any::Any;
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
-
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
 
 use axhal::arch::TaskContext;
+use kspin::SpinNoIrq;
 use memory_addr::{align_up_4k, VirtAddr};
 
+use crate::stats::{StatsState, TaskStats};
 use crate::task_ext::AxTaskExt;
-use crate::{AxRunQueue, AxTask, AxTaskRef, WaitQueue};
+use crate::{AxTask, AxTaskRef, CpuMask, WaitQueue};
 
 /// A unique identifier for a thread.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -22,13 +24,43 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
+    /// The task is running on a CPU.
     Running = 1,
+    /// The task is ready to run, and is in the run queue.
     Ready = 2,
+    /// The task is blocked, e.g. waiting in a wait queue or sleeping.
     Blocked = 3,
+    /// The task has exited, and is waiting to be dropped.
     Exited = 4,
 }
 
+/// The scheduling policy of a task.
+///
+/// The values are the same as `SCHED_OTHER`, `SCHED_FIFO` and `SCHED_RR` in
+/// Linux.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SchedPolicy {
+    /// The default policy for normal tasks, which are scheduled by the
+    /// scheduler selected by cargo features.
+    Normal = 0,
+    /// A real-time policy, in which a task runs until it blocks, yields, or is
+    /// preempted by a task with a higher priority.
+    Fifo = 1,
+    /// A real-time policy like [`SchedPolicy::Fifo`], but tasks with the same
+    /// priority take turns in time slices.
+    RoundRobin = 2,
+}
+
+/// The lowest priority of real-time tasks.
+pub const RT_PRIORITY_MIN: u8 = 1;
+/// The highest priority of real-time tasks.
+pub const RT_PRIORITY_MAX: u8 = 99;
+
+/// All alive tasks, indexed by their IDs.
+static TASK_TABLE: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
 /// The inner task structure.
 pub struct TaskInner {
     id: TaskId,
@@ -39,9 +71,40 @@ pub struct TaskInner {
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
 
+    sched_policy: AtomicU8,
+    /// The static priority of a real-time task, or 0 for a normal task.
+    rt_priority: AtomicU8,
+    /// The priority inherited from the tasks that wait for the locks it holds,
+    /// i.e. the highest one in `pi_donations`.
+    #[cfg(feature = "sched_rt")]
+    inherited_priority: AtomicU8,
+    /// The priorities lent to the task, for each lock that it holds.
+    #[cfg(feature = "sched_rt")]
+    pi_donations: SpinNoIrq<Vec<(usize, u8)>>,
+    #[cfg(feature = "sched_rt")]
+    rt_time_slice: AtomicUsize,
+    /// Whether the task is in the real-time queues of a run queue.
+    #[cfg(feature = "sched_rt")]
+    rt_queued: AtomicBool,
+    /// The deadline scheduling state, which is not inherited by new tasks.
+    #[cfg(feature = "sched_edf")]
+    edf: SpinNoIrq<crate::sched_edf::EdfState>,
+
+    stats: SpinNoIrq<StatsState>,
+
+    /// The CPU that the task is running on, or last ran on.
+    cpu_id: AtomicUsize,
+    /// The CPUs that the task is allowed to run on.
+    cpumask: SpinNoIrq<CpuMask>,
+    /// Whether the task is running on a CPU, including while it is being
+    /// switched out of.
+    #[cfg(feature = "smp")]
+    on_cpu: AtomicBool,
+
     in_wait_queue: AtomicBool,
+    /// Identifies the pending alarm of the task, to ignore canceled ones.
     #[cfg(feature = "irq")]
-    in_timer_list: AtomicBool,
+    timer_ticket: AtomicU64,
 
     #[cfg(feature = "preempt")]
     need_resched: AtomicBool,
@@ -54,6 +117,7 @@ pub struct TaskInner {
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
     task_ext: AxTaskExt,
+    fs_state: SpinNoIrq<Option<Arc<dyn Any + Send + Sync>>>,
 
     #[cfg(feature = "tls")]
     tls: TlsArea,
@@ -71,6 +135,18 @@ impl TaskId {
     }
 }
 
+impl From<u8> for SchedPolicy {
+    #[inline]
+    fn from(policy: u8) -> Self {
+        match policy {
+            0 => Self::Normal,
+            1 => Self::Fifo,
+            2 => Self::RoundRobin,
+            _ => unreachable!(),
+        }
+    }
+}
+
 impl From<u8> for TaskState {
     #[inline]
     fn from(state: u8) -> Self {
@@ -108,6 +184,12 @@ impl TaskInner {
         if t.name == "idle" {
             t.is_idle = true;
         }
+        if let Some(curr) = crate::current_may_uninit() {
+            t.fs_state = SpinNoIrq::new(curr.fs_state());
+            t.cpumask = SpinNoIrq::new(curr.cpumask());
+            t.sched_policy = AtomicU8::new(curr.sched_policy() as u8);
+            t.rt_priority = AtomicU8::new(curr.rt_priority());
+        }
         t
     }
 
@@ -159,6 +241,69 @@ impl TaskInner {
             None
         }
     }
+
+    /// Returns the state of the filesystem module for the task, such as its
+    /// current directory and its credentials.
+    ///
+    /// The state is opaque to this crate, and is only set and read by `axfs`.
+    /// It cannot be kept in the [task extension](crate::def_task_ext), which
+    /// belongs to the kernel and is not inherited. A new task shares the state
+    /// of the task that created it.
+    pub fn fs_state(&self) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.fs_state.lock().clone()
+    }
+
+    /// Replaces the state of the filesystem module for the task.
+    pub fn set_fs_state(&self, state: Option<Arc<dyn Any + Send + Sync>>) {
+        *self.fs_state.lock() = state;
+    }
+
+    /// Gets the scheduling policy of the task.
+    ///
+    /// A new task has the policy of the task that created it.
+    pub fn sched_policy(&self) -> SchedPolicy {
+        self.sched_policy.load(Ordering::Acquire).into()
+    }
+
+    /// Gets the static priority of the task, from [`RT_PRIORITY_MIN`] to
+    /// [`RT_PRIORITY_MAX`] for real-time tasks, or 0 for normal tasks.
+    pub fn rt_priority(&self) -> u8 {
+        self.rt_priority.load(Ordering::Acquire)
+    }
+
+    /// Gets the CPU affinity of the task, i.e. the CPUs that it is allowed to
+    /// run on.
+    ///
+    /// A new task has the affinity of the task that created it.
+    pub fn cpumask(&self) -> CpuMask {
+        *self.cpumask.lock()
+    }
+
+    /// Sets the CPU affinity of the task. Returns `false` if `cpumask` is
+    /// empty, or it does not contain the CPU that the task is placed on as a
+    /// deadline task.
+    ///
+    /// If the task is running or ready on a CPU that is not in `cpumask`, it is
+    /// moved the next time it is scheduled. Use [`set_task_affinity`] to move
+    /// a spawned task right away.
+    ///
+    /// [`set_task_affinity`]: crate::set_task_affinity
+    pub fn set_cpumask(&self, cpumask: CpuMask) -> bool {
+        if cpumask.is_empty() {
+            return false;
+        }
+        let mut mask = self.cpumask.lock();
+        #[cfg(feature = "sched_edf")]
+        if self
+            .edf()
+            .reserved_cpu()
+            .is_some_and(|cpu| !cpumask.get(cpu))
+        {
+            return false;
+        }
+        *mask = cpumask;
+        true
+    }
 }
 
 // private methods
@@ -171,9 +316,26 @@ impl TaskInner {
             is_init: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
+            sched_policy: AtomicU8::new(SchedPolicy::Normal as u8),
+            rt_priority: AtomicU8::new(0),
+            #[cfg(feature = "sched_rt")]
+            inherited_priority: AtomicU8::new(0),
+            #[cfg(feature = "sched_rt")]
+            pi_donations: SpinNoIrq::new(Vec::new()),
+            #[cfg(feature = "sched_rt")]
+            rt_time_slice: AtomicUsize::new(0),
+            #[cfg(feature = "sched_rt")]
+            rt_queued: AtomicBool::new(false),
+            #[cfg(feature = "sched_edf")]
+            edf: SpinNoIrq::new(crate::sched_edf::EdfState::default()),
+            stats: SpinNoIrq::new(StatsState::default()),
+            cpu_id: AtomicUsize::new(0),
+            cpumask: SpinNoIrq::new(CpuMask::full()),
+            #[cfg(feature = "smp")]
+            on_cpu: AtomicBool::new(false),
             in_wait_queue: AtomicBool::new(false),
             #[cfg(feature = "irq")]
-            in_timer_list: AtomicBool::new(false),
+            timer_ticket: AtomicU64::new(0),
             #[cfg(feature = "preempt")]
             need_resched: AtomicBool::new(false),
             #[cfg(feature = "preempt")]
@@ -183,6 +345,7 @@ impl TaskInner {
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
             task_ext: AxTaskExt::empty(),
+            fs_state: SpinNoIrq::new(None),
             #[cfg(feature = "tls")]
             tls: TlsArea::alloc(),
         }
@@ -206,11 +369,16 @@ impl TaskInner {
     }
 
     pub(crate) fn into_arc(self) -> AxTaskRef {
-        Arc::new(AxTask::new(self))
+        let task = Arc::new(AxTask::new(self));
+        TASK_TABLE
+            .lock()
+            .insert(task.id().as_u64(), Arc::downgrade(&task));
+        task
     }
 
+    /// Gets the state of the task.
     #[inline]
-    pub(crate) fn state(&self) -> TaskState {
+    pub fn state(&self) -> TaskState {
         self.state.load(Ordering::Acquire).into()
     }
 
@@ -219,6 +387,15 @@ impl TaskInner {
         self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Changes the state of the task from `from` to `to`. Returns `false` if
+    /// it is not in `from`.
+    #[inline]
+    pub(crate) fn transition_state(&self, from: TaskState, to: TaskState) -> bool {
+        self.state
+            .compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
     #[inline]
     pub(crate) fn is_running(&self) -> bool {
         matches!(self.state(), TaskState::Running)
@@ -239,8 +416,9 @@ impl TaskInner {
         self.is_init
     }
 
+    /// Whether the task is the idle task of a CPU.
     #[inline]
-    pub(crate) const fn is_idle(&self) -> bool {
+    pub const fn is_idle(&self) -> bool {
         self.is_idle
     }
 
@@ -254,16 +432,160 @@ impl TaskInner {
         self.in_wait_queue.store(in_wait_queue, Ordering::Release);
     }
 
+    /// Sets the scheduling policy and the static priority, which must be
+    /// valid for the policy.
+    pub(crate) fn set_sched_params(&self, policy: SchedPolicy, rt_priority: u8) {
+        self.sched_policy.store(policy as u8, Ordering::Release);
+        self.rt_priority.store(rt_priority, Ordering::Release);
+    }
+
+    /// Returns the priority that the task is scheduled with as a real-time
+    /// task, including the one inherited from the tasks waiting for its locks,
+    /// or 0 if it is a normal task.
+    #[inline]
+    pub fn effective_rt_priority(&self) -> u8 {
+        #[cfg(feature = "sched_rt")]
+        {
+            self.rt_priority()
+                .max(self.inherited_priority.load(Ordering::Acquire))
+        }
+        #[cfg(not(feature = "sched_rt"))]
+        self.rt_priority()
+    }
+
+    /// Lends `prio` to the task for `lock`, if higher than what has been lent
+    /// for it. Returns `false` if the task does not hold the lock, as told by
+    /// `holds_lock`, which is checked with the lent priorities locked, so that
+    /// they are not changed by [`withdraw_priority`](Self::withdraw_priority)
+    /// meanwhile.
+    #[cfg(feature = "sched_rt")]
+    pub(crate) fn lend_priority(
+        &self,
+        lock: usize,
+        prio: u8,
+        holds_lock: impl FnOnce() -> bool,
+    ) -> bool {
+        let mut donations = self.pi_donations.lock();
+        if !holds_lock() {
+            return false;
+        }
+        match donations.iter_mut().find(|(l, _)| *l == lock) {
+            Some((_, p)) => *p = prio.max(*p),
+            None => donations.push((lock, prio)),
+        }
+        self.update_inherited_priority(&donations);
+        true
+    }
+
+    /// Drops the priority lent to the task for `lock`, when it releases it.
+    #[cfg(feature = "sched_rt")]
+    pub(crate) fn withdraw_priority(&self, lock: usize) {
+        let mut donations = self.pi_donations.lock();
+        donations.retain(|(l, _)| *l != lock);
+        self.update_inherited_priority(&donations);
+    }
+
+    #[cfg(feature = "sched_rt")]
+    fn update_inherited_priority(&self, donations: &[(usize, u8)]) {
+        let prio = donations.iter().map(|(_, p)| *p).max().unwrap_or(0);
+        self.inherited_priority.store(prio, Ordering::Release);
+    }
+
+    #[inline]
+    #[cfg(feature = "sched_rt")]
+    pub(crate) fn rt_time_slice(&self) -> usize {
+        self.rt_time_slice.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "sched_rt")]
+    pub(crate) fn reset_rt_time_slice(&self, ticks: usize) {
+        self.rt_time_slice.store(ticks, Ordering::Release);
+    }
+
+    /// Consumes a tick of the time slice, returns `true` if it is used up.
+    #[inline]
+    #[cfg(feature = "sched_rt")]
+    pub(crate) fn tick_rt_time_slice(&self) -> bool {
+        let old = self.rt_time_slice.load(Ordering::Acquire);
+        let new = old.saturating_sub(1);
+        self.rt_time_slice.store(new, Ordering::Release);
+        new == 0
+    }
+
+    #[inline]
+    #[cfg(feature = "sched_rt")]
+    pub(crate) fn rt_queued(&self) -> bool {
+        self.rt_queued.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "sched_rt")]
+    pub(crate) fn set_rt_queued(&self, rt_queued: bool) {
+        self.rt_queued.store(rt_queued, Ordering::Release);
+    }
+
+    /// Returns a snapshot of the runtime statistics of the task, including the
+    /// CPU time until now if it is running.
+    pub fn stats(&self) -> TaskStats {
+        let running = self.is_running();
+        self.stats
+            .lock()
+            .snapshot(axhal::time::monotonic_time(), running)
+    }
+
+    #[inline]
+    pub(crate) fn stats_state(&self) -> kspin::SpinNoIrqGuard<'_, StatsState> {
+        self.stats.lock()
+    }
+
+    /// Returns the deadline parameters of the task, or `None` if it is not a
+    /// deadline task.
+    #[cfg(feature = "sched_edf")]
+    pub fn deadline_params(&self) -> Option<crate::sched_edf::DeadlineParams> {
+        self.edf.lock().params()
+    }
+
+    #[inline]
+    #[cfg(feature = "sched_edf")]
+    pub(crate) fn edf(&self) -> kspin::SpinNoIrqGuard<'_, crate::sched_edf::EdfState> {
+        self.edf.lock()
+    }
+
+    #[inline]
+    pub(crate) fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id, Ordering::Release);
+    }
+
+    #[inline]
+    #[cfg(feature = "smp")]
+    pub(crate) fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "smp")]
+    pub(crate) fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release);
+    }
+
     #[inline]
     #[cfg(feature = "irq")]
-    pub(crate) fn in_timer_list(&self) -> bool {
-        self.in_timer_list.load(Ordering::Acquire)
+    pub(crate) fn timer_ticket(&self) -> u64 {
+        self.timer_ticket.load(Ordering::Acquire)
     }
 
+    /// Invalidates the pending alarm of the task, and returns the ticket for
+    /// the next one.
     #[inline]
     #[cfg(feature = "irq")]
-    pub(crate) fn set_in_timer_list(&self, in_timer_list: bool) {
-        self.in_timer_list.store(in_timer_list, Ordering::Release);
+    pub(crate) fn next_timer_ticket(&self) -> u64 {
+        self.timer_ticket.fetch_add(1, Ordering::AcqRel) + 1
     }
 
     #[inline]
@@ -297,16 +619,16 @@ impl TaskInner {
     fn current_check_preempt_pending() {
         let curr = crate::current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
-            let mut rq = crate::RUN_QUEUE.lock();
+            let rq = crate::current_run_queue();
             if curr.need_resched.load(Ordering::Acquire) {
                 rq.preempt_resched();
             }
         }
     }
 
-    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
+    pub(crate) fn notify_exit(&self, exit_code: i32) {
         self.exit_code.store(exit_code, Ordering::Release);
-        self.wait_for_exit.notify_all_locked(false, rq);
+        self.wait_for_exit.notify_all(false);
     }
 
     #[inline]
@@ -343,9 +665,25 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        TASK_TABLE.lock().remove(&self.id.as_u64());
     }
 }
 
+/// Returns the alive task with the given ID.
+#[cfg(feature = "sched_rt")]
+pub(crate) fn find_task(id: u64) -> Option<AxTaskRef> {
+    TASK_TABLE.lock().get(&id).and_then(|task| task.upgrade())
+}
+
+/// Returns references to all alive tasks, ordered by their IDs.
+pub(crate) fn all_tasks() -> Vec<AxTaskRef> {
+    TASK_TABLE
+        .lock()
+        .values()
+        .filter_map(|task| task.upgrade())
+        .collect()
+}
+
 struct TaskStack {
     ptr: NonNull<u8>,
     layout: Layout,
@@ -429,8 +767,11 @@ impl Deref for CurrentTask {
 }
 
 extern "C" fn task_entry() -> ! {
-    // release the lock that was implicitly held across the reschedule
-    unsafe { crate::RUN_QUEUE.force_unlock() };
+    // finish the context switch that was started by the previous task
+    #[cfg(feature = "smp")]
+    unsafe {
+        crate::run_queue::finish_task_switch()
+    }
     #[cfg(feature = "irq")]
     axhal::arch::enable_irqs();
     let task = crate::current();
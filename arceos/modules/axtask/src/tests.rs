@@ -128,3 +128,135 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    assert!(!axtask::set_affinity(axtask::CpuMask::new()));
+    assert_eq!(current().cpumask(), axtask::CpuMask::full());
+
+    assert!(axtask::set_affinity(axtask::CpuMask::one_shot(0)));
+    assert_eq!(current().cpumask(), axtask::CpuMask::one_shot(0));
+
+    // A new task inherits the affinity of its creator.
+    let task = axtask::spawn(|| {
+        assert_eq!(current().cpumask(), axtask::CpuMask::one_shot(0));
+        axtask::yield_now();
+    });
+    assert_eq!(task.cpumask(), axtask::CpuMask::one_shot(0));
+    assert_eq!(task.join(), Some(0));
+
+    assert!(axtask::set_affinity(axtask::CpuMask::full()));
+
+    // The affinity of a ready task takes effect before it runs.
+    let task = axtask::spawn(|| assert_eq!(axhal::cpu::this_cpu_id(), 0));
+    assert!(!axtask::set_task_affinity(&task, axtask::CpuMask::new()));
+    assert!(axtask::set_task_affinity(
+        &task,
+        axtask::CpuMask::one_shot(0)
+    ));
+    assert_eq!(task.cpumask(), axtask::CpuMask::one_shot(0));
+    assert_eq!(task.join(), Some(0));
+}
+
+#[test]
+fn test_task_stats() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let task = axtask::spawn(|| {
+        for _ in 0..3 {
+            axtask::yield_now();
+        }
+    });
+    assert_eq!(task.join(), Some(0));
+
+    // It switched away at least when exiting, and never ran in user space.
+    let stats = task.stats();
+    assert!(stats.voluntary_switches >= 1);
+    assert_eq!(stats.user_time, core::time::Duration::ZERO);
+    assert_eq!(stats.cpu_time(), stats.kernel_time);
+    assert!(stats.last_cpu < axconfig::SMP);
+}
+
+#[test]
+fn test_sched_params() {
+    use axtask::SchedPolicy;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let curr = current().as_task_ref().clone();
+    assert_eq!(curr.sched_policy(), SchedPolicy::Normal);
+    assert_eq!(curr.rt_priority(), 0);
+
+    assert!(!axtask::set_sched_params(&curr, SchedPolicy::Normal, 1));
+    assert!(!axtask::set_sched_params(&curr, SchedPolicy::Fifo, 0));
+    assert!(!axtask::set_sched_params(
+        &curr,
+        SchedPolicy::RoundRobin,
+        100
+    ));
+    // Real-time policies need the `sched_rt` scheduler.
+    assert_eq!(
+        axtask::set_sched_params(&curr, SchedPolicy::Fifo, 10),
+        cfg!(feature = "sched_rt")
+    );
+    assert!(axtask::set_sched_params(&curr, SchedPolicy::Normal, 0));
+    assert_eq!(curr.sched_policy(), SchedPolicy::Normal);
+    assert_eq!(curr.rt_priority(), 0);
+}
+
+#[test]
+#[cfg(feature = "sched_edf")]
+fn test_deadline_params() {
+    use axtask::DeadlineParams;
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let ms = Duration::from_millis;
+    let params = |runtime, deadline, period| DeadlineParams {
+        runtime: ms(runtime),
+        deadline: ms(deadline),
+        period: ms(period),
+    };
+    let curr = current().as_task_ref().clone();
+    assert_eq!(curr.deadline_params(), None);
+
+    // Not satisfying `0 < runtime <= deadline <= period`.
+    assert!(!axtask::set_deadline_params(&curr, Some(params(0, 10, 10))));
+    assert!(!axtask::set_deadline_params(
+        &curr,
+        Some(params(20, 10, 30))
+    ));
+    assert!(!axtask::set_deadline_params(
+        &curr,
+        Some(params(10, 30, 20))
+    ));
+
+    let ok = params(1, 5, 10);
+    assert!(axtask::set_deadline_params(&curr, Some(ok)));
+    assert_eq!(curr.deadline_params(), Some(ok));
+
+    // The rest of the CPU can not be reserved by another task, unless it can
+    // run on other CPUs.
+    let other = axtask::spawn(|| {});
+    assert_eq!(
+        axtask::set_deadline_params(&other, Some(params(90, 100, 100))),
+        axconfig::SMP > 1
+    );
+    assert!(axtask::set_deadline_params(&other, None));
+    assert!(other.set_cpumask(axtask::CpuMask::one_shot(curr.cpu_id())));
+    assert!(!axtask::set_deadline_params(
+        &other,
+        Some(params(90, 100, 100))
+    ));
+    assert_eq!(other.join(), Some(0));
+
+    assert!(axtask::set_deadline_params(&curr, None));
+    assert_eq!(curr.deadline_params(), None);
+}
@@ -1,40 +1,93 @@
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
+
 use axhal::time::wall_time;
-use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use timer_list::{TimeValue, TimerEvent, TimerList};
 
-use crate::{AxTaskRef, RUN_QUEUE};
+use crate::{AxTask, AxTaskRef};
 
-// TODO: per-CPU
-static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<TaskWakeupEvent>>> = LazyInit::new();
+// Each CPU has its own timer list, so that tasks are woken up on the CPUs
+// they sleep on.
+#[percpu::def_percpu]
+static TIMER_LIST: LazyInit<TimerList<TaskTimerEvent>> = LazyInit::new();
 
-struct TaskWakeupEvent(AxTaskRef);
+enum TaskTimerEvent {
+    /// Wakes up a sleeping task, which is kept alive by the event.
+    Wakeup { ticket: u64, task: AxTaskRef },
+    /// Wakes up a task waiting in a wait queue, which keeps the task alive
+    /// instead, so that a canceled alarm does not.
+    Timeout { ticket: u64, task: Weak<AxTask> },
+    /// Wakes up a throttled deadline task at its next period.
+    #[cfg(feature = "sched_edf")]
+    Replenish(AxTaskRef),
+}
 
-impl TimerEvent for TaskWakeupEvent {
+impl TimerEvent for TaskTimerEvent {
     fn callback(self, _now: TimeValue) {
-        let mut rq = RUN_QUEUE.lock();
-        self.0.set_in_timer_list(false);
-        rq.unblock_task(self.0, true);
+        match self {
+            Self::Wakeup { ticket, task } => {
+                // The alarm is canceled if the ticket of the task has changed.
+                if task.timer_ticket() == ticket {
+                    crate::run_queue::unblock_task(task, true);
+                }
+            }
+            Self::Timeout { ticket, task } => {
+                if let Some(task) = task.upgrade().filter(|t| t.timer_ticket() == ticket) {
+                    crate::run_queue::unblock_task(task, true);
+                }
+            }
+            #[cfg(feature = "sched_edf")]
+            Self::Replenish(task) => {
+                // It may have been woken up by a stale alarm.
+                if crate::sched_edf::is_throttled(&task) {
+                    crate::run_queue::unblock_task(task, true);
+                }
+            }
+        }
     }
 }
 
+/// Wakes up `task` at `deadline` on the current CPU.
+///
+/// IRQs must be disabled.
 pub fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
-    task.set_in_timer_list(true);
-    timers.set(deadline, TaskWakeupEvent(task));
+    let ticket = task.next_timer_ticket();
+    let timers = unsafe { TIMER_LIST.current_ref_mut_raw() };
+    timers.set(deadline, TaskTimerEvent::Wakeup { ticket, task });
+}
+
+/// Wakes up `task` at `deadline` on the current CPU, if it is still waiting in
+/// a wait queue by then.
+///
+/// IRQs must be disabled.
+pub fn set_alarm_timeout(deadline: TimeValue, task: &AxTaskRef) {
+    let ticket = task.next_timer_ticket();
+    let task = Arc::downgrade(task);
+    let timers = unsafe { TIMER_LIST.current_ref_mut_raw() };
+    timers.set(deadline, TaskTimerEvent::Timeout { ticket, task });
+}
+
+/// Wakes up the throttled deadline task `task` at `deadline` on the current
+/// CPU. It does not affect the other alarm of the task.
+///
+/// IRQs must be disabled.
+#[cfg(feature = "sched_edf")]
+pub fn set_alarm_replenish(deadline: TimeValue, task: AxTaskRef) {
+    let timers = unsafe { TIMER_LIST.current_ref_mut_raw() };
+    timers.set(deadline, TaskTimerEvent::Replenish(task));
 }
 
+/// Cancels the alarm of `task`, which stays in the timer list of its CPU
+/// until it expires, but does nothing. Only the alarms set by
+/// [`set_alarm_timeout`] are canceled, which do not keep the task alive.
 pub fn cancel_alarm(task: &AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
-    task.set_in_timer_list(false);
-    timers.cancel(|t| Arc::ptr_eq(&t.0, task));
+    task.next_timer_ticket();
 }
 
 pub fn check_events() {
     loop {
         let now = wall_time();
-        let event = TIMER_LIST.lock().expire_one(now);
+        let event = unsafe { TIMER_LIST.current_ref_mut_raw() }.expire_one(now);
         if let Some((_deadline, event)) = event {
             event.callback(now);
         } else {
@@ -44,5 +97,7 @@ pub fn check_events() {
 }
 
 pub fn init() {
-    TIMER_LIST.init_once(SpinNoIrq::new(TimerList::new()));
+    TIMER_LIST.with_current(|timers| {
+        timers.init_once(TimerList::new());
+    });
 }
@@ -2,5 +2,13 @@
 
 define unit_test
   $(call run_cmd,cargo test,-p axfs $(1) --features "myfs" -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "ext4fs" -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "initramfs" -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "overlay-root" -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "sched_rt" -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "sched_edf" -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "smp" -- --nocapture)
+  $(call run_cmd,cargo test,-p axsync $(1) --features "sched_rt" -- --nocapture)
   $(call run_cmd,cargo test,--workspace $(1) -- --nocapture)
+  $(call run_cmd,cargo test,--manifest-path tools/fattool/Cargo.toml $(1))
 endef
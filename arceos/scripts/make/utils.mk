@@ -18,8 +18,15 @@ define make_disk_image_fat32
   @mkfs.fat -F 32 $(1)
 endef
 
+define make_disk_image_ext2
+  @printf "    $(GREEN_C)Creating$(END_C) ext2 disk image \"$(1)\" ...\n"
+  @dd if=/dev/zero of=$(1) bs=1M count=64
+  @mkfs.ext2 -F $(1)
+endef
+
 define make_disk_image
   $(if $(filter $(1),fat32), $(call make_disk_image_fat32,$(2)))
+  $(if $(filter $(1),ext2), $(call make_disk_image_ext2,$(2)))
 endef
 
 define mk_pflash
@@ -43,6 +50,16 @@ define setup_disk
   @rm -rf mnt
 endef
 
+define make_initramfs
+  $(call build_origin)
+  @printf "    $(GREEN_C)Creating$(END_C) initramfs \"$(1)\" ...\n"
+  @rm -rf /tmp/initramfs && mkdir -p /tmp/initramfs/sbin
+  @cp /tmp/origin.bin /tmp/initramfs/sbin/origin.bin
+  @ln /tmp/initramfs/sbin/origin.bin /tmp/initramfs/sbin/origin
+  $(if $(2),@cp $(2) /tmp/initramfs/sbin)
+  @cd /tmp/initramfs && find . | cpio -o -H newc --quiet > $(abspath $(1))
+endef
+
 define build_origin
   @RUSTFLAGS="" cargo build -p origin  --target riscv64gc-unknown-none-elf --release
   @rust-objcopy --binary-architecture=riscv64 --strip-all -O binary ./target/riscv64gc-unknown-none-elf/release/origin /tmp/origin.bin
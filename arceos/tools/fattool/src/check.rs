@@ -0,0 +1,556 @@
+//! Consistency checks of FAT images.
+//!
+//! The checks are done on the raw image rather than through `fatfs`, so that
+//! corrupted structures are reported instead of being followed, e.g. cluster
+//! chains that loop or that are shared by several files.
+
+use std::fmt;
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LFN: u8 = 0x0f;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// The layout of a FAT volume, as described by its boot sector.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub fat_type: FatType,
+    pub bytes_per_sector: usize,
+    pub sectors_per_cluster: usize,
+    pub reserved_sectors: usize,
+    pub fats: usize,
+    pub sectors_per_fat: usize,
+    pub root_entries: usize,
+    pub total_sectors: usize,
+    /// The first cluster of the root directory, on FAT32 only.
+    pub root_cluster: u32,
+    /// The number of clusters in the data area.
+    pub clusters: u32,
+    /// The sector of the FSInfo structure, on FAT32 only.
+    fs_info_sector: usize,
+}
+
+fn u16_at(buf: &[u8], pos: usize) -> usize {
+    u16::from_le_bytes([buf[pos], buf[pos + 1]]) as usize
+}
+
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap())
+}
+
+impl Layout {
+    /// Parses and validates the boot sector of `image`.
+    pub fn parse(image: &[u8]) -> Result<Self, String> {
+        if image.len() < 512 {
+            return Err("the image is smaller than a boot sector".into());
+        }
+        if image[510..512] != [0x55, 0xaa] {
+            return Err("the boot sector has no boot signature".into());
+        }
+        let bytes_per_sector = u16_at(image, 0x0b);
+        if ![512, 1024, 2048, 4096].contains(&bytes_per_sector) {
+            return Err(format!("invalid sector size {}", bytes_per_sector));
+        }
+        let sectors_per_cluster = image[0x0d] as usize;
+        if !sectors_per_cluster.is_power_of_two() {
+            return Err(format!("invalid cluster size {}", sectors_per_cluster));
+        }
+        let reserved_sectors = u16_at(image, 0x0e);
+        let fats = image[0x10] as usize;
+        if reserved_sectors == 0 || fats == 0 {
+            return Err("no reserved sectors or no FAT".into());
+        }
+        let root_entries = u16_at(image, 0x11);
+        let total_sectors = match u16_at(image, 0x13) {
+            0 => u32_at(image, 0x20) as usize,
+            sectors => sectors,
+        };
+        if total_sectors == 0 {
+            return Err("the volume has no sectors".into());
+        }
+        if total_sectors * bytes_per_sector > image.len() {
+            return Err(format!(
+                "the volume has {} bytes, but the image has only {}",
+                total_sectors * bytes_per_sector,
+                image.len()
+            ));
+        }
+        let sectors_per_fat = match u16_at(image, 0x16) {
+            0 => u32_at(image, 0x24) as usize,
+            sectors => sectors,
+        };
+        if sectors_per_fat == 0 {
+            return Err("the FAT has no sectors".into());
+        }
+
+        let root_dir_sectors = (root_entries * DIR_ENTRY_SIZE).div_ceil(bytes_per_sector);
+        let first_data_sector = reserved_sectors + fats * sectors_per_fat + root_dir_sectors;
+        if first_data_sector >= total_sectors {
+            return Err("the volume has no data area".into());
+        }
+        let clusters = ((total_sectors - first_data_sector) / sectors_per_cluster) as u32;
+        let fat_type = match clusters {
+            0..=4084 => FatType::Fat12,
+            4085..=65524 => FatType::Fat16,
+            _ => FatType::Fat32,
+        };
+
+        let mut layout = Self {
+            fat_type,
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors,
+            fats,
+            sectors_per_fat,
+            root_entries,
+            total_sectors,
+            root_cluster: 0,
+            clusters,
+            fs_info_sector: 0,
+        };
+        let entry_bits = match fat_type {
+            FatType::Fat12 => 12,
+            FatType::Fat16 => 16,
+            FatType::Fat32 => 32,
+        };
+        if sectors_per_fat * bytes_per_sector * 8 / entry_bits < clusters as usize + 2 {
+            return Err("the FAT is too small for the data area".into());
+        }
+        if fat_type == FatType::Fat32 {
+            if root_entries != 0 {
+                return Err("a FAT32 volume has a fixed root directory".into());
+            }
+            layout.root_cluster = u32_at(image, 0x2c);
+            if !layout.is_data_cluster(layout.root_cluster) {
+                return Err(format!("invalid root cluster {}", layout.root_cluster));
+            }
+            layout.fs_info_sector = u16_at(image, 0x30);
+        }
+        Ok(layout)
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster * self.bytes_per_sector
+    }
+
+    fn fat_range(&self, fat: usize) -> std::ops::Range<usize> {
+        let fat_size = self.sectors_per_fat * self.bytes_per_sector;
+        let start = self.reserved_sectors * self.bytes_per_sector + fat * fat_size;
+        start..start + fat_size
+    }
+
+    /// The byte range of the fixed root directory of FAT12 and FAT16.
+    fn root_dir_range(&self) -> std::ops::Range<usize> {
+        let start = self.fat_range(self.fats - 1).end;
+        start..start + self.root_entries * DIR_ENTRY_SIZE
+    }
+
+    fn cluster_range(&self, cluster: u32) -> std::ops::Range<usize> {
+        let data_start = self
+            .root_dir_range()
+            .end
+            .next_multiple_of(self.bytes_per_sector);
+        let start = data_start + (cluster as usize - 2) * self.cluster_size();
+        start..start + self.cluster_size()
+    }
+
+    fn is_data_cluster(&self, cluster: u32) -> bool {
+        (2..self.clusters + 2).contains(&cluster)
+    }
+
+    /// The FAT entry value marking a bad cluster. Greater values mark the
+    /// end of a chain.
+    fn bad_cluster(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat12 => 0xff7,
+            FatType::Fat16 => 0xfff7,
+            FatType::Fat32 => 0x0fff_fff7,
+        }
+    }
+
+    /// Reads the entry of `cluster` in the FAT number `fat`.
+    pub fn entry(&self, image: &[u8], fat: usize, cluster: u32) -> u32 {
+        let table = &image[self.fat_range(fat)];
+        let cluster = cluster as usize;
+        match self.fat_type {
+            FatType::Fat12 => {
+                let pair = u16_at(table, cluster + cluster / 2) as u32;
+                if cluster % 2 == 0 {
+                    pair & 0xfff
+                } else {
+                    pair >> 4
+                }
+            }
+            FatType::Fat16 => u16_at(table, cluster * 2) as u32,
+            FatType::Fat32 => u32_at(table, cluster * 4) & 0x0fff_ffff,
+        }
+    }
+
+    /// Writes the entry of `cluster` in all FATs.
+    pub fn set_entry(&self, image: &mut [u8], cluster: u32, value: u32) {
+        for fat in 0..self.fats {
+            let table = &mut image[self.fat_range(fat)];
+            let cluster = cluster as usize;
+            match self.fat_type {
+                FatType::Fat12 => {
+                    let pos = cluster + cluster / 2;
+                    let pair = u16_at(table, pos) as u32;
+                    let pair = if cluster % 2 == 0 {
+                        (pair & 0xf000) | (value & 0xfff)
+                    } else {
+                        (pair & 0x000f) | ((value & 0xfff) << 4)
+                    };
+                    table[pos..pos + 2].copy_from_slice(&(pair as u16).to_le_bytes());
+                }
+                FatType::Fat16 => table[cluster * 2..cluster * 2 + 2]
+                    .copy_from_slice(&(value as u16).to_le_bytes()),
+                FatType::Fat32 => {
+                    // the upper 4 bits are reserved, and must be preserved
+                    let old = u32_at(table, cluster * 4) & 0xf000_0000;
+                    let value = old | (value & 0x0fff_ffff);
+                    table[cluster * 4..cluster * 4 + 4].copy_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// An inconsistency found in an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The cluster chain of `path` leaves the valid clusters at `cluster`.
+    BrokenChain {
+        path: String,
+        cluster: u32,
+        reason: &'static str,
+    },
+    /// The cluster chain of `path` loops back to `cluster`.
+    Loop { path: String, cluster: u32 },
+    /// `cluster` is used by both `path` and `other`.
+    CrossLinked {
+        path: String,
+        other: String,
+        cluster: u32,
+    },
+    /// The size of `path` does not match the length of its cluster chain.
+    SizeMismatch {
+        path: String,
+        size: u64,
+        clusters: usize,
+    },
+    /// A directory has no clusters.
+    EmptyDirectory { path: String },
+    /// A backup FAT differs from the first one.
+    FatMismatch { fat: usize },
+    /// Clusters are allocated, but used by no file or directory.
+    LostClusters { clusters: Vec<u32> },
+}
+
+impl Problem {
+    /// Whether [`repair`] fixes the problem.
+    pub fn is_repairable(&self) -> bool {
+        matches!(self, Self::FatMismatch { .. } | Self::LostClusters { .. })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BrokenChain {
+                path,
+                cluster,
+                reason,
+            } => write!(f, "{}: cluster {} {}", path, cluster, reason),
+            Self::Loop { path, cluster } => {
+                write!(f, "{}: cluster chain loops back to {}", path, cluster)
+            }
+            Self::CrossLinked {
+                path,
+                other,
+                cluster,
+            } => write!(f, "{}: cluster {} is also used by {}", path, cluster, other),
+            Self::SizeMismatch {
+                path,
+                size,
+                clusters,
+            } => write!(
+                f,
+                "{}: size {} does not fit in {} clusters",
+                path, size, clusters
+            ),
+            Self::EmptyDirectory { path } => write!(f, "{}: directory has no clusters", path),
+            Self::FatMismatch { fat } => write!(f, "FAT #{} differs from FAT #0", fat),
+            Self::LostClusters { clusters } => {
+                write!(f, "{} clusters are allocated but unused", clusters.len())
+            }
+        }
+    }
+}
+
+/// The result of checking an image.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub files: usize,
+    pub dirs: usize,
+    pub used_clusters: usize,
+    pub problems: Vec<Problem>,
+}
+
+struct Checker<'a> {
+    layout: &'a Layout,
+    image: &'a [u8],
+    /// The path owning each cluster, as an index into `paths`.
+    owners: Vec<Option<usize>>,
+    paths: Vec<String>,
+    report: Report,
+}
+
+/// Checks the consistency of the FAT volume in `image`.
+pub fn check(layout: &Layout, image: &[u8]) -> Report {
+    let mut checker = Checker {
+        layout,
+        image,
+        owners: vec![None; layout.clusters as usize + 2],
+        paths: Vec::new(),
+        report: Report::default(),
+    };
+
+    if layout.fat_type == FatType::Fat32 {
+        let (chain, _) = checker.follow_chain("/", layout.root_cluster);
+        checker.check_dir("/", &chain);
+    } else {
+        checker.check_entries("/", &image[layout.root_dir_range()]);
+    }
+
+    let mut lost = Vec::new();
+    for cluster in 2..layout.clusters + 2 {
+        let entry = layout.entry(image, 0, cluster);
+        if entry != 0 && entry != layout.bad_cluster() && checker.owners[cluster as usize].is_none()
+        {
+            lost.push(cluster);
+        }
+    }
+    let mut report = checker.report;
+    if !lost.is_empty() {
+        report
+            .problems
+            .push(Problem::LostClusters { clusters: lost });
+    }
+    for fat in 1..layout.fats {
+        if image[layout.fat_range(fat)] != image[layout.fat_range(0)] {
+            report.problems.push(Problem::FatMismatch { fat });
+        }
+    }
+    report
+}
+
+impl Checker<'_> {
+    /// Follows the cluster chain of `path` from `first`, and marks its
+    /// clusters as used. Returns the clusters, and whether the chain is
+    /// complete.
+    fn follow_chain(&mut self, path: &str, first: u32) -> (Vec<u32>, bool) {
+        let idx = self.paths.len();
+        self.paths.push(path.into());
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        loop {
+            if !self.layout.is_data_cluster(cluster) {
+                self.problem(Problem::BrokenChain {
+                    path: path.into(),
+                    cluster,
+                    reason: "is out of the data area",
+                });
+                return (chain, false);
+            }
+            match self.owners[cluster as usize] {
+                Some(owner) if owner == idx => {
+                    self.problem(Problem::Loop {
+                        path: path.into(),
+                        cluster,
+                    });
+                    return (chain, false);
+                }
+                Some(owner) => {
+                    self.problem(Problem::CrossLinked {
+                        path: path.into(),
+                        other: self.paths[owner].clone(),
+                        cluster,
+                    });
+                    return (chain, false);
+                }
+                None => {}
+            }
+            self.owners[cluster as usize] = Some(idx);
+            self.report.used_clusters += 1;
+            chain.push(cluster);
+
+            let next = self.layout.entry(self.image, 0, cluster);
+            let reason = match next {
+                0 => "is followed by a free cluster",
+                1 => "is followed by a reserved cluster",
+                next if next == self.layout.bad_cluster() => "is followed by a bad cluster",
+                next if next > self.layout.bad_cluster() => return (chain, true),
+                next => {
+                    cluster = next;
+                    continue;
+                }
+            };
+            self.problem(Problem::BrokenChain {
+                path: path.into(),
+                cluster,
+                reason,
+            });
+            return (chain, false);
+        }
+    }
+
+    fn check_dir(&mut self, path: &str, chain: &[u32]) {
+        let mut entries = Vec::with_capacity(chain.len() * self.layout.cluster_size());
+        for &cluster in chain {
+            entries.extend_from_slice(&self.image[self.layout.cluster_range(cluster)]);
+        }
+        self.check_entries(path, &entries);
+    }
+
+    fn check_entries(&mut self, path: &str, entries: &[u8]) {
+        let mut long_name = Vec::new();
+        for entry in entries.chunks_exact(DIR_ENTRY_SIZE) {
+            match entry[0] {
+                0x00 => break,
+                0xe5 => {
+                    long_name.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            let attr = entry[11];
+            if attr & ATTR_LFN == ATTR_LFN {
+                push_long_name_part(&mut long_name, entry);
+                continue;
+            }
+            let name = if long_name.is_empty() {
+                short_name(entry)
+            } else {
+                String::from_utf16_lossy(&long_name)
+            };
+            long_name.clear();
+            if attr & ATTR_VOLUME_ID != 0 || name == "." || name == ".." {
+                continue;
+            }
+
+            let child = format!("{}/{}", path.trim_end_matches('/'), name);
+            let mut first = u16_at(entry, 0x1a) as u32;
+            if self.layout.fat_type == FatType::Fat32 {
+                first |= (u16_at(entry, 0x14) as u32) << 16;
+            }
+            let size = u32_at(entry, 0x1c) as u64;
+            if attr & ATTR_DIRECTORY != 0 {
+                self.report.dirs += 1;
+                if first == 0 {
+                    self.problem(Problem::EmptyDirectory { path: child });
+                } else {
+                    // clusters are followed only once, so that loops of
+                    // directories are not descended into
+                    let (chain, _) = self.follow_chain(&child, first);
+                    self.check_dir(&child, &chain);
+                }
+            } else {
+                self.report.files += 1;
+                let (clusters, complete) = if first == 0 {
+                    (0, true)
+                } else {
+                    let (chain, complete) = self.follow_chain(&child, first);
+                    (chain.len(), complete)
+                };
+                let cluster_size = self.layout.cluster_size() as u64;
+                if complete && size.div_ceil(cluster_size) != clusters as u64 {
+                    self.problem(Problem::SizeMismatch {
+                        path: child,
+                        size,
+                        clusters,
+                    });
+                }
+            }
+        }
+    }
+
+    fn problem(&mut self, problem: Problem) {
+        self.report.problems.push(problem);
+    }
+}
+
+/// Returns the 8.3 name of a directory entry.
+fn short_name(entry: &[u8]) -> String {
+    let mut base = entry[..8].to_vec();
+    if base[0] == 0x05 {
+        base[0] = 0xe5; // a name starting with 0xe5, which marks free entries
+    }
+    let base = String::from_utf8_lossy(&base).trim_end().to_string();
+    let ext = String::from_utf8_lossy(&entry[8..11])
+        .trim_end()
+        .to_string();
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, ext)
+    }
+}
+
+/// Prepends the characters of a long file name entry to `long_name`, since
+/// the entries are stored in reverse order.
+fn push_long_name_part(long_name: &mut Vec<u16>, entry: &[u8]) {
+    if entry[0] & 0x40 != 0 {
+        long_name.clear(); // the last part, which comes first
+    }
+    let part = [1..11, 14..26, 28..32]
+        .into_iter()
+        .flat_map(|range| entry[range].chunks_exact(2))
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0 && c != 0xffff)
+        .collect::<Vec<_>>();
+    long_name.splice(0..0, part);
+}
+
+/// Fixes the repairable problems of `report`: frees the lost clusters, and
+/// copies the first FAT to the backup ones. Returns the number of fixed
+/// problems.
+pub fn repair(layout: &Layout, image: &mut [u8], report: &Report) -> usize {
+    let mut fixed = 0;
+    for problem in &report.problems {
+        if let Problem::LostClusters { clusters } = problem {
+            for &cluster in clusters {
+                layout.set_entry(image, cluster, 0);
+            }
+            invalidate_free_count(layout, image);
+            fixed += 1;
+        }
+    }
+    let first = image[layout.fat_range(0)].to_vec();
+    for problem in &report.problems {
+        if let Problem::FatMismatch { fat } = problem {
+            image[layout.fat_range(*fat)].copy_from_slice(&first);
+            fixed += 1;
+        }
+    }
+    fixed
+}
+
+/// Marks the free cluster count of the FAT32 FSInfo sector as unknown, so
+/// that it is computed again on the next mount.
+fn invalidate_free_count(layout: &Layout, image: &mut [u8]) {
+    if layout.fat_type != FatType::Fat32 || layout.fs_info_sector == 0 {
+        return;
+    }
+    let start = layout.fs_info_sector * layout.bytes_per_sector;
+    let fs_info = &mut image[start..start + 512];
+    if u32_at(fs_info, 0) == 0x4161_5252 && u32_at(fs_info, 0x1e4) == 0x6141_7272 {
+        fs_info[0x1e8..0x1ec].copy_from_slice(&u32::MAX.to_le_bytes());
+    }
+}
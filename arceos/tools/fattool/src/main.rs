@@ -0,0 +1,263 @@
+//! A host tool to check the FAT disk images used by ArceOS, and to list,
+//! extract and inject their files without mounting them.
+
+mod check;
+#[cfg(test)]
+mod tests;
+
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use fatfs::{FsOptions, Read, StdIoWrapper, Write};
+
+use check::Layout;
+
+type FileSystem = fatfs::FileSystem<StdIoWrapper<std::fs::File>>;
+type Dir<'a> = fatfs::Dir<'a, StdIoWrapper<std::fs::File>>;
+type Result<T = ()> = std::result::Result<T, String>;
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Check, list, extract and inject files of FAT disk images"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check the consistency of an image
+    Check {
+        image: PathBuf,
+        /// Free lost clusters and restore the backup FATs from the first one
+        #[arg(short, long)]
+        repair: bool,
+    },
+    /// List the files of a directory in an image
+    Ls {
+        image: PathBuf,
+        #[arg(default_value = "/")]
+        path: String,
+        /// List subdirectories recursively
+        #[arg(short = 'R', long)]
+        recursive: bool,
+    },
+    /// Copy a file or directory out of an image
+    Extract {
+        image: PathBuf,
+        path: String,
+        dest: PathBuf,
+    },
+    /// Copy a host file or directory into an image, creating missing parent
+    /// directories
+    Inject {
+        image: PathBuf,
+        src: PathBuf,
+        path: String,
+    },
+}
+
+fn main() -> ExitCode {
+    let res = match Cli::parse().command {
+        Command::Check { image, repair } => check_image(&image, repair),
+        Command::Ls {
+            image,
+            path,
+            recursive,
+        } => open(&image, false).and_then(|fs| list(&fs.root_dir(), &path, recursive)),
+        Command::Extract { image, path, dest } => {
+            open(&image, false).and_then(|fs| extract(&fs.root_dir(), &path, &dest))
+        }
+        Command::Inject { image, src, path } => open(&image, true).and_then(|fs| {
+            inject(&fs.root_dir(), &src, &path)?;
+            fs.unmount().map_err(fat_err)
+        }),
+    };
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn fat_err(e: fatfs::Error<std::io::Error>) -> String {
+    format!("{:?}", e)
+}
+
+/// Returns the components of `path` in the image, which is always relative
+/// to the root directory.
+fn image_path(path: &str) -> String {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn open_dir<'a>(root: &Dir<'a>, path: &str) -> Result<Dir<'a>> {
+    let path = image_path(path);
+    if path.is_empty() {
+        return Ok(root.clone());
+    }
+    root.open_dir(&path)
+        .map_err(|e| format!("cannot open directory {:?}: {}", path, fat_err(e)))
+}
+
+/// Opens the filesystem in `image`. Images to be written must pass the
+/// consistency checks first, so that corruptions do not spread.
+fn open(image: &Path, writable: bool) -> Result<FileSystem> {
+    if writable {
+        let data = std::fs::read(image).map_err(|e| e.to_string())?;
+        let layout = Layout::parse(&data)?;
+        if !check::check(&layout, &data).problems.is_empty() {
+            return Err("the image is inconsistent, run `fattool check` first".into());
+        }
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(writable)
+        .open(image)
+        .map_err(|e| format!("cannot open {:?}: {}", image, e))?;
+    fatfs::FileSystem::new(file, FsOptions::new()).map_err(fat_err)
+}
+
+fn check_image(image: &Path, repair: bool) -> Result {
+    let mut data = std::fs::read(image).map_err(|e| e.to_string())?;
+    let layout = Layout::parse(&data)?;
+    let mut report = check::check(&layout, &data);
+    println!(
+        "{:?}, {} clusters of {} bytes, {} used",
+        layout.fat_type,
+        layout.clusters,
+        layout.cluster_size(),
+        report.used_clusters
+    );
+    println!("{} files, {} directories", report.files, report.dirs);
+    for problem in &report.problems {
+        println!("{}", problem);
+    }
+
+    if repair && report.problems.iter().any(|p| p.is_repairable()) {
+        let fixed = check::repair(&layout, &mut data, &report);
+        std::fs::write(image, &data).map_err(|e| e.to_string())?;
+        println!("{} problems repaired", fixed);
+        report = check::check(&layout, &data);
+    }
+    match report.problems.len() {
+        0 => {
+            println!("{:?} is clean", image);
+            Ok(())
+        }
+        n if report.problems.iter().all(|p| p.is_repairable()) => Err(format!(
+            "{} problems found, run with `--repair` to fix them",
+            n
+        )),
+        n => Err(format!("{} problems found", n)),
+    }
+}
+
+fn list(root: &Dir, path: &str, recursive: bool) -> Result {
+    let dir = open_dir(root, path)?;
+    let mut subdirs = Vec::new();
+    for entry in dir.iter() {
+        let entry = entry.map_err(fat_err)?;
+        let name = entry.file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+        let child = format!("{}/{}", image_path(path), name);
+        let modified = entry.modified();
+        println!(
+            "{} {:>10} {:04}-{:02}-{:02} {:02}:{:02} /{}",
+            if entry.is_dir() { 'd' } else { '-' },
+            entry.len(),
+            modified.date.year,
+            modified.date.month,
+            modified.date.day,
+            modified.time.hour,
+            modified.time.min,
+            child.trim_start_matches('/'),
+        );
+        if entry.is_dir() {
+            subdirs.push(child);
+        }
+    }
+    if recursive {
+        for subdir in subdirs {
+            list(root, &subdir, true)?;
+        }
+    }
+    Ok(())
+}
+
+fn extract(root: &Dir, path: &str, dest: &Path) -> Result {
+    let path = image_path(path);
+    if path.is_empty() || root.open_dir(&path).is_ok() {
+        std::fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+        for entry in open_dir(root, &path)?.iter() {
+            let name = entry.map_err(fat_err)?.file_name();
+            if name != "." && name != ".." {
+                extract(root, &format!("{}/{}", path, name), &dest.join(&name))?;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut file = root
+        .open_file(&path)
+        .map_err(|e| format!("cannot open {:?}: {}", path, fat_err(e)))?;
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        match file.read(&mut buf).map_err(fat_err)? {
+            0 => break,
+            n => data.extend_from_slice(&buf[..n]),
+        }
+    }
+    std::fs::write(dest, data).map_err(|e| format!("cannot write {:?}: {}", dest, e))
+}
+
+fn inject(root: &Dir, src: &Path, path: &str) -> Result {
+    let mut path = image_path(path);
+    // like `cp`, copy into an existing directory
+    if path.is_empty() || root.open_dir(&path).is_ok() {
+        let name = src
+            .file_name()
+            .ok_or_else(|| format!("invalid source {:?}", src))?;
+        path = image_path(&format!("{}/{}", path, name.to_string_lossy()));
+    }
+    inject_at(root, src, &path)
+}
+
+/// Copies `src` to `path` in the image, which is relative to the root.
+fn inject_at(root: &Dir, src: &Path, path: &str) -> Result {
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let mut dir = root.clone();
+    for component in parent.split('/').filter(|c| !c.is_empty()) {
+        dir = dir.create_dir(component).map_err(fat_err)?;
+    }
+
+    if src.is_dir() {
+        dir.create_dir(name).map_err(fat_err)?;
+        let entries = std::fs::read_dir(src).map_err(|e| e.to_string())?;
+        for entry in entries {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let child = format!("{}/{}", path, entry.file_name().to_string_lossy());
+            inject_at(root, &entry.path(), &child)?;
+        }
+        return Ok(());
+    }
+
+    let data = std::fs::read(src).map_err(|e| format!("cannot read {:?}: {}", src, e))?;
+    let mut file = dir.create_file(name).map_err(fat_err)?;
+    file.truncate().map_err(fat_err)?;
+    file.write_all(&data).map_err(fat_err)?;
+    file.flush().map_err(fat_err)?;
+    println!("{:?} -> /{}", src, path);
+    Ok(())
+}
@@ -0,0 +1,286 @@
+//! Tests on small images created in the temporary directory.
+
+use std::fs::{self, File};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use fatfs::{FormatVolumeOptions, StdIoWrapper, Write};
+
+use crate::check::{self, FatType, Layout, Problem};
+use crate::{extract, inject, open};
+
+const MB: u64 = 1024 * 1024;
+
+/// A path in the temporary directory, removed when dropped.
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> Self {
+        let name = format!("fattool-{}-{}", std::process::id(), name);
+        Self(std::env::temp_dir().join(name))
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = fs::remove_dir_all(&self.0);
+        } else {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+}
+
+/// Creates an image of `size` bytes, formatted as `fat_type`.
+fn format(name: &str, size: u64, fat_type: fatfs::FatType) -> TempPath {
+    let image = TempPath::new(name);
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&*image)
+        .unwrap();
+    file.set_len(size).unwrap();
+    let opts = FormatVolumeOptions::new().fat_type(fat_type);
+    fatfs::format_volume(&mut StdIoWrapper::new(file), opts).unwrap();
+    image
+}
+
+/// Creates a file in the root directory of `image`.
+fn add_file(image: &Path, name: &str, data: &[u8]) {
+    let fs = open(image, true).unwrap();
+    let root = fs.root_dir();
+    let mut file = root.create_file(name).unwrap();
+    file.write_all(data).unwrap();
+    file.flush().unwrap();
+    drop(file);
+    drop(root);
+    fs.unmount().unwrap();
+}
+
+/// Returns the cluster chain starting from `first`.
+fn chain(layout: &Layout, data: &[u8], first: u32) -> Vec<u32> {
+    let mut chain = vec![first];
+    loop {
+        match layout.entry(data, 0, *chain.last().unwrap()) {
+            next if next >= 0xfff8 => return chain,
+            next => chain.push(next),
+        }
+    }
+}
+
+/// A FAT16 image with `A.TXT` of 3 clusters and `B.TXT` of 1 cluster, and
+/// their cluster chains.
+fn two_files(name: &str) -> (TempPath, Layout, Vec<u8>, Vec<u32>, Vec<u32>) {
+    let image = format(name, 8 * MB, fatfs::FatType::Fat16);
+    let cluster_size = {
+        let data = fs::read(&*image).unwrap();
+        Layout::parse(&data).unwrap().cluster_size()
+    };
+    add_file(&image, "A.TXT", &vec![b'a'; cluster_size * 2 + 100]);
+    add_file(&image, "B.TXT", b"b");
+
+    let data = fs::read(&*image).unwrap();
+    let layout = Layout::parse(&data).unwrap();
+    // clusters are allocated from the start of the data area
+    let a = chain(&layout, &data, 2);
+    assert_eq!(a.len(), 3);
+    let b = chain(&layout, &data, a[2] + 1);
+    assert_eq!(b.len(), 1);
+    (image, layout, data, a, b)
+}
+
+#[test]
+fn parse_formatted_images() {
+    for (name, size, fat_type, expected) in [
+        ("fat12.img", MB, fatfs::FatType::Fat12, FatType::Fat12),
+        ("fat16.img", 8 * MB, fatfs::FatType::Fat16, FatType::Fat16),
+        ("fat32.img", 40 * MB, fatfs::FatType::Fat32, FatType::Fat32),
+    ] {
+        let image = format(name, size, fat_type);
+        let data = fs::read(&*image).unwrap();
+        let layout = Layout::parse(&data).unwrap();
+        assert_eq!(layout.fat_type, expected);
+        assert_eq!(
+            layout.total_sectors * layout.bytes_per_sector,
+            size as usize
+        );
+
+        let report = check::check(&layout, &data);
+        assert!(report.problems.is_empty());
+        assert_eq!((report.files, report.dirs), (0, 0));
+    }
+}
+
+#[test]
+fn parse_invalid_boot_sectors() {
+    let image = format("invalid.img", 8 * MB, fatfs::FatType::Fat16);
+    let data = fs::read(&*image).unwrap();
+
+    assert!(Layout::parse(&data[..256]).is_err());
+    // the volume is larger than the image
+    assert!(Layout::parse(&data[..data.len() / 2]).is_err());
+
+    let mut corrupted = data.clone();
+    corrupted[510] = 0;
+    assert!(Layout::parse(&corrupted).is_err());
+
+    let mut corrupted = data.clone();
+    corrupted[0x0b..0x0d].copy_from_slice(&100u16.to_le_bytes());
+    assert!(Layout::parse(&corrupted).is_err());
+
+    let mut corrupted = data.clone();
+    corrupted[0x0d] = 3; // sectors per cluster
+    assert!(Layout::parse(&corrupted).is_err());
+
+    let mut corrupted = data;
+    corrupted[0x10] = 0; // number of FATs
+    assert!(Layout::parse(&corrupted).is_err());
+}
+
+#[test]
+fn check_clean_image() {
+    let (_image, layout, data, a, b) = two_files("clean.img");
+    let report = check::check(&layout, &data);
+    assert!(report.problems.is_empty());
+    assert_eq!((report.files, report.dirs), (2, 0));
+    assert_eq!(report.used_clusters, a.len() + b.len());
+}
+
+#[test]
+fn check_loop() {
+    let (_image, layout, mut data, a, _) = two_files("loop.img");
+    layout.set_entry(&mut data, a[2], a[0]);
+    assert_eq!(
+        check::check(&layout, &data).problems,
+        [Problem::Loop {
+            path: "/A.TXT".into(),
+            cluster: a[0],
+        }]
+    );
+}
+
+#[test]
+fn check_broken_chain() {
+    let (_image, layout, mut data, a, _) = two_files("broken.img");
+    layout.set_entry(&mut data, a[1], 0);
+    assert_eq!(
+        check::check(&layout, &data).problems,
+        [
+            Problem::BrokenChain {
+                path: "/A.TXT".into(),
+                cluster: a[1],
+                reason: "is followed by a free cluster",
+            },
+            Problem::LostClusters {
+                clusters: vec![a[2]]
+            },
+        ]
+    );
+
+    layout.set_entry(&mut data, a[1], layout.clusters + 2);
+    let problems = check::check(&layout, &data).problems;
+    assert!(matches!(
+        &problems[0],
+        Problem::BrokenChain {
+            reason: "is out of the data area",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn check_cross_link() {
+    let (_image, layout, mut data, a, b) = two_files("cross.img");
+    layout.set_entry(&mut data, a[2], b[0]);
+    assert_eq!(
+        check::check(&layout, &data).problems,
+        [
+            Problem::SizeMismatch {
+                path: "/A.TXT".into(),
+                size: layout.cluster_size() as u64 * 2 + 100,
+                clusters: 4,
+            },
+            Problem::CrossLinked {
+                path: "/B.TXT".into(),
+                other: "/A.TXT".into(),
+                cluster: b[0],
+            },
+        ]
+    );
+}
+
+#[test]
+fn repair_lost_clusters_and_fats() {
+    let (image, layout, mut data, _, _) = two_files("repair.img");
+    layout.set_entry(&mut data, 100, 0xffff);
+    // corrupt the entry of cluster 200 in the second FAT only
+    let fat_size = layout.sectors_per_fat * layout.bytes_per_sector;
+    let second_fat = layout.reserved_sectors * layout.bytes_per_sector + fat_size;
+    data[second_fat + 200 * 2] = 0x42;
+
+    let report = check::check(&layout, &data);
+    assert_eq!(
+        report.problems,
+        [
+            Problem::LostClusters {
+                clusters: vec![100]
+            },
+            Problem::FatMismatch { fat: 1 },
+        ]
+    );
+    assert!(report.problems.iter().all(|p| p.is_repairable()));
+    // corrupted images are not written to
+    fs::write(&*image, &data).unwrap();
+    assert!(open(&image, true).is_err());
+
+    assert_eq!(check::repair(&layout, &mut data, &report), 2);
+    assert!(check::check(&layout, &data).problems.is_empty());
+    assert_eq!(layout.entry(&data, 0, 100), 0);
+    assert_eq!(layout.entry(&data, 1, 200), 0);
+}
+
+#[test]
+fn inject_and_extract() {
+    let image = format("roundtrip.img", 8 * MB, fatfs::FatType::Fat16);
+    let src = TempPath::new("src");
+    let long = (0..5000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("hello.txt"), "Rust is cool!\n").unwrap();
+    fs::write(src.join("sub/long.bin"), &long).unwrap();
+
+    let fat = open(&image, true).unwrap();
+    // a new directory, into an existing directory, and with new parents
+    inject(&fat.root_dir(), &src, "/apps").unwrap();
+    inject(&fat.root_dir(), &src.join("hello.txt"), "/").unwrap();
+    inject(&fat.root_dir(), &src.join("sub/long.bin"), "/a/b/c.bin").unwrap();
+    fat.unmount().unwrap();
+
+    let data = fs::read(&*image).unwrap();
+    let report = check::check(&Layout::parse(&data).unwrap(), &data);
+    assert!(report.problems.is_empty());
+    assert_eq!((report.files, report.dirs), (4, 4));
+
+    let dest = TempPath::new("dest");
+    let fat = open(&image, false).unwrap();
+    extract(&fat.root_dir(), "/apps", &dest).unwrap();
+    assert_eq!(
+        fs::read_to_string(dest.join("hello.txt")).unwrap(),
+        "Rust is cool!\n"
+    );
+    assert_eq!(fs::read(dest.join("sub/long.bin")).unwrap(), long);
+
+    let file = TempPath::new("c.bin");
+    extract(&fat.root_dir(), "/a/b/c.bin", &file).unwrap();
+    assert_eq!(fs::read(&*file).unwrap(), long);
+    assert!(extract(&fat.root_dir(), "/missing", &file).is_err());
+}
@@ -67,5 +67,7 @@ pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> Ax
     task.ctx_mut()
         .set_page_table_root(aspace.lock().page_table_root());
     task.init_task_ext(TaskExt::new(uctx, aspace));
+    // threads of the process share the current directory
+    axfs::FsContext::new_shared().install(&task);
     axtask::spawn_task(task)
 }
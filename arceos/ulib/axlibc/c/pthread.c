@@ -4,6 +4,8 @@
 #include <limits.h>
 #include <pthread.h>
 #include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
 #include <unistd.h>
 
 int pthread_setcancelstate(int new, int *old)
@@ -107,4 +109,46 @@ int pthread_attr_setstacksize(pthread_attr_t *a, size_t size)
     return 0;
 }
 
+int pthread_attr_destroy(pthread_attr_t *a)
+{
+    free((void *)a->_a_cpuset);
+    a->_a_cpuset = 0;
+    a->_a_cpusetsize = 0;
+    return 0;
+}
+
+int pthread_attr_setaffinity_np(pthread_attr_t *a, size_t size, const cpu_set_t *set)
+{
+    void *copy = NULL;
+    if (size && set) {
+        copy = malloc(size);
+        if (!copy)
+            return ENOMEM;
+        memcpy(copy, set, size);
+    } else {
+        size = 0;
+    }
+    free((void *)a->_a_cpuset);
+    a->_a_cpuset = (unsigned long)copy;
+    a->_a_cpusetsize = size;
+    return 0;
+}
+
+int pthread_attr_getaffinity_np(const pthread_attr_t *a, size_t size, cpu_set_t *set)
+{
+    const unsigned char *p = (const unsigned char *)a->_a_cpuset;
+    if (!p) {
+        // no affinity set, all CPUs are allowed
+        memset(set, -1, size);
+        return 0;
+    }
+    for (size_t i = size; i < a->_a_cpusetsize; i++)
+        if (p[i])
+            return EINVAL;
+    memcpy(set, p, size < a->_a_cpusetsize ? size : a->_a_cpusetsize);
+    if (size > a->_a_cpusetsize)
+        memset((unsigned char *)set + a->_a_cpusetsize, 0, size - a->_a_cpusetsize);
+    return 0;
+}
+
 #endif // AX_CONFIG_MULTITASK
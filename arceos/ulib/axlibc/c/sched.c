@@ -1,3 +1,5 @@
+#ifndef AX_CONFIG_MULTITASK
+
 #include <sched.h>
 #include <stdio.h>
 
@@ -7,3 +9,5 @@ int sched_setaffinity(pid_t __pid, size_t __cpusetsize, const cpu_set_t *__cpuse
     unimplemented();
     return 0;
 }
+
+#endif // AX_CONFIG_MULTITASK
@@ -6,6 +6,8 @@
 #include <time.h>
 #include <unistd.h>
 
+#ifndef AX_CONFIG_FS
+
 // TODO:
 uid_t geteuid(void)
 {
@@ -20,6 +22,8 @@ uid_t getuid(void)
     return 0;
 }
 
+#endif // AX_CONFIG_FS
+
 // TODO
 pid_t setsid(void)
 {
@@ -61,67 +65,16 @@ int access(const char *pathname, int mode)
     return 0;
 }
 
-// TODO:
-ssize_t readlink(const char *path, char *buf, size_t bufsiz)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int unlink(const char *pathname)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int rmdir(const char *pathname)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int fsync(int fd)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-int fdatasync(int __fildes)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int fchown(int fd, uid_t owner, gid_t group)
-{
-    unimplemented("owner: %x group: %x", owner, group);
-    return 0;
-}
-
-// TODO:
-int ftruncate(int fd, off_t length)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-int chdir(const char *__path)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
 int truncate(const char *path, off_t length)
 {
-    unimplemented();
-    return 0;
+    int fd = open(path, O_WRONLY);
+    if (fd < 0)
+        return -1;
+    int ret = ftruncate(fd, length);
+    int err = errno;
+    close(fd);
+    errno = err;
+    return ret;
 }
 
 #endif // AX_CONFIG_FS
@@ -2,6 +2,7 @@
 #define _PTHREAD_H
 
 #include <features.h>
+#include <sched.h>
 #include <time.h>
 
 #define PTHREAD_CANCEL_ENABLE  0
@@ -31,6 +32,8 @@ typedef struct {
 #define _a_stacksize __u.__s[0]
 #define _a_guardsize __u.__s[1]
 #define _a_stackaddr __u.__s[2]
+#define _a_cpuset     __u.__s[3]
+#define _a_cpusetsize __u.__s[4]
 
 typedef struct {
     union {
@@ -78,6 +81,11 @@ int pthread_attr_init(pthread_attr_t *__attr);
 int pthread_attr_getstacksize(const pthread_attr_t *__restrict__ __attr,
                               size_t *__restrict__ __stacksize);
 int pthread_attr_setstacksize(pthread_attr_t *__attr, size_t __stacksize);
+int pthread_attr_destroy(pthread_attr_t *__attr);
+int pthread_attr_setaffinity_np(pthread_attr_t *__attr, size_t __cpusetsize,
+                                const cpu_set_t *__cpuset);
+int pthread_attr_getaffinity_np(const pthread_attr_t *__attr, size_t __cpusetsize,
+                                cpu_set_t *__cpuset);
 
 #endif // AX_CONFIG_MULTITASK
 
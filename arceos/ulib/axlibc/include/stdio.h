@@ -61,6 +61,7 @@ int fclose(FILE *);
 
 int remove(const char *);
 int rename(const char *, const char *);
+int renameat(int, const char *, int, const char *);
 
 int feof(FILE *__stream);
 int ferror(FILE *);
@@ -31,6 +31,7 @@ struct rlimit {
 #define RLIMIT_NLIMITS    16
 
 #define RUSAGE_SELF     0
+#define RUSAGE_THREAD   1
 #define RUSAGE_CHILDREN -1
 
 struct rusage {
@@ -0,0 +1,30 @@
+#ifndef _SYS_STATVFS_H
+#define _SYS_STATVFS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#include <sys/types.h>
+
+struct statvfs {
+    unsigned long f_bsize, f_frsize;
+    fsblkcnt_t f_blocks, f_bfree, f_bavail;
+    fsfilcnt_t f_files, f_ffree, f_favail;
+    unsigned long f_fsid;
+    unsigned long f_flag, f_namemax;
+    unsigned int f_type;
+    int __reserved[5];
+};
+
+#define ST_RDONLY 1
+#define ST_NOSUID 2
+
+int statvfs(const char *__restrict, struct statvfs *__restrict);
+int fstatvfs(int, struct statvfs *);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // _SYS_STATVFS_H
@@ -1,7 +1,11 @@
 use core::ffi::{c_char, c_int};
 
 use arceos_posix_api::{
-    sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat,
+    sys_chdir, sys_fallocate, sys_fchdir, sys_fchmod, sys_fchmodat, sys_fchown, sys_fchownat,
+    sys_flock, sys_fstat, sys_fstatat, sys_fstatfs, sys_fsync, sys_ftruncate, sys_getcwd,
+    sys_inotify_add_watch, sys_inotify_init1, sys_inotify_rm_watch, sys_linkat, sys_lseek,
+    sys_lstat, sys_mkdirat, sys_open, sys_openat, sys_readlinkat, sys_rename, sys_renameat,
+    sys_stat, sys_statfs, sys_symlinkat, sys_umask, sys_unlinkat,
 };
 
 use crate::{ctypes, utils::e};
@@ -19,6 +23,21 @@ pub unsafe extern "C" fn ax_open(
     e(sys_open(filename, flags, mode))
 }
 
+/// Open a file by `filename` relative to the directory `dirfd`, and insert it
+/// into the file descriptor table.
+///
+/// Return its index in the file table (`fd`). Return `EMFILE` if it already
+/// has the maximum number of files open.
+#[no_mangle]
+pub unsafe extern "C" fn ax_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    e(sys_openat(dirfd, filename, flags, mode))
+}
+
 /// Set the position of the file indicated by `fd`.
 ///
 /// Return its position after seek.
@@ -27,6 +46,52 @@ pub unsafe extern "C" fn lseek(fd: c_int, offset: ctypes::off_t, whence: c_int)
     e(sys_lseek(fd, offset, whence) as _) as _
 }
 
+/// Write the cached data of the file indicated by `fd` to the storage device.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fsync(fd: c_int) -> c_int {
+    e(sys_fsync(fd))
+}
+
+/// Write the cached data of the file indicated by `fd` to the storage device.
+///
+/// Metadata is always written with the data, so it is the same as [`fsync`].
+#[no_mangle]
+pub unsafe extern "C" fn fdatasync(fd: c_int) -> c_int {
+    e(sys_fsync(fd))
+}
+
+/// Truncate or extend the file indicated by `fd` to `length` bytes.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ftruncate(fd: c_int, length: ctypes::off_t) -> c_int {
+    e(sys_ftruncate(fd, length))
+}
+
+/// Allocate or deallocate the storage of `len` bytes at `offset` of the file
+/// indicated by `fd`, depending on `mode`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fallocate(
+    fd: c_int,
+    mode: c_int,
+    offset: ctypes::off_t,
+    len: ctypes::off_t,
+) -> c_int {
+    e(sys_fallocate(fd, mode, offset, len))
+}
+
+/// Apply or remove an advisory lock on the whole file indicated by `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn flock(fd: c_int, operation: c_int) -> c_int {
+    e(sys_flock(fd, operation))
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -43,6 +108,24 @@ pub unsafe extern "C" fn fstat(fd: c_int, buf: *mut ctypes::stat) -> c_int {
     e(sys_fstat(fd, buf))
 }
 
+/// Get the statistics of the filesystem that contains the file at `path` and
+/// write into `buf`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_statfs(path, buf))
+}
+
+/// Get the statistics of the filesystem that contains the file indicated by
+/// `fd` and write into `buf`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_fstatfs(fd, buf))
+}
+
 /// Get the metadata of the symbolic link and write into `buf`.
 ///
 /// Return 0 if success.
@@ -51,12 +134,87 @@ pub unsafe extern "C" fn lstat(path: *const c_char, buf: *mut ctypes::stat) -> c
     e(sys_lstat(path, buf) as _)
 }
 
+/// Get the metadata of the file at `path` relative to the directory `dirfd`
+/// and write into `buf`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    e(sys_fstatat(dirfd, path, buf, flags))
+}
+
 /// Get the path of the current directory.
 #[no_mangle]
 pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
     sys_getcwd(buf, size)
 }
 
+/// Change the current directory to `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn chdir(path: *const c_char) -> c_int {
+    e(sys_chdir(path))
+}
+
+/// Change the current directory to the directory opened as `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fchdir(fd: c_int) -> c_int {
+    e(sys_fchdir(fd))
+}
+
+/// Create a directory at `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdirat(ctypes::AT_FDCWD, path, mode))
+}
+
+/// Create a directory at `path` relative to the directory `dirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdirat(dirfd, path, mode))
+}
+
+/// Remove the file at `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn unlink(path: *const c_char) -> c_int {
+    e(sys_unlinkat(ctypes::AT_FDCWD, path, 0))
+}
+
+/// Remove the file at `path` relative to the directory `dirfd`, or the empty
+/// directory if `flags` contains `AT_REMOVEDIR`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    e(sys_unlinkat(dirfd, path, flags))
+}
+
+/// Remove the empty directory at `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn rmdir(path: *const c_char) -> c_int {
+    e(sys_unlinkat(
+        ctypes::AT_FDCWD,
+        path,
+        ctypes::AT_REMOVEDIR as _,
+    ))
+}
+
 /// Rename `old` to `new`
 /// If new exists, it is first removed.
 ///
@@ -65,3 +223,216 @@ pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
     e(sys_rename(old, new))
 }
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to the
+/// directory `newdirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn renameat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+) -> c_int {
+    e(sys_renameat(olddirfd, old, newdirfd, new))
+}
+
+/// Create a symbolic link at `linkpath` which contains the string `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    e(sys_symlinkat(target, ctypes::AT_FDCWD, linkpath))
+}
+
+/// Create a symbolic link at `linkpath` relative to the directory `newdirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn symlinkat(
+    target: *const c_char,
+    newdirfd: c_int,
+    linkpath: *const c_char,
+) -> c_int {
+    e(sys_symlinkat(target, newdirfd, linkpath))
+}
+
+/// Read the target of the symbolic link at `path` into `buf`.
+///
+/// Return the number of bytes placed in `buf`, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsize: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlinkat(ctypes::AT_FDCWD, path, buf, bufsize) as _) as _
+}
+
+/// Read the target of the symbolic link at `path` relative to the directory
+/// `dirfd` into `buf`.
+///
+/// Return the number of bytes placed in `buf`, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn readlinkat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsize: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlinkat(dirfd, path, buf, bufsize) as _) as _
+}
+
+/// Create a hard link at `newpath` to the file at `oldpath`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn link(oldpath: *const c_char, newpath: *const c_char) -> c_int {
+    e(sys_linkat(
+        ctypes::AT_FDCWD,
+        oldpath,
+        ctypes::AT_FDCWD,
+        newpath,
+        0,
+    ))
+}
+
+/// Create a hard link at `newpath` relative to the directory `newdirfd`, to
+/// the file at `oldpath` relative to the directory `olddirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn linkat(
+    olddirfd: c_int,
+    oldpath: *const c_char,
+    newdirfd: c_int,
+    newpath: *const c_char,
+    flags: c_int,
+) -> c_int {
+    e(sys_linkat(olddirfd, oldpath, newdirfd, newpath, flags))
+}
+
+/// Create an inotify instance.
+///
+/// Return its file descriptor.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_init() -> c_int {
+    e(sys_inotify_init1(0))
+}
+
+/// Create an inotify instance, with `IN_NONBLOCK` and `IN_CLOEXEC` in `flags`.
+///
+/// Return its file descriptor.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_init1(flags: c_int) -> c_int {
+    e(sys_inotify_init1(flags))
+}
+
+/// Watch the events in `mask` of the file or directory at `pathname`.
+///
+/// Return the watch descriptor.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int {
+    e(sys_inotify_add_watch(fd, pathname, mask))
+}
+
+/// Remove the watch `wd` from the inotify instance `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    e(sys_inotify_rm_watch(fd, wd))
+}
+
+/// Change the permissions of the file at `path` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_fchmodat(ctypes::AT_FDCWD, path, mode, 0))
+}
+
+/// Change the permissions of the file at `path` relative to the directory
+/// `dirfd` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fchmodat(
+    dirfd: c_int,
+    path: *const c_char,
+    mode: ctypes::mode_t,
+    flags: c_int,
+) -> c_int {
+    e(sys_fchmodat(dirfd, path, mode, flags))
+}
+
+/// Change the permissions of the file indicated by `fd` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    e(sys_fchmod(fd, mode))
+}
+
+/// Change the owner and the group of the file at `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn chown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+) -> c_int {
+    e(sys_fchownat(ctypes::AT_FDCWD, path, owner, group, 0))
+}
+
+/// Change the owner and the group of the file at `path`, without following
+/// the symbolic link at its end.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn lchown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+) -> c_int {
+    e(sys_fchownat(
+        ctypes::AT_FDCWD,
+        path,
+        owner,
+        group,
+        ctypes::AT_SYMLINK_NOFOLLOW as _,
+    ))
+}
+
+/// Change the owner and the group of the file at `path` relative to the
+/// directory `dirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fchownat(
+    dirfd: c_int,
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+    flags: c_int,
+) -> c_int {
+    e(sys_fchownat(dirfd, path, owner, group, flags))
+}
+
+/// Change the owner and the group of the file indicated by `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    e(sys_fchown(fd, owner, group))
+}
+
+/// Set the umask of the current task to `mask`.
+///
+/// Return the previous umask.
+#[no_mangle]
+pub unsafe extern "C" fn umask(mask: ctypes::mode_t) -> ctypes::mode_t {
+    sys_umask(mask)
+}
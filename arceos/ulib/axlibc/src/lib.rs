@@ -63,6 +63,8 @@ mod net;
 mod pipe;
 #[cfg(feature = "multitask")]
 mod pthread;
+#[cfg(feature = "multitask")]
+mod sched;
 #[cfg(feature = "alloc")]
 mod strftime;
 #[cfg(feature = "fp_simd")]
@@ -85,10 +87,10 @@ pub use self::io::{read, writev};
 pub use self::errno::strerror;
 pub use self::mktime::mktime;
 pub use self::rand::{rand, random, srand};
-pub use self::resource::{getrlimit, setrlimit};
+pub use self::resource::{getrlimit, getrusage, setrlimit};
 pub use self::setjmp::{longjmp, setjmp};
 pub use self::sys::sysconf;
-pub use self::time::{clock_gettime, nanosleep};
+pub use self::time::{clock_gettime, nanosleep, times};
 pub use self::unistd::{abort, exit, getpid};
 
 #[cfg(feature = "alloc")]
@@ -100,7 +102,17 @@ pub use self::strftime::strftime;
 pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{
+    ax_open, ax_openat, chdir, chmod, chown, fallocate, fchdir, fchmod, fchmodat, fchown, fchownat,
+    fdatasync, flock, fstat, fstatat, fstatfs, fsync, ftruncate, getcwd, inotify_add_watch,
+    inotify_init, inotify_init1, inotify_rm_watch, lchown, link, linkat, lseek, lstat, mkdir,
+    mkdirat, readlink, readlinkat, rename, renameat, rmdir, stat, statfs, symlink, symlinkat,
+    umask, unlink, unlinkat,
+};
+#[cfg(feature = "fs")]
+pub use self::unistd::{
+    getegid, geteuid, getgid, getgroups, getuid, setegid, seteuid, setgid, setgroups, setuid,
+};
 
 #[cfg(feature = "net")]
 pub use self::net::{
@@ -112,6 +124,11 @@ pub use self::net::{
 pub use self::pthread::{pthread_create, pthread_exit, pthread_join, pthread_self};
 #[cfg(feature = "multitask")]
 pub use self::pthread::{pthread_mutex_init, pthread_mutex_lock, pthread_mutex_unlock};
+#[cfg(feature = "multitask")]
+pub use self::sched::{
+    sched_get_priority_max, sched_get_priority_min, sched_getaffinity, sched_getparam,
+    sched_getscheduler, sched_setaffinity, sched_setparam, sched_setscheduler,
+};
 
 #[cfg(feature = "pipe")]
 pub use self::pipe::pipe;
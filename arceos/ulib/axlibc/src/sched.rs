@@ -0,0 +1,73 @@
+use crate::{ctypes, utils::e};
+use arceos_posix_api::{
+    sys_sched_get_priority_max, sys_sched_get_priority_min, sys_sched_getaffinity,
+    sys_sched_getparam, sys_sched_getscheduler, sys_sched_setaffinity, sys_sched_setparam,
+    sys_sched_setscheduler,
+};
+use core::ffi::c_int;
+
+/// Set the CPU affinity of the thread `pid`, or the current thread if `pid` is
+/// 0.
+#[no_mangle]
+pub unsafe extern "C" fn sched_setaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    cpuset: *const ctypes::cpu_set_t,
+) -> c_int {
+    e(sys_sched_setaffinity(pid, cpusetsize, cpuset))
+}
+
+/// Get the CPU affinity of the thread `pid`, or the current thread if `pid` is
+/// 0.
+#[no_mangle]
+pub unsafe extern "C" fn sched_getaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> c_int {
+    e(sys_sched_getaffinity(pid, cpusetsize, cpuset))
+}
+
+/// Set the scheduling policy and the static priority of the thread `pid`, or
+/// the current thread if `pid` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn sched_setscheduler(
+    pid: c_int,
+    policy: c_int,
+    param: *const ctypes::sched_param,
+) -> c_int {
+    e(sys_sched_setscheduler(pid, policy, param))
+}
+
+/// Get the scheduling policy of the thread `pid`, or the current thread if
+/// `pid` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn sched_getscheduler(pid: c_int) -> c_int {
+    e(sys_sched_getscheduler(pid))
+}
+
+/// Set the static priority of the thread `pid`, or the current thread if `pid`
+/// is 0.
+#[no_mangle]
+pub unsafe extern "C" fn sched_setparam(pid: c_int, param: *const ctypes::sched_param) -> c_int {
+    e(sys_sched_setparam(pid, param))
+}
+
+/// Get the static priority of the thread `pid`, or the current thread if `pid`
+/// is 0.
+#[no_mangle]
+pub unsafe extern "C" fn sched_getparam(pid: c_int, param: *mut ctypes::sched_param) -> c_int {
+    e(sys_sched_getparam(pid, param))
+}
+
+/// Get the highest static priority of the scheduling policy `policy`.
+#[no_mangle]
+pub unsafe extern "C" fn sched_get_priority_max(policy: c_int) -> c_int {
+    e(sys_sched_get_priority_max(policy))
+}
+
+/// Get the lowest static priority of the scheduling policy `policy`.
+#[no_mangle]
+pub unsafe extern "C" fn sched_get_priority_min(policy: c_int) -> c_int {
+    e(sys_sched_get_priority_min(policy))
+}
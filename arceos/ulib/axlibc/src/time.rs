@@ -1,4 +1,4 @@
-use arceos_posix_api::{sys_clock_gettime, sys_nanosleep};
+use arceos_posix_api::{sys_clock_gettime, sys_nanosleep, sys_times};
 use core::ffi::c_int;
 
 use crate::{ctypes, utils::e};
@@ -9,6 +9,12 @@ pub unsafe extern "C" fn clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::
     e(sys_clock_gettime(clk, ts))
 }
 
+/// Get the CPU times of the process, in clock ticks
+#[no_mangle]
+pub unsafe extern "C" fn times(buf: *mut ctypes::tms) -> ctypes::clock_t {
+    sys_times(buf)
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno
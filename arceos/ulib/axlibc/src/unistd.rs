@@ -1,6 +1,14 @@
 use arceos_posix_api::{sys_exit, sys_getpid};
 use core::ffi::c_int;
 
+#[cfg(feature = "fs")]
+use {
+    crate::{ctypes, utils::e},
+    arceos_posix_api::{
+        sys_getgid, sys_getgroups, sys_getuid, sys_setgid, sys_setgroups, sys_setuid,
+    },
+};
+
 /// Get current thread ID.
 #[no_mangle]
 pub unsafe extern "C" fn getpid() -> c_int {
@@ -18,3 +26,87 @@ pub unsafe extern "C" fn abort() -> ! {
 pub unsafe extern "C" fn exit(exit_code: c_int) -> ! {
     sys_exit(exit_code)
 }
+
+/// Get the user ID of the current task.
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn getuid() -> ctypes::uid_t {
+    sys_getuid()
+}
+
+/// Get the effective user ID of the current task, which is the same as
+/// [`getuid`].
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn geteuid() -> ctypes::uid_t {
+    sys_getuid()
+}
+
+/// Get the group ID of the current task.
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn getgid() -> ctypes::gid_t {
+    sys_getgid()
+}
+
+/// Get the effective group ID of the current task, which is the same as
+/// [`getgid`].
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn getegid() -> ctypes::gid_t {
+    sys_getgid()
+}
+
+/// Set the user ID of the current task.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn setuid(uid: ctypes::uid_t) -> c_int {
+    e(sys_setuid(uid))
+}
+
+/// Set the effective user ID of the current task, which is the same as
+/// [`setuid`].
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn seteuid(uid: ctypes::uid_t) -> c_int {
+    e(sys_setuid(uid))
+}
+
+/// Set the group ID of the current task.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn setgid(gid: ctypes::gid_t) -> c_int {
+    e(sys_setgid(gid))
+}
+
+/// Set the effective group ID of the current task, which is the same as
+/// [`setgid`].
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn setegid(gid: ctypes::gid_t) -> c_int {
+    e(sys_setgid(gid))
+}
+
+/// Get the supplementary group IDs of the current task into `list`, which can
+/// hold `size` of them.
+///
+/// Return the number of the groups, otherwise return -1.
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn getgroups(size: c_int, list: *mut ctypes::gid_t) -> c_int {
+    e(sys_getgroups(size, list))
+}
+
+/// Set the supplementary group IDs of the current task to the `size` ones in
+/// `list`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[cfg(feature = "fs")]
+#[no_mangle]
+pub unsafe extern "C" fn setgroups(size: usize, list: *const ctypes::gid_t) -> c_int {
+    e(sys_setgroups(size, list))
+}
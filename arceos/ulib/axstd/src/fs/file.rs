@@ -1,4 +1,5 @@
 use crate::io::{prelude::*, Result, SeekFrom};
+use crate::time::{SystemTime, UNIX_EPOCH};
 use core::fmt;
 
 use arceos_api::fs as api;
@@ -16,7 +17,7 @@ pub struct File {
 }
 
 /// Metadata information about a file.
-pub struct Metadata(api::AxFileAttr);
+pub struct Metadata(pub(super) api::AxFileAttr);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -90,6 +91,14 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link. It can only be
+    /// `true` for the metadata returned by [`symlink_metadata`].
+    ///
+    /// [`symlink_metadata`]: super::symlink_metadata
+    pub const fn is_symlink(&self) -> bool {
+        self.0.file_type().is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -110,6 +119,16 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the last access time of this metadata.
+    pub fn accessed(&self) -> Result<SystemTime> {
+        Ok(UNIX_EPOCH + self.0.atime())
+    }
+
+    /// Returns the last modification time listed in this metadata.
+    pub fn modified(&self) -> Result<SystemTime> {
+        Ok(UNIX_EPOCH + self.0.mtime())
+    }
 }
 
 impl fmt::Debug for Metadata {
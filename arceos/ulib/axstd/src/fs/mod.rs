@@ -11,6 +11,9 @@ use alloc::{string::String, vec::Vec};
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
 
+/// Capacity and usage of a filesystem, returned by [`statfs`].
+pub type FsStats = arceos_api::fs::AxFsStats;
+
 /// Read the entire contents of a file into a bytes vector.
 #[cfg(feature = "alloc")]
 pub fn read(path: &str) -> io::Result<Vec<u8>> {
@@ -68,6 +71,30 @@ pub fn remove_file(path: &str) -> io::Result<()> {
     arceos_api::fs::ax_remove_file(path)
 }
 
+/// Queries the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    arceos_api::fs::ax_symlink_attr(path).map(Metadata)
+}
+
+/// Reads a symbolic link, returning the path that the link points to.
+#[cfg(feature = "alloc")]
+pub fn read_link(path: &str) -> io::Result<String> {
+    arceos_api::fs::ax_read_link(path)
+}
+
+/// Creates a new symbolic link at `link` that points to `original`.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_symlink(original, link)
+}
+
+/// Creates a new hard link at `link` to the file at `original`.
+///
+/// This only works in in-memory filesystems, and both paths must be in the
+/// same one.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_hard_link(original, link)
+}
+
 /// Rename a file or directory to a new name.
 /// Delete the original file if `old` already exists.
 ///
@@ -75,3 +102,25 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     arceos_api::fs::ax_rename(old, new)
 }
+
+/// Mounts a filesystem of type `fstype` at `target`.
+///
+/// The meaning of `source` depends on the filesystem type, and it is ignored
+/// by in-memory filesystems such as `ramfs`.
+pub fn mount(source: &str, target: &str, fstype: &str) -> io::Result<()> {
+    arceos_api::fs::ax_mount(source, target, fstype)
+}
+
+/// Unmounts the filesystem mounted at `target`.
+///
+/// Fails with [`ResourceBusy`](io::Error::ResourceBusy) if there are still
+/// opened files under it.
+pub fn umount(target: &str) -> io::Result<()> {
+    arceos_api::fs::ax_umount(target)
+}
+
+/// Returns the capacity and usage of the filesystem that the file at `path`
+/// resides in.
+pub fn statfs(path: &str) -> io::Result<FsStats> {
+    arceos_api::fs::ax_statfs(path)
+}
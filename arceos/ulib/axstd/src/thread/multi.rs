@@ -6,7 +6,7 @@ use crate::io;
 use alloc::{string::String, sync::Arc};
 use core::{cell::UnsafeCell, num::NonZeroU64};
 
-use arceos_api::task::{self as api, AxTaskHandle};
+use arceos_api::task::{self as api, AxCpuMask, AxTaskHandle};
 use axerrno::ax_err_type;
 
 /// A unique identifier for a running thread.
@@ -49,6 +49,8 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned thread in bytes
     stack_size: Option<usize>,
+    // The CPUs that the spawned thread is allowed to run on
+    affinity: Option<AxCpuMask>,
 }
 
 impl Builder {
@@ -58,6 +60,7 @@ impl Builder {
         Builder {
             name: None,
             stack_size: None,
+            affinity: None,
         }
     }
 
@@ -73,6 +76,15 @@ impl Builder {
         self
     }
 
+    /// Pins the new thread to the CPUs with the given IDs. It can run on all
+    /// CPUs by default.
+    ///
+    /// [`spawn`](Builder::spawn) fails if none of the CPUs exists.
+    pub fn affinity<I: IntoIterator<Item = usize>>(mut self, cpus: I) -> Builder {
+        self.affinity = Some(cpus.into_iter().collect());
+        self
+    }
+
     /// Spawns a new thread by taking ownership of the `Builder`, and returns an
     /// [`io::Result`] to its [`JoinHandle`].
     ///
@@ -115,7 +127,10 @@ impl Builder {
             drop(their_packet);
         };
 
-        let task = api::ax_spawn(main, name, stack_size);
+        let task = match self.affinity {
+            Some(cpumask) => api::ax_spawn_with_affinity(main, name, stack_size, cpumask)?,
+            None => api::ax_spawn(main, name, stack_size),
+        };
         Ok(JoinHandle {
             thread: Thread::from_id(task.id()),
             native: task,